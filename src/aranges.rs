@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! DWARF address range tables
+//!
+//! `.debug_aranges` maps address ranges to the compilation units in `.debug_info` which
+//! describe them, so that the unit covering an address can be found without parsing
+//! `.debug_info`.
+
+use crate::{table::Encoding, DebugSectionKind, DwarfSection, Elf};
+use core::ops::Range;
+
+/// The contents of `.debug_aranges`
+#[derive(Clone, Copy, Debug)]
+pub struct DebugAranges<'a> {
+    data: &'a [u8],
+    encoding: Encoding,
+}
+
+impl<'a> DebugAranges<'a> {
+    /// Read the contents of `.debug_aranges` in a little-endian file
+    #[inline]
+    pub const fn new(data: &'a [u8]) -> DebugAranges<'a> {
+        Self::with_encoding(data, Encoding::NATIVE)
+    }
+
+    /// Read the contents of `.debug_aranges` in a file laid out as `encoding`
+    #[inline]
+    pub(crate) const fn with_encoding(data: &'a [u8], encoding: Encoding) -> DebugAranges<'a> {
+        Self { data, encoding }
+    }
+
+    /// Returns an iterator over the address range sets, one for each compilation unit
+    ///
+    /// Iteration stops at the first malformed set.
+    pub fn sets(&self) -> impl Iterator<Item = ArangeSet<'a>> {
+        let (mut rest, encoding) = (self.data, self.encoding);
+        core::iter::from_fn(move || {
+            let (set, next) = ArangeSet::parse(rest, encoding)?;
+            rest = next;
+            Some(set)
+        })
+    }
+
+    /// Returns the `.debug_info` offset of the compilation unit covering `addr`
+    pub fn find(&self, addr: u64) -> Option<u64> {
+        self.sets()
+            .find(|set| set.contains(addr))
+            .map(|set| set.debug_info_offset())
+    }
+}
+
+/// The address ranges covered by one compilation unit
+#[derive(Clone, Copy, Debug)]
+pub struct ArangeSet<'a> {
+    version: u16,
+    debug_info_offset: u64,
+    address_size: u8,
+    segment_selector_size: u8,
+    tuples: &'a [u8],
+    encoding: Encoding,
+}
+
+impl<'a> ArangeSet<'a> {
+    /// Parse the set at the start of `data`, returning it and the data following it
+    fn parse(data: &'a [u8], encoding: Encoding) -> Option<(ArangeSet<'a>, &'a [u8])> {
+        let word = |i: usize| encoding.read_u32(data.get(i..)?);
+        let long = |i: usize| encoding.read_u64(data.get(i..)?);
+
+        let (offset_size, unit_length, header) = match word(0)? {
+            0xffff_ffff => (8, long(4)?, 12usize),
+            len => (4, len as u64, 4),
+        };
+        let end = header.checked_add(usize::try_from(unit_length).ok()?)?;
+        let unit = data.get(..end)?;
+
+        let version = encoding.read_u16(unit.get(header..)?)?;
+        if version != 2 {
+            return None;
+        }
+        let debug_info_offset = match offset_size {
+            8 => long(header + 2)?,
+            _ => word(header + 2)? as u64,
+        };
+        let at = header + 2 + offset_size;
+        let address_size = *unit.get(at)?;
+        let segment_selector_size = *unit.get(at + 1)?;
+        if !matches!(address_size, 1 | 2 | 4 | 8) || segment_selector_size > 8 {
+            return None;
+        }
+
+        // Tuples are aligned to twice the address size from the start of the set
+        let tuples = (at + 2).next_multiple_of(2 * address_size as usize);
+        let set = ArangeSet {
+            version,
+            debug_info_offset,
+            address_size,
+            segment_selector_size,
+            tuples: unit.get(tuples..).unwrap_or(&[]),
+            encoding,
+        };
+        Some((set, &data[end..]))
+    }
+
+    #[inline]
+    pub const fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Returns the offset of the compilation unit's header in `.debug_info`
+    #[inline]
+    pub const fn debug_info_offset(&self) -> u64 {
+        self.debug_info_offset
+    }
+
+    /// Returns an iterator over the address ranges covered by the unit
+    ///
+    /// Segment selectors are ignored, and empty ranges are skipped.
+    pub fn ranges(&self) -> impl Iterator<Item = Range<u64>> + 'a {
+        let address_size = self.address_size as usize;
+        let selector_size = self.segment_selector_size as usize;
+        let tuple_size = selector_size + 2 * address_size;
+        let big_endian = self.encoding.big_endian;
+        let read = move |bytes: &[u8]| {
+            let mut value = [0; 8];
+            match big_endian {
+                true => {
+                    value[8 - bytes.len()..].copy_from_slice(bytes);
+                    u64::from_be_bytes(value)
+                }
+                false => {
+                    value[..bytes.len()].copy_from_slice(bytes);
+                    u64::from_le_bytes(value)
+                }
+            }
+        };
+
+        self.tuples
+            .chunks_exact(tuple_size)
+            .map(move |tuple| {
+                let tuple = &tuple[selector_size..];
+                let start = read(&tuple[..address_size]);
+                let len = read(&tuple[address_size..]);
+                (start, len)
+            })
+            .take_while(|&(start, len)| (start, len) != (0, 0))
+            .filter(|&(_, len)| len != 0)
+            .map(|(start, len)| start..start.saturating_add(len))
+    }
+
+    /// Returns `true` if any of the unit's ranges contains `addr`
+    pub fn contains(&self, addr: u64) -> bool {
+        self.ranges().any(|range| range.contains(&addr))
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the contents of `.debug_aranges`, if there are any
+    pub fn debug_aranges(&self) -> Option<DebugAranges<'elf>> {
+        let sect = self.debug_section(DebugSectionKind::Main(DwarfSection::Aranges))?;
+        Some(DebugAranges::with_encoding(
+            sect.file_data()?,
+            Encoding::of(self),
+        ))
+    }
+
+    /// Returns the `.debug_info` offset of the compilation unit covering `addr`
+    ///
+    /// Not every compiler emits `.debug_aranges`, and units it omits are not found.
+    pub fn find_compilation_unit(&self, addr: u64) -> Option<u64> {
+        self.debug_aranges()?.find(addr)
+    }
+}
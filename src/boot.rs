@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! What a boot stub needs to know to enter a kernel
+
+use crate::{Elf, ElfType, Machine, ParseError, SegmentFlags, SegmentKind, Structure};
+
+/// Everything a boot stub needs to load and enter an image, taken from its headers and
+/// notes
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct BootInfo {
+    pub machine: Machine,
+    /// Virtual address of the entry point
+    pub entry_point: u64,
+    /// Physical address of the entry point, as loaded at the preferred physical address
+    pub physical_entry_point: u64,
+    /// Lowest virtual address of any `PT_LOAD` segment
+    pub load_address: u64,
+    /// Lowest physical address of any `PT_LOAD` segment
+    pub physical_load_address: u64,
+    /// `true` if paging must be enabled before jumping to the entry point
+    ///
+    /// This is always the case on x86-64, as long mode requires paging. Elsewhere it is
+    /// needed if any segment is linked at a virtual address other than its physical address.
+    pub requires_paging: bool,
+    /// `true` if the image may be loaded at any address (`ET_DYN`)
+    pub relocatable: bool,
+    /// Requested size of the initial stack, in bytes
+    ///
+    /// See [`Elf::stack_requirements()`].
+    pub stack_size: Option<u64>,
+    /// `true` if the initial stack must be executable
+    ///
+    /// The stack is executable unless `PT_GNU_STACK` says otherwise.
+    pub executable_stack: bool,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the information a boot stub needs to enter this image
+    ///
+    /// Fails if the image has no loadable segments or its entry point does not lie in an
+    /// executable one.
+    pub fn boot_info(&self) -> Result<BootInfo, ParseError> {
+        let error = |offset, message| ParseError::new(Structure::FileHeader, offset, message);
+        let machine = self.machine();
+        let entry_point = self.entry_point();
+        let loads = || {
+            self.segments()
+                .filter(|sgmt| sgmt.kind() == SegmentKind::Load)
+        };
+
+        let load_address = loads()
+            .map(|sgmt| sgmt.virtual_address())
+            .min()
+            .ok_or(error(0, "no loadable segments"))?;
+        let physical_load_address = loads()
+            .map(|sgmt| sgmt.physical_address())
+            .min()
+            .ok_or(error(0, "no loadable segments"))?;
+
+        let physical_entry_point = loads()
+            .filter(|sgmt| sgmt.flags().contains(SegmentFlags::EXEC))
+            .find_map(|sgmt| {
+                let offset = entry_point.checked_sub(sgmt.virtual_address())?;
+                (offset < sgmt.mem_size() as u64)
+                    .then(|| sgmt.physical_address().wrapping_add(offset))
+            })
+            .ok_or(error(0x18, "entry point is not in an executable segment"))?;
+
+        let requires_paging = machine == Machine::X86_64
+            || loads().any(|sgmt| sgmt.virtual_address() != sgmt.physical_address());
+
+        let stack = self.stack_requirements();
+
+        Ok(BootInfo {
+            machine,
+            entry_point,
+            physical_entry_point,
+            load_address,
+            physical_load_address,
+            requires_paging,
+            relocatable: self.file_type() == ElfType::Dyn,
+            stack_size: stack.size,
+            executable_stack: stack.executable(),
+        })
+    }
+}
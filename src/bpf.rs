@@ -0,0 +1,406 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! BPF object files
+//!
+//! BPF programs are compiled to relocatable objects in which each executable section holds
+//! one or more programs, named after how they attach (e.g. `kprobe/do_sys_open`). Maps are
+//! described by symbols in `.maps`, or the legacy `maps` section, and type information is
+//! carried in `.BTF` and `.BTF.ext`.
+
+use crate::{
+    table::{Encoding, Table},
+    Elf, Machine, Rel, RelocKind, Section, SectionType, StringTable, Sym, SHN_UNDEF,
+};
+
+pub const BTF_MAGIC: u16 = 0xeb9f;
+
+/// Size of a BPF instruction, in bytes
+pub const BPF_INSN_SIZE: usize = 8;
+
+/// Returns the byte order of BTF data, which is given by how its magic number is stored
+fn btf_encoding(data: &[u8]) -> Option<Encoding> {
+    [false, true].into_iter().find_map(|big_endian| {
+        let encoding = Encoding {
+            bits32: false,
+            big_endian,
+        };
+        (encoding.read_u16(data)? == BTF_MAGIC).then_some(encoding)
+    })
+}
+
+/// The header of a `.BTF` section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BtfHeader {
+    pub version: u8,
+    pub flags: u8,
+    pub header_len: u32,
+    /// Offset of the type section, relative to the end of the header
+    pub type_offset: u32,
+    pub type_len: u32,
+    /// Offset of the string section, relative to the end of the header
+    pub str_offset: u32,
+    pub str_len: u32,
+}
+
+impl BtfHeader {
+    pub fn parse(data: &[u8]) -> Option<BtfHeader> {
+        let encoding = btf_encoding(data)?;
+        let u32_at = |i: usize| encoding.read_u32(data.get(i..)?);
+
+        Some(BtfHeader {
+            version: data[2],
+            flags: data[3],
+            header_len: u32_at(4)?,
+            type_offset: u32_at(8)?,
+            type_len: u32_at(12)?,
+            str_offset: u32_at(16)?,
+            str_len: u32_at(20)?,
+        })
+    }
+}
+
+/// The contents of a `.BTF` section
+#[derive(Clone, Copy, Debug)]
+pub struct Btf<'elf> {
+    header: BtfHeader,
+    data: &'elf [u8],
+}
+
+impl<'elf> Btf<'elf> {
+    pub fn parse(data: &'elf [u8]) -> Option<Btf<'elf>> {
+        let header = BtfHeader::parse(data)?;
+        let body = data.get(header.header_len as usize..)?;
+        body.get(header.type_offset as usize..)?
+            .get(..header.type_len as usize)?;
+        body.get(header.str_offset as usize..)?
+            .get(..header.str_len as usize)?;
+
+        Some(Btf { header, data })
+    }
+
+    #[inline]
+    pub const fn header(&self) -> &BtfHeader {
+        &self.header
+    }
+
+    fn body(&self, offset: u32, len: u32) -> &'elf [u8] {
+        &self.data[self.header.header_len as usize..][offset as usize..][..len as usize]
+    }
+
+    /// Returns the raw type descriptors
+    pub fn types(&self) -> &'elf [u8] {
+        self.body(self.header.type_offset, self.header.type_len)
+    }
+
+    pub fn strings(&self) -> StringTable<'elf> {
+        StringTable::new(self.body(self.header.str_offset, self.header.str_len))
+    }
+}
+
+/// The header of a `.BTF.ext` section
+///
+/// Offsets are relative to the end of the header. The CO-RE relocation fields are zero if
+/// the header predates them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BtfExtHeader {
+    pub version: u8,
+    pub flags: u8,
+    pub header_len: u32,
+    pub func_info_offset: u32,
+    pub func_info_len: u32,
+    pub line_info_offset: u32,
+    pub line_info_len: u32,
+    pub core_relo_offset: u32,
+    pub core_relo_len: u32,
+}
+
+impl BtfExtHeader {
+    pub fn parse(data: &[u8]) -> Option<BtfExtHeader> {
+        let encoding = btf_encoding(data)?;
+        let u32_at = |i: usize| encoding.read_u32(data.get(i..)?);
+        let header_len = u32_at(4)?;
+        let has_core = header_len >= 32;
+
+        Some(BtfExtHeader {
+            version: data[2],
+            flags: data[3],
+            header_len,
+            func_info_offset: u32_at(8)?,
+            func_info_len: u32_at(12)?,
+            line_info_offset: u32_at(16)?,
+            line_info_len: u32_at(20)?,
+            core_relo_offset: if has_core { u32_at(24)? } else { 0 },
+            core_relo_len: if has_core { u32_at(28)? } else { 0 },
+        })
+    }
+}
+
+/// The role of a section in a BPF object, determined by its name and flags
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BpfSectionKind {
+    /// Executable code
+    Program,
+    /// BTF-defined maps
+    Maps,
+    /// Maps described by `struct bpf_map_def`
+    LegacyMaps,
+    /// Global variables, each of which is backed by an array map
+    Data,
+    License,
+    Version,
+    Btf,
+    BtfExt,
+    Other,
+}
+
+impl BpfSectionKind {
+    pub fn of(sect: &Section) -> BpfSectionKind {
+        let name = sect.name().unwrap_or("");
+
+        match name {
+            ".maps" => return Self::Maps,
+            "maps" => return Self::LegacyMaps,
+            "license" => return Self::License,
+            "version" => return Self::Version,
+            ".BTF" => return Self::Btf,
+            ".BTF.ext" => return Self::BtfExt,
+            _ => {}
+        }
+
+        let is_data = [".data", ".rodata", ".bss"].iter().any(|prefix| {
+            name == *prefix
+                || name
+                    .strip_prefix(prefix)
+                    .is_some_and(|s| s.starts_with('.'))
+        });
+
+        if sect.flags().execinstr() && sect.size() != 0 {
+            Self::Program
+        } else if is_data && sect.flags().alloc() {
+            Self::Data
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// What a relocation in a BPF program refers to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BpfTarget<'elf> {
+    /// A map, defined by a symbol in a maps section
+    Map {
+        section: u16,
+        name: Option<&'elf str>,
+    },
+    /// A global variable, at `offset` within a data section
+    Data {
+        section: u16,
+        offset: u64,
+    },
+    /// A BPF-to-BPF call, to `offset` within a program section
+    Call {
+        section: u16,
+        offset: u64,
+    },
+    /// A symbol which is not defined in this object, such as a kfunc or `.kconfig` extern
+    Extern {
+        name: Option<&'elf str>,
+    },
+    Other,
+}
+
+/// A relocation in a BPF program section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BpfRelocation<'elf> {
+    /// Index of the relocated instruction
+    pub insn: usize,
+    pub kind: RelocKind,
+    pub target: BpfTarget<'elf>,
+}
+
+/// A section of BPF code
+pub struct BpfProgram<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    section: Section<'a, 'elf>,
+}
+
+impl<'a, 'elf> BpfProgram<'a, 'elf> {
+    /// Returns the section name, e.g. `kprobe/do_sys_open`
+    pub fn name(&self) -> Option<&'elf str> {
+        self.section.name()
+    }
+
+    /// Returns the program type part of the section name, before any `/`
+    pub fn kind(&self) -> Option<&'elf str> {
+        self.name()?.split('/').next()
+    }
+
+    /// Returns the attach target part of the section name, after the first `/`
+    pub fn attach_target(&self) -> Option<&'elf str> {
+        Some(self.name()?.split_once('/')?.1)
+    }
+
+    /// Returns `true` if this is `.text`, which holds functions called by other programs
+    /// rather than programs of its own
+    pub fn is_subprogram(&self) -> bool {
+        self.name() == Some(".text")
+    }
+
+    #[inline]
+    pub fn section(&self) -> &Section<'a, 'elf> {
+        &self.section
+    }
+
+    /// Returns the instructions, or `None` if they lie outside of the file
+    pub fn code(&self) -> Option<&'elf [u8]> {
+        self.section.file_data()
+    }
+
+    /// Returns an iterator over the relocations against this program's code
+    pub fn relocations(&self) -> impl Iterator<Item = BpfRelocation<'elf>> + 'a {
+        let elf = self.elf;
+        let index = self.section.index() as u32;
+        let rels = elf
+            .sections()
+            .find(|rel| rel.section_type() == SectionType::Rel && rel.info() == index)
+            .and_then(|rel| Table::<Rel>::from_section(&rel).ok())
+            .unwrap_or_default();
+        let symtab = elf
+            .symtab_section()
+            .and_then(|shdr| Table::<Sym>::from_section(&shdr).ok())
+            .unwrap_or_default();
+        let strtab = elf.string_table();
+
+        rels.iter().map(move |rel| {
+            let sym = symtab.get(rel.sym() as usize);
+            let name = sym.and_then(|sym| strtab?.get_string(sym.name_index()));
+            let target = match sym {
+                Some(sym) if sym.section_index() == SHN_UNDEF => BpfTarget::Extern { name },
+                Some(sym) => {
+                    let section = sym.section_index();
+                    match elf.section(section).map(|s| BpfSectionKind::of(&s)) {
+                        Some(BpfSectionKind::Maps | BpfSectionKind::LegacyMaps) => {
+                            BpfTarget::Map { section, name }
+                        }
+                        Some(BpfSectionKind::Data) => BpfTarget::Data {
+                            section,
+                            offset: sym.value(),
+                        },
+                        Some(BpfSectionKind::Program) => BpfTarget::Call {
+                            section,
+                            offset: sym.value(),
+                        },
+                        _ => BpfTarget::Other,
+                    }
+                }
+                None => BpfTarget::Other,
+            };
+
+            BpfRelocation {
+                insn: rel.offset() as usize / BPF_INSN_SIZE,
+                kind: rel.kind(),
+                target,
+            }
+        })
+    }
+}
+
+/// A map definition, from a symbol in a maps section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BpfMap<'elf> {
+    pub name: Option<&'elf str>,
+    pub section: u16,
+    /// The map's definition, as a BTF-described struct or `struct bpf_map_def`
+    pub definition: &'elf [u8],
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns `true` if this is a BPF object file
+    pub fn is_bpf(&self) -> bool {
+        self.machine() == Machine::Bpf
+    }
+
+    /// Returns an iterator over the program sections
+    pub fn bpf_programs(&self) -> impl Iterator<Item = BpfProgram<'_, 'elf>> {
+        self.sections()
+            .filter(|sect| BpfSectionKind::of(sect) == BpfSectionKind::Program)
+            .map(|section| BpfProgram { elf: self, section })
+    }
+
+    /// Returns an iterator over the map definitions in `.maps` and `maps`
+    pub fn bpf_maps(&self) -> impl Iterator<Item = BpfMap<'elf>> + '_ {
+        let strtab = self.string_table();
+
+        self.symtab().into_iter().flatten().filter_map(move |sym| {
+            let section = sym.section_index();
+            let sect = self.section(section)?;
+            if !matches!(
+                BpfSectionKind::of(&sect),
+                BpfSectionKind::Maps | BpfSectionKind::LegacyMaps
+            ) || sym.size() == 0
+            {
+                return None;
+            }
+
+            let definition = match sect.section_type() {
+                SectionType::Nobits => &[][..],
+                _ => sect
+                    .file_data()?
+                    .get(crate::to_usize(sym.value())..)?
+                    .get(..sym.size() as usize)?,
+            };
+
+            Some(BpfMap {
+                name: strtab.and_then(|strtab| strtab.get_string(sym.name_index())),
+                section,
+                definition,
+            })
+        })
+    }
+
+    /// Returns the license string from the `license` section
+    pub fn bpf_license(&self) -> Option<&'elf str> {
+        let data = self.find_section("license")?.file_data()?;
+        let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        core::str::from_utf8(&data[..len]).ok()
+    }
+
+    /// Returns the type information from `.BTF`
+    pub fn btf(&self) -> Option<Btf<'elf>> {
+        Btf::parse(self.find_section(".BTF")?.file_data()?)
+    }
+
+    /// Returns the header of `.BTF.ext`, along with the section contents
+    pub fn btf_ext(&self) -> Option<(BtfExtHeader, &'elf [u8])> {
+        let data = self.find_section(".BTF.ext")?.file_data()?;
+        Some((BtfExtHeader::parse(data)?, data))
+    }
+}
@@ -0,0 +1,211 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Operating system brands
+//!
+//! Most toolchains leave `EI_OSABI` as `ELFOSABI_NONE`, so the operating system a file was
+//! built for is usually identified by a note or an OS-specific section instead.
+
+use crate::{table::Encoding, Elf, OsAbi, NT_GNU_ABI_TAG};
+
+/// `NT_FREEBSD_ABI_TAG`, `NT_NETBSD_IDENT` and `NT_OPENBSD_IDENT`
+pub const NT_IDENT: u32 = 1;
+
+/// The operating system identified by [`Elf::brand()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Brand {
+    /// A GNU ABI tag, naming the operating system and the oldest kernel supported
+    Gnu {
+        os: GnuAbiOs,
+        version: [u32; 3],
+    },
+    /// `__FreeBSD_version` of the system the file was built on
+    FreeBSD {
+        version: Option<u32>,
+    },
+    /// `__NetBSD_Version__` of the system the file was built on
+    NetBSD {
+        version: Option<u32>,
+    },
+    OpenBSD,
+    Solaris,
+}
+
+/// The operating system field of a GNU ABI tag
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GnuAbiOs {
+    Linux,
+    Hurd,
+    Solaris,
+    FreeBSD,
+    Unknown(u32),
+}
+
+impl GnuAbiOs {
+    pub const fn from_u32(x: u32) -> GnuAbiOs {
+        match x {
+            0 => Self::Linux,
+            1 => Self::Hurd,
+            2 => Self::Solaris,
+            3 => Self::FreeBSD,
+            _ => Self::Unknown(x),
+        }
+    }
+
+    pub const fn to_u32(self) -> u32 {
+        match self {
+            Self::Linux => 0,
+            Self::Hurd => 1,
+            Self::Solaris => 2,
+            Self::FreeBSD => 3,
+            Self::Unknown(x) => x,
+        }
+    }
+}
+
+/// Recognizes the files branded for one operating system
+pub trait OsBackend: Sync {
+    fn os_abi(&self) -> OsAbi;
+
+    /// Returns the brand of `elf`, or `None` if it was not built for this operating system
+    fn brand(&self, elf: &Elf) -> Option<Brand>;
+}
+
+struct GnuBackend;
+struct FreeBSDBackend;
+struct NetBSDBackend;
+struct OpenBSDBackend;
+struct SolarisBackend;
+
+/// Every built-in backend, in the order [`Elf::brand()`] tries them
+static BACKENDS: [&dyn OsBackend; 5] = [
+    &FreeBSDBackend,
+    &NetBSDBackend,
+    &OpenBSDBackend,
+    &SolarisBackend,
+    &GnuBackend,
+];
+
+/// Returns the built-in backend for `os_abi`
+pub fn os_backend_for(os_abi: OsAbi) -> Option<&'static dyn OsBackend> {
+    match os_abi {
+        OsAbi::Linux => Some(&GnuBackend),
+        OsAbi::FreeBSD => Some(&FreeBSDBackend),
+        OsAbi::NetBSD => Some(&NetBSDBackend),
+        OsAbi::OpenBSD => Some(&OpenBSDBackend),
+        OsAbi::Solaris => Some(&SolarisBackend),
+        _ => None,
+    }
+}
+
+/// Returns the descriptor of the first `NT_IDENT`-style note from `owner` as a `u32`
+fn ident_version(elf: &Elf, owner: &[u8]) -> Option<Option<u32>> {
+    let note = elf.find_note(owner, NT_IDENT)?;
+    Some(Encoding::of(elf).read_u32(note.desc()))
+}
+
+impl OsBackend for GnuBackend {
+    fn os_abi(&self) -> OsAbi {
+        OsAbi::Linux
+    }
+
+    fn brand(&self, elf: &Elf) -> Option<Brand> {
+        let desc = elf.find_note(b"GNU", NT_GNU_ABI_TAG)?.desc();
+        let encoding = Encoding::of(elf);
+        let word = |i: usize| encoding.read_u32(desc.get(i * 4..)?);
+
+        Some(Brand::Gnu {
+            os: GnuAbiOs::from_u32(word(0)?),
+            version: [word(1)?, word(2)?, word(3)?],
+        })
+    }
+}
+
+impl OsBackend for FreeBSDBackend {
+    fn os_abi(&self) -> OsAbi {
+        OsAbi::FreeBSD
+    }
+
+    fn brand(&self, elf: &Elf) -> Option<Brand> {
+        match ident_version(elf, b"FreeBSD") {
+            Some(version) => Some(Brand::FreeBSD { version }),
+            None if elf.os_abi() == OsAbi::FreeBSD => Some(Brand::FreeBSD { version: None }),
+            None => None,
+        }
+    }
+}
+
+impl OsBackend for NetBSDBackend {
+    fn os_abi(&self) -> OsAbi {
+        OsAbi::NetBSD
+    }
+
+    fn brand(&self, elf: &Elf) -> Option<Brand> {
+        match ident_version(elf, b"NetBSD") {
+            Some(version) => Some(Brand::NetBSD { version }),
+            None if elf.os_abi() == OsAbi::NetBSD => Some(Brand::NetBSD { version: None }),
+            None => None,
+        }
+    }
+}
+
+impl OsBackend for OpenBSDBackend {
+    fn os_abi(&self) -> OsAbi {
+        OsAbi::OpenBSD
+    }
+
+    fn brand(&self, elf: &Elf) -> Option<Brand> {
+        let branded = elf.os_abi() == OsAbi::OpenBSD || ident_version(elf, b"OpenBSD").is_some();
+        branded.then_some(Brand::OpenBSD)
+    }
+}
+
+impl OsBackend for SolarisBackend {
+    fn os_abi(&self) -> OsAbi {
+        OsAbi::Solaris
+    }
+
+    fn brand(&self, elf: &Elf) -> Option<Brand> {
+        let branded = elf.os_abi() == OsAbi::Solaris || elf.capabilities_section().is_some();
+        branded.then_some(Brand::Solaris)
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the operating system this file was built for
+    ///
+    /// The backend for the file's `EI_OSABI` is consulted first. If it has none, or does
+    /// not recognize the file, every built-in backend is tried in turn.
+    pub fn brand(&self) -> Option<Brand> {
+        os_backend_for(self.os_abi())
+            .and_then(|backend| backend.brand(self))
+            .or_else(|| BACKENDS.iter().find_map(|backend| backend.brand(self)))
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Lazily computed lookups cached inside [`Elf`](crate::Elf)
+//!
+//! Only the indices of well-known sections are cached, since they fit in atomics and so
+//! keep `Elf` `Sync` without allocating. Lookups of arbitrary names are not indexed.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const UNINIT: u32 = u32::MAX;
+const ABSENT: u32 = u32::MAX - 1;
+
+/// A table index that is computed on first use and then remembered
+///
+/// The computation must be deterministic; if two threads race to fill the cell
+/// they simply store the same value twice.
+pub(crate) struct LazyIndex(AtomicU32);
+
+impl LazyIndex {
+    #[inline]
+    pub const fn new() -> LazyIndex {
+        Self(AtomicU32::new(UNINIT))
+    }
+
+    pub fn get_or_init<F>(&self, f: F) -> Option<u32>
+    where
+        F: FnOnce() -> Option<u32>,
+    {
+        match self.0.load(Ordering::Relaxed) {
+            UNINIT => {
+                let index = f().filter(|&i| i < ABSENT);
+                self.0.store(index.unwrap_or(ABSENT), Ordering::Relaxed);
+                index
+            }
+            ABSENT => None,
+            index => Some(index),
+        }
+    }
+}
+
+/// Indices of the sections and segments `Elf` looks up repeatedly
+pub(crate) struct Cache {
+    pub symtab: LazyIndex,
+    pub strtab: LazyIndex,
+    pub dynsym: LazyIndex,
+    pub dynstr: LazyIndex,
+    pub hash: LazyIndex,
+    pub gnu_hash: LazyIndex,
+    pub verdef: LazyIndex,
+    pub dynamic: LazyIndex,
+}
+
+impl Cache {
+    pub const fn new() -> Cache {
+        Self {
+            symtab: LazyIndex::new(),
+            strtab: LazyIndex::new(),
+            dynsym: LazyIndex::new(),
+            dynstr: LazyIndex::new(),
+            hash: LazyIndex::new(),
+            gnu_hash: LazyIndex::new(),
+            verdef: LazyIndex::new(),
+            dynamic: LazyIndex::new(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use crate::{testdata::Fixture, Elf};
+
+    #[test]
+    fn sections_are_fetched_by_index() {
+        for fixture in Fixture::all() {
+            let file = fixture.build();
+            let elf = Elf::new(&file).unwrap();
+            for (index, sect) in elf.sections().enumerate() {
+                let fetched = elf.section(index as u16).unwrap();
+                assert_eq!(fetched.index(), sect.index());
+                assert_eq!(fetched.name(), sect.name());
+            }
+            assert!(elf.section(elf.shdr_num()).is_none());
+
+            // The cached lookup finds the same section each time.
+            for _ in 0..2 {
+                let symtab = elf.symtab_section().map(|sect| sect.index());
+                assert_eq!(
+                    symtab,
+                    elf.section_by_name(".symtab").map(|sect| sect.index())
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Hardware and software capability requirements
+//!
+//! Solaris records the capabilities a file requires in an `SHT_SUNW_cap` section, and
+//! NetBSD records the PaX protections a program opts in or out of in a `PaX` note.
+
+use crate::{table::Encoding, Elf, Section, SectionType};
+
+pub const CA_SUNW_NULL: u64 = 0;
+pub const CA_SUNW_HW_1: u64 = 1;
+pub const CA_SUNW_SF_1: u64 = 2;
+pub const CA_SUNW_HW_2: u64 = 3;
+pub const CA_SUNW_PLAT: u64 = 4;
+pub const CA_SUNW_MACH: u64 = 5;
+pub const CA_SUNW_ID: u64 = 6;
+
+pub const NT_NETBSD_PAX: u32 = 3;
+
+/// An entry of a Solaris capabilities section (`SHT_SUNW_cap`)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Capability {
+    pub tag: u64,
+    pub value: u64,
+}
+
+bitflags::bitflags! {
+    /// x86 hardware capabilities (`AV_386_*`), as found in `CA_SUNW_HW_1`
+    #[repr(transparent)]
+    pub struct X86HwCaps : u32 {
+        const FPU        = 0x0000_0001;
+        const TSC        = 0x0000_0002;
+        const CX8        = 0x0000_0004;
+        const SEP        = 0x0000_0008;
+        const AMD_SYSC   = 0x0000_0010;
+        const CMOV       = 0x0000_0020;
+        const MMX        = 0x0000_0040;
+        const AMD_MMX    = 0x0000_0080;
+        const AMD_3DNOW  = 0x0000_0100;
+        const AMD_3DNOWX = 0x0000_0200;
+        const FXSR       = 0x0000_0400;
+        const SSE        = 0x0000_0800;
+        const SSE2       = 0x0000_1000;
+        const PAUSE      = 0x0000_2000;
+        const SSE3       = 0x0000_4000;
+        const MON        = 0x0000_8000;
+        const CX16       = 0x0001_0000;
+        const AHF        = 0x0002_0000;
+        const TSCP       = 0x0004_0000;
+        const AMD_SSE4A  = 0x0008_0000;
+        const POPCNT     = 0x0010_0000;
+        const AMD_LZCNT  = 0x0020_0000;
+        const SSSE3      = 0x0040_0000;
+        const SSE4_1     = 0x0080_0000;
+        const SSE4_2     = 0x0100_0000;
+        const MOVBE      = 0x0200_0000;
+        const AES        = 0x0400_0000;
+        const PCLMULQDQ  = 0x0800_0000;
+        const XSAVE      = 0x1000_0000;
+        const AVX        = 0x2000_0000;
+        const VMX        = 0x4000_0000;
+        const AMD_SVM    = 0x8000_0000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Software capabilities (`SF1_SUNW_*`), as found in `CA_SUNW_SF_1`
+    #[derive(Default)]
+    #[repr(transparent)]
+    pub struct SoftwareCaps : u64 {
+        /// The frame pointer usage of the file is known
+        const FPKNWN = 0x1;
+        /// The file uses the frame pointer
+        const FPUSED = 0x2;
+        /// The file requires a 32-bit address space
+        const ADDR32 = 0x4;
+    }
+}
+
+bitflags::bitflags! {
+    /// NetBSD PaX protections (`NT_NETBSD_PAX_*`)
+    #[repr(transparent)]
+    pub struct PaxFlags : u32 {
+        const MPROTECT   = 0x01;
+        const NOMPROTECT = 0x02;
+        const GUARD      = 0x04;
+        const NOGUARD    = 0x08;
+        const ASLR       = 0x10;
+        const NOASLR     = 0x20;
+    }
+}
+
+/// The capabilities required by a file, as returned by [`Elf::required_capabilities()`]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RequiredCapabilities {
+    /// Machine-specific hardware capabilities (`CA_SUNW_HW_1`)
+    pub hardware: u64,
+    /// Further machine-specific hardware capabilities (`CA_SUNW_HW_2`)
+    pub hardware2: u64,
+    pub software: SoftwareCaps,
+    /// The PaX note, if the file has one
+    pub pax: Option<PaxFlags>,
+}
+
+impl RequiredCapabilities {
+    /// Returns the hardware capabilities interpreted for x86
+    #[inline]
+    pub const fn x86(&self) -> X86HwCaps {
+        X86HwCaps::from_bits_truncate(self.hardware as u32)
+    }
+
+    /// Returns `true` if the file requires every x86 capability in `caps`
+    #[inline]
+    pub const fn requires_x86(&self, caps: X86HwCaps) -> bool {
+        self.x86().contains(caps)
+    }
+
+    /// Returns `true` if the file is exempted from W^X enforcement
+    #[inline]
+    pub fn wx_exempt(&self) -> bool {
+        self.pax
+            .is_some_and(|pax| pax.contains(PaxFlags::NOMPROTECT))
+    }
+
+    /// Returns `true` if the file opts out of address space layout randomization
+    #[inline]
+    pub fn aslr_exempt(&self) -> bool {
+        self.pax.is_some_and(|pax| pax.contains(PaxFlags::NOASLR))
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the `SHT_SUNW_cap` section, if there is one
+    pub fn capabilities_section(&self) -> Option<Section<'_, 'elf>> {
+        self.sections()
+            .find(|sect| sect.section_type() == SectionType::SUNW_CAP)
+    }
+
+    /// Returns an iterator over the entries of the Solaris capabilities section
+    ///
+    /// Iteration stops at the first `CA_SUNW_NULL` entry.
+    pub fn capabilities(&self) -> impl Iterator<Item = Capability> + 'elf {
+        let data = self
+            .capabilities_section()
+            .and_then(|sect| sect.file_data())
+            .unwrap_or_default();
+        let encoding = Encoding::of(self);
+        let size = 2 * encoding.word_size();
+
+        data.chunks_exact(size)
+            .map(move |entry| Capability {
+                tag: encoding.read_word(entry).unwrap_or(CA_SUNW_NULL),
+                value: encoding.read_word(&entry[size / 2..]).unwrap_or(0),
+            })
+            .take_while(|cap| cap.tag != CA_SUNW_NULL)
+    }
+
+    /// Returns the flags of the NetBSD PaX note, if there is one
+    pub fn pax_flags(&self) -> Option<PaxFlags> {
+        let note = self.find_note(b"PaX", NT_NETBSD_PAX)?;
+        let flags = Encoding::of(self).read_u32(note.desc())?;
+        Some(PaxFlags::from_bits_truncate(flags))
+    }
+
+    /// Returns the capabilities this file requires of the hardware and operating system
+    pub fn required_capabilities(&self) -> RequiredCapabilities {
+        let mut caps = RequiredCapabilities {
+            pax: self.pax_flags(),
+            ..Default::default()
+        };
+
+        for cap in self.capabilities() {
+            match cap.tag {
+                CA_SUNW_HW_1 => caps.hardware |= cap.value,
+                CA_SUNW_HW_2 => caps.hardware2 |= cap.value,
+                CA_SUNW_SF_1 => caps.software |= SoftwareCaps::from_bits_truncate(cap.value),
+                _ => {}
+            }
+        }
+
+        caps
+    }
+}
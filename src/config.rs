@@ -0,0 +1,140 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Key/value configuration notes
+//!
+//! A kernel declares its requirements, such as its stack size or the features it expects,
+//! in a note owned by [`CONFIG_NOTE_NAME`]. The descriptor is a sequence of entries, each a
+//! key and a value terminated by NUL bytes. Numeric values are written in decimal, or in
+//! hexadecimal with a `0x` prefix.
+
+use crate::{note_size, write_note, Elf};
+
+/// The owner of configuration notes
+pub const CONFIG_NOTE_NAME: &[u8] = b"bolt-os";
+
+/// The type of a configuration note
+pub const NT_BOLT_CONFIG: u32 = 1;
+
+/// Well-known configuration keys
+pub mod config_keys {
+    /// Size of the initial stack, in bytes
+    pub const STACK_SIZE: &str = "stack-size";
+    /// A comma-separated list of features the kernel requires
+    pub const FEATURES: &str = "features";
+    /// The kernel command line
+    pub const CMDLINE: &str = "cmdline";
+}
+
+/// The entries of a configuration note
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigNote<'elf> {
+    desc: &'elf [u8],
+}
+
+impl<'elf> ConfigNote<'elf> {
+    #[inline]
+    pub const fn new(desc: &'elf [u8]) -> ConfigNote<'elf> {
+        Self { desc }
+    }
+
+    /// Returns an iterator over the key/value pairs, in order
+    ///
+    /// Iteration stops at the first malformed entry.
+    pub fn entries(&self) -> impl Iterator<Item = (&'elf str, &'elf str)> {
+        let mut strings = self
+            .desc
+            .split(|&b| b == 0)
+            .map(|s| core::str::from_utf8(s).ok());
+        // A well-formed descriptor ends with a NUL, leaving an empty final piece, but
+        // padding may add more.
+        core::iter::from_fn(move || match (strings.next()??, strings.next()??) {
+            ("", _) => None,
+            (key, value) => Some((key, value)),
+        })
+    }
+
+    /// Returns the value of the last entry with the given key
+    pub fn get(&self, key: &str) -> Option<&'elf str> {
+        self.entries()
+            .filter(|&(k, _)| k == key)
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value of the last entry with the given key, parsed as an integer
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        parse_u64(self.get(key)?)
+    }
+}
+
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Returns the size of the configuration note holding `entries`
+pub fn config_note_size(entries: &[(&str, &str)]) -> usize {
+    let desc_len = entries.iter().map(|(k, v)| k.len() + v.len() + 2).sum();
+    note_size(CONFIG_NOTE_NAME.len(), desc_len)
+}
+
+/// Write a configuration note holding `entries` to the start of `buf`
+///
+/// Returns the number of bytes written, or `None` if `buf` is too small or a key is empty
+/// or either a key or value contains a NUL byte. The result can be placed in an `SHT_NOTE`
+/// section, for example with [`ElfMut::append_section()`](crate::ElfMut::append_section).
+pub fn write_config_note(buf: &mut [u8], entries: &[(&str, &str)]) -> Option<usize> {
+    let valid = entries
+        .iter()
+        .all(|(k, v)| !k.is_empty() && !k.contains('\0') && !v.contains('\0'));
+    if !valid {
+        return None;
+    }
+
+    let desc_len = entries.iter().map(|(k, v)| k.len() + v.len() + 2).sum();
+    write_note(buf, CONFIG_NOTE_NAME, NT_BOLT_CONFIG, desc_len, |desc| {
+        let mut at = 0;
+        for s in entries.iter().flat_map(|(k, v)| [k, v]) {
+            desc[at..][..s.len()].copy_from_slice(s.as_bytes());
+            at += s.len() + 1;
+        }
+    })
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the configuration note, if there is one
+    pub fn config_note(&self) -> Option<ConfigNote<'elf>> {
+        let note = self.find_note(CONFIG_NOTE_NAME, NT_BOLT_CONFIG)?;
+        Some(ConfigNote::new(note.desc()))
+    }
+}
@@ -0,0 +1,798 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Core dumps
+
+use crate::{
+    table::Encoding, Elf, ElfType, Machine, Note, NoteIter, ParseError, SegmentKind, Structure,
+    Symbolized, Symbolizer, NT_GNU_BUILD_ID,
+};
+use core::fmt;
+
+/// Status and general-purpose registers of a thread
+pub const NT_PRSTATUS: u32 = 1;
+/// Floating-point registers of a thread
+pub const NT_FPREGSET: u32 = 2;
+/// Information about the process
+pub const NT_PRPSINFO: u32 = 3;
+/// The auxiliary vector passed to the process
+pub const NT_AUXV: u32 = 6;
+/// The `siginfo_t` of the signal which caused the dump
+pub const NT_SIGINFO: u32 = 0x53494749;
+/// Files mapped into the process
+pub const NT_FILE: u32 = 0x46494c45;
+
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_BASE: u64 = 7;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_EXECFN: u64 = 31;
+pub const AT_SYSINFO_EHDR: u64 = 33;
+
+/// The name given to the vDSO by [`Core::loaded_objects()`]
+pub const VDSO_NAME: &[u8] = b"[vdso]";
+
+/// Why memory could not be read from a core dump
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MemoryError {
+    /// The address was not mapped in the process
+    Unmapped(u64),
+    /// The address was mapped, but its contents were excluded from the dump
+    ///
+    /// This is usually the case for unmodified file-backed mappings such as code.
+    NotDumped(u64),
+    /// The contents of the address lie past the end of the file
+    Truncated(u64),
+    /// The range continues into another segment, so it cannot be returned as one slice
+    CrossesSegment(u64),
+}
+
+/// A view of an `ET_CORE` file
+///
+/// The notes of a core dump are laid out as the structures of the dumping kernel, which are
+/// only decoded for little-endian ELF64 files.
+#[derive(Clone, Copy)]
+pub struct Core<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+}
+
+impl<'a, 'elf> Core<'a, 'elf> {
+    pub fn new(elf: &'a Elf<'elf>) -> Result<Core<'a, 'elf>, ParseError> {
+        let error = |offset, message| Err(ParseError::new(Structure::FileHeader, offset, message));
+
+        if elf.file_type() != ElfType::Core {
+            return error(0x10, "not a core file");
+        }
+        if Encoding::of(elf) != Encoding::NATIVE {
+            return error(4, "not a little-endian ELF64 file");
+        }
+        Ok(Self { elf })
+    }
+
+    #[inline]
+    pub fn elf(&self) -> &'a Elf<'elf> {
+        self.elf
+    }
+
+    /// Returns an iterator over the notes in the `PT_NOTE` segments, in file order
+    pub fn notes(&self) -> impl Iterator<Item = Note<'elf>> + 'a {
+        self.elf
+            .segments()
+            .filter(|sgmt| sgmt.kind() == SegmentKind::Note)
+            .flat_map(|sgmt| {
+                let data = self
+                    .elf
+                    .data
+                    .get(sgmt.file_offset()..)
+                    .and_then(|data| data.get(..sgmt.file_size()))
+                    .unwrap_or(&[]);
+                NoteIter::new(data, sgmt.alignment())
+            })
+    }
+
+    /// Returns the contents of the process memory at `vaddr`
+    ///
+    /// The whole range must lie in one `PT_LOAD` segment; use
+    /// [`read_into()`](Core::read_into) to read across segment boundaries.
+    pub fn read_memory(&self, vaddr: u64, len: usize) -> Result<&'elf [u8], MemoryError> {
+        let end = vaddr
+            .checked_add(len as u64)
+            .ok_or(MemoryError::Unmapped(vaddr))?;
+
+        let sgmt = self
+            .elf
+            .segments()
+            .filter(|sgmt| sgmt.kind() == SegmentKind::Load)
+            .find(|sgmt| {
+                let start = sgmt.virtual_address();
+                vaddr >= start && vaddr - start < sgmt.mem_size() as u64
+            })
+            .ok_or(MemoryError::Unmapped(vaddr))?;
+
+        let start = sgmt.virtual_address();
+        let mem_end = start.saturating_add(sgmt.mem_size() as u64);
+        if end > mem_end {
+            return Err(MemoryError::CrossesSegment(mem_end));
+        }
+
+        let offset = vaddr - start;
+        let file_size = sgmt.file_size() as u64;
+        if offset + len as u64 > file_size {
+            return Err(MemoryError::NotDumped(start + file_size.max(offset)));
+        }
+
+        let file_start = sgmt.file_offset().saturating_add(offset as usize);
+        let available = self.elf.data.len().saturating_sub(file_start);
+        if available < len {
+            return Err(MemoryError::Truncated(vaddr + available as u64));
+        }
+
+        Ok(&self.elf.data[file_start..][..len])
+    }
+
+    /// Fill `buf` with the contents of the process memory at `vaddr`, which may span
+    /// several segments
+    pub fn read_into(&self, mut vaddr: u64, mut buf: &mut [u8]) -> Result<(), MemoryError> {
+        while !buf.is_empty() {
+            let len = match self.read_memory(vaddr, buf.len()) {
+                Err(MemoryError::CrossesSegment(end)) => (end - vaddr) as usize,
+                result => result?.len(),
+            };
+            let data = self.read_memory(vaddr, len)?;
+            let (head, tail) = buf.split_at_mut(len);
+            head.copy_from_slice(data);
+            buf = tail;
+            vaddr += len as u64;
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the file-backed mappings recorded in the `NT_FILE` note
+    ///
+    /// The iterator is empty if there is no such note.
+    pub fn file_mappings(&self) -> FileMappings<'elf> {
+        let desc = self
+            .notes()
+            .find(|note| note.is(b"CORE", NT_FILE))
+            .map_or(&[][..], |note| note.desc());
+        FileMappings::new(desc)
+    }
+
+    /// Returns the file mapping which contains `vaddr`
+    pub fn mapping_for(&self, vaddr: u64) -> Option<FileMapping<'elf>> {
+        self.file_mappings()
+            .find(|mapping| (mapping.start..mapping.end).contains(&vaddr))
+    }
+
+    /// Returns an iterator over the threads of the process
+    ///
+    /// The kernel writes the notes of each thread together, starting with its
+    /// `NT_PRSTATUS` note, and the first thread is the one which received the signal.
+    pub fn threads(&self) -> impl Iterator<Item = Thread<'a, 'elf>> + 'a {
+        let core = *self;
+        let is_status = |note: &Note| note.is(b"CORE", NT_PRSTATUS);
+        let mut notes = self.notes().enumerate().peekable();
+
+        core::iter::from_fn(move || loop {
+            let (first, note) = notes.find(|(_, note)| is_status(note))?;
+            let mut count = 1;
+            while notes.next_if(|(_, note)| !is_status(note)).is_some() {
+                count += 1;
+            }
+
+            if let Some(status) = PrStatus::new(note.desc()) {
+                return Some(Thread {
+                    core,
+                    status,
+                    first,
+                    count,
+                });
+            }
+        })
+    }
+
+    /// Returns an iterator over the entries of the `NT_AUXV` note, up to `AT_NULL`
+    pub fn auxv(&self) -> impl Iterator<Item = (u64, u64)> + 'elf {
+        let desc = self
+            .notes()
+            .find(|note| note.is(b"CORE", NT_AUXV))
+            .map_or(&[][..], |note| note.desc());
+
+        desc.chunks_exact(16)
+            .map(|entry| (read_u64(entry, 0).unwrap(), read_u64(entry, 8).unwrap()))
+            .take_while(|&(key, _)| key != AT_NULL)
+    }
+
+    /// Returns the value of an auxiliary vector entry
+    pub fn auxv_value(&self, key: u64) -> Option<u64> {
+        self.auxv().find(|&(k, _)| k == key).map(|(_, value)| value)
+    }
+
+    /// Returns an iterator over the ELF objects mapped into the process
+    ///
+    /// Objects are found from the `NT_FILE` mappings of offset zero whose memory starts
+    /// with an ELF header, followed by the vDSO, which is located through
+    /// `AT_SYSINFO_EHDR`. The header page must have been included in the dump, which it
+    /// is by default.
+    pub fn loaded_objects(&self) -> impl Iterator<Item = LoadedObject<'elf>> + 'a {
+        let this = *self;
+        let files = self
+            .file_mappings()
+            .filter(|mapping| mapping.file_offset == 0)
+            .filter_map(move |mapping| this.loaded_object(mapping.name, mapping.start));
+        let vdso = self
+            .auxv_value(AT_SYSINFO_EHDR)
+            .and_then(|base| this.loaded_object(VDSO_NAME, base));
+
+        files.chain(vdso)
+    }
+
+    /// Returns the loaded object whose mappings contain `vaddr`
+    pub fn object_at(&self, vaddr: u64) -> Option<LoadedObject<'elf>> {
+        match self.mapping_for(vaddr) {
+            Some(mapping) => self
+                .loaded_objects()
+                .filter(|obj| obj.name == mapping.name && obj.base <= vaddr)
+                .max_by_key(|obj| obj.base),
+            None => {
+                let base = self.auxv_value(AT_SYSINFO_EHDR)?;
+                let vdso = self.loaded_object(VDSO_NAME, base)?;
+                let sgmt = self.load_segment(base)?;
+                let end = sgmt.virtual_address() + sgmt.mem_size() as u64;
+                (vaddr >= base && vaddr < end).then_some(vdso)
+            }
+        }
+    }
+
+    fn load_segment(&self, vaddr: u64) -> Option<crate::Segment<'a, 'elf>> {
+        self.elf.segments().find(|sgmt| {
+            let start = sgmt.virtual_address();
+            sgmt.kind() == SegmentKind::Load
+                && vaddr >= start
+                && vaddr - start < sgmt.mem_size() as u64
+        })
+    }
+
+    /// Read the headers of the ELF object mapped at `base`
+    fn loaded_object(&self, name: &'elf [u8], base: u64) -> Option<LoadedObject<'elf>> {
+        let ehdr = self.read_memory(base, 64).ok()?;
+        if !ehdr.starts_with(b"\x7fELF") {
+            return None;
+        }
+        let phoff = read_u64(ehdr, 0x20)?;
+        let phnum = u16::from_le_bytes([ehdr[0x38], ehdr[0x39]]);
+
+        let phdr = |i: u16| {
+            let address = base.checked_add(phoff)?.checked_add(i as u64 * 56)?;
+            let phdr = self.read_memory(address, 56).ok()?;
+            let kind = u32::from_le_bytes(phdr[..4].try_into().unwrap());
+            Some((
+                kind,
+                read_u64(phdr, 8)?,
+                read_u64(phdr, 16)?,
+                read_u64(phdr, 32)?,
+            ))
+        };
+
+        // The mapping at file offset zero holds the start of the first PT_LOAD segment.
+        let (_, offset, vaddr, _) = (0..phnum)
+            .filter_map(phdr)
+            .filter(|&(kind, ..)| kind == 1)
+            .min_by_key(|&(_, offset, ..)| offset)?;
+        let bias = base.wrapping_sub(vaddr.wrapping_sub(offset));
+
+        let build_id = (0..phnum)
+            .filter_map(phdr)
+            .filter(|&(kind, ..)| kind == 4)
+            .filter_map(|(_, _, vaddr, size)| {
+                let data = self
+                    .read_memory(vaddr.wrapping_add(bias), usize::try_from(size).ok()?)
+                    .ok()?;
+                NoteIter::new(data, 4).find(|note| note.is(b"GNU", NT_GNU_BUILD_ID))
+            })
+            .map(|note| note.desc())
+            .next();
+
+        Some(LoadedObject {
+            name,
+            base,
+            bias,
+            build_id,
+        })
+    }
+}
+
+/// An ELF object mapped into a crashed process, found by [`Core::loaded_objects()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LoadedObject<'elf> {
+    /// Path of the object, or [`VDSO_NAME`]
+    pub name: &'elf [u8],
+    /// Address of the object's ELF header
+    pub base: u64,
+    /// Difference between the object's run-time and link-time addresses
+    pub bias: u64,
+    /// The object's build ID, if it has one and its notes were dumped
+    pub build_id: Option<&'elf [u8]>,
+}
+
+impl LoadedObject<'_> {
+    /// Returns `true` if `elf` is the file this object was loaded from, by build ID
+    pub fn matches(&self, elf: &Elf) -> bool {
+        self.build_id.is_some() && self.build_id == elf.build_id()
+    }
+}
+
+/// A symbol resolved through [`CoreView::symbolize()`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CoreSymbol<'a> {
+    /// The object containing the address
+    pub object: LoadedObject<'a>,
+    /// Index of the matching file in the slice given to [`CoreView::new()`]
+    pub file: usize,
+    pub symbol: Symbolized<'a>,
+}
+
+/// A core dump together with the files of the objects loaded in the process
+///
+/// Files are matched to the loaded objects by build ID, and addresses in the process are
+/// translated to link-time addresses of the matching file using the object's load bias.
+#[derive(Clone, Copy)]
+pub struct CoreView<'a> {
+    core: Core<'a, 'a>,
+    files: &'a [Elf<'a>],
+}
+
+impl<'a> CoreView<'a> {
+    pub fn new(core: Core<'a, 'a>, files: &'a [Elf<'a>]) -> CoreView<'a> {
+        Self { core, files }
+    }
+
+    #[inline]
+    pub fn core(&self) -> &Core<'a, 'a> {
+        &self.core
+    }
+
+    /// Returns the object containing `vaddr`, and the index of its file if one matched
+    pub fn object_at(&self, vaddr: u64) -> Option<(LoadedObject<'a>, Option<usize>)> {
+        let object = self.core.object_at(vaddr)?;
+        let file = self.files.iter().position(|elf| object.matches(elf));
+        Some((object, file))
+    }
+
+    /// Returns the link-time address of `vaddr` in its object's file
+    pub fn file_address(&self, vaddr: u64) -> Option<(usize, u64)> {
+        let (object, file) = self.object_at(vaddr)?;
+        Some((file?, vaddr.wrapping_sub(object.bias)))
+    }
+
+    /// Find the symbol for `vaddr`, an address in the crashed process
+    pub fn symbolize(&self, vaddr: u64) -> Option<CoreSymbol<'a>> {
+        let (object, file) = self.object_at(vaddr)?;
+        let file = file?;
+        let symbol =
+            Symbolizer::new(&self.files[file]).symbolize(vaddr.wrapping_sub(object.bias))?;
+
+        Some(CoreSymbol {
+            object,
+            file,
+            symbol,
+        })
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns a view of this file as a core dump, or `None` if it is not one
+    pub fn as_core(&self) -> Option<Core<'_, 'elf>> {
+        Core::new(self).ok()
+    }
+}
+
+/// A file mapped into the process, from the `NT_FILE` note
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FileMapping<'elf> {
+    pub start: u64,
+    pub end: u64,
+    /// Offset in the file of the first mapped byte
+    pub file_offset: u64,
+    /// Path of the file, as the kernel saw it
+    pub name: &'elf [u8],
+}
+
+/// An iterator over the entries of an `NT_FILE` note, returned by [`Core::file_mappings()`]
+///
+/// Iteration stops at the first malformed entry.
+#[derive(Clone, Debug)]
+pub struct FileMappings<'elf> {
+    entries: &'elf [u8],
+    names: &'elf [u8],
+    page_size: u64,
+}
+
+impl<'elf> FileMappings<'elf> {
+    /// Parse the descriptor of an `NT_FILE` note
+    pub fn new(desc: &'elf [u8]) -> FileMappings<'elf> {
+        let empty = FileMappings {
+            entries: &[],
+            names: &[],
+            page_size: 0,
+        };
+        let (Some(count), Some(page_size)) = (read_u64(desc, 0), read_u64(desc, 8)) else {
+            return empty;
+        };
+
+        let table_len = count
+            .checked_mul(24)
+            .and_then(|len| usize::try_from(len).ok())
+            .and_then(|len| len.checked_add(16));
+        match table_len.filter(|&len| len <= desc.len()) {
+            Some(len) => FileMappings {
+                entries: &desc[16..len],
+                names: &desc[len..],
+                page_size,
+            },
+            None => empty,
+        }
+    }
+}
+
+impl<'elf> Iterator for FileMappings<'elf> {
+    type Item = FileMapping<'elf>;
+
+    fn next(&mut self) -> Option<FileMapping<'elf>> {
+        let start = read_u64(self.entries, 0)?;
+        let end = read_u64(self.entries, 8)?;
+        let file_offset = read_u64(self.entries, 16)?.checked_mul(self.page_size);
+        let name_len = self.names.iter().position(|&b| b == 0);
+
+        let (Some(file_offset), Some(name_len)) = (file_offset, name_len) else {
+            self.entries = &[];
+            return None;
+        };
+
+        let name = &self.names[..name_len];
+        self.entries = &self.entries[24..];
+        self.names = &self.names[name_len + 1..];
+
+        Some(FileMapping {
+            start,
+            end,
+            file_offset,
+            name,
+        })
+    }
+}
+
+/// A thread of a crashed process, returned by [`Core::threads()`]
+#[derive(Clone, Copy)]
+pub struct Thread<'a, 'elf> {
+    core: Core<'a, 'elf>,
+    status: PrStatus<'elf>,
+    /// Index of the `NT_PRSTATUS` note among the notes of the core
+    first: usize,
+    /// Number of notes up to the next thread's
+    count: usize,
+}
+
+impl<'a, 'elf> Thread<'a, 'elf> {
+    #[inline]
+    pub fn core(&self) -> &Core<'a, 'elf> {
+        &self.core
+    }
+
+    #[inline]
+    pub fn status(&self) -> &PrStatus<'elf> {
+        &self.status
+    }
+
+    /// Returns the thread ID
+    #[inline]
+    pub fn tid(&self) -> i32 {
+        self.status.pid()
+    }
+
+    /// Returns the thread's general-purpose registers, decoded for the core's machine
+    pub fn registers(&self) -> Option<Registers> {
+        self.status.registers(self.core.elf.machine())
+    }
+
+    /// Returns an iterator over the notes which belong to this thread, starting with its
+    /// `NT_PRSTATUS`
+    ///
+    /// The process-wide notes which the kernel writes among the first thread's notes are
+    /// not included.
+    pub fn notes(&self) -> impl Iterator<Item = Note<'elf>> + 'a {
+        self.core
+            .notes()
+            .skip(self.first)
+            .take(self.count)
+            .filter(|note| {
+                !(note.name() == b"CORE" && matches!(note.kind(), NT_PRPSINFO | NT_AUXV | NT_FILE))
+            })
+    }
+
+    /// Returns the thread's note of the given type, such as `NT_FPREGSET`
+    ///
+    /// Notes from any owner match, since extended register sets are owned by `LINUX`
+    /// rather than `CORE`.
+    pub fn note(&self, kind: u32) -> Option<Note<'elf>> {
+        self.notes().find(|note| note.kind() == kind)
+    }
+
+    /// Returns the raw `siginfo_t` of the signal which caused the dump, if this thread
+    /// received it
+    pub fn siginfo(&self) -> Option<&'elf [u8]> {
+        Some(self.note(NT_SIGINFO)?.desc())
+    }
+}
+
+impl fmt::Debug for Thread<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Thread")
+            .field("tid", &self.tid())
+            .field("signal", &self.status.current_signal())
+            .field("registers", &self.registers())
+            .finish()
+    }
+}
+
+/// The descriptor of an `NT_PRSTATUS` note, as written by Linux for 64-bit processes
+#[derive(Clone, Copy, Debug)]
+pub struct PrStatus<'elf> {
+    desc: &'elf [u8],
+}
+
+impl<'elf> PrStatus<'elf> {
+    /// Offset of `pr_reg` within the descriptor
+    const REGS_OFFSET: usize = 112;
+
+    /// Parse the descriptor of an `NT_PRSTATUS` note
+    pub fn new(desc: &'elf [u8]) -> Option<PrStatus<'elf>> {
+        (desc.len() >= Self::REGS_OFFSET).then_some(Self { desc })
+    }
+
+    fn i32_at(&self, offset: usize) -> i32 {
+        i32::from_le_bytes(self.desc[offset..][..4].try_into().unwrap())
+    }
+
+    /// Returns the number of the signal which caused the dump
+    #[inline]
+    pub fn signal(&self) -> i32 {
+        self.i32_at(0)
+    }
+
+    #[inline]
+    pub fn signal_code(&self) -> i32 {
+        self.i32_at(4)
+    }
+
+    #[inline]
+    pub fn errno(&self) -> i32 {
+        self.i32_at(8)
+    }
+
+    /// Returns the signal the thread was handling
+    #[inline]
+    pub fn current_signal(&self) -> i16 {
+        i16::from_le_bytes([self.desc[12], self.desc[13]])
+    }
+
+    /// Returns the set of pending signals
+    #[inline]
+    pub fn pending_signals(&self) -> u64 {
+        read_u64(self.desc, 16).unwrap()
+    }
+
+    /// Returns the set of blocked signals
+    #[inline]
+    pub fn held_signals(&self) -> u64 {
+        read_u64(self.desc, 24).unwrap()
+    }
+
+    /// Returns the thread ID
+    #[inline]
+    pub fn pid(&self) -> i32 {
+        self.i32_at(32)
+    }
+
+    #[inline]
+    pub fn ppid(&self) -> i32 {
+        self.i32_at(36)
+    }
+
+    #[inline]
+    pub fn pgrp(&self) -> i32 {
+        self.i32_at(40)
+    }
+
+    #[inline]
+    pub fn sid(&self) -> i32 {
+        self.i32_at(44)
+    }
+
+    /// Returns the raw general-purpose register set
+    #[inline]
+    pub fn raw_registers(&self) -> &'elf [u8] {
+        &self.desc[Self::REGS_OFFSET..]
+    }
+
+    /// Decode the general-purpose registers for `machine`, which is usually the machine
+    /// of the core file
+    ///
+    /// This does not depend on the architecture of the host.
+    pub fn registers(&self, machine: Machine) -> Option<Registers> {
+        Registers::parse(machine, self.raw_registers())
+    }
+}
+
+/// General-purpose registers of a thread, in the layout of `user_regs_struct`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Registers {
+    X86_64(X86_64Registers),
+    Aarch64(Aarch64Registers),
+    Riscv(RiscvRegisters),
+}
+
+impl Registers {
+    /// Decode a register set for `machine`
+    ///
+    /// Returns `None` if the machine is not supported or `data` is too short.
+    pub fn parse(machine: Machine, data: &[u8]) -> Option<Registers> {
+        match machine {
+            Machine::X86_64 => {
+                let [r15, r14, r13, r12, rbp, rbx, r11, r10, r9, r8, rax, rcx, rdx, rsi, rdi, orig_rax, rip, cs, eflags, rsp, ss, fs_base, gs_base, ds, es, fs, gs] =
+                    words(data)?;
+                Some(Registers::X86_64(X86_64Registers {
+                    r15,
+                    r14,
+                    r13,
+                    r12,
+                    rbp,
+                    rbx,
+                    r11,
+                    r10,
+                    r9,
+                    r8,
+                    rax,
+                    rcx,
+                    rdx,
+                    rsi,
+                    rdi,
+                    orig_rax,
+                    rip,
+                    cs,
+                    eflags,
+                    rsp,
+                    ss,
+                    fs_base,
+                    gs_base,
+                    ds,
+                    es,
+                    fs,
+                    gs,
+                }))
+            }
+            Machine::Aarch64 => {
+                let regs: [u64; 34] = words(data)?;
+                Some(Registers::Aarch64(Aarch64Registers {
+                    x: regs[..31].try_into().unwrap(),
+                    sp: regs[31],
+                    pc: regs[32],
+                    pstate: regs[33],
+                }))
+            }
+            Machine::Riscv => {
+                let mut x: [u64; 32] = words(data)?;
+                let pc = core::mem::replace(&mut x[0], 0);
+                Some(Registers::Riscv(RiscvRegisters { pc, x }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the program counter
+    pub const fn pc(&self) -> u64 {
+        match self {
+            Registers::X86_64(regs) => regs.rip,
+            Registers::Aarch64(regs) => regs.pc,
+            Registers::Riscv(regs) => regs.pc,
+        }
+    }
+
+    /// Returns the stack pointer
+    pub const fn sp(&self) -> u64 {
+        match self {
+            Registers::X86_64(regs) => regs.rsp,
+            Registers::Aarch64(regs) => regs.sp,
+            Registers::Riscv(regs) => regs.x[2],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct X86_64Registers {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    /// The system call number, if the thread was in a system call
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Aarch64Registers {
+    /// `x0` through `x30`
+    pub x: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub pstate: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RiscvRegisters {
+    pub pc: u64,
+    /// `x0` through `x31`, where `x0` is always zero
+    pub x: [u64; 32],
+}
+
+/// Read an array of little-endian words from the start of `data`
+fn words<const N: usize>(data: &[u8]) -> Option<[u64; N]> {
+    let data = data.get(..N * 8)?;
+    Some(core::array::from_fn(|i| read_u64(data, i * 8).unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..)?.get(..8)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
@@ -0,0 +1,274 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Global constructors in relocatable files
+
+use crate::{
+    init_priority, split_priority_suffix, table::Encoding, Elf, ParseError, Section, SectionType,
+};
+
+/// The kind of section a constructor table came from
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ConstructorKind {
+    /// `.init_array` or `.init_array.NNNNN`, run in order
+    InitArray,
+    /// `.ctors` or `.ctors.NNNNN`, run in reverse order
+    Ctors,
+}
+
+/// A section of constructor pointers
+pub struct ConstructorSection<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    section: Section<'a, 'elf>,
+    kind: ConstructorKind,
+    priority: Option<u16>,
+}
+
+/// A constructor to be run
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Constructor {
+    /// Offset of the function pointer within its constructor section
+    pub offset: u64,
+    /// The function, as an offset into [`section`]
+    ///
+    /// [`section`]: Constructor::section
+    pub address: u64,
+    /// Index of the section containing the function, if the pointer is relocated
+    pub section: Option<u16>,
+}
+
+impl<'a, 'elf> ConstructorSection<'a, 'elf> {
+    #[inline]
+    pub fn section(&self) -> &Section<'a, 'elf> {
+        &self.section
+    }
+
+    #[inline]
+    pub const fn kind(&self) -> ConstructorKind {
+        self.kind
+    }
+
+    /// Returns the initialization priority, with `.ctors` priorities converted to their
+    /// `.init_array` equivalents, or `None` for unsuffixed sections
+    #[inline]
+    pub const fn priority(&self) -> Option<u16> {
+        self.priority
+    }
+
+    /// Returns an iterator over the constructors in the order they should be run
+    ///
+    /// Returns an error if the relocations against the section are malformed.
+    pub fn constructors(&self) -> Result<impl Iterator<Item = Constructor> + 'a, ParseError> {
+        let elf = self.elf;
+        let relas = elf.relas_for_section(self.section.index())?;
+        let data = self.section.file_data().unwrap_or_default();
+        let encoding = Encoding::of(elf);
+        let word_size = encoding.word_size();
+        let count = data.len() / word_size;
+        let reverse = self.kind == ConstructorKind::Ctors;
+
+        Ok((0..count).map(move |i| {
+            let i = if reverse { count - 1 - i } else { i };
+            let offset = (i * word_size) as u64;
+            let raw = encoding.read_word(&data[i * word_size..]).unwrap_or(0);
+            let (address, section) = elf.relocated_address(relas, offset, raw);
+
+            Constructor {
+                offset,
+                address,
+                section,
+            }
+        }))
+    }
+}
+
+/// Classify a constructor section by name
+fn constructor_priority(name: &str) -> Option<(ConstructorKind, Option<u16>)> {
+    let kind = match split_priority_suffix(name).0 {
+        ".init_array" => ConstructorKind::InitArray,
+        ".ctors" => ConstructorKind::Ctors,
+        _ => return None,
+    };
+
+    Some((kind, init_priority(name)))
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over the constructor sections in the order they should be run
+    ///
+    /// Sections with a priority suffix run first, from the lowest priority to the highest,
+    /// followed by unsuffixed sections. Ties are broken by section index. This matches the
+    /// order in which the linker combines these sections into `.init_array`.
+    pub fn constructor_sections(&self) -> impl Iterator<Item = ConstructorSection<'_, 'elf>> {
+        let key = |sect: &Section| {
+            let (_, priority) = constructor_priority(sect.name()?)?;
+            let is_progbits = matches!(
+                sect.section_type(),
+                SectionType::InitArray | SectionType::Progbits
+            );
+            is_progbits.then_some((priority.is_none(), priority, sect.index()))
+        };
+        let mut prev = None;
+
+        core::iter::from_fn(move || {
+            let (next, sect) = self
+                .sections()
+                .filter_map(|sect| Some((key(&sect)?, sect)))
+                .filter(|(k, _)| prev.is_none_or(|prev| *k > prev))
+                .min_by_key(|(k, _)| *k)?;
+            prev = Some(next);
+
+            let (kind, priority) = constructor_priority(sect.name()?)?;
+            Some(ConstructorSection {
+                elf: self,
+                section: sect,
+                kind,
+                priority,
+            })
+        })
+    }
+}
+
+/// The function pointers of an `SHT_INIT_ARRAY`, `SHT_FINI_ARRAY` or `SHT_PREINIT_ARRAY`
+/// section
+///
+/// Entries are words of the file's class, read in its byte order. Some toolchains emit `0`
+/// or `-1` entries as list terminators; see [`FnPointerArray::functions()`].
+#[derive(Clone, Copy, Debug)]
+pub struct FnPointerArray<'elf> {
+    kind: SectionType,
+    data: &'elf [u8],
+    encoding: Encoding,
+}
+
+impl<'elf> FnPointerArray<'elf> {
+    /// Returns the type of the section the array came from
+    #[inline]
+    pub const fn kind(&self) -> SectionType {
+        self.kind
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.data.len() / self.encoding.word_size()
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the entry at `index`
+    pub fn get(&self, index: usize) -> Option<u64> {
+        let offset = index.checked_mul(self.encoding.word_size())?;
+        self.encoding.read_word(self.data.get(offset..)?)
+    }
+
+    /// Returns an iterator over every entry, including sentinels
+    pub fn entries(&self) -> impl Iterator<Item = u64> + 'elf {
+        let this = *self;
+        (0..self.len()).map(move |i| this.get(i).unwrap())
+    }
+
+    /// Returns an iterator over the entries which are not sentinels
+    pub fn functions(&self) -> impl Iterator<Item = u64> + 'elf {
+        let this = *self;
+        self.entries()
+            .filter(move |&entry| !this.is_sentinel(entry))
+    }
+
+    /// Returns `true` if any entry is a sentinel
+    pub fn has_sentinels(&self) -> bool {
+        self.entries().any(|entry| self.is_sentinel(entry))
+    }
+
+    /// Returns `true` if `entry` is a `0` or `-1` terminator of the file's word size
+    #[inline]
+    pub const fn is_sentinel(&self, entry: u64) -> bool {
+        match self.encoding.bits32 {
+            true => entry == 0 || entry == u32::MAX as u64,
+            false => is_sentinel(entry),
+        }
+    }
+}
+
+/// Returns `true` if `entry` is a `0` or `-1` terminator of an ELF64 file rather than a
+/// function
+///
+/// See [`FnPointerArray::is_sentinel()`] for files of either class.
+#[inline]
+pub const fn is_sentinel(entry: u64) -> bool {
+    entry == 0 || entry == u64::MAX
+}
+
+impl<'elf> Section<'_, 'elf> {
+    /// Returns the contents of an init, fini or preinit array section
+    ///
+    /// Returns `None` for other section types, or if the size is not a multiple of the
+    /// pointer size. Entries of a relocatable file are not relocated.
+    pub fn as_fn_pointer_array(&self) -> Option<FnPointerArray<'elf>> {
+        let kind = self.section_type();
+        if !matches!(
+            kind,
+            SectionType::InitArray | SectionType::FiniArray | SectionType::PreinitArray
+        ) {
+            return None;
+        }
+
+        let data = self.file_data()?;
+        let encoding = Encoding::of(self.elf());
+        if data.len() % encoding.word_size() != 0 {
+            return None;
+        }
+
+        Some(FnPointerArray {
+            kind,
+            data,
+            encoding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_priority_is_kept() {
+        assert_eq!(
+            constructor_priority(".init_array.70000"),
+            Some((ConstructorKind::InitArray, None))
+        );
+        assert_eq!(
+            constructor_priority(".ctors.00100"),
+            Some((ConstructorKind::Ctors, Some(u16::MAX - 100)))
+        );
+    }
+}
@@ -0,0 +1,288 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! DWARF debug section discovery
+
+use crate::{leb128::read_uleb128, table::Encoding, Elf, Section};
+
+/// A standard DWARF debug section
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DwarfSection {
+    Abbrev,
+    Addr,
+    Aranges,
+    Frame,
+    Info,
+    Line,
+    LineStr,
+    Loc,
+    Loclists,
+    Macinfo,
+    Macro,
+    Names,
+    Pubnames,
+    Pubtypes,
+    Ranges,
+    Rnglists,
+    Str,
+    StrOffsets,
+    Types,
+}
+
+impl DwarfSection {
+    /// Returns the section's name without the leading `.debug_`
+    pub const fn suffix(self) -> &'static str {
+        match self {
+            Self::Abbrev => "abbrev",
+            Self::Addr => "addr",
+            Self::Aranges => "aranges",
+            Self::Frame => "frame",
+            Self::Info => "info",
+            Self::Line => "line",
+            Self::LineStr => "line_str",
+            Self::Loc => "loc",
+            Self::Loclists => "loclists",
+            Self::Macinfo => "macinfo",
+            Self::Macro => "macro",
+            Self::Names => "names",
+            Self::Pubnames => "pubnames",
+            Self::Pubtypes => "pubtypes",
+            Self::Ranges => "ranges",
+            Self::Rnglists => "rnglists",
+            Self::Str => "str",
+            Self::StrOffsets => "str_offsets",
+            Self::Types => "types",
+        }
+    }
+
+    pub fn from_suffix(suffix: &str) -> Option<DwarfSection> {
+        Some(match suffix {
+            "abbrev" => Self::Abbrev,
+            "addr" => Self::Addr,
+            "aranges" => Self::Aranges,
+            "frame" => Self::Frame,
+            "info" => Self::Info,
+            "line" => Self::Line,
+            "line_str" => Self::LineStr,
+            "loc" => Self::Loc,
+            "loclists" => Self::Loclists,
+            "macinfo" => Self::Macinfo,
+            "macro" => Self::Macro,
+            "names" => Self::Names,
+            "pubnames" => Self::Pubnames,
+            "pubtypes" => Self::Pubtypes,
+            "ranges" => Self::Ranges,
+            "rnglists" => Self::Rnglists,
+            "str" => Self::Str,
+            "str_offsets" => Self::StrOffsets,
+            "types" => Self::Types,
+            _ => return None,
+        })
+    }
+}
+
+/// The role of a debug section
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DebugSectionKind {
+    /// A regular (or skeleton) DWARF section, e.g. `.debug_info`
+    Main(DwarfSection),
+    /// A split DWARF section, e.g. `.debug_info.dwo`
+    Dwo(DwarfSection),
+    /// The compilation unit index of a DWARF package (`.debug_cu_index`)
+    CuIndex,
+    /// The type unit index of a DWARF package (`.debug_tu_index`)
+    TuIndex,
+}
+
+impl DebugSectionKind {
+    /// Classify a section by its name
+    pub fn from_name(name: &str) -> Option<DebugSectionKind> {
+        let suffix = name.strip_prefix(".debug_")?;
+
+        match suffix {
+            "cu_index" => Some(Self::CuIndex),
+            "tu_index" => Some(Self::TuIndex),
+            _ => match suffix.strip_suffix(".dwo") {
+                Some(suffix) => DwarfSection::from_suffix(suffix).map(Self::Dwo),
+                None => DwarfSection::from_suffix(suffix).map(Self::Main),
+            },
+        }
+    }
+}
+
+/// How the debug information of a file is split
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SplitDebug {
+    /// No DWARF debug information
+    None,
+    /// All debug information is contained in the file itself
+    Unsplit,
+    /// The file contains skeleton units pointing at separate `.dwo` files
+    Skeleton,
+    /// The file is a `.dwo` file holding split debug information
+    Dwo,
+    /// The file is a DWARF package (`.dwp`) combining many `.dwo` files
+    Dwp,
+}
+
+const DW_UT_SKELETON: u8 = 0x04;
+const DW_AT_DWO_NAME: u64 = 0x76;
+const DW_AT_GNU_DWO_NAME: u64 = 0x2130;
+
+/// The sections of a skeleton file a consumer needs to interpret the matching `.dwo`
+const SKELETON_COMPANIONS: &[DwarfSection] = &[
+    DwarfSection::Addr,
+    DwarfSection::StrOffsets,
+    DwarfSection::Rnglists,
+    DwarfSection::Ranges,
+    DwarfSection::Line,
+    DwarfSection::Str,
+];
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over every DWARF debug section with its role
+    pub fn debug_sections(&self) -> impl Iterator<Item = (DebugSectionKind, Section<'_, 'elf>)> {
+        self.sections()
+            .filter_map(|s| Some((DebugSectionKind::from_name(s.name()?)?, s)))
+    }
+
+    /// Returns the debug section of the given kind
+    pub fn debug_section(&self, kind: DebugSectionKind) -> Option<Section<'_, 'elf>> {
+        self.debug_sections()
+            .find_map(|(k, s)| (k == kind).then_some(s))
+    }
+
+    /// Determine how this file's debug information is split
+    pub fn split_debug(&self) -> SplitDebug {
+        let mut info = None;
+        let mut dwo = false;
+
+        for (kind, sect) in self.debug_sections() {
+            match kind {
+                DebugSectionKind::CuIndex | DebugSectionKind::TuIndex => return SplitDebug::Dwp,
+                DebugSectionKind::Dwo(_) => dwo = true,
+                DebugSectionKind::Main(DwarfSection::Info) => info = Some(sect),
+                DebugSectionKind::Main(_) => {}
+            }
+        }
+
+        match info {
+            _ if dwo => SplitDebug::Dwo,
+            None => SplitDebug::None,
+            Some(info) => {
+                let abbrev = self.debug_section(DebugSectionKind::Main(DwarfSection::Abbrev));
+                let abbrev = abbrev.and_then(|s| s.file_data()).unwrap_or_default();
+                let info = info.file_data().unwrap_or_default();
+                if has_skeleton_unit(info, abbrev, Encoding::of(self)) {
+                    SplitDebug::Skeleton
+                } else {
+                    SplitDebug::Unsplit
+                }
+            }
+        }
+    }
+
+    /// Returns the sections of a skeleton file needed to interpret its `.dwo` files
+    ///
+    /// These are the sections referenced by the attributes of a split unit through the
+    /// skeleton unit named by `DW_AT_dwo_name`.
+    pub fn skeleton_companion_sections(
+        &self,
+    ) -> impl Iterator<Item = (DwarfSection, Section<'_, 'elf>)> {
+        self.debug_sections().filter_map(|(kind, sect)| match kind {
+            DebugSectionKind::Main(s) if SKELETON_COMPANIONS.contains(&s) => Some((s, sect)),
+            _ => None,
+        })
+    }
+}
+
+/// Returns `true` if the first unit in `info` is a skeleton unit
+///
+/// DWARF 5 marks skeleton units in the unit header. The GNU extension for DWARF 4
+/// instead gives the unit DIE a `DW_AT_GNU_dwo_name` attribute, which is found by
+/// looking up the DIE's abbreviation.
+fn has_skeleton_unit(info: &[u8], abbrev: &[u8], encoding: Encoding) -> bool {
+    (|| {
+        let word = |i: usize| encoding.read_u32(info.get(i..)?);
+        let (offset_size, header) = match word(0)? {
+            0xffff_ffff => (8, 12),
+            _ => (4, 4),
+        };
+        let version = encoding.read_u16(info.get(header..)?)?;
+
+        let (abbrev_offset, die) = match version {
+            5 => {
+                if *info.get(header + 2)? == DW_UT_SKELETON {
+                    return Some(true);
+                }
+                (header + 4, header + 4 + offset_size)
+            }
+            2..=4 => (header + 2, header + 3 + offset_size),
+            _ => return None,
+        };
+        let abbrev_offset = match offset_size {
+            8 => encoding.read_u64(info.get(abbrev_offset..)?)?,
+            _ => word(abbrev_offset)? as u64,
+        };
+
+        let mut die = info.get(die..)?;
+        let code = read_uleb128(&mut die)?;
+        let mut abbrev = abbrev.get(usize::try_from(abbrev_offset).ok()?..)?;
+
+        loop {
+            let this = read_uleb128(&mut abbrev)?;
+            if this == 0 {
+                return None;
+            }
+            let _tag = read_uleb128(&mut abbrev)?;
+            abbrev = abbrev.get(1..)?; // children flag
+
+            loop {
+                let name = read_uleb128(&mut abbrev)?;
+                let form = read_uleb128(&mut abbrev)?;
+                if name == 0 && form == 0 {
+                    break;
+                }
+                // DW_FORM_implicit_const carries its value in the abbreviation
+                if form == 0x21 {
+                    read_uleb128(&mut abbrev)?;
+                }
+                if this == code && (name == DW_AT_DWO_NAME || name == DW_AT_GNU_DWO_NAME) {
+                    return Some(true);
+                }
+            }
+
+            if this == code {
+                return Some(false);
+            }
+        }
+    })()
+    .unwrap_or(false)
+}
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Relocation-masked section hashing
+//!
+//! Two sections compiled from the same code hold the same bytes except where relocations
+//! will patch in addresses. Hashing the contents with those fields cleared gives a key which
+//! is stable across builds and identifies candidates for identical code folding.
+
+use crate::{
+    backend_for, Data, Digest, ElfType, PatchOp, Patches, RelocError, Relocation, Section,
+    SectionType,
+};
+
+/// Number of bytes of a section masked and fed to the digest at a time
+const CHUNK_SIZE: usize = 256;
+
+impl<'a, 'elf> Section<'a, 'elf> {
+    /// Feed the contents of this section into `digest`, with the fields patched by its
+    /// relocations cleared
+    ///
+    /// The digest is also fed the section's type, flags and size, followed by the offset,
+    /// type and addend of each relocation. Symbols are not included, so sections whose
+    /// relocations refer to different symbols hash the same; identical code folding must
+    /// still compare the targets of the relocations.
+    ///
+    /// Returns an error if the section has relocations and the file's machine has no
+    /// relocation backend, a relocation is not supported by it, or a relocated field lies
+    /// outside of the section.
+    pub fn content_digest<D: Digest>(&self, digest: &mut D) -> Result<(), RelocError> {
+        let elf = self.elf();
+        let machine = elf.machine();
+        let backend = backend_for(machine);
+        let big_endian = elf.data() == Data::TwosCompBig;
+        let le_code = backend.is_some_and(|b| b.little_endian_code());
+
+        // Relocations hold section offsets in relocatable files, and addresses otherwise.
+        let base = match elf.file_type() {
+            ElfType::Rel => 0,
+            _ => self.addr(),
+        };
+
+        let patches = |rel: &Relocation| -> Result<Patches, RelocError> {
+            let backend = backend.ok_or(RelocError::Incompatible {
+                machine,
+                class: elf.class(),
+                data: elf.data(),
+            })?;
+            let kind = rel.kind();
+            let place = rel.offset().wrapping_sub(base);
+            backend
+                .fields(kind, place)
+                .ok_or(RelocError::Unsupported { machine, kind })
+        };
+
+        let data: &[u8] = match self.section_type() {
+            SectionType::Nobits => &[],
+            _ => self.file_data().ok_or(RelocError::OutOfBounds(0))?,
+        };
+        for rel in self.relocations() {
+            for patch in patches(&rel)? {
+                let end = patch.address.checked_add(patch.size as u64);
+                if end.is_none_or(|end| end > data.len() as u64) {
+                    return Err(RelocError::OutOfBounds(patch.address));
+                }
+            }
+        }
+
+        digest.update(&self.section_type().to_u32().to_le_bytes());
+        digest.update(&self.flags().bits().to_le_bytes());
+        digest.update(&(self.size() as u64).to_le_bytes());
+
+        let mut buf = [0; CHUNK_SIZE];
+        for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            let start = (i * CHUNK_SIZE) as u64;
+            let buf = &mut buf[..chunk.len()];
+            buf.copy_from_slice(chunk);
+
+            for rel in self.relocations() {
+                for patch in patches(&rel)? {
+                    let mask = match patch.op {
+                        PatchOp::Insert(mask) => mask & patch.size_mask(),
+                        _ => patch.size_mask(),
+                    };
+                    let big_endian =
+                        big_endian && !(le_code && matches!(patch.op, PatchOp::Insert(_)));
+
+                    for byte in 0..patch.size as u64 {
+                        let Some(pos) = (patch.address + byte).checked_sub(start) else {
+                            continue;
+                        };
+                        let Some(b) = buf.get_mut(pos as usize) else {
+                            continue;
+                        };
+                        let shift = match big_endian {
+                            true => patch.size as u64 - 1 - byte,
+                            false => byte,
+                        };
+                        *b &= !((mask >> (shift * 8)) as u8);
+                    }
+                }
+            }
+
+            digest.update(buf);
+        }
+
+        for rel in self.relocations() {
+            digest.update(&rel.offset().wrapping_sub(base).to_le_bytes());
+            digest.update(&rel.kind().to_u32().to_le_bytes());
+            digest.update(&rel.addend().unwrap_or(0).to_le_bytes());
+        }
+
+        Ok(())
+    }
+}
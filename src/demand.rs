@@ -0,0 +1,382 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+//! Demand paging descriptors
+//!
+//! A load descriptor records where the contents of every page of a file's `PT_LOAD`
+//! segments come from, so that a loader can populate pages as they fault in without
+//! keeping the parsed file around. Consecutive pages of a segment are stored as a single
+//! [`PageRun`], and descriptors are serialized into a compact byte format.
+
+use crate::{Elf, ParseError, SegmentFlags, SegmentKind, Structure};
+
+/// The contents of one page, as returned by [`LoadDescriptor::page()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PageEntry {
+    /// Address of the page, aligned to the page size
+    pub vaddr: u64,
+    /// Offset of the bytes read into the start of the page, if [`file_len`] is non-zero
+    ///
+    /// [`file_len`]: PageEntry::file_len
+    pub file_offset: u64,
+    /// Number of bytes read from the file
+    pub file_len: u64,
+    /// Number of bytes after those read from the file which are zero-filled
+    pub zero_len: u64,
+    pub flags: SegmentFlags,
+}
+
+/// A run of consecutive pages of one segment
+///
+/// The file contents of the run are read into memory starting at its first page, and
+/// everything after them is zero-filled.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PageRun {
+    /// Address of the first page, aligned to the page size
+    pub vaddr: u64,
+    /// Number of pages in the run
+    pub pages: u64,
+    /// Offset of the file contents, if [`file_len`] is non-zero
+    ///
+    /// [`file_len`]: PageRun::file_len
+    pub file_offset: u64,
+    /// Number of bytes read from the file
+    pub file_len: u64,
+    pub flags: SegmentFlags,
+}
+
+impl PageRun {
+    /// Size of an encoded run, in bytes
+    pub const ENCODED_SIZE: usize = 33;
+
+    /// Returns the address just past the end of the run
+    #[inline]
+    pub fn end(&self, page_size: u64) -> u64 {
+        self.vaddr
+            .saturating_add(self.pages.saturating_mul(page_size))
+    }
+
+    /// Returns the contents of the page at `index` within the run
+    pub fn page(&self, index: u64, page_size: u64) -> PageEntry {
+        let offset = index.saturating_mul(page_size);
+        let file_len = self.file_len.saturating_sub(offset).min(page_size);
+
+        PageEntry {
+            vaddr: self.vaddr.wrapping_add(offset),
+            file_offset: match file_len {
+                0 => 0,
+                _ => self.file_offset.wrapping_add(offset),
+            },
+            file_len,
+            zero_len: page_size - file_len,
+            flags: self.flags,
+        }
+    }
+
+    /// Returns the run without its first page
+    fn skip_page(&self, page_size: u64) -> PageRun {
+        let file_len = self.file_len.saturating_sub(page_size);
+        PageRun {
+            vaddr: self.vaddr + page_size,
+            pages: self.pages - 1,
+            file_offset: match file_len {
+                0 => 0,
+                _ => self.file_offset + page_size,
+            },
+            file_len,
+            flags: self.flags,
+        }
+    }
+
+    /// Encode the run as `vaddr`, `pages`, `file_offset`, and `file_len` (little-endian),
+    /// followed by a byte holding the segment flags
+    pub const fn encode(&self) -> [u8; Self::ENCODED_SIZE] {
+        let mut buf = [0; Self::ENCODED_SIZE];
+        let fields = [self.vaddr, self.pages, self.file_offset, self.file_len];
+        let mut i = 0;
+        while i < 32 {
+            buf[i] = fields[i / 8].to_le_bytes()[i % 8];
+            i += 1;
+        }
+        buf[32] = self.flags.bits() as u8;
+        buf
+    }
+
+    /// Decode a run produced by [`encode()`](Self::encode)
+    pub fn decode(buf: &[u8]) -> Option<PageRun> {
+        let buf = buf.get(..Self::ENCODED_SIZE)?;
+        let field = |i: usize| Some(u64::from_le_bytes(buf[i * 8..][..8].try_into().ok()?));
+
+        Some(PageRun {
+            vaddr: field(0)?,
+            pages: field(1)?,
+            file_offset: field(2)?,
+            file_len: field(3)?,
+            flags: SegmentFlags::from_bits(buf[32] as u32)?,
+        })
+    }
+}
+
+/// A serialized load descriptor
+///
+/// The descriptor begins with the page size (little-endian), followed by the encoded
+/// [`PageRun`]s in order of address.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadDescriptor<'a> {
+    page_size: u64,
+    runs: &'a [u8],
+}
+
+impl<'a> LoadDescriptor<'a> {
+    /// Size of the header preceding the runs, in bytes
+    pub const HEADER_SIZE: usize = 8;
+
+    /// Parse a descriptor written by [`Elf::write_load_descriptor()`]
+    ///
+    /// Returns `None` if the page size is not a power of two, or the runs are malformed,
+    /// out of order, or overlap.
+    pub fn parse(buf: &'a [u8]) -> Option<LoadDescriptor<'a>> {
+        let page_size = u64::from_le_bytes(buf.get(..8)?.try_into().ok()?);
+        let runs = &buf[Self::HEADER_SIZE..];
+        if !page_size.is_power_of_two() || runs.len() % PageRun::ENCODED_SIZE != 0 {
+            return None;
+        }
+
+        let desc = LoadDescriptor { page_size, runs };
+        let mut end = 0;
+        for run in runs.chunks_exact(PageRun::ENCODED_SIZE) {
+            let run = PageRun::decode(run)?;
+            if run.vaddr < end || run.vaddr % page_size != 0 || run.pages == 0 {
+                return None;
+            }
+            end = run.vaddr.checked_add(run.pages.checked_mul(page_size)?)?;
+        }
+
+        Some(desc)
+    }
+
+    #[inline]
+    pub fn page_size(&self) -> u64 {
+        self.page_size
+    }
+
+    pub fn runs(&self) -> impl Iterator<Item = PageRun> + 'a {
+        self.runs
+            .chunks_exact(PageRun::ENCODED_SIZE)
+            .filter_map(PageRun::decode)
+    }
+
+    /// Returns the contents of the page containing `vaddr`, or `None` if it is not part
+    /// of any segment
+    pub fn page(&self, vaddr: u64) -> Option<PageEntry> {
+        let page = vaddr & !(self.page_size - 1);
+        let run = |index: usize| PageRun::decode(&self.runs[index * PageRun::ENCODED_SIZE..]);
+
+        // Find the last run starting at or before the page.
+        let (mut low, mut high) = (0, self.runs.len() / PageRun::ENCODED_SIZE);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match run(mid)?.vaddr <= page {
+                true => low = mid + 1,
+                false => high = mid,
+            }
+        }
+
+        let run = run(low.checked_sub(1)?)?;
+        let index = (page - run.vaddr) / self.page_size;
+        (index < run.pages).then(|| run.page(index, self.page_size))
+    }
+}
+
+impl Elf<'_> {
+    /// Calls `f` with the page runs of every `PT_LOAD` segment, in order of address
+    ///
+    /// Each segment is mapped as an exec-style loader would: whole pages are read from the
+    /// file, so bytes which precede the segment in its first page come from the file as
+    /// well. A page shared by two segments becomes a run of its own, with the permissions
+    /// of both.
+    ///
+    /// Returns an error if `page_size` is not a power of two, the offset and address of a
+    /// segment with file contents differ modulo the page size, its contents lie outside of
+    /// the file, segments overlap or are out of order, or two segments share a page whose
+    /// contents cannot be described by a single file range. Errors in a segment are located
+    /// at its program header.
+    pub fn load_runs<F>(&self, page_size: u64, mut f: F) -> Result<(), ParseError>
+    where
+        F: FnMut(PageRun),
+    {
+        if !page_size.is_power_of_two() {
+            let message = "page size is not a power of two";
+            return Err(ParseError::new(Structure::FileHeader, 0, message));
+        }
+
+        // The last run, which may share its last page with the next segment, and the end
+        // of the memory and file images of its segment.
+        let mut pending: Option<(PageRun, u64, u64)> = None;
+
+        let loads = self.segments().enumerate();
+        for (index, sgmt) in loads.filter(|(_, s)| s.kind() == SegmentKind::Load) {
+            let error = |message| self.program_header_error(index, message);
+            let vaddr = sgmt.virtual_address();
+            let mem_size = sgmt.mem_size() as u64;
+            let file_size = (sgmt.file_size() as u64).min(mem_size);
+            let offset = sgmt.file_offset() as u64;
+            if mem_size == 0 {
+                continue;
+            }
+            if file_size != 0 && offset % page_size != vaddr % page_size {
+                return Err(error("segment offset is not congruent with its address"));
+            }
+
+            let start = vaddr & !(page_size - 1);
+            let mem_end = vaddr
+                .checked_add(mem_size)
+                .ok_or(error("segment out of range"))?;
+            let end = mem_end
+                .checked_next_multiple_of(page_size)
+                .ok_or(error("segment out of range"))?;
+            let file_len = match file_size {
+                0 => 0,
+                _ => vaddr + file_size - start,
+            };
+            let file_offset = match file_len {
+                0 => 0,
+                _ => offset - (vaddr - start),
+            };
+            if file_offset.saturating_add(file_len) > self.data.len() as u64 {
+                return Err(error("segment contents out of bounds"));
+            }
+
+            let mut run = PageRun {
+                vaddr: start,
+                pages: (end - start) / page_size,
+                file_offset,
+                file_len,
+                flags: sgmt.flags(),
+            };
+
+            if let Some((prev, prev_mem_end, prev_file_end)) = pending.take() {
+                if vaddr < prev_mem_end {
+                    return Err(error("segments overlap or are out of order"));
+                }
+
+                if start < prev.end(page_size) {
+                    // Split off the shared page from both runs and merge it.
+                    let last = prev.page(prev.pages - 1, page_size);
+                    let first = run.page(0, page_size);
+                    if prev.pages > 1 {
+                        f(PageRun {
+                            pages: prev.pages - 1,
+                            ..prev
+                        });
+                    }
+
+                    let zero_filled = prev_mem_end > prev_file_end.max(start);
+                    if zero_filled && first.file_len > last.file_len {
+                        return Err(error("segment contents overlap zero-filled memory"));
+                    }
+                    if last.file_len != 0
+                        && first.file_len != 0
+                        && last.file_offset != first.file_offset
+                    {
+                        return Err(error("segments share a page but not its contents"));
+                    }
+
+                    let shared = PageRun {
+                        vaddr: start,
+                        pages: 1,
+                        file_offset: match last.file_len {
+                            0 => first.file_offset,
+                            _ => last.file_offset,
+                        },
+                        file_len: last.file_len.max(first.file_len),
+                        flags: last.flags | first.flags,
+                    };
+
+                    // The shared page may be shared with the next segment as well.
+                    if run.pages == 1 {
+                        run = shared;
+                    } else {
+                        f(shared);
+                        run = run.skip_page(page_size);
+                    }
+                } else {
+                    f(prev);
+                }
+            }
+
+            pending = Some((run, mem_end, vaddr + file_size));
+        }
+
+        if let Some((run, ..)) = pending {
+            f(run);
+        }
+        Ok(())
+    }
+
+    /// Write the load descriptor of this file to `buf`
+    ///
+    /// Returns the number of bytes written. See [`load_runs()`](Elf::load_runs).
+    pub fn write_load_descriptor(
+        &self,
+        page_size: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, ParseError> {
+        let buffer_too_small = ParseError::new(Structure::FileHeader, 0, "buffer too small");
+        let header = buf
+            .get_mut(..LoadDescriptor::HEADER_SIZE)
+            .ok_or(buffer_too_small)?;
+        header.copy_from_slice(&page_size.to_le_bytes());
+
+        let mut len = LoadDescriptor::HEADER_SIZE;
+        let mut overflow = false;
+        self.load_runs(page_size, |run| {
+            match buf.get_mut(len..len + PageRun::ENCODED_SIZE) {
+                Some(out) => out.copy_from_slice(&run.encode()),
+                None => overflow = true,
+            }
+            len += PageRun::ENCODED_SIZE;
+        })?;
+
+        match overflow {
+            true => Err(buffer_too_small),
+            false => Ok(len),
+        }
+    }
+
+    /// Returns the load descriptor of this file
+    ///
+    /// See [`load_runs()`](Elf::load_runs).
+    #[cfg(feature = "alloc")]
+    pub fn to_load_descriptor(&self, page_size: u64) -> Result<alloc::vec::Vec<u8>, ParseError> {
+        let mut buf = alloc::vec::Vec::from(page_size.to_le_bytes());
+        self.load_runs(page_size, |run| buf.extend_from_slice(&run.encode()))?;
+        Ok(buf)
+    }
+}
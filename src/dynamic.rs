@@ -28,75 +28,221 @@
  * SPDX-License-Identifier: BSD-3-Clause
  */
 
-use crate::{assert_struct_size, Elf};
-use core::mem::size_of;
+use crate::{
+    assert_struct_size,
+    table::{impl_entry, Encoding, Entry, Table},
+    Elf, Endian, LittleEndian, ParseError, SectionType, SegmentKind, Structure, I32, I64, U32, U64,
+};
 
 pub struct DynamicTable<'a, 'elf> {
     _elf: &'a Elf<'elf>,
-    data: &'elf [Dyn],
+    data: Table<'elf, Dyn>,
+    structure: Structure,
+    offset: u64,
+    entry_size: u64,
 }
 
 impl<'a, 'elf> DynamicTable<'a, 'elf> {
-    pub fn new(_elf: &'a Elf<'elf>, data: &'elf [u8]) -> DynamicTable<'a, 'elf> {
-        let len = data.len() / size_of::<Dyn>();
-        let data = data.as_ptr().cast::<Dyn>();
-        let mut dyntab = unsafe { core::slice::from_raw_parts(data, len) };
-
-        if !dyntab.is_empty() {
-            let last = dyntab.len() - 1;
-            assert_eq!(dyntab[last].tag, DynTag::NULL);
-            dyntab = &dyntab[..last];
-        }
+    /// Create a table from the contents of a `PT_DYNAMIC` segment or `SHT_DYNAMIC` section
+    ///
+    /// The table ends at the first `DT_NULL` entry; any entries after it are ignored.
+    /// Returns an error if a non-empty table has none.
+    pub fn new(elf: &'a Elf<'elf>, data: &'elf [u8]) -> Result<DynamicTable<'a, 'elf>, ParseError> {
+        let encoding = Encoding::of(elf);
+        let dyntab = Table::<Dyn>::new(data, encoding);
+        let (structure, offset) = locate(elf, data);
+
+        let end = match dyntab.iter().position(|entry| entry.tag() == DynTag::NULL) {
+            Some(end) => end,
+            None if dyntab.len() == 0 => 0,
+            None => {
+                let message = "dynamic table has no DT_NULL entry";
+                return Err(ParseError::new(structure, offset, message));
+            }
+        };
+
+        Ok(Self {
+            _elf: elf,
+            data: dyntab.slice(0..end),
+            structure,
+            offset,
+            entry_size: Dyn::encoded_size(encoding) as u64,
+        })
+    }
+}
+
+/// Returns the structure holding the dynamic table `data`, and its offset in the file
+///
+/// This is the `PT_DYNAMIC` segment or `SHT_DYNAMIC` section at the table's offset, or the
+/// file header if `data` does not lie in the file.
+fn locate(elf: &Elf, data: &[u8]) -> (Structure, u64) {
+    let Some(offset) = (data.as_ptr() as usize)
+        .checked_sub(elf.data.as_ptr() as usize)
+        .filter(|&offset| offset < elf.data.len())
+    else {
+        return (Structure::FileHeader, 0);
+    };
 
-        Self { _elf, data: dyntab }
+    let segment = elf
+        .segments()
+        .position(|sgmt| sgmt.kind() == SegmentKind::Dynamic && sgmt.file_offset() == offset)
+        .map(|index| Structure::Segment(index as u16));
+    let section = || {
+        elf.sections()
+            .find(|sect| {
+                sect.section_type() == SectionType::Dynamic && sect.file_offset() == offset
+            })
+            .map(|sect| Structure::Section(sect.index()))
+    };
+    match segment.or_else(section) {
+        Some(structure) => (structure, offset as u64),
+        None => (Structure::FileHeader, 0),
     }
 }
 
 impl<'elf> DynamicTable<'_, 'elf> {
+    /// Returns the raw entries, or `None` unless the file is little-endian ELF64
     #[inline]
-    pub fn table_raw(&self) -> &'elf [Dyn] {
-        self.data
+    pub fn table_raw(&self) -> Option<&'elf [Dyn]> {
+        self.data.native()
+    }
+
+    /// Returns an iterator over the entries, up to the terminating `DT_NULL`
+    pub fn entries(&self) -> impl Iterator<Item = Dyn> + 'elf {
+        self.data.iter()
+    }
+
+    /// Returns an iterator over every entry with the given tag
+    ///
+    /// Tags such as `DT_NEEDED` and `DT_AUXILIARY` may appear any number of times.
+    pub fn all(&self, tag: DynTag) -> impl Iterator<Item = Dyn> + 'elf {
+        self.entries().filter(move |entry| entry.tag() == tag)
+    }
+
+    /// Returns the entry with the given tag, or `None` if there is none
+    ///
+    /// Returns an error at the second entry if the tag appears more than once.
+    pub fn get_unique(&self, tag: DynTag) -> Result<Option<Dyn>, ParseError> {
+        let mut entries = self
+            .entries()
+            .enumerate()
+            .filter(|(_, entry)| entry.tag() == tag);
+        let entry = entries.next().map(|(_, entry)| entry);
+        match entries.next() {
+            Some((index, _)) => {
+                let offset = self.offset + index as u64 * self.entry_size;
+                Err(ParseError::new(
+                    self.structure,
+                    offset,
+                    "duplicate dynamic tag",
+                ))
+            }
+            None => Ok(entry),
+        }
     }
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Eq, Hash, PartialEq, PartialOrd)]
-pub struct Dyn {
-    pub tag: DynTag,
-    pub value: usize,
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct Dyn<E: Endian = LittleEndian> {
+    tag: I64<E>,
+    value: U64<E>,
 }
 
 assert_struct_size!(Dyn, 16);
 
-impl Dyn {
+/// A dynamic table entry of an ELF32 file
+#[repr(C)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct Dyn32<E: Endian = LittleEndian> {
+    tag: I32<E>,
+    value: U32<E>,
+}
+
+assert_struct_size!(Dyn32, 8);
+
+impl<E: Endian> From<Dyn32<E>> for Dyn<E> {
+    fn from(entry: Dyn32<E>) -> Dyn<E> {
+        Dyn {
+            tag: I64::new(entry.tag.get() as i64),
+            value: U64::new(entry.value.get() as u64),
+        }
+    }
+}
+
+impl_entry!(Dyn, Dyn32);
+
+impl<E: Endian> Dyn<E> {
+    /// Re-encode the entry in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> Dyn<F> {
+        Dyn {
+            tag: self.tag.convert(),
+            value: self.value.convert(),
+        }
+    }
+
+    pub const fn new(tag: DynTag, value: u64) -> Dyn<E> {
+        Dyn {
+            tag: I64::new(tag.0),
+            value: U64::new(value),
+        }
+    }
+
+    /// Returns the entry as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    pub fn set_value(&mut self, value: u64) {
+        self.value.set(value);
+    }
+
+    #[inline]
+    pub const fn tag(&self) -> DynTag {
+        DynTag(self.tag.get())
+    }
+
+    #[inline]
+    pub const fn value(&self) -> u64 {
+        self.value.get()
+    }
+
     #[inline]
     pub fn as_ptr<T>(&self) -> *const T {
-        self.value as *const T
+        self.value() as usize as *const T
     }
 
     #[inline]
     pub fn as_mut_ptr<T>(&self) -> *mut T {
-        self.value as *mut T
+        self.value() as usize as *mut T
     }
 }
 
-impl core::fmt::Debug for Dyn {
+impl<E: Endian> core::fmt::Debug for Dyn<E> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Dyn")
-            .field("tag", &self.tag)
-            .field("value", &format_args!("{:#018x}", self.value))
+            .field("tag", &self.tag())
+            .field("value", &format_args!("{:#018x}", self.value()))
             .finish()
     }
 }
 
 #[repr(transparent)]
 #[derive(Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct DynTag(isize);
+pub struct DynTag(i64);
 
 macro_rules! dyn_tags {
     ($($(#[$meta:meta])* const $name:ident = $val:expr;)*) => {
         impl DynTag {
             $($(#[$meta])*pub const $name: DynTag = Self($val);)*
+
+            pub const fn from_i64(x: i64) -> DynTag {
+                Self(x)
+            }
+
+            pub const fn to_i64(self) -> i64 {
+                self.0
+            }
         }
 
         impl core::fmt::Debug for DynTag {
@@ -148,13 +294,61 @@ dyn_tags! {
     // This needs to come second so `PREINIT_ARRAY` is chosed for Debug output
     const ENCODING          = 32;
     const PREINIT_ARRAYSZ   = 33;
+    const SYMTAB_SHNDX      = 34;
+    const RELRSZ            = 35;
+    const RELR              = 36;
+    const RELRENT           = 37;
     const GNU_HASH          = 0x6ffffef5;
     const RELACOUNT         = 0x6ffffff9;
     const RELCOUNT          = 0x6ffffffa;
     const FLAGS_1           = 0x6ffffffb;
+    const AUXILIARY         = 0x7ffffffd;
+    const FILTER            = 0x7fffffff;
 
     const LOOS          = 0x60000000;
     const HIOS          = 0x6FFFFFFF;
     const LOPROC        = 0x70000000;
     const HIPROC        = 0x7FFFFFFF;
 }
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use super::*;
+    use crate::testdata::Fixture;
+
+    /// Returns a fixture with the tag of dynamic entry `index` replaced by `tag`, and the
+    /// offset of its dynamic table
+    fn fixture_with_tag(index: usize, tag: DynTag) -> (alloc::vec::Vec<u8>, usize) {
+        let mut buf = Fixture::new().build();
+        let elf = Elf::new(&buf).unwrap();
+        let offset = elf.section_by_name(".dynamic").unwrap().file_offset();
+        let entry = offset + index * size_of::<Dyn>();
+        buf[entry..entry + 8].copy_from_slice(&tag.to_i64().to_le_bytes());
+        (buf, offset)
+    }
+
+    #[test]
+    fn missing_null_is_located() {
+        let (buf, offset) = fixture_with_tag(5, DynTag::STRSZ);
+        let elf = Elf::new(&buf).unwrap();
+        let sgmt = elf.segments().nth(1).unwrap();
+
+        let error = DynamicTable::new(&elf, sgmt.file_data().unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(error.structure, Structure::Segment(1));
+        assert_eq!(error.offset, offset as u64);
+    }
+
+    #[test]
+    fn duplicate_tag_is_located() {
+        let (buf, offset) = fixture_with_tag(3, DynTag::STRSZ);
+        let elf = Elf::new(&buf).unwrap();
+        let dynamic = elf.dynamic_table().unwrap();
+
+        let error = dynamic.get_unique(DynTag::STRSZ).unwrap_err();
+        assert_eq!(error.structure, Structure::Segment(1));
+        assert_eq!(error.offset, (offset + 4 * size_of::<Dyn>()) as u64);
+        assert!(dynamic.get_unique(DynTag::RELA).unwrap().is_some());
+    }
+}
@@ -0,0 +1,1331 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! In-place editing of ELF files
+
+use crate::{
+    from_bytes, from_bytes_mut, Binding, Class, Data, Dyn, DynTag, Elf, FileHeader, OsAbi,
+    ProgramHeader, Rel, RelocInfo, SectionFlags, SectionHeader, SectionType, SegmentFlags,
+    SegmentKind, Sym, SHN_LORESERVE, SHN_UNDEF,
+};
+use core::mem::size_of;
+
+/// A mutable view of an ELF file
+///
+/// Edits are made directly to the underlying buffer. The file may occupy only the start of
+/// the buffer, in which case the remainder is used as room to grow by operations such as
+/// [`ElfMut::append_section()`].
+///
+/// Padding inserted between the contents of the file is filled with the
+/// [fill byte](ElfMut::with_fill), which is zero by default.
+pub struct ElfMut<'elf> {
+    data: &'elf mut [u8],
+    len: usize,
+    fill: u8,
+}
+
+impl<'elf> ElfMut<'elf> {
+    pub fn new(data: &'elf mut [u8]) -> Result<ElfMut<'elf>, &'static str> {
+        let len = data.len();
+        Self::with_len(data, len)
+    }
+
+    /// Create a view of a file which occupies the first `len` bytes of `data`
+    pub fn with_len(data: &'elf mut [u8], len: usize) -> Result<ElfMut<'elf>, &'static str> {
+        let elf =
+            Elf::new(data.get(..len).ok_or("length exceeds buffer")?).map_err(|e| e.message)?;
+        if elf.class() != Class::Bits64 || elf.data() != Data::TwosCompLittle {
+            return Err("not a little-endian ELF64 file");
+        }
+        Ok(ElfMut { data, len, fill: 0 })
+    }
+
+    /// Set the byte used to fill padding, such as `0xff` for images written to NOR flash
+    pub fn with_fill(mut self, fill: u8) -> ElfMut<'elf> {
+        self.fill = fill;
+        self
+    }
+
+    /// Returns the current size of the file
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a read-only view of the file in its current state
+    pub fn as_elf(&self) -> Elf<'_> {
+        Elf::new(&self.data[..self.len]).unwrap()
+    }
+
+    /// Returns the underlying buffer
+    ///
+    /// Only the first [`len()`](ElfMut::len) bytes are part of the file.
+    pub fn into_inner(self) -> &'elf mut [u8] {
+        self.data
+    }
+
+    fn file_header_mut(&mut self) -> &mut FileHeader {
+        from_bytes_mut(self.data)
+    }
+
+    pub fn set_entry_point(&mut self, entry_point: u64) {
+        self.file_header_mut().set_entry_point(entry_point);
+    }
+
+    pub fn set_os_abi(&mut self, os_abi: OsAbi) {
+        self.file_header_mut().set_os_abi(os_abi);
+    }
+
+    /// Set the processor-specific flags
+    pub fn set_flags(&mut self, flags: u32) {
+        self.file_header_mut().set_flags(flags);
+    }
+
+    /// Returns the program header at `index`
+    pub fn program_header_mut(&mut self, index: u16) -> Option<&mut ProgramHeader> {
+        let elf = self.as_elf();
+        if index >= elf.phdr_num() {
+            return None;
+        }
+        let offset = elf
+            .phdr_offset()
+            .checked_add(index as usize * size_of::<ProgramHeader>())?;
+        let buf = self
+            .data
+            .get_mut(offset..)?
+            .get_mut(..size_of::<ProgramHeader>())?;
+
+        Some(from_bytes_mut(buf))
+    }
+
+    /// Returns the section header at `index`
+    pub fn section_header_mut(&mut self, index: u16) -> Option<&mut SectionHeader> {
+        let elf = self.as_elf();
+        if index >= elf.shdr_num() {
+            return None;
+        }
+        let offset = elf
+            .shdr_offset()
+            .checked_add(index as usize * size_of::<SectionHeader>())?;
+        let buf = self
+            .data
+            .get_mut(offset..)?
+            .get_mut(..size_of::<SectionHeader>())?;
+
+        Some(from_bytes_mut(buf))
+    }
+
+    /// Returns the contents of the section at `index`
+    ///
+    /// Returns `None` for `SHT_NOBITS` sections, which have no contents in the file.
+    pub fn section_data_mut(&mut self, index: u16) -> Option<&mut [u8]> {
+        let elf = self.as_elf();
+        let sect = elf.section(index)?;
+        if sect.section_type() == SectionType::Nobits {
+            return None;
+        }
+        let (offset, size) = (sect.file_offset(), sect.size());
+        self.data[..self.len].get_mut(offset..)?.get_mut(..size)
+    }
+
+    /// Set the flags of the segment at `index`
+    ///
+    /// This can be used to, for example, clear [`SegmentFlags::EXEC`] on `PT_GNU_STACK`.
+    pub fn set_segment_flags(
+        &mut self,
+        index: u16,
+        flags: SegmentFlags,
+    ) -> Result<(), &'static str> {
+        self.program_header_mut(index)
+            .ok_or("segment index out of range")?
+            .set_flags(flags);
+        Ok(())
+    }
+
+    /// Set the value of the first entry in the dynamic table with the given tag
+    pub fn set_dynamic_value(&mut self, tag: DynTag, value: u64) -> Result<(), &'static str> {
+        let elf = self.as_elf();
+        let sgmt = elf
+            .segments()
+            .find(|sgmt| sgmt.kind() == SegmentKind::Dynamic)
+            .ok_or("no dynamic segment")?;
+        let (offset, size) = (sgmt.file_offset(), sgmt.file_size());
+
+        let table = self
+            .data
+            .get_mut(offset..)
+            .and_then(|data| data.get_mut(..size))
+            .ok_or("dynamic segment out of bounds")?;
+
+        for entry in table.chunks_exact_mut(size_of::<Dyn>()) {
+            let entry: &mut Dyn = from_bytes_mut(entry);
+            match entry.tag() {
+                DynTag::NULL => break,
+                t if t == tag => {
+                    entry.set_value(value);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        Err("dynamic tag not found")
+    }
+
+    /// Append a non-allocated section to the end of the file, returning its index
+    ///
+    /// The contents, an enlarged copy of the section name string table, and a new section
+    /// header table are written after the current end of the file. The old string table and
+    /// header table are left in place but are no longer referenced. The section is not
+    /// covered by any segment, and so is not loaded.
+    pub fn append_section(
+        &mut self,
+        name: &str,
+        kind: SectionType,
+        flags: SectionFlags,
+        contents: &[u8],
+    ) -> Result<u16, &'static str> {
+        let elf = self.as_elf();
+        let shnum = elf.shdr_num();
+        let shoff = elf.shdr_offset();
+        let strtab_index = elf.shdr_strtab_index();
+        if strtab_index == 0 || strtab_index >= shnum {
+            return Err("no section name string table");
+        }
+        if shnum + 1 >= SHN_LORESERVE {
+            return Err("too many sections");
+        }
+        let strtab = elf
+            .section(strtab_index)
+            .ok_or("no section name string table")?;
+        let (strtab_offset, strtab_size) = (strtab.file_offset(), strtab.size());
+        let table_size = shnum as usize * size_of::<SectionHeader>();
+        let in_bounds =
+            |offset: usize, size| offset.checked_add(size).is_some_and(|e| e <= self.len);
+        if !in_bounds(strtab_offset, strtab_size) || !in_bounds(shoff, table_size) {
+            return Err("section headers out of bounds");
+        }
+
+        // Everything before `end` is within the buffer, so none of these overflow.
+        let contents_offset = self.len.next_multiple_of(8);
+        let end = contents_offset
+            .checked_add(contents.len())
+            .and_then(|e| e.checked_add(strtab_size + name.len() + 1))
+            .and_then(|e| e.checked_next_multiple_of(8))
+            .and_then(|e| e.checked_add(table_size + size_of::<SectionHeader>()))
+            .filter(|&e| e <= self.data.len())
+            .ok_or("not enough space in buffer")?;
+        let new_strtab_offset = contents_offset + contents.len();
+        let new_strtab_size = strtab_size + name.len() + 1;
+        let new_shoff = (new_strtab_offset + new_strtab_size).next_multiple_of(8);
+
+        self.data[self.len..end].fill(self.fill);
+        self.data[contents_offset..][..contents.len()].copy_from_slice(contents);
+
+        self.data.copy_within(
+            strtab_offset..strtab_offset + strtab_size,
+            new_strtab_offset,
+        );
+        self.data[new_strtab_offset + strtab_size..][..name.len()].copy_from_slice(name.as_bytes());
+        self.data[new_strtab_offset + new_strtab_size - 1] = 0;
+
+        self.data.copy_within(shoff..shoff + table_size, new_shoff);
+        let shdr = <SectionHeader>::new(strtab_size as u32, kind, flags)
+            .with_file_range(contents_offset as u64, contents.len() as u64)
+            .with_addr_align(1);
+        self.data[new_shoff + table_size..end].copy_from_slice(shdr.as_bytes());
+
+        let strtab_hdr =
+            &mut self.data[new_shoff + strtab_index as usize * size_of::<SectionHeader>()..];
+        let strtab_hdr: &mut SectionHeader = from_bytes_mut(strtab_hdr);
+        strtab_hdr.set_file_range(new_strtab_offset as u64, new_strtab_size as u64);
+
+        self.file_header_mut()
+            .set_section_headers(new_shoff as u64, shnum + 1);
+        self.len = end;
+
+        Ok(shnum)
+    }
+
+    /// Add a program header, returning its index
+    ///
+    /// The program header table cannot grow in place, so it is moved to the end of the
+    /// file and `e_phoff` is updated. If the old table was loaded into memory, as it must
+    /// be for `PT_PHDR` and the `AT_PHDR` auxiliary vector entry to be valid, a read-only
+    /// `PT_LOAD` segment is added for the new table above all existing segments, and
+    /// `PT_PHDR` is updated to point at it. `page_size` must be a power of two, and is used
+    /// as the alignment of that segment.
+    ///
+    /// The new header is placed at the end of the table.
+    pub fn add_program_header(
+        &mut self,
+        phdr: ProgramHeader,
+        page_size: u64,
+    ) -> Result<u16, &'static str> {
+        const PHDR_SIZE: usize = size_of::<ProgramHeader>();
+
+        if !page_size.is_power_of_two() {
+            return Err("page size is not a power of two");
+        }
+
+        let elf = self.as_elf();
+        let phnum = elf.phdr_num() as usize;
+        let phoff = elf.phdr_offset();
+        let table_size = phnum * PHDR_SIZE;
+        if phoff
+            .checked_add(table_size)
+            .is_none_or(|end| end > self.len)
+        {
+            return Err("program headers out of bounds");
+        }
+
+        let loads = || elf.segments().filter(|s| s.kind() == SegmentKind::Load);
+        let loaded = loads().any(|sgmt| {
+            sgmt.file_offset() <= phoff
+                && phoff + table_size <= sgmt.file_offset().saturating_add(sgmt.file_size())
+        });
+        let last_load = elf
+            .program_headers()
+            .enumerate()
+            .filter(|(_, p)| p.kind() == SegmentKind::Load)
+            .map(|(index, _)| index)
+            .last();
+
+        let new_num = phnum + 1 + loaded as usize;
+        let new_num = u16::try_from(new_num)
+            .ok()
+            .filter(|&num| num < u16::MAX)
+            .ok_or("too many program headers")?;
+        let new_phoff = self.len.next_multiple_of(8);
+        let new_size = new_num as usize * PHDR_SIZE;
+        let end = new_phoff + new_size;
+        if end > self.data.len() {
+            return Err("not enough space in buffer");
+        }
+
+        // Place the table above every loaded byte, congruent to its file offset.
+        let table_load = match (loaded, last_load) {
+            (true, Some(last_load)) => {
+                let top = loads()
+                    .map(|s| s.virtual_address().saturating_add(s.mem_size() as u64))
+                    .max()
+                    .unwrap_or(0);
+                let vaddr = top
+                    .checked_next_multiple_of(page_size)
+                    .and_then(|top| top.checked_add(new_phoff as u64 % page_size))
+                    .ok_or("no room for program headers in the address space")?;
+                let last = elf.program_headers().nth(last_load).unwrap();
+                let paddr = vaddr
+                    .wrapping_add(last.physical_address().wrapping_sub(last.virtual_address()));
+                Some((last_load, vaddr, paddr))
+            }
+            _ => None,
+        };
+
+        self.data[self.len..end].fill(self.fill);
+
+        let mut dst = new_phoff;
+        for index in 0..phnum {
+            let src = phoff + index * PHDR_SIZE;
+            self.data.copy_within(src..src + PHDR_SIZE, dst);
+            dst += PHDR_SIZE;
+
+            if let Some((last_load, vaddr, paddr)) = table_load {
+                if index == last_load {
+                    let load = <ProgramHeader>::new(SegmentKind::Load, SegmentFlags::READ)
+                        .with_addr(vaddr, paddr)
+                        .with_file_range(new_phoff as u64, new_size as u64)
+                        .with_mem_size(new_size as u64)
+                        .with_alignment(page_size);
+                    self.data[dst..][..PHDR_SIZE].copy_from_slice(load.as_bytes());
+                    dst += PHDR_SIZE;
+                }
+            }
+        }
+        self.data[dst..][..PHDR_SIZE].copy_from_slice(phdr.as_bytes());
+
+        self.file_header_mut()
+            .set_program_headers(new_phoff as u64, new_num);
+        self.len = end;
+
+        if let Some((_, vaddr, paddr)) = table_load {
+            let index = self
+                .as_elf()
+                .program_headers()
+                .position(|p| p.kind() == SegmentKind::Phdr);
+            if let Some(index) = index {
+                let entry = self.program_header_mut(index as u16).unwrap();
+                *entry = <ProgramHeader>::new(SegmentKind::Phdr, entry.flags())
+                    .with_addr(vaddr, paddr)
+                    .with_file_range(new_phoff as u64, new_size as u64)
+                    .with_mem_size(new_size as u64)
+                    .with_alignment(8);
+            }
+        }
+
+        Ok(new_num - 1)
+    }
+
+    /// Grow the contents of the section at `index` by `additional` zero bytes
+    ///
+    /// Everything in the file after the section is moved up, unless there is already
+    /// enough unused space after it. The distance moved is a multiple of the largest
+    /// `sh_addralign` and `p_align` of the sections and segments being moved, so their
+    /// offsets keep their alignment and loadable segments keep their offset congruent to
+    /// their address. Section headers, program headers, and the file header are updated.
+    ///
+    /// Sections which are loaded into memory cannot be grown, since that would change the
+    /// addresses of what follows them. Nor can sections inside a segment which does not
+    /// also end with them. Segments which do end with the section, such as a `PT_NOTE`
+    /// segment covering it, grow with it.
+    pub fn grow_section(&mut self, index: u16, additional: usize) -> Result<(), &'static str> {
+        const MOVED: &str = "section is followed by a segment which would be split";
+
+        let elf = self.as_elf();
+        let sect = elf.section(index).ok_or("section index out of range")?;
+        if sect.section_type() == SectionType::Nobits {
+            return Err("section has no contents in the file");
+        }
+        let start = sect.file_offset();
+        let old_end = start
+            .checked_add(sect.size())
+            .filter(|&end| end <= self.len)
+            .ok_or("section out of bounds")?;
+        let new_size = sect
+            .size()
+            .checked_add(additional)
+            .ok_or("section too large")?;
+
+        for sgmt in elf.segments().filter(|s| s.file_size() != 0) {
+            let s = sgmt.file_offset();
+            let e = s
+                .checked_add(sgmt.file_size())
+                .ok_or("segment out of bounds")?;
+            if sgmt.kind() == SegmentKind::Load && s <= start && start < e {
+                return Err("section is loaded into memory");
+            }
+            if s < old_end && e > old_end || (s < old_end && e == old_end && s > start) {
+                return Err(MOVED);
+            }
+        }
+
+        // Find what follows the section, and the alignment it needs.
+        let mut next = self.len;
+        let mut align = 1;
+        let mut follows = |offset: usize, size: usize, a: u64| {
+            if offset >= old_end && (size != 0 || offset < self.len) {
+                next = next.min(offset);
+                align = align.max(a);
+            }
+        };
+        for other in elf.sections().filter(|s| s.index() != index) {
+            let size = match other.section_type() {
+                SectionType::Nobits => 0,
+                _ => other.size(),
+            };
+            follows(other.file_offset(), size, other.addr_align());
+        }
+        for sgmt in elf.segments() {
+            follows(sgmt.file_offset(), sgmt.file_size(), sgmt.alignment());
+        }
+        follows(elf.shdr_offset(), elf.shdr_num() as usize, 8);
+        follows(elf.phdr_offset(), elf.phdr_num() as usize, 8);
+
+        if !align.is_power_of_two() {
+            return Err("alignment is not a power of two");
+        }
+        let shift = match (old_end + additional).checked_sub(next) {
+            None | Some(0) => 0,
+            Some(needed) => needed
+                .checked_next_multiple_of(align as usize)
+                .ok_or("section too large")?,
+        };
+        let new_len = self.len.checked_add(shift).ok_or("section too large")?;
+        if new_len > self.data.len() {
+            return Err("not enough space in buffer");
+        }
+
+        let shift_offset = |offset: usize| match offset >= old_end {
+            true => offset + shift,
+            false => offset,
+        };
+        let phoff = shift_offset(elf.phdr_offset());
+        let shoff = shift_offset(elf.shdr_offset());
+        let (phnum, shnum) = (elf.phdr_num(), elf.shdr_num());
+
+        self.data.copy_within(old_end..self.len, old_end + shift);
+        self.data[old_end..old_end + additional].fill(0);
+        if shift > additional {
+            self.data[old_end + additional..old_end + shift].fill(self.fill);
+        }
+        self.len = new_len;
+
+        self.file_header_mut()
+            .set_program_headers(phoff as u64, phnum);
+        self.file_header_mut()
+            .set_section_headers(shoff as u64, shnum);
+
+        for i in 0..phnum {
+            let phdr = self.program_header_mut(i).unwrap();
+            let (offset, size) = (phdr.file_offset(), phdr.file_size());
+            match size != 0 && offset <= start && offset + size == old_end {
+                true => {
+                    let size = size + additional;
+                    phdr.set_file_range(offset as u64, size as u64);
+                    if phdr.mem_size() < size {
+                        *phdr = phdr.with_mem_size(size as u64);
+                    }
+                }
+                false => phdr.set_file_range(shift_offset(offset) as u64, size as u64),
+            }
+        }
+        for i in 0..shnum {
+            let shdr = self.section_header_mut(i).unwrap();
+            let (offset, size) = match i == index {
+                true => (shdr.file_offset(), new_size),
+                false => (shift_offset(shdr.file_offset()), shdr.size()),
+            };
+            shdr.set_file_range(offset as u64, size as u64);
+        }
+
+        Ok(())
+    }
+    /// Rename sections and change their flags, like `objcopy --rename-section` and
+    /// `--set-section-flags`
+    ///
+    /// `edit` is called once with the name of each section after the null section. New
+    /// names are appended to a copy of the section name string table written at the end of
+    /// the file, and the old string table is left in place but is no longer referenced.
+    ///
+    /// If an error is returned, the file may have been partially modified.
+    pub fn edit_sections<'a, F>(&mut self, mut edit: F) -> Result<(), &'static str>
+    where
+        F: FnMut(&str, &SectionHeader) -> SectionEdit<'a>,
+    {
+        const SHDR_SIZE: usize = size_of::<SectionHeader>();
+
+        let elf = self.as_elf();
+        let (shoff, shnum) = (elf.shdr_offset(), elf.shdr_num() as usize);
+        let strtab_index = elf.shdr_strtab_index();
+        let strtab = elf
+            .section(strtab_index)
+            .filter(|_| strtab_index != 0)
+            .ok_or("no section name string table")?;
+        let (str_offset, str_size) = (strtab.file_offset(), strtab.size());
+        if shoff
+            .checked_add(shnum * SHDR_SIZE)
+            .is_none_or(|e| e > self.len)
+            || str_offset
+                .checked_add(str_size)
+                .is_none_or(|e| e > self.len)
+        {
+            return Err("section headers out of bounds");
+        }
+
+        // Copy the string table to the end of the file, then append new names to it.
+        let new_str_offset = self.len;
+        let mut new_str_size = str_size;
+        if new_str_offset + str_size > self.data.len() {
+            return Err("not enough space in buffer");
+        }
+        self.data
+            .copy_within(str_offset..str_offset + str_size, new_str_offset);
+
+        for index in 1..shnum {
+            let (head, tail) = self.data.split_at_mut(new_str_offset);
+            let shdr: &mut SectionHeader = from_bytes_mut(&mut head[shoff + index * SHDR_SIZE..]);
+            let name_index = shdr.name_index() as usize;
+            let name = tail[..new_str_size]
+                .get(name_index..)
+                .and_then(|s| s.split(|&b| b == 0).next())
+                .and_then(|s| core::str::from_utf8(s).ok())
+                .ok_or("invalid section name")?;
+            let name_len = name.len();
+
+            let SectionEdit {
+                name: new_name,
+                flags,
+            } = edit(name, shdr);
+            if let Some(index) =
+                append_name(tail, &mut new_str_size, name_index, name_len, new_name)?
+            {
+                shdr.set_name_index(index);
+            }
+            if let Some(flags) = flags {
+                shdr.set_flags(flags);
+            }
+        }
+
+        if new_str_size != str_size {
+            self.len = new_str_offset + new_str_size;
+            self.section_header_mut(strtab_index)
+                .unwrap()
+                .set_file_range(new_str_offset as u64, new_str_size as u64);
+        }
+
+        Ok(())
+    }
+
+    /// Rename symbols and change their binding, like `objcopy --redefine-sym`,
+    /// `--prefix-symbols`, `--localize-symbol`, and `--weaken-symbol`
+    ///
+    /// `edit` is called once with the name of each symbol in `.symtab` after the null
+    /// symbol. New names are appended to a copy of the string table written at the end of
+    /// the file, and the old string table is left in place but is no longer referenced.
+    ///
+    /// Local symbols must precede all others, so symbols whose binding changes to or from
+    /// [`Binding::Local`] are moved, and the symbol indices in relocation sections, group
+    /// sections, and `SHT_SYMTAB_SHNDX` sections are updated to match.
+    ///
+    /// If an error is returned, the file may have been partially modified.
+    pub fn edit_symbols<'a, F>(&mut self, mut edit: F) -> Result<(), &'static str>
+    where
+        F: FnMut(&str, &Sym) -> SymbolEdit<'a>,
+    {
+        const SYM_SIZE: usize = size_of::<Sym>();
+
+        let elf = self.as_elf();
+        let symtab = elf.symtab_section().ok_or("no symbol table")?;
+        let symtab_index = symtab.index();
+        let strtab_index = symtab.link() as u16;
+        let strtab = elf.section(strtab_index).ok_or("no symbol string table")?;
+        if strtab.section_type() != SectionType::Strtab {
+            return Err("no symbol string table");
+        }
+        let (sym_offset, sym_count) = (symtab.file_offset(), symtab.size() / SYM_SIZE);
+        let (str_offset, str_size) = (strtab.file_offset(), strtab.size());
+        if symtab.entry_size() != SYM_SIZE as u64
+            || sym_offset
+                .checked_add(sym_count * SYM_SIZE)
+                .is_none_or(|e| e > self.len)
+            || str_offset
+                .checked_add(str_size)
+                .is_none_or(|e| e > self.len)
+        {
+            return Err("symbol table out of bounds");
+        }
+
+        // Copy the string table to the end of the file, then append new names to it.
+        let new_str_offset = self.len;
+        let mut new_str_size = str_size;
+        if new_str_offset + str_size > self.data.len() {
+            return Err("not enough space in buffer");
+        }
+        self.data
+            .copy_within(str_offset..str_offset + str_size, new_str_offset);
+
+        for index in 1..sym_count {
+            let (head, tail) = self.data.split_at_mut(new_str_offset);
+            let sym: &mut Sym = from_bytes_mut(&mut head[sym_offset + index * SYM_SIZE..]);
+            let name_index = sym.name_index();
+            let name = tail[..new_str_size]
+                .get(name_index..)
+                .and_then(|s| s.split(|&b| b == 0).next())
+                .and_then(|s| core::str::from_utf8(s).ok())
+                .ok_or("invalid symbol name")?;
+            let name_len = name.len();
+
+            let SymbolEdit {
+                name: new_name,
+                binding,
+            } = edit(name, sym);
+            if let Some(index) =
+                append_name(tail, &mut new_str_size, name_index, name_len, new_name)?
+            {
+                sym.set_name_index(index);
+            }
+
+            if let Some(binding) = binding {
+                if binding == Binding::Local && sym.section_index() == SHN_UNDEF {
+                    return Err("cannot make an undefined symbol local");
+                }
+                sym.set_binding(binding);
+            }
+        }
+
+        if new_str_size != str_size {
+            self.len = new_str_offset + new_str_size;
+            self.section_header_mut(strtab_index)
+                .unwrap()
+                .set_file_range(new_str_offset as u64, new_str_size as u64);
+        }
+
+        // Move local symbols in front of the others, keeping their relative order.
+        let mut locals = 0;
+        for index in 0..sym_count {
+            let sym = &self.data[sym_offset + index * SYM_SIZE..][..SYM_SIZE];
+            let sym: &Sym = from_bytes(sym);
+            if sym.binding() != Binding::Local {
+                continue;
+            }
+            if index != locals {
+                self.move_symbol(symtab_index, index, locals)?;
+            }
+            locals += 1;
+        }
+        self.section_header_mut(symtab_index)
+            .unwrap()
+            .set_info(locals as u32);
+
+        Ok(())
+    }
+
+    /// Move the symbol at `from` down to `to`, shifting those in between up by one, and
+    /// update references to them
+    fn move_symbol(&mut self, symtab: u16, from: usize, to: usize) -> Result<(), &'static str> {
+        let remap = |index: usize| match index {
+            i if i == from => to,
+            i if (to..from).contains(&i) => i + 1,
+            i => i,
+        };
+
+        let shnum = self.as_elf().shdr_num();
+        for index in 0..shnum {
+            let elf = self.as_elf();
+            let sect = elf.section(index).unwrap();
+            let (kind, link, info) = (sect.section_type(), sect.link(), sect.info());
+            let (offset, size, entry_size) = (sect.file_offset(), sect.size(), sect.entry_size());
+
+            let contents = self.data[..self.len]
+                .get_mut(offset..)
+                .and_then(|data| data.get_mut(..size));
+            let rotate = |data: Option<&mut [u8]>, entry_size: usize| {
+                data.and_then(|data| data.get_mut(to * entry_size..(from + 1) * entry_size))
+                    .ok_or("section out of bounds")
+                    .map(|range| range.rotate_right(entry_size))
+            };
+            match kind {
+                _ if index == symtab => rotate(contents, size_of::<Sym>())?,
+                _ if link != symtab as u32 => {}
+                SectionType::SymtabShndx => rotate(contents, 4)?,
+                SectionType::Group => self
+                    .section_header_mut(index)
+                    .unwrap()
+                    .set_info(remap(info as usize) as u32),
+                SectionType::Rel | SectionType::Rela => {
+                    let contents = contents.ok_or("section out of bounds")?;
+                    if entry_size < size_of::<Rel>() as u64 {
+                        return Err("unexpected entry size");
+                    }
+                    for entry in contents.chunks_exact_mut(entry_size as usize) {
+                        let entry: &mut Rel = from_bytes_mut(entry);
+                        let info = entry.info();
+                        let symbol = remap(info.symbol() as usize) as u32;
+                        entry.set_info(RelocInfo::new(symbol, info.kind()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the sections selected by `matcher`, returning the number removed
+    ///
+    /// Relocation sections which apply to a removed section, or which use a removed symbol
+    /// table, are removed as well. The section header table is compacted, and section
+    /// indices in `sh_link`, `sh_info`, section groups, and symbol tables are updated.
+    /// Symbols defined in a removed section become undefined, and links to removed
+    /// sections are cleared. The contents of removed sections are left in the file, but
+    /// are no longer referenced.
+    ///
+    /// Sections which are part of a segment and the section name string table cannot be
+    /// removed. The file is checked before it is modified, so it is left unchanged if an
+    /// error is returned.
+    pub fn remove_sections<M: SectionMatcher>(
+        &mut self,
+        mut matcher: M,
+    ) -> Result<u16, &'static str> {
+        const SHDR_SIZE: usize = size_of::<SectionHeader>();
+
+        let elf = self.as_elf();
+        let (shoff, shnum) = (elf.shdr_offset(), elf.shdr_num());
+        let strtab_index = elf.shdr_strtab_index();
+        if shoff
+            .checked_add(shnum as usize * SHDR_SIZE)
+            .is_none_or(|e| e > self.len)
+        {
+            return Err("section headers out of bounds");
+        }
+
+        let mut removed = SectionSet::new();
+        for sect in elf.sections().skip(1) {
+            if !matcher.matches(sect.name().unwrap_or(""), &sect) {
+                continue;
+            }
+            if sect.index() == strtab_index {
+                return Err("cannot remove the section name string table");
+            }
+            if elf.segments().any(|sgmt| sgmt.contains_section(&sect)) {
+                return Err("section is part of a segment");
+            }
+            removed.insert(sect.index());
+        }
+        for sect in elf.sections().skip(1) {
+            if matches!(sect.section_type(), SectionType::Rel | SectionType::Rela)
+                && sect.index() != strtab_index
+                && (removed.new_index(shnum, sect.link()).is_none()
+                    || removed.new_index(shnum, sect.info()).is_none())
+            {
+                removed.insert(sect.index());
+            }
+        }
+        for sect in elf.sections().skip(1) {
+            if removed.contains(sect.index()) || sect.section_type() == SectionType::Null {
+                continue;
+            }
+            if sect
+                .file_offset()
+                .checked_add(sect.size())
+                .is_none_or(|end| end > self.len)
+            {
+                return Err("section out of bounds");
+            }
+        }
+
+        for index in 1..shnum {
+            let elf = self.as_elf();
+            let sect = elf.section(index).unwrap();
+            let kind = sect.section_type();
+            let (link, info, flags) = (sect.link(), sect.info(), sect.flags());
+            let (offset, size) = (sect.file_offset(), sect.size());
+            if removed.contains(index) || kind == SectionType::Null {
+                continue;
+            }
+
+            let link = removed.new_index(shnum, link).unwrap_or(0);
+            let info =
+                match matches!(kind, SectionType::Rel | SectionType::Rela) || flags.info_link() {
+                    true => removed.new_index(shnum, info).unwrap_or(0),
+                    false => info,
+                };
+            let shdr = self.section_header_mut(index).unwrap();
+            shdr.set_link(link);
+            shdr.set_info(info);
+
+            let contents = offset..offset + size;
+            match kind {
+                SectionType::Group => {
+                    let mut kept = 4;
+                    for entry in (offset + 4..contents.end).step_by(4) {
+                        let member =
+                            u32::from_le_bytes(self.data[entry..entry + 4].try_into().unwrap());
+                        if let Some(member) = removed.new_index(shnum, member) {
+                            self.data[offset + kept..][..4].copy_from_slice(&member.to_le_bytes());
+                            kept += 4;
+                        }
+                    }
+                    self.section_header_mut(index)
+                        .unwrap()
+                        .set_file_range(offset as u64, kept as u64);
+                }
+                SectionType::SymtabShndx => {
+                    for entry in contents.step_by(4) {
+                        let word = &self.data[entry..][..4];
+                        let old = u32::from_le_bytes(word.try_into().unwrap());
+                        if old != 0 {
+                            let new = removed.new_index(shnum, old).unwrap_or(0);
+                            self.data[entry..][..4].copy_from_slice(&new.to_le_bytes());
+                        }
+                    }
+                }
+                SectionType::Symtab | SectionType::Dynsym => {
+                    for entry in contents.step_by(size_of::<Sym>()) {
+                        let Some(sym) = self.data.get(entry..entry + size_of::<Sym>()) else {
+                            break;
+                        };
+                        let old = from_bytes::<Sym>(sym).section_index();
+                        if old == SHN_UNDEF || old >= SHN_LORESERVE {
+                            continue;
+                        }
+                        let new = removed.new_index(shnum, old as u32).unwrap_or(0) as u16;
+                        let sym: &mut Sym = from_bytes_mut(&mut self.data[entry..]);
+                        sym.set_section_index(new);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let new_strtab_index = removed.new_index(shnum, strtab_index as u32).unwrap() as u16;
+        let mut kept = 0;
+        for index in 0..shnum as usize {
+            if removed.contains(index as u16) {
+                continue;
+            }
+            let src = shoff + index * SHDR_SIZE;
+            self.data
+                .copy_within(src..src + SHDR_SIZE, shoff + kept * SHDR_SIZE);
+            kept += 1;
+        }
+
+        let header = self.file_header_mut();
+        header.set_section_headers(shoff as u64, kept as u16);
+        header.set_shdr_strtab_index(new_strtab_index);
+
+        Ok(shnum - kept as u16)
+    }
+
+    /// Remove symbols from `.symtab` which are neither referenced nor selected by `keep`,
+    /// returning the number removed
+    ///
+    /// Symbols referenced by a relocation section or used as the signature of a section
+    /// group are always kept. `keep` is called with the name of each other symbol after
+    /// the null symbol. The table is compacted in place, keeping the order of the remaining
+    /// symbols, and the symbol indices in relocation sections and section groups are
+    /// updated. Names of removed symbols are left in the string table.
+    #[cfg(feature = "alloc")]
+    pub fn prune_symbols<F>(&mut self, mut keep: F) -> Result<usize, &'static str>
+    where
+        F: FnMut(&str, &Sym) -> bool,
+    {
+        const SYM_SIZE: usize = size_of::<Sym>();
+
+        let elf = self.as_elf();
+        let symtab = elf.symtab_section().ok_or("no symbol table")?;
+        let symtab_index = symtab.index();
+        let sym_offset = symtab.file_offset();
+        let syms = crate::table::Table::<Sym>::from_section(&symtab).map_err(|e| e.message)?;
+        let strtab = elf
+            .section(symtab.link() as u16)
+            .and_then(|s| Some(crate::StringTable::new(s.file_data()?)));
+
+        let mut kept = alloc::vec![false; syms.len()];
+        if let Some(null) = kept.first_mut() {
+            *null = true;
+        }
+        let mut referenced = |index: u32| {
+            *kept
+                .get_mut(index as usize)
+                .ok_or("symbol index out of range")? = true;
+            Ok::<_, &'static str>(())
+        };
+        for sect in elf.sections().filter(|s| s.link() == symtab_index as u32) {
+            match sect.section_type() {
+                SectionType::Rel => crate::table::Table::<Rel>::from_section(&sect)
+                    .map_err(|e| e.message)?
+                    .iter()
+                    .try_for_each(|rel| referenced(rel.sym()))?,
+                SectionType::Rela => crate::table::Table::<crate::Rela>::from_section(&sect)
+                    .map_err(|e| e.message)?
+                    .iter()
+                    .try_for_each(|rela| referenced(rela.sym()))?,
+                SectionType::Group => referenced(sect.info())?,
+                _ => {}
+            }
+        }
+        for (index, sym) in syms.iter().enumerate().skip(1) {
+            if !kept[index] {
+                let name = strtab.and_then(|s| s.get_string(sym.name_index()));
+                kept[index] = keep(name.unwrap_or(""), &sym);
+            }
+        }
+
+        // Map the old index of each symbol to its new one.
+        let mut new_index = alloc::vec![u32::MAX; syms.len()];
+        let mut count = 0;
+        let mut locals = 0;
+        for (index, sym) in syms.iter().enumerate() {
+            if kept[index] {
+                new_index[index] = count as u32;
+                count += 1;
+                if sym.binding() == Binding::Local {
+                    locals = count;
+                }
+            }
+        }
+        let shndx = elf
+            .sections()
+            .find(|s| {
+                s.section_type() == SectionType::SymtabShndx && s.link() == symtab_index as u32
+            })
+            .map(|s| (s.index(), s.file_offset()));
+        let shnum = elf.shdr_num();
+
+        for (old, &new) in new_index.iter().enumerate() {
+            if new == u32::MAX || new as usize == old {
+                continue;
+            }
+            let (old, new) = (old, new as usize);
+            self.data.copy_within(
+                sym_offset + old * SYM_SIZE..sym_offset + (old + 1) * SYM_SIZE,
+                sym_offset + new * SYM_SIZE,
+            );
+            if let Some((_, offset)) = shndx {
+                self.data
+                    .copy_within(offset + old * 4..offset + (old + 1) * 4, offset + new * 4);
+            }
+        }
+        let shdr = self.section_header_mut(symtab_index).unwrap();
+        shdr.set_file_range(sym_offset as u64, (count * SYM_SIZE) as u64);
+        shdr.set_info(locals as u32);
+        if let Some((index, offset)) = shndx {
+            self.section_header_mut(index)
+                .unwrap()
+                .set_file_range(offset as u64, (count * 4) as u64);
+        }
+
+        for index in 0..shnum {
+            let elf = self.as_elf();
+            let sect = elf.section(index).unwrap();
+            if sect.link() != symtab_index as u32 {
+                continue;
+            }
+            let (kind, info) = (sect.section_type(), sect.info());
+            let (offset, size, entry_size) = (sect.file_offset(), sect.size(), sect.entry_size());
+            match kind {
+                SectionType::Group => self
+                    .section_header_mut(index)
+                    .unwrap()
+                    .set_info(new_index[info as usize]),
+                SectionType::Rel | SectionType::Rela => {
+                    for entry in
+                        self.data[offset..offset + size].chunks_exact_mut(entry_size as usize)
+                    {
+                        let entry: &mut Rel = from_bytes_mut(entry);
+                        let info = entry.info();
+                        let symbol = new_index[info.symbol() as usize];
+                        entry.set_info(RelocInfo::new(symbol, info.kind()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(new_index.len() - count)
+    }
+}
+
+/// A set of section indices, such as those of the sections being removed
+struct SectionSet([u64; (u16::MAX as usize + 1) / 64]);
+
+impl SectionSet {
+    const fn new() -> SectionSet {
+        SectionSet([0; (u16::MAX as usize + 1) / 64])
+    }
+
+    fn insert(&mut self, index: u16) {
+        self.0[index as usize / 64] |= 1 << (index % 64);
+    }
+
+    fn contains(&self, index: u16) -> bool {
+        self.0[index as usize / 64] & 1 << (index % 64) != 0
+    }
+
+    /// Returns the index section `index` will have once the sections in the set are
+    /// removed from a table of `shnum` sections, or `None` if it is being removed
+    fn new_index(&self, shnum: u16, index: u32) -> Option<u32> {
+        match index {
+            0 => Some(0),
+            i if i >= shnum as u32 => Some(i),
+            i if self.contains(i as u16) => None,
+            i => {
+                let (word, bit) = (i as usize / 64, i % 64);
+                let below = self.0[..word].iter().map(|w| w.count_ones()).sum::<u32>()
+                    + (self.0[word] & ((1 << bit) - 1)).count_ones();
+                Some(i - below)
+            }
+        }
+    }
+}
+
+/// Append the new name of the string at `name_index` to `table`, returning its index
+///
+/// `table` holds a string table of `size` bytes followed by free space.
+fn append_name(
+    table: &mut [u8],
+    size: &mut usize,
+    name_index: usize,
+    name_len: usize,
+    new_name: NewName,
+) -> Result<Option<u32>, &'static str> {
+    let len = match new_name {
+        NewName::Keep => return Ok(None),
+        NewName::Rename(name) => name.len() + 1,
+        NewName::Prefix(prefix) => prefix.len() + name_len + 1,
+    };
+    let index = u32::try_from(*size).map_err(|_| "string table too large")?;
+    let dst = table
+        .get_mut(*size..*size + len)
+        .ok_or("not enough space in buffer")?;
+    match new_name {
+        NewName::Keep => {}
+        NewName::Rename(name) => dst[..name.len()].copy_from_slice(name.as_bytes()),
+        NewName::Prefix(prefix) => {
+            dst[..prefix.len()].copy_from_slice(prefix.as_bytes());
+            table.copy_within(name_index..name_index + name_len, *size + prefix.len());
+        }
+    }
+    table[*size + len - 1] = 0;
+    *size += len;
+    Ok(Some(index))
+}
+
+/// A change to a section made by [`ElfMut::edit_sections()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SectionEdit<'a> {
+    pub name: NewName<'a>,
+    /// The new flags, if they change
+    pub flags: Option<SectionFlags>,
+}
+
+impl<'a> SectionEdit<'a> {
+    /// Leave the section unchanged
+    pub const KEEP: SectionEdit<'static> = SectionEdit {
+        name: NewName::Keep,
+        flags: None,
+    };
+
+    pub const fn rename(name: &'a str) -> SectionEdit<'a> {
+        SectionEdit {
+            name: NewName::Rename(name),
+            flags: None,
+        }
+    }
+
+    pub const fn with_flags(mut self, flags: SectionFlags) -> SectionEdit<'a> {
+        self.flags = Some(flags);
+        self
+    }
+}
+
+/// A change to a symbol made by [`ElfMut::edit_symbols()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SymbolEdit<'a> {
+    pub name: NewName<'a>,
+    /// The new binding, if it changes
+    pub binding: Option<Binding>,
+}
+
+impl<'a> SymbolEdit<'a> {
+    /// Leave the symbol unchanged
+    pub const KEEP: SymbolEdit<'static> = SymbolEdit {
+        name: NewName::Keep,
+        binding: None,
+    };
+
+    pub const fn rename(name: &'a str) -> SymbolEdit<'a> {
+        SymbolEdit {
+            name: NewName::Rename(name),
+            binding: None,
+        }
+    }
+
+    pub const fn prefix(prefix: &'a str) -> SymbolEdit<'a> {
+        SymbolEdit {
+            name: NewName::Prefix(prefix),
+            binding: None,
+        }
+    }
+
+    pub const fn with_binding(mut self, binding: Binding) -> SymbolEdit<'a> {
+        self.binding = Some(binding);
+        self
+    }
+
+    /// Make the symbol local, hiding it from other files
+    pub const fn localize(self) -> SymbolEdit<'a> {
+        self.with_binding(Binding::Local)
+    }
+
+    /// Make the symbol weak, so other definitions take precedence over it
+    pub const fn weaken(self) -> SymbolEdit<'a> {
+        self.with_binding(Binding::Weak)
+    }
+}
+
+/// The new name of a symbol or section
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NewName<'a> {
+    Keep,
+    Rename(&'a str),
+    /// Prepend a prefix to the current name
+    Prefix(&'a str),
+}
+
+/// Selects sections for [`ElfMut::remove_sections()`]
+///
+/// This is implemented for predicates taking the name and header of a section, and for
+/// slices and arrays of patterns, which match sections whose name matches any of them.
+/// See [`glob_match()`] for the pattern syntax.
+pub trait SectionMatcher {
+    fn matches(&mut self, name: &str, shdr: &SectionHeader) -> bool;
+}
+
+impl<F: FnMut(&str, &SectionHeader) -> bool> SectionMatcher for F {
+    fn matches(&mut self, name: &str, shdr: &SectionHeader) -> bool {
+        self(name, shdr)
+    }
+}
+
+impl SectionMatcher for &[&str] {
+    fn matches(&mut self, name: &str, _: &SectionHeader) -> bool {
+        self.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+impl<const N: usize> SectionMatcher for [&str; N] {
+    fn matches(&mut self, name: &str, shdr: &SectionHeader) -> bool {
+        self.as_slice().matches(name, shdr)
+    }
+}
+
+/// Returns `true` if `name` matches `pattern`
+///
+/// `*` matches any sequence of bytes and `?` matches any single byte, as in
+/// `.debug_*` or `.text.?`. All other characters match themselves.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*`, and of the name when it was reached
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` match one more byte.
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use super::*;
+    use crate::testdata::Fixture;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn remove_sections_checks_before_modifying() {
+        let mut buf = Fixture::new().build();
+        let original = buf.clone();
+
+        let mut elf = ElfMut::new(&mut buf).unwrap();
+        let result = elf.remove_sections([".strtab", ".shstrtab"]);
+        assert_eq!(result, Err("cannot remove the section name string table"));
+        assert_eq!(buf, original);
+    }
+
+    #[test]
+    fn remove_sections_keeps_null_sections() {
+        let mut buf = Fixture::new().build();
+        let mut elf = ElfMut::new(&mut buf).unwrap();
+        let symtab = elf.as_elf().symtab_section().unwrap().index();
+        elf.section_header_mut(symtab)
+            .unwrap()
+            .set_section_type(SectionType::Null);
+        let shnum = elf.as_elf().shdr_num();
+
+        assert_eq!(elf.remove_sections([".strtab"]), Ok(1));
+        let elf = elf.as_elf();
+        assert_eq!(elf.shdr_num(), shnum - 1);
+        assert_eq!(
+            elf.section(symtab).unwrap().section_type(),
+            SectionType::Null
+        );
+        assert!(elf.section_by_name(".strtab").is_none());
+        assert!(elf.section_by_name(".shstrtab").is_some());
+    }
+
+    /// Returns a fixture whose `PT_DYNAMIC` segment has been moved to cover `.strtab`
+    fn fixture_with_segment_over_strtab() -> (Vec<u8>, u16, usize) {
+        let mut buf = Fixture::new().build();
+        let mut elf = ElfMut::new(&mut buf).unwrap();
+        let (index, shdr) = elf.as_elf().section_header_by_name(".strtab").unwrap();
+        let (offset, size) = (shdr.file_offset(), shdr.size());
+        elf.program_header_mut(1)
+            .unwrap()
+            .set_file_range(offset as u64, size as u64);
+        (buf, index, size)
+    }
+
+    #[test]
+    fn grow_section_grows_covering_segment() {
+        let (mut buf, index, size) = fixture_with_segment_over_strtab();
+        buf.resize(buf.len() + 0x100, 0);
+        let len = buf.len() - 0x100;
+        let mut elf = ElfMut::with_len(&mut buf, len).unwrap();
+
+        elf.grow_section(index, 8).unwrap();
+        let elf = elf.as_elf();
+        let sect = elf.section(index).unwrap();
+        let sgmt = elf.segments().nth(1).unwrap();
+        assert_eq!(sect.size(), size + 8);
+        assert_eq!(sgmt.file_offset(), sect.file_offset());
+        assert_eq!(sgmt.file_size(), size + 8);
+        assert!(sgmt.mem_size() >= sgmt.file_size());
+    }
+
+    #[test]
+    fn grow_section_rejects_segment_overflow() {
+        let (mut buf, index, _) = fixture_with_segment_over_strtab();
+        let mut elf = ElfMut::new(&mut buf).unwrap();
+        let phdr = elf.program_header_mut(1).unwrap();
+        phdr.set_file_range(phdr.file_offset() as u64, u64::MAX);
+
+        assert_eq!(elf.grow_section(index, 8), Err("segment out of bounds"));
+    }
+
+    #[test]
+    fn append_section_rejects_string_table_overflow() {
+        let mut buf = Fixture::new().build();
+        buf.resize(buf.len() + 0x400, 0);
+        let len = buf.len() - 0x400;
+        let mut elf = ElfMut::with_len(&mut buf, len).unwrap();
+        let strtab = elf.as_elf().shdr_strtab_index();
+        let shdr = elf.section_header_mut(strtab).unwrap();
+        shdr.set_file_range(shdr.file_offset() as u64, u64::MAX);
+
+        let result =
+            elf.append_section(".note", SectionType::Note, SectionFlags::from_bits(0), &[]);
+        assert_eq!(result, Err("section headers out of bounds"));
+    }
+}
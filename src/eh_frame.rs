@@ -0,0 +1,475 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Exception handling frame tables
+//!
+//! `.eh_frame_hdr` holds a table of the functions described by `.eh_frame`, sorted by
+//! address, which allows the FDE covering an address to be found by binary search.
+
+use crate::{
+    leb128::{read_sleb128, read_uleb128},
+    table::Encoding,
+    Elf, SegmentKind,
+};
+use core::ops::Range;
+
+/// A `DW_EH_PE_*` pointer encoding
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PointerEncoding(pub u8);
+
+impl PointerEncoding {
+    pub const ABSPTR: Self = Self(0x00);
+    pub const ULEB128: Self = Self(0x01);
+    pub const UDATA2: Self = Self(0x02);
+    pub const UDATA4: Self = Self(0x03);
+    pub const UDATA8: Self = Self(0x04);
+    pub const SLEB128: Self = Self(0x09);
+    pub const SDATA2: Self = Self(0x0a);
+    pub const SDATA4: Self = Self(0x0b);
+    pub const SDATA8: Self = Self(0x0c);
+
+    pub const PCREL: Self = Self(0x10);
+    pub const TEXTREL: Self = Self(0x20);
+    pub const DATAREL: Self = Self(0x30);
+    pub const FUNCREL: Self = Self(0x40);
+    pub const ALIGNED: Self = Self(0x50);
+
+    pub const INDIRECT: Self = Self(0x80);
+    pub const OMIT: Self = Self(0xff);
+
+    /// Returns the size of an encoded value in a little-endian ELF64 file, or `None` if it
+    /// is variable
+    pub const fn fixed_size(self) -> Option<usize> {
+        self.fixed_size_in(Encoding::NATIVE)
+    }
+
+    /// Returns the size of an encoded value in a file laid out as `encoding`
+    pub(crate) const fn fixed_size_in(self, encoding: Encoding) -> Option<usize> {
+        match self.0 & 0x0f {
+            0x00 => Some(encoding.word_size()),
+            0x04 | 0x0c => Some(8),
+            0x02 | 0x0a => Some(2),
+            0x03 | 0x0b => Some(4),
+            _ => None,
+        }
+    }
+
+    /// Decode a pointer from the start of `data`, in a little-endian ELF64 file
+    ///
+    /// `pc` is the address of the encoded value, used by `DW_EH_PE_pcrel`, and `data_base`
+    /// is the base used by `DW_EH_PE_datarel`. Indirect, text-relative, function-relative
+    /// and aligned pointers are not supported.
+    pub fn read(self, data: &mut &[u8], pc: u64, data_base: u64) -> Option<u64> {
+        self.read_in(data, pc, data_base, Encoding::NATIVE)
+    }
+
+    /// Decode a pointer from the start of `data`, in a file laid out as `encoding`
+    ///
+    /// See [`read()`](Self::read).
+    pub(crate) fn read_in(
+        self,
+        data: &mut &[u8],
+        pc: u64,
+        data_base: u64,
+        encoding: Encoding,
+    ) -> Option<u64> {
+        if self == Self::OMIT || self.0 & Self::INDIRECT.0 != 0 {
+            return None;
+        }
+
+        let value = match self.0 & 0x0f {
+            0x01 => read_uleb128(data)?,
+            0x09 => read_sleb128(data)? as u64,
+            format => {
+                let size = self.fixed_size_in(encoding)?;
+                let bytes = data.get(..size)?;
+                *data = &data[size..];
+                match format {
+                    0x00 => encoding.read_word(bytes)?,
+                    0x02 => encoding.read_u16(bytes)? as u64,
+                    0x03 => encoding.read_u32(bytes)? as u64,
+                    0x04 | 0x0c => encoding.read_u64(bytes)?,
+                    0x0a => encoding.read_u16(bytes)? as i16 as u64,
+                    _ => encoding.read_u32(bytes)? as i32 as u64,
+                }
+            }
+        };
+
+        let base = match self.0 & 0x70 {
+            0x00 => 0,
+            0x10 => pc,
+            0x30 => data_base,
+            _ => return None,
+        };
+
+        Some(base.wrapping_add(value))
+    }
+}
+
+/// The contents of `.eh_frame_hdr`
+#[derive(Clone, Copy, Debug)]
+pub struct EhFrameHdr<'a> {
+    vaddr: u64,
+    eh_frame_ptr: u64,
+    fde_count: usize,
+    table_enc: PointerEncoding,
+    table: &'a [u8],
+    table_vaddr: u64,
+    encoding: Encoding,
+}
+
+impl<'a> EhFrameHdr<'a> {
+    /// Parse `.eh_frame_hdr` of a little-endian ELF64 file, located at `vaddr`
+    ///
+    /// The table is only usable for searching if its entries have a fixed size.
+    pub fn parse(data: &'a [u8], vaddr: u64) -> Option<EhFrameHdr<'a>> {
+        EhFrameHdr::parse_in(data, vaddr, Encoding::NATIVE)
+    }
+
+    /// Parse `.eh_frame_hdr` of a file laid out as `encoding`, located at `vaddr`
+    pub(crate) fn parse_in(
+        data: &'a [u8],
+        vaddr: u64,
+        encoding: Encoding,
+    ) -> Option<EhFrameHdr<'a>> {
+        let (&version, rest) = data.split_first()?;
+        if version != 1 || rest.len() < 3 {
+            return None;
+        }
+        let eh_frame_enc = PointerEncoding(rest[0]);
+        let count_enc = PointerEncoding(rest[1]);
+        let table_enc = PointerEncoding(rest[2]);
+
+        let mut cursor = &data[4..];
+        let pc = |cursor: &[u8]| vaddr + (data.len() - cursor.len()) as u64;
+        let at = pc(cursor);
+        let eh_frame_ptr = eh_frame_enc.read_in(&mut cursor, at, vaddr, encoding)?;
+        let at = pc(cursor);
+        let fde_count = match count_enc {
+            PointerEncoding::OMIT => 0,
+            enc => enc.read_in(&mut cursor, at, vaddr, encoding)? as usize,
+        };
+
+        let entry_size = 2 * table_enc.fixed_size_in(encoding)?;
+        let table = cursor.get(..fde_count.checked_mul(entry_size)?)?;
+        let table_vaddr = pc(cursor);
+
+        Some(EhFrameHdr {
+            vaddr,
+            eh_frame_ptr,
+            fde_count,
+            table_enc,
+            table,
+            table_vaddr,
+            encoding,
+        })
+    }
+
+    /// Returns the address of `.eh_frame`
+    #[inline]
+    pub const fn eh_frame_ptr(&self) -> u64 {
+        self.eh_frame_ptr
+    }
+
+    /// Returns the number of entries in the search table
+    #[inline]
+    pub const fn fde_count(&self) -> usize {
+        self.fde_count
+    }
+
+    /// Returns the initial location and FDE address of the `index`th table entry
+    pub fn entry(&self, index: usize) -> Option<(u64, u64)> {
+        let (enc, encoding) = (self.table_enc, self.encoding);
+        let size = enc.fixed_size_in(encoding)?;
+        let mut entry = self.table.get(index * 2 * size..)?;
+        let pc = self.table_vaddr + (index * 2 * size) as u64;
+
+        let initial_loc = enc.read_in(&mut entry, pc, self.vaddr, encoding)?;
+        let fde = enc.read_in(&mut entry, pc + size as u64, self.vaddr, encoding)?;
+        Some((initial_loc, fde))
+    }
+
+    /// Returns the address of the FDE which may cover `pc`
+    ///
+    /// This is the FDE with the greatest initial location not above `pc`. The caller must
+    /// still check that `pc` lies within the FDE's address range.
+    pub fn find_fde(&self, pc: u64) -> Option<u64> {
+        // The number of entries with an initial location not above `pc`
+        let mut lo = 0;
+        let mut hi = self.fde_count;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.entry(mid)?.0 <= pc {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        Some(self.entry(lo.checked_sub(1)?)?.1)
+    }
+}
+
+/// Returns the total length of the CIE or FDE at the start of `data`, in bytes
+///
+/// This includes the length field itself. Returns `None` for the zero terminator. The
+/// entry is read as in a little-endian file.
+pub fn frame_entry_len(data: &[u8]) -> Option<usize> {
+    frame_entry_len_in(data, Encoding::NATIVE)
+}
+
+/// Returns the total length of the CIE or FDE at the start of `data`, in a file laid out
+/// as `encoding`
+fn frame_entry_len_in(data: &[u8], encoding: Encoding) -> Option<usize> {
+    match encoding.read_u32(data)? {
+        0 => None,
+        0xffff_ffff => {
+            let len = encoding.read_u64(data.get(4..)?)?;
+            usize::try_from(len).ok()?.checked_add(12)
+        }
+        len => Some(len as usize + 4),
+    }
+}
+
+/// The personality routine and LSDA of the function covering an address
+///
+/// These are what a landing-pad driven unwinder, such as Rust's panic runtime, needs to
+/// run cleanups and catch exceptions in a frame.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FdeInfo {
+    /// The addresses the FDE covers
+    pub pc_range: Range<u64>,
+    /// Address of the personality routine, from the CIE
+    pub personality: Option<u64>,
+    /// `personality` is the address of a pointer to the routine rather than the routine
+    /// itself, typically a GOT entry, which must be read at run time
+    pub personality_indirect: bool,
+    /// Address of the language-specific data area, from the FDE
+    pub lsda: Option<u64>,
+}
+
+/// The parts of a CIE's augmentation which affect how its FDEs are read
+struct Augmentation {
+    has_data: bool,
+    fde_enc: PointerEncoding,
+    lsda_enc: PointerEncoding,
+    personality: Option<u64>,
+    personality_indirect: bool,
+}
+
+/// Returns the offset of the contents of the CIE or FDE at the start of `data`
+fn frame_entry_header_len(data: &[u8], encoding: Encoding) -> Option<usize> {
+    match encoding.read_u32(data)? {
+        0xffff_ffff => Some(12),
+        _ => Some(4),
+    }
+}
+
+/// Returns the address of the CIE used by the FDE at the start of `fde`, located at `vaddr`
+///
+/// The FDE is read as in a little-endian file.
+pub fn fde_cie_pointer(fde: &[u8], vaddr: u64) -> Option<u64> {
+    fde_cie_pointer_in(fde, vaddr, Encoding::NATIVE)
+}
+
+/// Returns the address of the CIE used by an FDE in a file laid out as `encoding`
+fn fde_cie_pointer_in(fde: &[u8], vaddr: u64, encoding: Encoding) -> Option<u64> {
+    let at = frame_entry_header_len(fde, encoding)?;
+    let id = encoding.read_u32(fde.get(at..)?)?;
+    match id {
+        // A CIE
+        0 => None,
+        id => (vaddr + at as u64).checked_sub(id as u64),
+    }
+}
+
+impl FdeInfo {
+    /// Parse the FDE at the start of `fde` and the CIE at the start of `cie`
+    ///
+    /// `fde_vaddr` and `cie_vaddr` are the addresses they are located at, used to decode
+    /// PC-relative pointers, and `data_base` is the base of data-relative pointers. Returns
+    /// `None` if either is malformed or uses an unsupported pointer encoding. Both are read
+    /// as in a little-endian ELF64 file.
+    pub fn parse(
+        fde: &[u8],
+        fde_vaddr: u64,
+        cie: &[u8],
+        cie_vaddr: u64,
+        data_base: u64,
+    ) -> Option<FdeInfo> {
+        FdeInfo::parse_in(fde, fde_vaddr, cie, cie_vaddr, data_base, Encoding::NATIVE)
+    }
+
+    /// Parse an FDE and its CIE in a file laid out as `encoding`
+    ///
+    /// See [`FdeInfo::parse()`].
+    pub(crate) fn parse_in(
+        fde: &[u8],
+        fde_vaddr: u64,
+        cie: &[u8],
+        cie_vaddr: u64,
+        data_base: u64,
+        encoding: Encoding,
+    ) -> Option<FdeInfo> {
+        let aug = parse_cie(cie, cie_vaddr, data_base, encoding)?;
+
+        let start = frame_entry_header_len(fde, encoding)? + 4;
+        let body = fde.get(..frame_entry_len_in(fde, encoding)?)?;
+        let mut cursor = body.get(start..)?;
+        let pc = |cursor: &[u8]| fde_vaddr + (body.len() - cursor.len()) as u64;
+
+        let at = pc(cursor);
+        let pc_begin = aug.fde_enc.read_in(&mut cursor, at, data_base, encoding)?;
+        // The range is an unsigned value of the same size, without any base
+        let pc_len = PointerEncoding(aug.fde_enc.0 & 0x0f).read_in(&mut cursor, 0, 0, encoding)?;
+
+        let mut lsda = None;
+        if aug.has_data {
+            // Positions must be measured from the end of the entry, so the augmentation
+            // data is checked for length but read from `cursor`.
+            let len = read_uleb128(&mut cursor)?;
+            cursor.get(..usize::try_from(len).ok()?)?;
+            if aug.lsda_enc != PointerEncoding::OMIT {
+                let at = pc(cursor);
+                lsda = Some(aug.lsda_enc.read_in(&mut cursor, at, data_base, encoding)?);
+            }
+        }
+
+        Some(FdeInfo {
+            pc_range: pc_begin..pc_begin.wrapping_add(pc_len),
+            personality: aug.personality,
+            personality_indirect: aug.personality_indirect,
+            lsda,
+        })
+    }
+}
+
+fn parse_cie(cie: &[u8], vaddr: u64, data_base: u64, encoding: Encoding) -> Option<Augmentation> {
+    let start = frame_entry_header_len(cie, encoding)?;
+    let body = cie.get(..frame_entry_len_in(cie, encoding)?)?;
+    if body.get(start..start + 4)? != [0; 4] {
+        return None;
+    }
+    let mut cursor = body.get(start + 4..)?;
+    let pc = |cursor: &[u8]| vaddr + (body.len() - cursor.len()) as u64;
+
+    let (&version, rest) = cursor.split_first()?;
+    let nul = rest.iter().position(|&b| b == 0)?;
+    let augmentation = &rest[..nul];
+    cursor = &rest[nul + 1..];
+
+    let mut aug = Augmentation {
+        has_data: augmentation.first() == Some(&b'z'),
+        fde_enc: PointerEncoding::ABSPTR,
+        lsda_enc: PointerEncoding::OMIT,
+        personality: None,
+        personality_indirect: false,
+    };
+    if !aug.has_data {
+        return (augmentation.is_empty()).then_some(aug);
+    }
+
+    // Code and data alignment factors, and the return address register
+    read_uleb128(&mut cursor)?;
+    read_sleb128(&mut cursor)?;
+    match version {
+        1 => cursor = cursor.get(1..)?,
+        _ => _ = read_uleb128(&mut cursor)?,
+    }
+
+    let len = read_uleb128(&mut cursor)?;
+    cursor.get(..usize::try_from(len).ok()?)?;
+    let mut data = cursor;
+    for &c in &augmentation[1..] {
+        match c {
+            b'L' => (aug.lsda_enc, data) = (PointerEncoding(*data.first()?), &data[1..]),
+            b'R' => (aug.fde_enc, data) = (PointerEncoding(*data.first()?), &data[1..]),
+            b'P' => {
+                let (&enc, rest) = data.split_first()?;
+                data = rest;
+                let at = pc(data);
+                let direct = PointerEncoding(enc & !PointerEncoding::INDIRECT.0);
+                aug.personality = Some(direct.read_in(&mut data, at, data_base, encoding)?);
+                aug.personality_indirect = enc & PointerEncoding::INDIRECT.0 != 0;
+            }
+            b'S' | b'B' => {}
+            // The remaining augmentation data cannot be interpreted
+            _ => break,
+        }
+    }
+
+    Some(aug)
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the contents of `.eh_frame_hdr`, from the `PT_GNU_EH_FRAME` segment
+    pub fn eh_frame_hdr(&self) -> Option<EhFrameHdr<'elf>> {
+        let sgmt = self
+            .segments()
+            .find(|sgmt| sgmt.kind() == SegmentKind::EhFrame)?;
+        EhFrameHdr::parse_in(
+            sgmt.file_data()?,
+            sgmt.virtual_address(),
+            Encoding::of(self),
+        )
+    }
+
+    /// Find the FDE which may cover `pc`, returning its address range
+    ///
+    /// Addresses are link-time virtual addresses. The caller must still check that `pc` lies
+    /// within the FDE's address range.
+    pub fn find_fde(&self, pc: u64) -> Option<Range<u64>> {
+        let fde = self.eh_frame_hdr()?.find_fde(pc)?;
+        let encoding = Encoding::of(self);
+        let header = self.data_at(fde, 12).or_else(|| self.data_at(fde, 4))?;
+        let len = frame_entry_len_in(header, encoding)?;
+        Some(fde..fde + len as u64)
+    }
+
+    /// Returns the personality routine and LSDA of the function covering `pc`
+    ///
+    /// Addresses are link-time virtual addresses. Data-relative pointers are taken relative
+    /// to `.eh_frame_hdr`. Returns `None` if no FDE covers `pc`.
+    pub fn fde_info(&self, pc: u64) -> Option<FdeInfo> {
+        let hdr = self.eh_frame_hdr()?;
+        let encoding = hdr.encoding;
+        let read = |vaddr: u64| {
+            let header = self.data_at(vaddr, 12).or_else(|| self.data_at(vaddr, 4))?;
+            self.data_at(vaddr, frame_entry_len_in(header, encoding)?)
+        };
+
+        let fde_vaddr = hdr.find_fde(pc)?;
+        let fde = read(fde_vaddr)?;
+        let cie_vaddr = fde_cie_pointer_in(fde, fde_vaddr, encoding)?;
+        let cie = read(cie_vaddr)?;
+        let info = FdeInfo::parse_in(fde, fde_vaddr, cie, cie_vaddr, hdr.vaddr, encoding)?;
+        info.pc_range.contains(&pc).then_some(info)
+    }
+}
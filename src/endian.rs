@@ -0,0 +1,136 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Byte order handling for on-disk structures
+//!
+//! Fields of the raw ELF structures are stored as byte arrays tagged with the byte order
+//! of the file they were read from. Reading a field always decodes it explicitly, so a
+//! structure can never be accidentally interpreted in the host's byte order.
+
+use core::{fmt, marker::PhantomData};
+
+/// A byte order
+pub trait Endian: Copy + Default + fmt::Debug + Eq + Send + Sync + 'static {
+    /// `true` if values are stored most significant byte first
+    const BIG_ENDIAN: bool;
+}
+
+/// Least significant byte first
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LittleEndian;
+
+impl Endian for LittleEndian {
+    const BIG_ENDIAN: bool = false;
+}
+
+/// Most significant byte first
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BigEndian;
+
+impl Endian for BigEndian {
+    const BIG_ENDIAN: bool = true;
+}
+
+/// The byte order of the host
+#[cfg(target_endian = "little")]
+pub type NativeEndian = LittleEndian;
+
+/// The byte order of the host
+#[cfg(target_endian = "big")]
+pub type NativeEndian = BigEndian;
+
+macro_rules! endian_types {
+    ($($(#[$meta:meta])* $name:ident($int:ty, $size:literal);)*) => {$(
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+        pub struct $name<E: Endian = LittleEndian>([u8; $size], PhantomData<E>);
+
+        impl<E: Endian> $name<E> {
+            #[inline]
+            pub const fn new(value: $int) -> $name<E> {
+                if E::BIG_ENDIAN {
+                    Self(value.to_be_bytes(), PhantomData)
+                } else {
+                    Self(value.to_le_bytes(), PhantomData)
+                }
+            }
+
+            #[inline]
+            pub const fn from_bytes(bytes: [u8; $size]) -> $name<E> {
+                Self(bytes, PhantomData)
+            }
+
+            #[inline]
+            pub const fn to_bytes(self) -> [u8; $size] {
+                self.0
+            }
+
+            #[inline]
+            pub const fn get(self) -> $int {
+                if E::BIG_ENDIAN {
+                    <$int>::from_be_bytes(self.0)
+                } else {
+                    <$int>::from_le_bytes(self.0)
+                }
+            }
+
+            #[inline]
+            pub fn set(&mut self, value: $int) {
+                *self = Self::new(value);
+            }
+
+            /// Re-encode the value in byte order `F`
+            #[inline]
+            pub const fn convert<F: Endian>(self) -> $name<F> {
+                $name::new(self.get())
+            }
+        }
+
+        impl<E: Endian> fmt::Debug for $name<E> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.get(), f)
+            }
+        }
+    )*};
+}
+
+endian_types! {
+    /// A `u16` stored with byte order `E`
+    U16(u16, 2);
+    /// A `u32` stored with byte order `E`
+    U32(u32, 4);
+    /// A `u64` stored with byte order `E`
+    U64(u64, 8);
+    /// An `i32` stored with byte order `E`
+    I32(i32, 4);
+    /// An `i64` stored with byte order `E`
+    I64(i64, 8);
+}
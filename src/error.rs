@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Parse errors
+
+use core::fmt;
+
+/// The structure being parsed when an error occurred
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Structure {
+    FileHeader,
+    /// The program header at this index
+    ProgramHeader(u16),
+    /// The section header at this index
+    SectionHeader(u16),
+    /// The contents of the section at this index
+    Section(u16),
+    /// The contents of the segment at this index
+    Segment(u16),
+}
+
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FileHeader => f.write_str("file header"),
+            Self::ProgramHeader(index) => write!(f, "program header {index}"),
+            Self::SectionHeader(index) => write!(f, "section header {index}"),
+            Self::Section(index) => write!(f, "section {index}"),
+            Self::Segment(index) => write!(f, "segment {index}"),
+        }
+    }
+}
+
+/// An error found while parsing a file, with the location of the offending structure
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParseError {
+    pub structure: Structure,
+    /// File offset of the structure
+    pub offset: u64,
+    pub message: &'static str,
+}
+
+impl ParseError {
+    #[inline]
+    pub const fn new(structure: Structure, offset: u64, message: &'static str) -> ParseError {
+        Self {
+            structure,
+            offset,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:#x}: {}",
+            self.structure, self.offset, self.message
+        )
+    }
+}
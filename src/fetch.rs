@@ -0,0 +1,276 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+//! Fetching the headers of a file in chunks
+//!
+//! [`HeaderFetcher`] performs no I/O of its own. It asks for the next range of the file it
+//! needs, and the caller feeds it the bytes of that range as they arrive, in chunks of any
+//! size. This lets boot loaders drive it from TFTP or HTTP transfers, or from an async
+//! runtime, without the crate depending on one.
+
+use crate::{
+    parse_file_header,
+    table::{Encoding, Entry},
+    FileHeader, ParseError, ProgramHeader, SectionHeader, Structure,
+};
+use core::mem::size_of;
+
+/// A range of the file requested by [`HeaderFetcher::request()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FetchRequest {
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// A structure decoded by [`HeaderFetcher::feed()`]
+///
+/// Headers are decoded into the same little-endian ELF64 layouts used by
+/// [`Elf`](crate::Elf).
+#[derive(Clone, Copy)]
+pub enum Fetched {
+    FileHeader(FileHeader),
+    /// The program header at this index
+    ProgramHeader(u16, ProgramHeader),
+    /// The section header at this index
+    SectionHeader(u16, SectionHeader),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Step {
+    FileHeader,
+    ProgramHeaders,
+    SectionHeaders,
+    Done,
+}
+
+/// A sans-I/O reader of the file header and header tables
+///
+/// The file header is requested first, followed by the program header table and then the
+/// section header table, skipping tables which are empty.
+#[derive(Clone, Debug)]
+pub struct HeaderFetcher {
+    step: Step,
+    /// The range of the structure being fetched
+    range: FetchRequest,
+    /// Number of bytes of `range` fed so far
+    fetched: u64,
+    header: Option<FileHeader>,
+    encoding: Encoding,
+    /// Index of the header table entry being fetched
+    index: u16,
+    /// The bytes fed of the entry being fetched
+    buf: [u8; size_of::<FileHeader>()],
+    buf_len: usize,
+}
+
+impl HeaderFetcher {
+    pub const fn new() -> HeaderFetcher {
+        HeaderFetcher {
+            step: Step::FileHeader,
+            // Read the ELF32 header first, which is all there is of a truncated ELF32 file.
+            range: FetchRequest {
+                offset: 0,
+                len: FileHeader::SIZE32 as u64,
+            },
+            fetched: 0,
+            header: None,
+            encoding: Encoding::NATIVE,
+            index: 0,
+            buf: [0; size_of::<FileHeader>()],
+            buf_len: 0,
+        }
+    }
+
+    /// Returns the file header, once it has been fetched
+    #[inline]
+    pub fn header(&self) -> Option<&FileHeader> {
+        self.header.as_ref()
+    }
+
+    /// Returns the range of the file to be fed next, or `None` once every header has been
+    /// fetched or an error has occurred
+    ///
+    /// After a partial feed, this is the rest of the previously requested range.
+    pub fn request(&self) -> Option<FetchRequest> {
+        match self.step {
+            Step::Done => None,
+            _ => Some(FetchRequest {
+                offset: self.range.offset + self.fetched,
+                len: self.range.len - self.fetched,
+            }),
+        }
+    }
+
+    /// Returns the size of the structure being collected in the buffer
+    fn entry_size(&self) -> usize {
+        match self.step {
+            Step::FileHeader => (self.range.offset + self.range.len) as usize,
+            Step::ProgramHeaders => ProgramHeader::encoded_size(self.encoding),
+            _ => SectionHeader::encoded_size(self.encoding),
+        }
+    }
+
+    /// Feed the bytes at the start of the requested range, calling `f` with each structure
+    /// as it is completed
+    ///
+    /// `data` may hold any part of the range. Returns the number of bytes consumed, which is
+    /// less than the length of `data` only if it extends past the end of the range and no
+    /// more headers are to be fetched. Returns an error if the file header is malformed,
+    /// after which nothing more is requested.
+    pub fn feed<F>(&mut self, mut data: &[u8], mut f: F) -> Result<usize, ParseError>
+    where
+        F: FnMut(Fetched),
+    {
+        let mut consumed = 0;
+        while self.step != Step::Done && !data.is_empty() {
+            let len = (self.entry_size() - self.buf_len).min(data.len());
+            self.buf[self.buf_len..][..len].copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            self.fetched += len as u64;
+            data = &data[len..];
+            consumed += len;
+
+            if self.buf_len == self.entry_size() {
+                if let Err(error) = self.complete(&mut f) {
+                    self.step = Step::Done;
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    /// Decode the structure collected in the buffer, and move on to the next one
+    fn complete<F>(&mut self, f: &mut F) -> Result<(), ParseError>
+    where
+        F: FnMut(Fetched),
+    {
+        let ehdr = match self.step {
+            Step::FileHeader => {
+                if self.buf_len == FileHeader::SIZE32 && self.buf[4] == 2 {
+                    // The rest of the ELF64 header.
+                    self.range = FetchRequest {
+                        offset: FileHeader::SIZE32 as u64,
+                        len: (size_of::<FileHeader>() - FileHeader::SIZE32) as u64,
+                    };
+                    self.fetched = 0;
+                    return Ok(());
+                }
+
+                let (ehdr, encoding) = parse_file_header(&self.buf[..self.buf_len])?;
+                self.header = Some(ehdr);
+                self.encoding = encoding;
+                self.buf_len = 0;
+                f(Fetched::FileHeader(ehdr));
+                return self.next_table();
+            }
+            _ => match self.header {
+                Some(ehdr) => ehdr,
+                None => return Ok(()),
+            },
+        };
+        let encoding = self.encoding;
+
+        let index = self.index;
+        let entry = &self.buf[..self.buf_len];
+        let (fetched, num) = match self.step {
+            Step::ProgramHeaders => (
+                ProgramHeader::read(entry, encoding)
+                    .map(|phdr| Fetched::ProgramHeader(index, phdr)),
+                ehdr.phdr_num(),
+            ),
+            _ => (
+                SectionHeader::read(entry, encoding)
+                    .map(|shdr| Fetched::SectionHeader(index, shdr)),
+                ehdr.shdr_num(),
+            ),
+        };
+        self.buf_len = 0;
+        self.index += 1;
+        if let Some(fetched) = fetched {
+            f(fetched);
+        }
+
+        match self.index == num {
+            true => self.next_table(),
+            false => Ok(()),
+        }
+    }
+
+    /// Request the next non-empty header table
+    fn next_table(&mut self) -> Result<(), ParseError> {
+        let Some(ehdr) = self.header else {
+            return Ok(());
+        };
+        let encoding = self.encoding;
+        let table = |offset: usize, num: u16, size: usize| FetchRequest {
+            offset: offset as u64,
+            len: num as u64 * size as u64,
+        };
+
+        (self.step, self.range) = match self.step {
+            Step::FileHeader if ehdr.phdr_num() != 0 => (
+                Step::ProgramHeaders,
+                table(
+                    ehdr.phdr_offset(),
+                    ehdr.phdr_num(),
+                    ProgramHeader::encoded_size(encoding),
+                ),
+            ),
+            Step::FileHeader | Step::ProgramHeaders if ehdr.shdr_num() != 0 => (
+                Step::SectionHeaders,
+                table(
+                    ehdr.shdr_offset(),
+                    ehdr.shdr_num(),
+                    SectionHeader::encoded_size(encoding),
+                ),
+            ),
+            _ => (Step::Done, FetchRequest { offset: 0, len: 0 }),
+        };
+        self.fetched = 0;
+        self.index = 0;
+
+        if self.range.offset.checked_add(self.range.len).is_none() {
+            return Err(ParseError::new(
+                Structure::FileHeader,
+                0,
+                "header table out of range",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for HeaderFetcher {
+    #[inline]
+    fn default() -> HeaderFetcher {
+        HeaderFetcher::new()
+    }
+}
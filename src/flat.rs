@@ -0,0 +1,251 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Flat binary images
+
+use crate::{
+    Elf, ElfType, Endian, FileHeader, Machine, MapAddresses, ParseError, ProgramHeader,
+    SegmentFlags, SegmentKind, Structure,
+};
+use core::mem::size_of;
+
+/// The placement of a flat binary image in memory
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FlatBinary {
+    /// Address of the first byte of the image
+    pub base: u64,
+    /// Size of the image in bytes
+    pub size: u64,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the address and size of the flat binary image of this file, or `None` if
+    /// no segment has contents in the file
+    ///
+    /// See [`write_flat_binary()`](Elf::write_flat_binary).
+    pub fn flat_binary_layout(&self, addresses: MapAddresses) -> Option<FlatBinary> {
+        let mut layout: Option<(u64, u64)> = None;
+
+        for (start, data) in self.flat_segments(addresses).ok()? {
+            let end = start.checked_add(data.len() as u64)?;
+            layout = Some(match layout {
+                None => (start, end),
+                Some((s, e)) => (s.min(start), e.max(end)),
+            });
+        }
+
+        layout.map(|(base, end)| FlatBinary {
+            base,
+            size: end - base,
+        })
+    }
+
+    /// Write the flat binary image of this file to `buf`, like `objcopy -O binary`
+    ///
+    /// The file contents of the `PT_LOAD` segments are placed at their addresses relative
+    /// to the lowest one, and gaps between them are filled with `fill`. Memory beyond a
+    /// segment's file size, such as `.bss`, is not included, so the image ends with the
+    /// last byte of file contents.
+    ///
+    /// Returns the layout of the image, whose size is the number of bytes written.
+    pub fn write_flat_binary(
+        &self,
+        addresses: MapAddresses,
+        fill: u8,
+        buf: &mut [u8],
+    ) -> Result<FlatBinary, ParseError> {
+        let error = |message| ParseError::new(Structure::FileHeader, 0, message);
+
+        let layout = self
+            .flat_binary_layout(addresses)
+            .ok_or(error("no loadable contents"))?;
+        let size = usize::try_from(layout.size).map_err(|_| error("image too large"))?;
+        let image = buf.get_mut(..size).ok_or(error("buffer too small"))?;
+
+        image.fill(fill);
+        for (start, data) in self.flat_segments(addresses)? {
+            let offset = (start - layout.base) as usize;
+            image[offset..][..data.len()].copy_from_slice(data);
+        }
+
+        Ok(layout)
+    }
+
+    /// Returns the flat binary image of this file and its layout
+    ///
+    /// See [`write_flat_binary()`](Elf::write_flat_binary).
+    #[cfg(feature = "alloc")]
+    pub fn to_flat_binary(
+        &self,
+        addresses: MapAddresses,
+        fill: u8,
+    ) -> Result<(FlatBinary, alloc::vec::Vec<u8>), ParseError> {
+        let error = |message| ParseError::new(Structure::FileHeader, 0, message);
+
+        let layout = self
+            .flat_binary_layout(addresses)
+            .ok_or(error("no loadable contents"))?;
+        let size = usize::try_from(layout.size).map_err(|_| error("image too large"))?;
+        let mut image = alloc::vec![fill; size];
+        self.write_flat_binary(addresses, fill, &mut image)?;
+        Ok((layout, image))
+    }
+
+    /// Returns the address and file contents of each non-empty `PT_LOAD` segment
+    ///
+    /// Returns an error if the contents of any of them lie outside of the file.
+    fn flat_segments(
+        &self,
+        addresses: MapAddresses,
+    ) -> Result<impl Iterator<Item = (u64, &'elf [u8])> + '_, ParseError> {
+        let loads = || {
+            self.segments()
+                .filter(|sgmt| sgmt.kind() == SegmentKind::Load && sgmt.file_size() != 0)
+        };
+        let out_of_bounds = self.segments().position(|sgmt| {
+            sgmt.kind() == SegmentKind::Load && sgmt.file_size() != 0 && sgmt.file_data().is_none()
+        });
+        if let Some(index) = out_of_bounds {
+            return Err(self.program_header_error(index, "contents out of bounds"));
+        }
+
+        Ok(loads().map(move |sgmt| {
+            let start = match addresses {
+                MapAddresses::Physical => sgmt.physical_address(),
+                MapAddresses::Virtual { bias } => sgmt.virtual_address().wrapping_add(bias),
+            };
+            (start, sgmt.file_data().unwrap_or_default())
+        }))
+    }
+}
+
+/// Describes how to wrap a flat binary in an executable
+///
+/// See [`write_flat_executable()`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FlatExecutable {
+    pub machine: Machine,
+    /// Address the image is loaded at, used for both the virtual and physical address
+    pub load_address: u64,
+    pub entry_point: u64,
+    /// Alignment of the segment, which must be a power of two
+    pub page_size: u64,
+    /// Byte used to fill the padding between the headers and the image
+    pub fill: u8,
+}
+
+impl FlatExecutable {
+    /// Returns the offset of the image in the file
+    ///
+    /// The image follows the file and program headers, at an offset congruent to the load
+    /// address modulo the page size. Errors are located in the executable being built.
+    pub fn image_offset(&self) -> Result<u64, ParseError> {
+        if !self.page_size.is_power_of_two() {
+            return Err(ParseError::new(
+                Structure::ProgramHeader(0),
+                size_of::<FileHeader>() as u64,
+                "page size is not a power of two",
+            ));
+        }
+        let headers = (size_of::<FileHeader>() + size_of::<ProgramHeader>()) as u64;
+        let mask = self.page_size - 1;
+        Ok(headers + (self.load_address.wrapping_sub(headers) & mask))
+    }
+
+    /// Returns the size of the executable wrapping an image of `len` bytes
+    pub fn file_size(&self, len: usize) -> Result<usize, ParseError> {
+        usize::try_from(self.image_offset()?)
+            .ok()
+            .and_then(|offset| offset.checked_add(len))
+            .ok_or(ParseError::new(Structure::FileHeader, 0, "image too large"))
+    }
+}
+
+/// Wrap the flat binary `image` in an `ET_EXEC` file with a single `PT_LOAD` segment, and
+/// write it to `buf`
+///
+/// The segment is readable, writable, and executable, and its memory size is the size of
+/// the image. The file has no section headers. This is the inverse of
+/// [`Elf::write_flat_binary()`], for boot protocols which only accept ELF files.
+///
+/// Returns the number of bytes written. Errors are located in the executable being built.
+pub fn write_flat_executable<E: Endian>(
+    desc: &FlatExecutable,
+    image: &[u8],
+    buf: &mut [u8],
+) -> Result<usize, ParseError> {
+    let error = |offset, message| ParseError::new(Structure::FileHeader, offset, message);
+
+    let offset = desc.image_offset()?;
+    let size = desc.file_size(image.len())?;
+    let end = desc
+        .load_address
+        .checked_add(image.len() as u64)
+        .ok_or(ParseError::new(
+            Structure::ProgramHeader(0),
+            size_of::<FileHeader>() as u64,
+            "image exceeds the address space",
+        ))?;
+    if !(desc.load_address..end).contains(&desc.entry_point) {
+        return Err(error(0x18, "entry point is outside of the image"));
+    }
+    let buf = buf.get_mut(..size).ok_or(error(0, "buffer too small"))?;
+
+    let fhdr = FileHeader::<E>::new(ElfType::Exec, desc.machine)
+        .with_entry_point(desc.entry_point)
+        .with_program_headers(size_of::<FileHeader>() as u64, 1);
+    let phdr = ProgramHeader::<E>::new(SegmentKind::Load, SegmentFlags::all())
+        .with_addr(desc.load_address, desc.load_address)
+        .with_file_range(offset, image.len() as u64)
+        .with_mem_size(image.len() as u64)
+        .with_alignment(desc.page_size);
+
+    buf.fill(desc.fill);
+    let (headers, contents) = buf.split_at_mut(offset as usize);
+    let (fhdr_bytes, rest) = headers.split_at_mut(size_of::<FileHeader>());
+    fhdr_bytes.copy_from_slice(fhdr.as_bytes());
+    rest[..size_of::<ProgramHeader>()].copy_from_slice(phdr.as_bytes());
+    contents.copy_from_slice(image);
+
+    Ok(size)
+}
+
+/// Wrap the flat binary `image` in an executable
+///
+/// See [`write_flat_executable()`].
+#[cfg(feature = "alloc")]
+pub fn to_flat_executable<E: Endian>(
+    desc: &FlatExecutable,
+    image: &[u8],
+) -> Result<alloc::vec::Vec<u8>, ParseError> {
+    let mut buf = alloc::vec![desc.fill; desc.file_size(image.len())?];
+    write_flat_executable::<E>(desc, image, &mut buf)?;
+    Ok(buf)
+}
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Frame pointer based stack walking
+//!
+//! This is a fallback for when no unwind tables are available, such as in early boot.
+//! It requires code to be compiled with frame pointers.
+
+use crate::{Elf, Machine, SegmentFlags, SegmentKind};
+
+/// Where a frame record is stored relative to the frame pointer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FrameLayout {
+    /// Offset of the caller's frame pointer
+    pub fp_offset: i64,
+    /// Offset of the return address
+    pub ra_offset: i64,
+}
+
+impl FrameLayout {
+    pub const fn for_machine(machine: Machine) -> Option<FrameLayout> {
+        match machine {
+            // The frame pointer points at the saved frame pointer, followed by the return
+            // address (pushed by `call` on x86-64, `x30` on AArch64).
+            Machine::X86_64 | Machine::Aarch64 => Some(FrameLayout {
+                fp_offset: 0,
+                ra_offset: 8,
+            }),
+            // The frame pointer points just past the saved return address and frame pointer.
+            Machine::Riscv => Some(FrameLayout {
+                fp_offset: -16,
+                ra_offset: -8,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the return addresses found by following a frame pointer chain
+///
+/// Walking stops at a null frame pointer, or at the first frame which looks invalid: a
+/// misaligned frame pointer, one which does not move towards the base of the stack, a
+/// return address outside of the executable segments of the file, or a failed read.
+pub struct FrameWalker<'a, 'elf, R> {
+    elf: &'a Elf<'elf>,
+    load_bias: u64,
+    layout: FrameLayout,
+    read: R,
+    fp: u64,
+    remaining: usize,
+}
+
+impl<'a, 'elf, R> FrameWalker<'a, 'elf, R>
+where
+    R: FnMut(u64) -> Option<u64>,
+{
+    /// Create a walker starting from the frame pointer `fp`
+    ///
+    /// `elf` is loaded at `load_bias`, and `read` reads a word of stack memory, returning
+    /// `None` if the address cannot be read. At most `max_depth` frames are visited.
+    pub fn new(
+        elf: &'a Elf<'elf>,
+        load_bias: u64,
+        fp: u64,
+        max_depth: usize,
+        read: R,
+    ) -> Option<FrameWalker<'a, 'elf, R>> {
+        Some(FrameWalker {
+            elf,
+            load_bias,
+            layout: FrameLayout::for_machine(elf.machine())?,
+            read,
+            fp,
+            remaining: max_depth,
+        })
+    }
+
+    /// Returns `true` if `addr` lies within an executable segment
+    pub fn is_code(&self, addr: u64) -> bool {
+        self.elf.segments().any(|sgmt| {
+            let start = sgmt.virtual_address().wrapping_add(self.load_bias);
+            sgmt.kind() == SegmentKind::Load
+                && sgmt.flags().contains(SegmentFlags::EXEC)
+                && addr.wrapping_sub(start) < sgmt.mem_size() as u64
+        })
+    }
+}
+
+impl<R> Iterator for FrameWalker<'_, '_, R>
+where
+    R: FnMut(u64) -> Option<u64>,
+{
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.fp == 0 || self.fp % 8 != 0 || self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let next_fp = (self.read)(self.fp.wrapping_add_signed(self.layout.fp_offset));
+        let ra = (self.read)(self.fp.wrapping_add_signed(self.layout.ra_offset));
+        let (next_fp, ra) = match (next_fp, ra) {
+            (Some(next_fp), Some(ra)) if self.is_code(ra) => (next_fp, ra),
+            _ => {
+                self.fp = 0;
+                return None;
+            }
+        };
+
+        // The stack grows down, so callers' frames are always at higher addresses.
+        self.fp = if next_fp > self.fp { next_fp } else { 0 };
+        Some(ra)
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Go toolchain build information
+
+use crate::{leb128::read_uleb128, Elf};
+
+/// Note type of the Go build ID note (owner `"Go"`)
+pub const NT_GO_BUILD_ID: u32 = 4;
+
+const BUILDINFO_MAGIC: &[u8; 14] = b"\xff Go buildinf:";
+const BUILDINFO_HEADER_SIZE: usize = 32;
+const BUILDINFO_FLAG_INLINE_STRINGS: u8 = 0x2;
+
+/// Build information embedded by the Go linker in `.go.buildinfo`
+#[derive(Clone, Copy, Debug)]
+pub struct GoBuildInfo<'elf> {
+    pointer_size: u8,
+    version: &'elf str,
+    mod_info: &'elf str,
+}
+
+impl<'elf> GoBuildInfo<'elf> {
+    /// Parse the contents of a `.go.buildinfo` section
+    ///
+    /// Only the format used since Go 1.18, which stores the strings inline, is supported.
+    pub fn parse(data: &'elf [u8]) -> Option<GoBuildInfo<'elf>> {
+        if data.get(..BUILDINFO_MAGIC.len())? != BUILDINFO_MAGIC {
+            return None;
+        }
+
+        let pointer_size = data[14];
+        let flags = *data.get(15)?;
+        if flags & BUILDINFO_FLAG_INLINE_STRINGS == 0 {
+            return None;
+        }
+
+        let mut rest = data.get(BUILDINFO_HEADER_SIZE..)?;
+        let version = read_string(&mut rest)?;
+        let mut mod_info = read_string(&mut rest)?;
+
+        // The module information is wrapped in 16-byte sentinels.
+        if mod_info.len() >= 32 {
+            mod_info = mod_info.get(16..mod_info.len() - 16)?;
+        }
+
+        Some(GoBuildInfo {
+            pointer_size,
+            version,
+            mod_info,
+        })
+    }
+
+    /// Returns the pointer size of the target, in bytes
+    #[inline]
+    pub const fn pointer_size(&self) -> u8 {
+        self.pointer_size
+    }
+
+    /// Returns the toolchain version, e.g. `go1.21.4`
+    #[inline]
+    pub const fn version(&self) -> &'elf str {
+        self.version
+    }
+
+    /// Returns the raw module information, as printed by `go version -m`
+    #[inline]
+    pub const fn mod_info(&self) -> &'elf str {
+        self.mod_info
+    }
+
+    /// Returns the import path of the main package
+    pub fn main_path(&self) -> Option<&'elf str> {
+        self.mod_info
+            .lines()
+            .find_map(|line| line.strip_prefix("path\t"))
+    }
+
+    /// Returns the main module, if known
+    pub fn main_module(&self) -> Option<GoModule<'elf>> {
+        self.mod_info
+            .lines()
+            .find_map(|line| GoModule::parse(line.strip_prefix("mod\t")?))
+    }
+
+    /// Returns an iterator over the module's dependencies
+    pub fn dependencies(&self) -> impl Iterator<Item = GoModule<'elf>> {
+        self.mod_info
+            .lines()
+            .filter_map(|line| GoModule::parse(line.strip_prefix("dep\t")?))
+    }
+
+    /// Returns the value of a build setting, e.g. `GOOS` or `-compiler`
+    pub fn setting(&self, key: &str) -> Option<&'elf str> {
+        self.mod_info.lines().find_map(|line| {
+            let (k, v) = line.strip_prefix("build\t")?.split_once('=')?;
+            (k == key).then_some(v)
+        })
+    }
+}
+
+/// A module listed in Go build information
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct GoModule<'elf> {
+    pub path: &'elf str,
+    pub version: &'elf str,
+    /// The module checksum (`h1:...`), if recorded
+    pub sum: Option<&'elf str>,
+}
+
+impl<'elf> GoModule<'elf> {
+    fn parse(line: &'elf str) -> Option<GoModule<'elf>> {
+        let mut fields = line.split('\t');
+
+        Some(GoModule {
+            path: fields.next()?,
+            version: fields.next()?,
+            sum: fields.next(),
+        })
+    }
+}
+
+fn read_string<'a>(data: &mut &'a [u8]) -> Option<&'a str> {
+    let len = usize::try_from(read_uleb128(data)?).ok()?;
+    let bytes = data.get(..len)?;
+    *data = &data[len..];
+    core::str::from_utf8(bytes).ok()
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the Go build ID, if this file was built by the Go toolchain
+    pub fn go_build_id(&self) -> Option<&'elf str> {
+        let note = self.find_note(b"Go", NT_GO_BUILD_ID)?;
+        core::str::from_utf8(note.desc()).ok()
+    }
+
+    /// Returns the Go build information, if present
+    pub fn go_build_info(&self) -> Option<GoBuildInfo<'elf>> {
+        GoBuildInfo::parse(self.find_section(".go.buildinfo")?.file_data()?)
+    }
+}
@@ -0,0 +1,197 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Section groups and COMDAT deduplication
+
+use crate::{table::Encoding, Elf, Section, SectionType, SymbolKind};
+
+/// Set in a group's flags if it is a COMDAT group
+pub const GRP_COMDAT: u32 = 0x1;
+
+/// A `SHT_GROUP` section
+pub struct SectionGroup<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    section: Section<'a, 'elf>,
+}
+
+impl<'a, 'elf> SectionGroup<'a, 'elf> {
+    #[inline]
+    pub fn section(&self) -> &Section<'a, 'elf> {
+        &self.section
+    }
+
+    /// Returns the group flags
+    pub fn group_flags(&self) -> u32 {
+        self.words().next().unwrap_or(0)
+    }
+
+    /// Returns `true` if only one group with this signature should be kept when linking
+    pub fn is_comdat(&self) -> bool {
+        self.group_flags() & GRP_COMDAT != 0
+    }
+
+    /// Returns the signature which identifies the group
+    ///
+    /// This is the name of the symbol referenced by the group's `sh_info`, or the name of
+    /// the section it refers to if it is a section symbol.
+    pub fn signature(&self) -> Option<&'elf str> {
+        let index = self.section.info_as_signature_symbol()?;
+        let sym = self.elf.symtab()?.nth(index as usize)?;
+
+        match sym.kind() {
+            SymbolKind::Section => self.elf.section(sym.section_index())?.name(),
+            _ => self.elf.string_table()?.get_string(sym.name_index()),
+        }
+    }
+
+    /// Returns an iterator over the indices of the sections in the group
+    pub fn members(&self) -> impl Iterator<Item = u16> + 'a {
+        self.words().skip(1).map(|index| index as u16)
+    }
+
+    fn words(&self) -> impl Iterator<Item = u32> + 'a {
+        let encoding = Encoding::of(self.elf);
+        self.section
+            .file_data()
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .filter_map(move |word| encoding.read_u32(word))
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over the section groups
+    pub fn section_groups(&self) -> impl Iterator<Item = SectionGroup<'_, 'elf>> {
+        self.sections()
+            .filter(|sect| sect.section_type() == SectionType::Group)
+            .map(|section| SectionGroup { elf: self, section })
+    }
+
+    /// Returns the COMDAT group with the given signature, if any
+    pub fn comdat_group(&self, signature: &str) -> Option<SectionGroup<'_, 'elf>> {
+        self.section_groups()
+            .find(|group| group.is_comdat() && group.signature() == Some(signature))
+    }
+}
+
+/// A section discarded because an earlier input defined the same COMDAT group
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiscardedSection<'elf> {
+    /// Index of the input the section belongs to
+    pub input: usize,
+    /// Index of the section within its input
+    pub section: u16,
+    pub signature: &'elf str,
+    /// Index of the input whose copy of the group is kept
+    pub kept_input: usize,
+}
+
+/// COMDAT group resolution over a set of relocatable files
+///
+/// The first group with a given signature is kept, and the members of every later group
+/// with the same signature are discarded, including the group sections themselves. This
+/// does not allocate, so each lookup scans the preceding inputs.
+#[derive(Clone, Copy)]
+pub struct ComdatResolver<'a, 'elf> {
+    inputs: &'a [Elf<'elf>],
+}
+
+impl<'a, 'elf> ComdatResolver<'a, 'elf> {
+    pub fn new(inputs: &'a [Elf<'elf>]) -> ComdatResolver<'a, 'elf> {
+        ComdatResolver { inputs }
+    }
+
+    /// Returns the index of the input whose COMDAT group `signature` is kept
+    pub fn kept_input(&self, signature: &str) -> Option<usize> {
+        self.inputs
+            .iter()
+            .position(|elf| elf.comdat_group(signature).is_some())
+    }
+
+    /// If the section at `section` in input `input` is discarded, returns the input which
+    /// provides the kept copy and the group's signature
+    pub fn discarded_by(&self, input: usize, section: u16) -> Option<(usize, &'elf str)> {
+        let elf = self.inputs.get(input)?;
+
+        elf.section_groups()
+            .filter(|group| group.is_comdat())
+            .filter(|group| {
+                group.section().index() == section || group.members().any(|m| m == section)
+            })
+            .find_map(|group| {
+                let signature = group.signature()?;
+                let kept = self.kept_input(signature)?;
+                let is_first_in_input = elf
+                    .section_groups()
+                    .find(|g| g.is_comdat() && g.signature() == Some(signature))
+                    .is_some_and(|g| g.section().index() == group.section().index());
+
+                match kept < input || !is_first_in_input {
+                    true => Some((kept, signature)),
+                    false => None,
+                }
+            })
+    }
+
+    /// Returns `true` if the section at `section` in input `input` should be discarded
+    pub fn is_discarded(&self, input: usize, section: u16) -> bool {
+        self.discarded_by(input, section).is_some()
+    }
+
+    /// Returns an iterator over the sections of every input which are kept
+    pub fn kept_sections(&self) -> impl Iterator<Item = (usize, Section<'a, 'elf>)> + '_ {
+        self.inputs
+            .iter()
+            .enumerate()
+            .flat_map(move |(input, elf)| {
+                elf.sections()
+                    .filter(move |sect| !self.is_discarded(input, sect.index()))
+                    .map(move |sect| (input, sect))
+            })
+    }
+
+    /// Returns an iterator over the sections of every input which are discarded
+    pub fn discarded_sections(&self) -> impl Iterator<Item = DiscardedSection<'elf>> + '_ {
+        self.inputs
+            .iter()
+            .enumerate()
+            .flat_map(move |(input, elf)| {
+                elf.sections().filter_map(move |sect| {
+                    let (kept_input, signature) = self.discarded_by(input, sect.index())?;
+                    Some(DiscardedSection {
+                        input,
+                        section: sect.index(),
+                        signature,
+                        kept_input,
+                    })
+                })
+            })
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Hex dumps for debugging output
+
+use crate::{Section, SectionType, Segment};
+use core::fmt;
+
+/// Formats bytes as a hex dump, 16 bytes per line
+///
+/// Each line starts with the file offset of its first byte and, if the data is loaded, its
+/// virtual address. Writing the dump allocates nothing, so it can be printed directly to a
+/// serial console.
+#[derive(Clone, Copy, Debug)]
+pub struct HexDump<'a> {
+    data: &'a [u8],
+    file_offset: u64,
+    vaddr: Option<u64>,
+}
+
+impl<'a> HexDump<'a> {
+    /// Create a dump of `data`, which is found at `file_offset` and loaded at `vaddr`
+    #[inline]
+    pub const fn new(data: &'a [u8], file_offset: u64, vaddr: Option<u64>) -> HexDump<'a> {
+        Self {
+            data,
+            file_offset,
+            vaddr,
+        }
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.data.chunks(16).enumerate() {
+            let offset = i as u64 * 16;
+
+            write!(f, "{:#010x}", self.file_offset.wrapping_add(offset))?;
+            if let Some(vaddr) = self.vaddr {
+                write!(f, " {:#018x}", vaddr.wrapping_add(offset))?;
+            }
+            f.write_str(" ")?;
+
+            for j in 0..16 {
+                if j == 8 {
+                    f.write_str(" ")?;
+                }
+                match line.get(j) {
+                    Some(byte) => write!(f, " {byte:02x}")?,
+                    None => f.write_str("   ")?,
+                }
+            }
+
+            f.write_str("  |")?;
+            for &byte in line {
+                let c = match byte {
+                    0x20..=0x7e => byte as char,
+                    _ => '.',
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f, "|")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'elf> Section<'_, 'elf> {
+    /// Returns a hex dump of the section's contents
+    ///
+    /// Virtual addresses are shown for allocated sections. `SHT_NOBITS` sections have no
+    /// contents in the file, and produce an empty dump, as do sections whose contents lie
+    /// outside of the file.
+    pub fn hexdump(&self) -> HexDump<'elf> {
+        let data = match self.section_type() {
+            SectionType::Nobits => &[],
+            _ => self.file_data().unwrap_or_default(),
+        };
+        let vaddr = self.flags().alloc().then(|| self.addr());
+        HexDump::new(data, self.file_offset() as u64, vaddr)
+    }
+}
+
+impl<'elf> Segment<'_, 'elf> {
+    /// Returns a hex dump of the segment's file image
+    ///
+    /// The dump is empty if the file image lies outside of the file.
+    pub fn hexdump(&self) -> HexDump<'elf> {
+        HexDump::new(
+            self.file_data().unwrap_or_default(),
+            self.file_offset() as u64,
+            Some(self.virtual_address()),
+        )
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Motorola S-record and Intel HEX export
+
+use crate::{Elf, MapAddresses, ParseError, SegmentKind, Structure};
+use core::fmt::{self, Write};
+
+/// Number of data bytes in each record
+const RECORD_SIZE: usize = 16;
+
+impl Elf<'_> {
+    /// Write the file contents of the `PT_LOAD` segments as Motorola S-records
+    ///
+    /// The address size is the smallest which fits every address: S1, S2, or S3 data
+    /// records, terminated by the matching S9, S8, or S7 record holding the entry point.
+    pub fn write_srec<W: Write>(
+        &self,
+        addresses: MapAddresses,
+        out: &mut W,
+    ) -> Result<(), ParseError> {
+        let error = |message| ParseError::new(Structure::FileHeader, 0, message);
+
+        let top = self
+            .hex_segments(addresses)?
+            .map(|(start, data)| start.checked_add(data.len() as u64))
+            .try_fold(self.hex_entry(addresses), |max, end| Some(max.max(end?)))
+            .ok_or(error("address out of range"))?;
+        let (data_kind, end_kind, addr_len) = match top {
+            0..=0x1_0000 => (1, 9, 2),
+            0x1_0001..=0x100_0000 => (2, 8, 3),
+            0x100_0001..=0x1_0000_0000 => (3, 7, 4),
+            _ => return Err(error("address out of range")),
+        };
+
+        let write = |out: &mut W, kind: u8, address: u64, data: &[u8]| -> fmt::Result {
+            let count = (addr_len + data.len() + 1) as u8;
+            let address = &address.to_be_bytes()[8 - addr_len..];
+            write!(out, "S{kind}{count:02X}")?;
+            let mut sum = count;
+            for &byte in address.iter().chain(data) {
+                write!(out, "{byte:02X}")?;
+                sum = sum.wrapping_add(byte);
+            }
+            writeln!(out, "{:02X}", !sum)
+        };
+
+        let result = (|| {
+            write(out, 0, 0, b"")?;
+            // The segments were checked to be in bounds above
+            for (start, data) in self.hex_segments(addresses).into_iter().flatten() {
+                for (i, chunk) in data.chunks(RECORD_SIZE).enumerate() {
+                    write(out, data_kind, start + (i * RECORD_SIZE) as u64, chunk)?;
+                }
+            }
+            write(out, end_kind, self.hex_entry(addresses), b"")
+        })();
+        result.map_err(|_| error("write failed"))
+    }
+
+    /// Write the file contents of the `PT_LOAD` segments as Intel HEX
+    ///
+    /// Extended linear address records select the upper 16 bits of each address, and a
+    /// start linear address record holds the entry point.
+    pub fn write_ihex<W: Write>(
+        &self,
+        addresses: MapAddresses,
+        out: &mut W,
+    ) -> Result<(), ParseError> {
+        let error = |message| ParseError::new(Structure::FileHeader, 0, message);
+
+        let fits = self
+            .hex_segments(addresses)?
+            .all(|(start, data)| start.saturating_add(data.len() as u64) <= 0x1_0000_0000);
+        let entry = u32::try_from(self.hex_entry(addresses));
+        let (true, Ok(entry)) = (fits, entry) else {
+            return Err(error("address out of range"));
+        };
+
+        let write = |out: &mut W, kind: u8, address: u16, data: &[u8]| -> fmt::Result {
+            let count = data.len() as u8;
+            let [hi, lo] = address.to_be_bytes();
+            write!(out, ":{count:02X}{address:04X}{kind:02X}")?;
+            let mut sum = count.wrapping_add(hi).wrapping_add(lo).wrapping_add(kind);
+            for &byte in data {
+                write!(out, "{byte:02X}")?;
+                sum = sum.wrapping_add(byte);
+            }
+            writeln!(out, "{:02X}", sum.wrapping_neg())
+        };
+
+        let result = (|| {
+            let mut upper = 0;
+            // The segments were checked to be in bounds above
+            for (mut address, mut data) in self.hex_segments(addresses).into_iter().flatten() {
+                while !data.is_empty() {
+                    if address >> 16 != upper {
+                        upper = address >> 16;
+                        write(out, 4, 0, &(upper as u16).to_be_bytes())?;
+                    }
+                    // Records may not cross a 64 KiB boundary.
+                    let room = 0x1_0000 - (address & 0xffff) as usize;
+                    let (chunk, rest) = data.split_at(data.len().min(RECORD_SIZE).min(room));
+                    write(out, 0, address as u16, chunk)?;
+                    address += chunk.len() as u64;
+                    data = rest;
+                }
+            }
+            write(out, 5, 0, &entry.to_be_bytes())?;
+            write(out, 1, 0, b"")
+        })();
+        result.map_err(|_| error("write failed"))
+    }
+
+    /// Returns the address and file contents of each non-empty `PT_LOAD` segment
+    ///
+    /// Returns an error if the contents of any of them lie outside of the file.
+    fn hex_segments(
+        &self,
+        addresses: MapAddresses,
+    ) -> Result<impl Iterator<Item = (u64, &[u8])> + '_, ParseError> {
+        let loads = || {
+            self.segments()
+                .filter(|sgmt| sgmt.kind() == SegmentKind::Load && sgmt.file_size() != 0)
+        };
+        let out_of_bounds = self.segments().position(|sgmt| {
+            sgmt.kind() == SegmentKind::Load && sgmt.file_size() != 0 && sgmt.file_data().is_none()
+        });
+        if let Some(index) = out_of_bounds {
+            return Err(self.program_header_error(index, "contents out of bounds"));
+        }
+
+        Ok(loads().map(move |sgmt| {
+            let start = match addresses {
+                MapAddresses::Physical => sgmt.physical_address(),
+                MapAddresses::Virtual { bias } => sgmt.virtual_address().wrapping_add(bias),
+            };
+            (start, sgmt.file_data().unwrap_or_default())
+        }))
+    }
+
+    /// Returns the entry point, translated to a physical address if necessary
+    fn hex_entry(&self, addresses: MapAddresses) -> u64 {
+        let entry = self.entry_point();
+        match addresses {
+            MapAddresses::Virtual { bias } => entry.wrapping_add(bias),
+            MapAddresses::Physical => self
+                .segments()
+                .filter(|sgmt| sgmt.kind() == SegmentKind::Load)
+                .find(|sgmt| {
+                    let start = sgmt.virtual_address();
+                    entry >= start && entry - start < sgmt.mem_size() as u64
+                })
+                .map_or(entry, |sgmt| {
+                    entry - sgmt.virtual_address() + sgmt.physical_address()
+                }),
+        }
+    }
+}
@@ -0,0 +1,361 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+use crate::{Elf, Section, Segment, SegmentFlags, SegmentKind};
+
+/// The range of virtual addresses spanned by an object's loadable segments
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LoadExtent {
+    /// Lowest virtual address of any `PT_LOAD` segment
+    pub start: u64,
+    /// One past the highest virtual address of any `PT_LOAD` segment
+    pub end: u64,
+    /// Largest alignment required by any `PT_LOAD` segment
+    pub align: u64,
+}
+
+impl LoadExtent {
+    #[inline]
+    pub const fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the extent of the loadable segments, or `None` if there are none
+    pub fn load_extent(&self) -> Option<LoadExtent> {
+        let mut extent: Option<LoadExtent> = None;
+
+        for sgmt in self.segments().filter(|s| s.kind() == SegmentKind::Load) {
+            let start = sgmt.virtual_address();
+            let end = start.checked_add(sgmt.mem_size() as u64)?;
+            let align = sgmt.alignment().max(1);
+
+            extent = Some(match extent {
+                None => LoadExtent { start, end, align },
+                Some(ext) => LoadExtent {
+                    start: ext.start.min(start),
+                    end: ext.end.max(end),
+                    align: ext.align.max(align),
+                },
+            });
+        }
+
+        extent
+    }
+
+    /// Returns an iterator over allocated sections which are not part of any `PT_LOAD`
+    /// segment
+    ///
+    /// Such sections will not be loaded, which usually indicates a broken linker script.
+    /// Empty sections are ignored.
+    pub fn orphan_sections(&self) -> impl Iterator<Item = Section<'_, 'elf>> {
+        self.sections().filter(move |sect| {
+            sect.flags().alloc()
+                && sect.size() != 0
+                && !self
+                    .segments()
+                    .filter(|sgmt| sgmt.kind() == SegmentKind::Load)
+                    .any(|sgmt| sgmt.contains_section(sect))
+        })
+    }
+
+    /// Returns an iterator over `PT_LOAD` segments which contain no sections
+    ///
+    /// Files without section headers have no sections to compare against, so nothing is
+    /// reported for them.
+    pub fn empty_load_segments(&self) -> impl Iterator<Item = Segment<'_, 'elf>> {
+        let has_sections = self.shdr_num() != 0;
+
+        self.segments().filter(move |sgmt| {
+            has_sections
+                && sgmt.kind() == SegmentKind::Load
+                && !self.sections().any(|sect| sgmt.contains_section(&sect))
+        })
+    }
+}
+
+/// Where an object was placed by a [`LayoutPlanner`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Placement {
+    /// Address of the first byte of the reserved range
+    pub start: u64,
+    /// One past the last byte of the reserved range
+    pub end: u64,
+    /// Value to add to the object's virtual addresses (wrapping)
+    pub bias: u64,
+}
+
+/// Chooses non-overlapping load biases for a set of position-independent objects
+///
+/// Objects are placed in order, each one above the previous, inside the window
+/// `[base, limit)`. Every placement respects the largest `p_align` of the object
+/// and is rounded out to whole pages.
+///
+/// If an ASLR window is configured, each object is additionally slid up by a random
+/// multiple of its alignment, no further than the window size.
+#[derive(Clone, Debug)]
+pub struct LayoutPlanner {
+    cursor: u64,
+    limit: u64,
+    page_size: u64,
+    aslr_window: u64,
+}
+
+impl LayoutPlanner {
+    /// Create a planner for the address range `[base, limit)`
+    ///
+    /// `page_size` must be a power of two.
+    pub const fn new(base: u64, limit: u64, page_size: u64) -> LayoutPlanner {
+        assert!(page_size.is_power_of_two());
+        Self {
+            cursor: base,
+            limit,
+            page_size,
+            aslr_window: 0,
+        }
+    }
+
+    /// Allow each object to be slid up by at most `window` bytes
+    pub const fn with_aslr_window(mut self, window: u64) -> LayoutPlanner {
+        self.aslr_window = window;
+        self
+    }
+
+    /// Returns the lowest address the next object may be placed at
+    #[inline]
+    pub const fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Place an object without randomization
+    ///
+    /// Returns `None` if the object does not fit in the remaining space or its
+    /// alignment is not a power of two.
+    pub fn place(&mut self, extent: &LoadExtent) -> Option<Placement> {
+        self.place_inner(extent, None)
+    }
+
+    /// Place an object, drawing the random slide from `rng`
+    pub fn place_random<R>(&mut self, extent: &LoadExtent, mut rng: R) -> Option<Placement>
+    where
+        R: FnMut() -> u64,
+    {
+        self.place_inner(extent, Some(&mut rng))
+    }
+
+    fn place_inner(
+        &mut self,
+        extent: &LoadExtent,
+        rng: Option<&mut dyn FnMut() -> u64>,
+    ) -> Option<Placement> {
+        let align = extent.align.max(self.page_size);
+        if !align.is_power_of_two() || extent.end < extent.start {
+            return None;
+        }
+
+        let image_start = align_down(extent.start, align);
+        let span = align_up(extent.end - image_start, self.page_size)?;
+
+        let mut start = align_up(self.cursor, align)?;
+        if let Some(rng) = rng {
+            let slots = self.aslr_window / align;
+            if slots != 0 {
+                // Every value is a valid slot when the window spans the whole address space.
+                let slot = match slots.checked_add(1) {
+                    Some(count) => rng() % count,
+                    None => rng(),
+                };
+                let slide = slot * align;
+                start = start.checked_add(slide)?;
+            }
+        }
+
+        let end = start.checked_add(span)?;
+        if end > self.limit {
+            return None;
+        }
+
+        self.cursor = end;
+
+        Some(Placement {
+            start,
+            end,
+            bias: start.wrapping_sub(image_start),
+        })
+    }
+}
+
+/// Which addresses of the loadable segments a [`MemoryMap`] describes
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MapAddresses {
+    /// Physical addresses (`p_paddr`)
+    Physical,
+    /// Virtual addresses (`p_vaddr`) plus a load bias
+    Virtual { bias: u64 },
+}
+
+/// A range of memory occupied by the loaded image
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MemoryMapEntry {
+    pub base: u64,
+    pub size: u64,
+    /// Union of the flags of the segments in the range
+    pub flags: SegmentFlags,
+}
+
+impl MemoryMapEntry {
+    #[inline]
+    pub const fn end(&self) -> u64 {
+        self.base + self.size
+    }
+}
+
+/// The memory occupied by the `PT_LOAD` segments of an image, in ascending order
+///
+/// Each segment's memory image is rounded out to whole pages. Segments which share a page
+/// are coalesced into one entry with the union of their flags, as are adjacent segments
+/// with the same flags. Returned by [`Elf::memory_map()`].
+#[derive(Clone)]
+pub struct MemoryMap<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    addresses: MapAddresses,
+    page_size: u64,
+    cursor: u64,
+    done: bool,
+}
+
+impl MemoryMap<'_, '_> {
+    /// Returns the page-rounded range and flags of each non-empty `PT_LOAD` segment
+    fn ranges(&self) -> impl Iterator<Item = (u64, u64, SegmentFlags)> + '_ {
+        self.elf
+            .segments()
+            .filter(|sgmt| sgmt.kind() == SegmentKind::Load && sgmt.mem_size() != 0)
+            .filter_map(|sgmt| {
+                let start = match self.addresses {
+                    MapAddresses::Physical => sgmt.physical_address(),
+                    MapAddresses::Virtual { bias } => sgmt.virtual_address().wrapping_add(bias),
+                };
+                let end = start.checked_add(sgmt.mem_size() as u64)?;
+                let start = align_down(start, self.page_size);
+                let end = align_up(end, self.page_size)?;
+                Some((start, end, sgmt.flags()))
+            })
+    }
+}
+
+impl Iterator for MemoryMap<'_, '_> {
+    type Item = MemoryMapEntry;
+
+    fn next(&mut self) -> Option<MemoryMapEntry> {
+        if self.done {
+            return None;
+        }
+
+        let (mut start, mut end, mut flags) = self
+            .ranges()
+            .filter(|&(_, end, _)| end > self.cursor)
+            .map(|(start, end, flags)| (start.max(self.cursor), end, flags))
+            .min_by_key(|&(start, _, _)| start)?;
+
+        loop {
+            let mut changed = false;
+            for (s, e, f) in self.ranges() {
+                let overlaps = s < end && e > start;
+                let extends = e > end && (s < end || (s == end && f == flags));
+                if (overlaps && !flags.contains(f)) || extends {
+                    start = start.min(s.max(self.cursor));
+                    end = end.max(e);
+                    flags |= f;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        self.cursor = end;
+        self.done = end == u64::MAX;
+
+        Some(MemoryMapEntry {
+            base: start,
+            size: end - start,
+            flags,
+        })
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the memory the loadable segments occupy, for a boot protocol's memory map
+    /// or to reserve in a frame allocator
+    ///
+    /// `page_size` must be a power of two.
+    pub fn memory_map(&self, addresses: MapAddresses, page_size: u64) -> MemoryMap<'_, 'elf> {
+        assert!(page_size.is_power_of_two());
+        MemoryMap {
+            elf: self,
+            addresses,
+            page_size,
+            cursor: 0,
+            done: false,
+        }
+    }
+}
+
+#[inline]
+const fn align_down(x: u64, align: u64) -> u64 {
+    x & !(align - 1)
+}
+
+#[inline]
+const fn align_up(x: u64, align: u64) -> Option<u64> {
+    match x.checked_add(align - 1) {
+        Some(x) => Some(x & !(align - 1)),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LayoutPlanner, LoadExtent};
+
+    #[test]
+    fn aslr_window_of_whole_address_space() {
+        let extent = LoadExtent {
+            start: 0,
+            end: 1,
+            align: 1,
+        };
+        let mut planner = LayoutPlanner::new(0, u64::MAX, 1).with_aslr_window(u64::MAX);
+        let placement = planner.place_random(&extent, || 0x1234).unwrap();
+        assert_eq!(placement.start, 0x1234);
+    }
+}
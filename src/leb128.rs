@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! LEB128 variable-length integers
+//!
+//! Used throughout DWARF, exception handling tables, and other toolchain metadata. The
+//! `read` functions advance the input past the decoded value, and return `None` if the input
+//! ends early or the value does not fit in 64 bits.
+
+/// The maximum encoded size of a 64-bit value, in bytes
+pub const MAX_LEN: usize = 10;
+
+/// Decode an unsigned LEB128 value
+pub fn read_uleb128(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+
+    for (i, &byte) in data.iter().enumerate().take(MAX_LEN) {
+        // The last byte may only contribute the top bit.
+        if i == MAX_LEN - 1 && byte > 1 {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Decode a signed LEB128 value
+pub fn read_sleb128(data: &mut &[u8]) -> Option<i64> {
+    let mut value = 0i64;
+
+    for (i, &byte) in data.iter().enumerate().take(MAX_LEN) {
+        // The last byte may only contribute the sign, as all zeroes or all ones.
+        if i == MAX_LEN - 1 && byte != 0x00 && byte != 0x7f {
+            return None;
+        }
+        let shift = i * 7;
+        value |= ((byte & 0x7f) as i64) << shift;
+        if byte & 0x80 == 0 {
+            if shift + 7 < 64 && byte & 0x40 != 0 {
+                value |= -1 << (shift + 7);
+            }
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Encode an unsigned LEB128 value into `buf`, returning the number of bytes written
+///
+/// Returns `None` if `buf` is too small.
+pub fn write_uleb128(buf: &mut [u8], mut value: u64) -> Option<usize> {
+    let mut len = 0;
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = value == 0;
+        *buf.get_mut(len)? = if done { byte } else { byte | 0x80 };
+        len += 1;
+        if done {
+            return Some(len);
+        }
+    }
+}
+
+/// Encode a signed LEB128 value into `buf`, returning the number of bytes written
+///
+/// Returns `None` if `buf` is too small.
+pub fn write_sleb128(buf: &mut [u8], mut value: i64) -> Option<usize> {
+    let mut len = 0;
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        *buf.get_mut(len)? = if done { byte } else { byte | 0x80 };
+        len += 1;
+        if done {
+            return Some(len);
+        }
+    }
+}
+
+/// Returns the encoded size of an unsigned value, in bytes
+pub const fn uleb128_len(value: u64) -> usize {
+    let bits = 64 - (value | 1).leading_zeros() as usize;
+    bits.div_ceil(7)
+}
+
+/// Returns the encoded size of a signed value, in bytes
+pub const fn sleb128_len(value: i64) -> usize {
+    // One extra bit is needed for the sign.
+    let bits = 65 - (value ^ (value >> 63)).leading_zeros() as usize;
+    bits.div_ceil(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uleb(mut data: &[u8]) -> Option<(u64, usize)> {
+        let value = read_uleb128(&mut data)?;
+        Some((value, data.len()))
+    }
+
+    fn sleb(mut data: &[u8]) -> Option<(i64, usize)> {
+        let value = read_sleb128(&mut data)?;
+        Some((value, data.len()))
+    }
+
+    #[test]
+    fn multi_byte() {
+        assert_eq!(uleb(&[0x02]), Some((2, 0)));
+        assert_eq!(uleb(&[0xe5, 0x8e, 0x26, 0xff]), Some((624485, 1)));
+        assert_eq!(sleb(&[0xc0, 0xbb, 0x78]), Some((-123456, 0)));
+        assert_eq!(sleb(&[0x80, 0x01]), Some((128, 0)));
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(sleb(&[0x40]), Some((-64, 0)));
+        assert_eq!(sleb(&[0x3f]), Some((63, 0)));
+        assert_eq!(sleb(&[0x7f]), Some((-1, 0)));
+        assert_eq!(sleb(&[0xff, 0x7e]), Some((-129, 0)));
+
+        let min = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f];
+        assert_eq!(sleb(&min), Some((i64::MIN, 0)));
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+        assert_eq!(sleb(&max), Some((i64::MAX, 0)));
+    }
+
+    #[test]
+    fn overlong() {
+        // Redundant continuation bytes are accepted while the value fits in 64 bits.
+        assert_eq!(uleb(&[0x82, 0x80, 0x80, 0x00]), Some((2, 0)));
+        assert_eq!(sleb(&[0xff, 0xff, 0x7f]), Some((-1, 0)));
+
+        let eleven = [
+            0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00,
+        ];
+        assert_eq!(uleb(&eleven), None);
+        assert_eq!(sleb(&eleven), None);
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(uleb(&[]), None);
+        assert_eq!(uleb(&[0x80]), None);
+        assert_eq!(sleb(&[0xff, 0xff]), None);
+
+        let mut data: &[u8] = &[0xe5, 0x8e];
+        assert_eq!(read_uleb128(&mut data), None);
+        assert_eq!(data.len(), 2);
+    }
+
+    #[test]
+    fn shift_past_64_bits() {
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert_eq!(uleb(&max), Some((u64::MAX, 0)));
+
+        // Bits above bit 63 in the tenth byte do not fit.
+        let wide = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+        assert_eq!(uleb(&wide), None);
+        let wide = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01];
+        assert_eq!(sleb(&wide), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0; MAX_LEN];
+
+        for value in [0, 1, 127, 128, 624485, u32::MAX as u64, u64::MAX] {
+            let len = write_uleb128(&mut buf, value).unwrap();
+            assert_eq!(len, uleb128_len(value));
+            assert_eq!(uleb(&buf[..len]), Some((value, 0)));
+        }
+        for value in [0, -1, 63, -64, 64, -65, -123456, i64::MIN, i64::MAX] {
+            let len = write_sleb128(&mut buf, value).unwrap();
+            assert_eq!(len, sleb128_len(value));
+            assert_eq!(sleb(&buf[..len]), Some((value, 0)));
+        }
+
+        assert_eq!(write_uleb128(&mut buf[..1], 128), None);
+    }
+}
@@ -36,19 +36,131 @@
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod aranges;
+mod boot;
+mod bpf;
+mod brand;
+mod cache;
+mod capability;
+mod config;
+mod coredump;
+mod ctors;
+mod debug;
+mod dedup;
+mod demand;
 mod dynamic;
+mod edit;
+mod eh_frame;
+mod endian;
+mod error;
+mod fetch;
+mod flat;
+mod frame;
+mod go;
+mod group;
+mod hexdump;
+#[cfg(feature = "hexfile")]
+mod hexfile;
+mod layout;
+pub mod leb128;
+mod limits;
+#[cfg(feature = "debug-line")]
+mod line;
+mod merge;
+mod modinfo;
+mod module;
+mod note;
+#[cfg(feature = "alloc")]
+mod owned;
+mod patchable;
+mod patchlist;
+mod pie;
+mod prefix;
+mod priority;
+mod properties;
+mod rdebug;
+mod regset;
 mod reloc;
+mod relocate;
+mod rust;
+mod script;
 mod section;
 mod segment;
+mod sign;
+mod source;
+mod stack;
 mod symbol;
+mod symbolize;
+mod table;
+#[cfg(feature = "testdata")]
+pub mod testdata;
+mod tls;
+mod typed;
 mod types;
-
+mod validate;
+mod vdso;
+mod version;
+mod wx;
+
+pub use aranges::*;
+pub use boot::*;
+pub use bpf::*;
+pub use brand::*;
+pub use capability::*;
+pub use config::*;
+pub use coredump::*;
+pub use ctors::*;
+pub use debug::*;
+pub use demand::*;
 pub use dynamic::*;
+pub use edit::*;
+pub use eh_frame::*;
+pub use endian::*;
+pub use error::*;
+pub use fetch::*;
+pub use flat::*;
+pub use frame::*;
+pub use go::*;
+pub use group::*;
+pub use hexdump::*;
+pub use layout::*;
+pub use limits::*;
+#[cfg(feature = "debug-line")]
+pub use line::*;
+pub use merge::*;
+pub use modinfo::*;
+pub use module::*;
+pub use note::*;
+#[cfg(feature = "alloc")]
+pub use owned::*;
+pub use patchable::*;
+pub use patchlist::*;
+pub use pie::*;
+pub use prefix::*;
+pub use priority::*;
+pub use properties::*;
+pub use rdebug::*;
+pub use regset::*;
 pub use reloc::*;
+pub use relocate::*;
+pub use rust::*;
+pub use script::*;
 pub use section::*;
 pub use segment::*;
+pub use sign::*;
+pub use source::*;
+pub use stack::*;
 pub use symbol::*;
+pub use symbolize::*;
+pub use tls::*;
+pub use typed::*;
 pub use types::*;
+pub use vdso::*;
+pub use version::*;
+pub use wx::*;
 
 macro_rules! assert_struct_size {
     ($struc:ty, $size:expr) => {
@@ -59,19 +171,125 @@ macro_rules! assert_struct_size {
 }
 pub(crate) use assert_struct_size;
 
-unsafe fn strlen(s: *const u8) -> usize {
-    let mut len = 0;
-
-    while *s.add(len) != 0 {
-        len += 1;
+/// Convert a file offset or size to `usize`
+///
+/// Values which do not fit saturate to `usize::MAX`, so they fail bounds checks rather
+/// than wrapping around to a valid offset on 32-bit hosts.
+#[inline]
+pub(crate) const fn to_usize(x: u64) -> usize {
+    if x > usize::MAX as u64 {
+        usize::MAX
+    } else {
+        x as usize
     }
+}
+
+/// A plain on-disk structure, which can be viewed as bytes and read from any bytes
+///
+/// # Safety
+///
+/// Implementors must have an alignment of 1, contain no padding bytes, and be valid for
+/// any bit pattern. This holds for structures made only of bytes and byte arrays, which
+/// [`impl_pod!`] checks the alignment of.
+pub(crate) unsafe trait Pod: Copy + 'static {}
+
+/// Implement [`Pod`] for structures generic over their byte order
+///
+/// Every field must be a byte, a byte array, or another such structure.
+macro_rules! impl_pod {
+    ($($t:ident),* $(,)?) => {
+        $(
+            // SAFETY: The structure consists only of bytes and byte arrays, so it has no
+            // padding and any bit pattern is valid. The alignment is checked below.
+            unsafe impl<E: $crate::Endian> $crate::Pod for $t<E> {}
+
+            const _: () = ::core::assert!(::core::mem::align_of::<$t>() == 1);
+        )*
+    };
+}
+pub(crate) use impl_pod;
+
+/// Returns the raw bytes of an on-disk structure
+pub(crate) const fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    // SAFETY: `T` has no padding, so every byte of it is initialized.
+    unsafe { core::slice::from_raw_parts((value as *const T).cast(), size_of::<T>()) }
+}
+
+/// Returns the raw bytes of an on-disk structure, for filling it in place
+pub(crate) fn bytes_of_mut<T: Pod>(value: &mut T) -> &mut [u8] {
+    // SAFETY: `T` has no padding and is valid for any bit pattern written to it.
+    unsafe { core::slice::from_raw_parts_mut((value as *mut T).cast(), size_of::<T>()) }
+}
+
+/// Returns the raw bytes of a slice of on-disk structures
+pub(crate) fn bytes_of_slice<T: Pod>(values: &[T]) -> &[u8] {
+    let len = core::mem::size_of_val(values);
+    // SAFETY: `T` has no padding, so every byte of the slice is initialized.
+    unsafe { core::slice::from_raw_parts(values.as_ptr().cast(), len) }
+}
+
+/// Reinterpret the start of `buf` as an on-disk structure
+///
+/// # Panics
+///
+/// Panics if `buf` is shorter than `T`.
+pub(crate) fn from_bytes<T: Pod>(buf: &[u8]) -> &T {
+    assert!(buf.len() >= size_of::<T>());
+    // SAFETY: `T` has an alignment of 1 and is valid for any bit pattern.
+    unsafe { &*buf.as_ptr().cast() }
+}
+
+/// Reinterpret the start of `buf` as a mutable on-disk structure
+///
+/// # Panics
+///
+/// Panics if `buf` is shorter than `T`.
+pub(crate) fn from_bytes_mut<T: Pod>(buf: &mut [u8]) -> &mut T {
+    assert!(buf.len() >= size_of::<T>());
+    // SAFETY: `T` has an alignment of 1 and is valid for any bit pattern.
+    unsafe { &mut *buf.as_mut_ptr().cast() }
+}
+
+/// Reinterpret `buf` as a slice of on-disk structures, ignoring any partial one at its end
+pub(crate) fn slice_from_bytes<T: Pod>(buf: &[u8]) -> &[T] {
+    let len = buf.len() / size_of::<T>().max(1);
+    // SAFETY: `T` has an alignment of 1 and is valid for any bit pattern.
+    unsafe { core::slice::from_raw_parts(buf.as_ptr().cast(), len) }
+}
+
+/// Copy an on-disk structure out of the start of `buf`, or `None` if it is too short
+pub(crate) fn read_struct<T: Pod>(buf: &[u8]) -> Option<T> {
+    let buf = buf.get(..size_of::<T>())?;
+    Some(*from_bytes(buf))
+}
 
-    len
+macro_rules! assert_send_sync {
+    ($($t:ty),* $(,)?) => {
+        const _: () = {
+            const fn assert_send_sync<T: ?Sized + Send + Sync>() {}
+            $(assert_send_sync::<$t>();)*
+        };
+    };
 }
 
+// Every view into an ELF file only borrows immutable data, so they may be shared
+// freely between threads (e.g. to process a large symbol table in parallel).
+assert_send_sync!(
+    Elf<'_>,
+    Section<'_, '_>,
+    Segment<'_, '_>,
+    SymbolTable<'_>,
+    Symbol<'_, '_>,
+    StringTable<'_>,
+    DynamicTable<'_, '_>,
+    LayoutPlanner,
+);
+
+use cache::{Cache, LazyIndex};
 use core::{fmt, mem::size_of, ops::Deref};
+use table::{impl_entry, Encoding, Entry, Table};
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct StringTable<'elf> {
     table: &'elf [u8],
 }
@@ -101,12 +319,8 @@ impl<'elf> StringTable<'elf> {
     }
 
     pub fn get_slice(&self, index: usize) -> Option<&'elf [u8]> {
-        if index >= self.len() {
-            return None;
-        }
-
-        let buf = &self.table[index..];
-        let len = unsafe { strlen(buf.as_ptr()) };
+        let buf = self.table.get(index..)?;
+        let len = buf.iter().position(|&b| b == 0)?;
 
         Some(&buf[..len])
     }
@@ -115,11 +329,8 @@ impl<'elf> StringTable<'elf> {
     pub fn get_string(&self, index: usize) -> Option<&'elf str> {
         if index < self.len() {
             let buf = &self.table[index..];
-            let mut len = 0;
-            while buf[len] != 0 {
-                len += 1;
-            }
-            Some(core::str::from_utf8(&buf[..len]).unwrap())
+            let len = buf.iter().position(|&b| b == 0)?;
+            core::str::from_utf8(&buf[..len]).ok()
         } else {
             None
         }
@@ -128,98 +339,217 @@ impl<'elf> StringTable<'elf> {
 
 pub struct Elf<'elf> {
     data: &'elf [u8],
-    ehdr: &'elf FileHeader,
+    /// The file header, in the little-endian ELF64 layout
+    ehdr: FileHeader,
+    cache: Cache,
 }
 
-impl<'elf> Elf<'elf> {
-    pub fn new(data: &'elf [u8]) -> Result<Elf<'elf>, &'static str> {
-        if !FileHeader::check_buffer(data) {
-            return Err("invalid ELF");
-        }
+/// Parse and check the file header at the start of `data`
+///
+/// This checks the identification bytes and the sizes of the header table entries, but not
+/// that the tables lie within the file.
+pub(crate) fn parse_file_header(data: &[u8]) -> Result<(FileHeader, Encoding), ParseError> {
+    let error = |offset, message| Err(ParseError::new(Structure::FileHeader, offset, message));
 
-        let ehdr = FileHeader::from_buffer(data);
+    if !<FileHeader32>::check_buffer(data) {
+        return error(0, "invalid ELF");
+    }
 
-        if ehdr.class != Class::Bits64.to_u8() {
-            return Err("not ELF64");
-        }
+    let bits32 = match Class::from_u8(data[4]) {
+        Class::Bits32 => true,
+        Class::Bits64 => false,
+        _ => return error(4, "unknown class"),
+    };
+    let big_endian = match Data::from_u8(data[5]) {
+        Data::TwosCompLittle => false,
+        Data::TwosCompBig => true,
+        _ => return error(5, "unknown data encoding"),
+    };
+    let encoding = Encoding { bits32, big_endian };
+    let Some(ehdr) = FileHeader::read(data, encoding) else {
+        return error(0, "invalid ELF");
+    };
+
+    // Offsets of the fields holding the sizes of the header table entries.
+    let (phentsize, shentsize) = match bits32 {
+        true => (0x2a, 0x2e),
+        false => (0x36, 0x3a),
+    };
+
+    // Relocatable files without program headers leave the entry size as zero.
+    if ehdr.phdr_num() != 0 && ehdr.phdr_size() as usize != ProgramHeader::encoded_size(encoding) {
+        return error(phentsize, "bad program header size");
+    }
+    if ehdr.shdr_num() != 0 && ehdr.shdr_size() as usize != SectionHeader::encoded_size(encoding) {
+        return error(shentsize, "bad section header size");
+    }
 
-        if ehdr.phdr_size as usize != size_of::<ProgramHeader>() {
-            return Err("bad program header size");
+    Ok((ehdr, encoding))
+}
+
+impl<'elf> Elf<'elf> {
+    /// Parse an ELF32 or ELF64 file of either byte order
+    ///
+    /// Headers are widened to their ELF64 layouts and re-encoded in little-endian byte order
+    /// as they are read, so every file is accessed through the same types.
+    pub fn new(data: &'elf [u8]) -> Result<Elf<'elf>, ParseError> {
+        let (ehdr, encoding) = parse_file_header(data)?;
+        let error = |offset, message| Err(ParseError::new(Structure::FileHeader, offset, message));
+
+        // Offsets of the fields holding the offsets of the header tables.
+        let (phoff, shoff) = match encoding.bits32 {
+            true => (0x1c, 0x20),
+            false => (0x20, 0x28),
+        };
+        let phdr_size = ProgramHeader::encoded_size(encoding);
+        let shdr_size = SectionHeader::encoded_size(encoding);
+
+        let in_bounds = |offset: usize, num: u16, size: usize| {
+            offset
+                .checked_add(num as usize * size)
+                .is_some_and(|end| end <= data.len())
+        };
+        if !in_bounds(ehdr.phdr_offset(), ehdr.phdr_num(), phdr_size) {
+            return error(phoff, "program header table out of bounds");
         }
-        if ehdr.shdr_size as usize != size_of::<SectionHeader>() {
-            return Err("bad section header size");
+        if !in_bounds(ehdr.shdr_offset(), ehdr.shdr_num(), shdr_size) {
+            return error(shoff, "section header table out of bounds");
         }
 
-        Ok(Elf { data, ehdr })
+        Ok(Elf {
+            data,
+            ehdr,
+            cache: Cache::new(),
+        })
     }
 
-    fn get_slice(&self, offset: usize, size: usize) -> &'elf [u8] {
-        &self.data[offset..][..size]
+    /// Returns the entries of a header table, read as in files laid out as `layout`
+    pub(crate) fn header_table<T: Entry, L: Layout>(
+        &self,
+        offset: usize,
+        num: u16,
+        layout: L,
+    ) -> Table<'elf, T, L> {
+        let table = self
+            .get_slice(offset, num as usize * T::encoded_size(layout))
+            .unwrap_or_default();
+
+        Table::new(table, layout)
     }
 
-    unsafe fn get_slice_of<T>(&self, offset: usize, size: usize) -> &'elf [T] {
-        let buf = &self.data[offset..][..size];
-        let data = buf.as_ptr().cast::<T>();
-        let len = size / size_of::<T>();
+    fn get_slice(&self, offset: usize, size: usize) -> Option<&'elf [u8]> {
+        self.data.get(offset..)?.get(..size)
+    }
 
-        core::slice::from_raw_parts(data, len)
+    /// Returns an error in the program header at `index`, located at its table entry
+    pub(crate) fn program_header_error(&self, index: usize, message: &'static str) -> ParseError {
+        let offset = self.phdr_offset() as u64 + index as u64 * self.phdr_size() as u64;
+        ParseError::new(Structure::ProgramHeader(index as u16), offset, message)
     }
 
     /// Return the section name string table, if it exists
     pub fn section_string_table(&self) -> Option<StringTable<'elf>> {
-        Some(StringTable::new(
-            self.section(self.ehdr.shdr_strtab_index as _)?.file_data(),
-        ))
+        match self.ehdr.shdr_strtab_index() {
+            SHN_UNDEF => None,
+            index => Some(StringTable::new(self.section(index)?.file_data()?)),
+        }
     }
 
+    /// Find a section the first time it is asked for, and remember its index afterwards
+    fn cached_section<F>(&self, cell: &LazyIndex, mut f: F) -> Option<Section<'_, 'elf>>
+    where
+        F: FnMut(&Section<'_, 'elf>) -> bool,
+    {
+        let index =
+            cell.get_or_init(|| self.sections().position(|sect| f(&sect)).map(|i| i as u32))?;
+        self.section(index as u16)
+    }
+
+    /// Return the `.strtab` string table, if it exists
     pub fn string_table(&self) -> Option<StringTable<'elf>> {
         let shdr = self
-            .sections()
-            .find(|shdr| shdr.name() == Some(".strtab"))?;
+            .cached_section(&self.cache.strtab, |s| s.name() == Some(".strtab"))
+            .or_else(|| self.guess_section(".strtab"))?;
+
+        Some(StringTable::new(shdr.file_data()?))
+    }
+
+    /// Return the `.dynstr` string table, if it exists
+    pub fn dynamic_string_table(&self) -> Option<StringTable<'elf>> {
+        self.cached_section(&self.cache.dynstr, |s| s.name() == Some(".dynstr"))
+            .or_else(|| self.guess_section(".dynstr"))
+            .and_then(|shdr| Some(StringTable::new(shdr.file_data()?)))
+    }
+
+    /// Return the `.symtab` section, if it exists
+    pub fn symtab_section(&self) -> Option<Section<'_, 'elf>> {
+        self.cached_section(&self.cache.symtab, |s| {
+            s.section_type() == SectionType::Symtab
+        })
+    }
 
-        Some(StringTable {
-            table: self.get_slice(shdr.file_offset(), shdr.size()),
+    /// Return the `.dynsym` section, if it exists
+    pub fn dynsym_section(&self) -> Option<Section<'_, 'elf>> {
+        self.cached_section(&self.cache.dynsym, |s| {
+            s.section_type() == SectionType::Dynsym
         })
     }
 
-    pub fn symtab(&self) -> Option<impl Iterator<Item = &Sym>> {
-        let shdr = self.sections().find(|s| s.name() == Some(".symtab"))?;
-        assert!(shdr.entry_size() as usize == size_of::<Sym>());
-        let symtab_len = shdr.size() / size_of::<Sym>();
-        let table = self.data[shdr.file_offset()..].as_ptr().cast::<Sym>();
-        let mut index = 0;
+    /// Return the SysV `SHT_HASH` section, if it exists
+    pub fn hash_section(&self) -> Option<Section<'_, 'elf>> {
+        self.cached_section(&self.cache.hash, |s| s.section_type() == SectionType::Hash)
+    }
 
-        Some(core::iter::from_fn(move || {
-            if index < symtab_len {
-                let hdr = unsafe { &*table.add(index) };
-                index += 1;
-                Some(hdr)
-            } else {
-                None
-            }
-        }))
+    /// Return the `SHT_GNU_HASH` section, if it exists
+    pub fn gnu_hash_section(&self) -> Option<Section<'_, 'elf>> {
+        self.cached_section(&self.cache.gnu_hash, |s| {
+            s.section_type() == SectionType::GNU_HASH
+        })
+    }
+
+    /// Return the `SHT_GNU_verdef` section, if it exists
+    pub fn verdef_section(&self) -> Option<Section<'_, 'elf>> {
+        self.cached_section(&self.cache.verdef, |s| {
+            s.section_type() == SectionType::GNU_VERDEF
+        })
+    }
+
+    pub fn symtab(&self) -> Option<impl Iterator<Item = Sym> + 'elf> {
+        let shdr = self.symtab_section()?;
+        Some(Table::<Sym>::from_section(&shdr).ok()?.iter())
     }
 
     pub fn symbol_table(&'elf self) -> Option<SymbolTable<'elf>> {
-        let shdr = self
-            .sections()
-            .find(|shdr| shdr.name() == Some(".symtab"))?;
+        let shdr = self.symtab_section()?;
+
+        Some(SymbolTable::from_table(
+            self,
+            Table::from_section(&shdr).ok()?,
+            shdr.linked_string_table().or_else(|| self.string_table()),
+        ))
+    }
 
-        Some(SymbolTable::new(
+    pub fn dynamic_symbol_table(&'elf self) -> Option<SymbolTable<'elf>> {
+        let shdr = self.dynsym_section()?;
+
+        Some(SymbolTable::from_table(
             self,
-            unsafe { self.get_slice_of(shdr.file_offset(), shdr.size()) },
-            self.string_table(),
+            Table::from_section(&shdr).ok()?,
+            shdr.linked_string_table()
+                .or_else(|| self.dynamic_string_table()),
         ))
     }
 
     #[inline]
-    pub fn section_header(&self, index: u32) -> Option<&'elf SectionHeader> {
+    pub fn section_header(&self, index: u32) -> Option<SectionHeader> {
         self.section_headers().nth(index as usize)
     }
 
     #[inline]
     pub fn sections(&self) -> impl Iterator<Item = Section<'_, 'elf>> {
-        self.section_headers().map(|hdr| Section::new(self, hdr))
+        self.section_headers()
+            .enumerate()
+            .map(|(index, hdr)| Section::new(self, index as u16, hdr))
     }
 
     #[inline]
@@ -227,69 +557,134 @@ impl<'elf> Elf<'elf> {
         self.program_headers().map(|hdr| Segment::new(self, hdr))
     }
 
-    pub fn program_headers(&self) -> impl Iterator<Item = &'elf ProgramHeader> {
-        let table = self.data[self.phdr_offset()..]
-            [..self.phdr_num as usize * size_of::<ProgramHeader>()]
-            .as_ptr()
-            .cast::<ProgramHeader>();
-        let mut index = 0;
-        let num_phdrs = self.ehdr.phdr_num as usize;
-
-        core::iter::from_fn(move || {
-            if index < num_phdrs {
-                let hdr = unsafe { &*table.add(index) };
-                index += 1;
-                Some(hdr)
-            } else {
-                None
-            }
-        })
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + 'elf {
+        self.header_table(self.phdr_offset(), self.phdr_num(), Encoding::of(self))
+            .iter()
     }
 
-    pub fn section_headers(&self) -> impl Iterator<Item = &'elf SectionHeader> + '_ {
-        let table = self.data[self.shdr_offset()..]
-            [..self.shdr_num as usize * size_of::<SectionHeader>()]
-            .as_ptr()
-            .cast::<SectionHeader>();
-        let mut index = 0;
-        let num_shdrs = self.ehdr.shdr_num as usize;
-
-        core::iter::from_fn(move || {
-            if index < num_shdrs {
-                let hdr = unsafe { &*table.add(index) };
-                index += 1;
-                Some(hdr)
-            } else {
-                None
-            }
-        })
+    pub fn section_headers(&self) -> impl Iterator<Item = SectionHeader> + 'elf {
+        self.header_table(self.shdr_offset(), self.shdr_num(), Encoding::of(self))
+            .iter()
     }
 
-    #[inline]
     pub fn section(&self, index: u16) -> Option<Section<'_, 'elf>> {
-        self.sections().nth(index as _)
+        let hdr = self
+            .header_table(self.shdr_offset(), self.shdr_num(), Encoding::of(self))
+            .get(index as usize)?;
+        Some(Section::new(self, index, hdr))
+    }
+
+    /// Returns an iterator over the sections contained in `segment`
+    ///
+    /// See [`Segment::contains_section()`] for how containment is decided.
+    pub fn sections_in_segment<'a>(
+        &'a self,
+        segment: &'a Segment<'_, 'elf>,
+    ) -> impl Iterator<Item = Section<'a, 'elf>> + 'a {
+        self.sections()
+            .filter(move |sect| segment.contains_section(sect))
     }
 
     /// Returns the section with the given `name`, or `None` if one can't be found.
-    #[inline]
+    ///
+    /// If the file has no section name string table, well-known sections are instead
+    /// identified by their type and flags.
     pub fn find_section(&self, name: &str) -> Option<Section<'_, 'elf>> {
-        self.sections().find(|sect| sect.name() == Some(name))
+        if self.section_string_table().is_none() {
+            return self.guess_section(name);
+        }
+        self.section_by_name(name)
     }
 
-    pub fn dynamic_table(&self) -> Option<DynamicTable<'_, 'elf>> {
-        self.segments()
-            .find(|sgmt| sgmt.kind() == SegmentKind::Dynamic)
-            .map(|sgmt| {
-                let data = &self.data[sgmt.file_offset()..][..sgmt.file_size()];
-                DynamicTable::new(self, data)
+    /// Returns the first section named `name`
+    ///
+    /// Unlike [`find_section()`](Elf::find_section), sections are only matched by name.
+    /// This compares the name of each section header in turn; no index of names is kept,
+    /// as building one would need an allocation. Sections which are looked up repeatedly
+    /// should be found through the cached accessors, such as
+    /// [`symtab_section()`](Elf::symtab_section), or kept by index.
+    pub fn section_by_name(&self, name: &str) -> Option<Section<'_, 'elf>> {
+        let (index, hdr) = self.section_header_by_name(name)?;
+        Some(Section::new(self, index, hdr))
+    }
+
+    /// Returns the index and header of the first section named `name`
+    pub fn section_header_by_name(&self, name: &str) -> Option<(u16, SectionHeader)> {
+        let strtab = self.section_string_table()?;
+        self.section_headers()
+            .enumerate()
+            .find(|(_, hdr)| {
+                hdr.name_index() != 0 && strtab.get_string(hdr.name_index() as _) == Some(name)
             })
+            .map(|(index, hdr)| (index as u16, hdr))
+    }
+
+    /// Identify a well-known section without its name
+    fn guess_section(&self, name: &str) -> Option<Section<'_, 'elf>> {
+        let by_type = |kind| self.sections().find(|sect| sect.section_type() == kind);
+        let linked = |sect: Section<'_, 'elf>| self.section(sect.link().try_into().ok()?);
+        let nobits = |tls| {
+            self.sections().find(|sect| {
+                let flags = sect.flags();
+                sect.section_type() == SectionType::Nobits
+                    && flags.alloc()
+                    && flags.write()
+                    && flags.tls() == tls
+            })
+        };
+        let segment_section = |kind| {
+            let sgmt = self.segments().find(|sgmt| sgmt.kind() == kind)?;
+            self.sections().find(|sect| {
+                sect.section_type() != SectionType::Nobits
+                    && sect.file_offset() == sgmt.file_offset()
+                    && sect.size() == sgmt.file_size()
+            })
+        };
+
+        match name {
+            ".symtab" => self.symtab_section(),
+            ".strtab" => linked(self.symtab_section()?),
+            ".dynsym" => self.dynsym_section(),
+            ".dynstr" => linked(self.dynsym_section()?),
+            ".dynamic" => by_type(SectionType::Dynamic),
+            ".hash" => self.hash_section(),
+            ".gnu.hash" => self.gnu_hash_section(),
+            ".gnu.version" => by_type(SectionType::GNU_VERSYM),
+            ".gnu.version_d" => self.verdef_section(),
+            ".gnu.version_r" => by_type(SectionType::GNU_VERNEED),
+            ".init_array" => by_type(SectionType::InitArray),
+            ".fini_array" => by_type(SectionType::FiniArray),
+            ".preinit_array" => by_type(SectionType::PreinitArray),
+            ".symtab_shndx" => by_type(SectionType::SymtabShndx),
+            ".bss" => nobits(false),
+            ".tbss" => nobits(true),
+            ".interp" => segment_section(SegmentKind::Interp),
+            ".eh_frame_hdr" => segment_section(SegmentKind::EhFrame),
+            ".note.gnu.build-id" => self
+                .sections()
+                .find(|sect| sect.notes().any(|note| note.is(b"GNU", NT_GNU_BUILD_ID))),
+            _ => None,
+        }
+    }
+
+    /// Returns the dynamic table, or `None` if there is no `PT_DYNAMIC` segment or it is
+    /// malformed
+    pub fn dynamic_table(&self) -> Option<DynamicTable<'_, 'elf>> {
+        let index = self.cache.dynamic.get_or_init(|| {
+            self.segments()
+                .position(|sgmt| sgmt.kind() == SegmentKind::Dynamic)
+                .map(|i| i as u32)
+        })?;
+        let sgmt = self.segments().nth(index as usize)?;
+
+        DynamicTable::new(self, sgmt.file_data()?).ok()
     }
 }
 
 impl fmt::Debug for Elf<'_> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        <FileHeader as fmt::Debug>::fmt(self.ehdr, f)
+        <FileHeader as fmt::Debug>::fmt(&self.ehdr, f)
     }
 }
 
@@ -298,12 +693,13 @@ impl Deref for Elf<'_> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.ehdr
+        &self.ehdr
     }
 }
 
 #[repr(C)]
-pub struct FileHeader {
+#[derive(Clone, Copy)]
+pub struct FileHeader<E: Endian = LittleEndian> {
     magic: [u8; 4],
     class: u8,
     data: u8,
@@ -311,27 +707,151 @@ pub struct FileHeader {
     os_abi: u8,
     os_abi_version: u8,
     _padding: [u8; 7],
-    elf_type: u16,
-    machine: u16,
-    version: u32,
-    entry_point: u64,
-    phdr_offset: u64,
-    shdr_offset: u64,
-    flags: u32,
-    header_size: u16,
+    elf_type: U16<E>,
+    machine: U16<E>,
+    version: U32<E>,
+    entry_point: U64<E>,
+    phdr_offset: U64<E>,
+    shdr_offset: U64<E>,
+    flags: U32<E>,
+    header_size: U16<E>,
     /// Program header size, in bytes
-    phdr_size: u16,
+    phdr_size: U16<E>,
     /// Number of program headers
-    phdr_num: u16,
-    shdr_size: u16,
-    shdr_num: u16,
+    phdr_num: U16<E>,
+    shdr_size: U16<E>,
+    shdr_num: U16<E>,
     /// Index of the section header which describes the section name string table
-    shdr_strtab_index: u16,
+    shdr_strtab_index: U16<E>,
 }
 
 assert_struct_size!(FileHeader, 64);
 
-impl FileHeader {
+impl<E: Endian> FileHeader<E> {
+    /// Re-encode the header in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> FileHeader<F> {
+        FileHeader {
+            magic: self.magic,
+            class: self.class,
+            data: self.data,
+            header_version: self.header_version,
+            os_abi: self.os_abi,
+            os_abi_version: self.os_abi_version,
+            _padding: self._padding,
+            elf_type: self.elf_type.convert(),
+            machine: self.machine.convert(),
+            version: self.version.convert(),
+            entry_point: self.entry_point.convert(),
+            phdr_offset: self.phdr_offset.convert(),
+            shdr_offset: self.shdr_offset.convert(),
+            flags: self.flags.convert(),
+            header_size: self.header_size.convert(),
+            phdr_size: self.phdr_size.convert(),
+            phdr_num: self.phdr_num.convert(),
+            shdr_size: self.shdr_size.convert(),
+            shdr_num: self.shdr_num.convert(),
+            shdr_strtab_index: self.shdr_strtab_index.convert(),
+        }
+    }
+
+    /// Create a header for an empty 64-bit file with no program or section headers
+    pub const fn new(file_type: ElfType, machine: Machine) -> FileHeader<E> {
+        let data = match E::BIG_ENDIAN {
+            true => Data::TwosCompBig,
+            false => Data::TwosCompLittle,
+        };
+        FileHeader {
+            magic: *b"\x7fELF",
+            class: Class::Bits64.to_u8(),
+            data: data.to_u8(),
+            header_version: Version::Current.to_u32() as u8,
+            os_abi: OsAbi::SysV.to_u8(),
+            os_abi_version: 0,
+            _padding: [0; 7],
+            elf_type: U16::new(file_type.to_u16()),
+            machine: U16::new(machine.to_u16()),
+            version: U32::new(Version::Current.to_u32()),
+            entry_point: U64::new(0),
+            phdr_offset: U64::new(0),
+            shdr_offset: U64::new(0),
+            flags: U32::new(0),
+            header_size: U16::new(size_of::<FileHeader>() as u16),
+            phdr_size: U16::new(size_of::<ProgramHeader>() as u16),
+            phdr_num: U16::new(0),
+            shdr_size: U16::new(size_of::<SectionHeader>() as u16),
+            shdr_num: U16::new(0),
+            shdr_strtab_index: U16::new(0),
+        }
+    }
+
+    pub const fn with_os_abi(mut self, os_abi: OsAbi, version: u8) -> FileHeader<E> {
+        self.os_abi = os_abi.to_u8();
+        self.os_abi_version = version;
+        self
+    }
+
+    pub const fn with_entry_point(mut self, entry_point: u64) -> FileHeader<E> {
+        self.entry_point = U64::new(entry_point);
+        self
+    }
+
+    pub const fn with_flags(mut self, flags: u32) -> FileHeader<E> {
+        self.flags = U32::new(flags);
+        self
+    }
+
+    /// Set the location and number of entries of the program header table
+    pub const fn with_program_headers(mut self, offset: u64, num: u16) -> FileHeader<E> {
+        self.phdr_offset = U64::new(offset);
+        self.phdr_num = U16::new(num);
+        self
+    }
+
+    /// Set the location and number of entries of the section header table, and the index of
+    /// the section name string table
+    pub const fn with_section_headers(
+        mut self,
+        offset: u64,
+        num: u16,
+        strtab_index: u16,
+    ) -> FileHeader<E> {
+        self.shdr_offset = U64::new(offset);
+        self.shdr_num = U16::new(num);
+        self.shdr_strtab_index = U16::new(strtab_index);
+        self
+    }
+
+    /// Returns the header as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        bytes_of(self)
+    }
+
+    pub fn set_os_abi(&mut self, os_abi: OsAbi) {
+        self.os_abi = os_abi.to_u8();
+    }
+
+    pub fn set_entry_point(&mut self, entry_point: u64) {
+        self.entry_point.set(entry_point);
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        self.flags.set(flags);
+    }
+
+    pub fn set_program_headers(&mut self, offset: u64, num: u16) {
+        self.phdr_offset.set(offset);
+        self.phdr_num.set(num);
+    }
+
+    pub fn set_section_headers(&mut self, offset: u64, num: u16) {
+        self.shdr_offset.set(offset);
+        self.shdr_num.set(num);
+    }
+
+    pub fn set_shdr_strtab_index(&mut self, index: u16) {
+        self.shdr_strtab_index.set(index);
+    }
+
     pub const fn magic(&self) -> &[u8; 4] {
         &self.magic
     }
@@ -357,27 +877,61 @@ impl FileHeader {
     }
 
     pub const fn file_type(&self) -> ElfType {
-        ElfType::from_u16(self.elf_type)
+        ElfType::from_u16(self.elf_type.get())
     }
 
     pub const fn machine(&self) -> Machine {
-        Machine::from_u16(self.machine)
+        Machine::from_u16(self.machine.get())
     }
 
     pub const fn file_version(&self) -> Version {
-        Version::from_u32(self.version)
+        Version::from_u32(self.version.get())
     }
 
     pub const fn entry_point(&self) -> u64 {
-        self.entry_point
+        self.entry_point.get()
     }
 
     pub const fn phdr_offset(&self) -> usize {
-        self.phdr_offset as _
+        crate::to_usize(self.phdr_offset.get())
     }
 
     pub const fn shdr_offset(&self) -> usize {
-        self.shdr_offset as _
+        crate::to_usize(self.shdr_offset.get())
+    }
+
+    /// Returns the processor-specific flags
+    pub const fn flags(&self) -> u32 {
+        self.flags.get()
+    }
+
+    pub const fn header_size(&self) -> u16 {
+        self.header_size.get()
+    }
+
+    /// Returns the size of a program header, in bytes
+    pub const fn phdr_size(&self) -> u16 {
+        self.phdr_size.get()
+    }
+
+    /// Returns the number of program headers
+    pub const fn phdr_num(&self) -> u16 {
+        self.phdr_num.get()
+    }
+
+    /// Returns the size of a section header, in bytes
+    pub const fn shdr_size(&self) -> u16 {
+        self.shdr_size.get()
+    }
+
+    /// Returns the number of section headers
+    pub const fn shdr_num(&self) -> u16 {
+        self.shdr_num.get()
+    }
+
+    /// Returns the index of the section header which describes the section name string table
+    pub const fn shdr_strtab_index(&self) -> u16 {
+        self.shdr_strtab_index.get()
     }
 
     pub fn check_buffer(buf: &[u8]) -> bool {
@@ -389,14 +943,84 @@ impl FileHeader {
             && buf[3] == b'F'
     }
 
-    pub fn from_buffer(buf: &[u8]) -> &FileHeader {
+    pub fn from_buffer(buf: &[u8]) -> &FileHeader<E> {
         assert!(Self::check_buffer(buf));
 
-        unsafe { &*buf.as_ptr().cast() }
+        from_bytes(buf)
     }
 }
 
-impl fmt::Debug for FileHeader {
+/// The file header of an ELF32 file
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FileHeader32<E: Endian = LittleEndian> {
+    magic: [u8; 4],
+    class: u8,
+    data: u8,
+    header_version: u8,
+    os_abi: u8,
+    os_abi_version: u8,
+    _padding: [u8; 7],
+    elf_type: U16<E>,
+    machine: U16<E>,
+    version: U32<E>,
+    entry_point: U32<E>,
+    phdr_offset: U32<E>,
+    shdr_offset: U32<E>,
+    flags: U32<E>,
+    header_size: U16<E>,
+    phdr_size: U16<E>,
+    phdr_num: U16<E>,
+    shdr_size: U16<E>,
+    shdr_num: U16<E>,
+    shdr_strtab_index: U16<E>,
+}
+
+assert_struct_size!(FileHeader32, 52);
+
+impl<E: Endian> FileHeader32<E> {
+    /// Returns the header as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        bytes_of(self)
+    }
+
+    pub fn check_buffer(buf: &[u8]) -> bool {
+        buf.len() >= size_of::<Self>() && buf[..4] == *b"\x7fELF"
+    }
+}
+
+impl<E: Endian> From<FileHeader32<E>> for FileHeader<E> {
+    /// Widen the header, keeping the class and the file's header sizes
+    fn from(ehdr: FileHeader32<E>) -> FileHeader<E> {
+        let widen = |x: U32<E>| U64::new(x.get() as u64);
+        FileHeader {
+            magic: ehdr.magic,
+            class: ehdr.class,
+            data: ehdr.data,
+            header_version: ehdr.header_version,
+            os_abi: ehdr.os_abi,
+            os_abi_version: ehdr.os_abi_version,
+            _padding: ehdr._padding,
+            elf_type: ehdr.elf_type,
+            machine: ehdr.machine,
+            version: ehdr.version,
+            entry_point: widen(ehdr.entry_point),
+            phdr_offset: widen(ehdr.phdr_offset),
+            shdr_offset: widen(ehdr.shdr_offset),
+            flags: ehdr.flags,
+            header_size: ehdr.header_size,
+            phdr_size: ehdr.phdr_size,
+            phdr_num: ehdr.phdr_num,
+            shdr_size: ehdr.shdr_size,
+            shdr_num: ehdr.shdr_num,
+            shdr_strtab_index: ehdr.shdr_strtab_index,
+        }
+    }
+}
+
+impl_entry!(FileHeader, FileHeader32);
+
+impl<E: Endian> fmt::Debug for FileHeader<E> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FileHeader")
             .field("magic", &self.magic)
@@ -408,16 +1032,16 @@ impl fmt::Debug for FileHeader {
             .field("elf_type", &self.file_type())
             .field("machine", &self.machine())
             .field("version", &self.file_version())
-            .field("entry_point", &format_args!("{:#018x}", self.entry_point))
-            .field("phdrs_offset", &format_args!("{:#x}", self.phdr_offset))
-            .field("shdrs_offset", &format_args!("{:#x}", self.shdr_offset))
-            .field("flags", &format_args!("{:x}", self.flags))
-            .field("header_size", &self.header_size)
-            .field("phdr_size", &self.phdr_size)
-            .field("phdr_num", &self.phdr_num)
-            .field("shdr_size", &self.shdr_size)
-            .field("shdr_num", &self.shdr_num)
-            .field("shdr_strtab_index", &self.shdr_strtab_index)
+            .field("entry_point", &format_args!("{:#018x}", self.entry_point()))
+            .field("phdrs_offset", &format_args!("{:#x}", self.phdr_offset()))
+            .field("shdrs_offset", &format_args!("{:#x}", self.shdr_offset()))
+            .field("flags", &format_args!("{:x}", self.flags()))
+            .field("header_size", &self.header_size())
+            .field("phdr_size", &self.phdr_size())
+            .field("phdr_num", &self.phdr_num())
+            .field("shdr_size", &self.shdr_size())
+            .field("shdr_num", &self.shdr_num())
+            .field("shdr_strtab_index", &self.shdr_strtab_index())
             .finish()
     }
 }
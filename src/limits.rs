@@ -0,0 +1,210 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Resource limits enforced while parsing
+
+use crate::{
+    table::{Encoding, Entry},
+    Elf, ParseError, ProgramHeader, SectionHeader, SectionType, Structure, Sym,
+};
+
+/// Upper bounds on the sizes an ELF file may claim
+///
+/// Headers can claim far more sections or symbols than a file plausibly holds, for example
+/// by pointing thousands of section headers at the same bytes. Checking these limits up
+/// front bounds the work done by anything iterating over the file.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParseLimits {
+    /// Maximum number of section headers
+    pub max_sections: u16,
+    /// Maximum number of program headers
+    pub max_segments: u16,
+    /// Maximum number of entries in any symbol table
+    pub max_symbols: usize,
+    /// Maximum size, in bytes, of any string table
+    pub max_string_table_size: usize,
+}
+
+impl ParseLimits {
+    /// No limits beyond those of the file format
+    pub const UNLIMITED: ParseLimits = ParseLimits {
+        max_sections: u16::MAX,
+        max_segments: u16::MAX,
+        max_symbols: usize::MAX,
+        max_string_table_size: usize::MAX,
+    };
+
+    /// Limits which comfortably fit kernels, modules, and ordinary programs
+    pub const DEFAULT: ParseLimits = ParseLimits {
+        max_sections: 4096,
+        max_segments: 256,
+        max_symbols: 1 << 20,
+        max_string_table_size: 16 << 20,
+    };
+}
+
+impl Default for ParseLimits {
+    fn default() -> ParseLimits {
+        Self::DEFAULT
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Parse a file, failing if it exceeds `limits`
+    ///
+    /// The program and section header tables, and the contents of every segment and section
+    /// which occupies space in the file, must also lie within `data`.
+    pub fn with_limits(data: &'elf [u8], limits: &ParseLimits) -> Result<Elf<'elf>, ParseError> {
+        let elf = Elf::new(data)?;
+        let header_error =
+            |offset, message| Err(ParseError::new(Structure::FileHeader, offset, message));
+        let encoding = Encoding::of(&elf);
+        let phdr_size = ProgramHeader::encoded_size(encoding);
+        let shdr_size = SectionHeader::encoded_size(encoding);
+
+        // Offsets of e_phoff, e_shoff, e_phnum, and e_shnum
+        let (phoff_field, shoff_field, phnum_field, shnum_field) = match encoding.bits32 {
+            true => (0x1c, 0x20, 0x2c, 0x30),
+            false => (0x20, 0x28, 0x38, 0x3c),
+        };
+
+        if elf.shdr_num() > limits.max_sections {
+            return header_error(shnum_field, "too many sections");
+        }
+        if elf.phdr_num() > limits.max_segments {
+            return header_error(phnum_field, "too many program headers");
+        }
+
+        let phoff = elf.phdr_offset();
+        let table_size = elf.phdr_num() as usize * phdr_size;
+        if phoff
+            .checked_add(table_size)
+            .is_none_or(|end| end > data.len())
+        {
+            return header_error(phoff_field, "program headers out of bounds");
+        }
+
+        for (index, sgmt) in elf.segments().enumerate() {
+            let offset = (phoff + index * phdr_size) as u64;
+            let in_bounds = sgmt
+                .file_offset()
+                .checked_add(sgmt.file_size())
+                .is_some_and(|end| end <= data.len());
+            if !in_bounds {
+                let structure = Structure::ProgramHeader(index as u16);
+                return Err(ParseError::new(structure, offset, "p_offset out of range"));
+            }
+        }
+
+        let shoff = elf.shdr_offset();
+        let table_size = elf.shdr_num() as usize * shdr_size;
+        if shoff
+            .checked_add(table_size)
+            .is_none_or(|end| end > data.len())
+        {
+            return header_error(shoff_field, "section headers out of bounds");
+        }
+
+        for sect in elf.sections() {
+            let index = sect.index();
+            let offset = (shoff + index as usize * shdr_size) as u64;
+            let error = |message| {
+                Err(ParseError::new(
+                    Structure::SectionHeader(index),
+                    offset,
+                    message,
+                ))
+            };
+
+            let in_bounds = sect
+                .file_offset()
+                .checked_add(sect.size())
+                .is_some_and(|end| end <= data.len());
+            if sect.section_type() != SectionType::Nobits && !in_bounds {
+                return error("sh_offset out of range");
+            }
+
+            match sect.section_type() {
+                SectionType::Symtab | SectionType::Dynsym
+                    if sect.size() / Sym::encoded_size(encoding) > limits.max_symbols =>
+                {
+                    return error("too many symbols");
+                }
+                SectionType::Strtab if sect.size() > limits.max_string_table_size => {
+                    return error("string table too large");
+                }
+                _ => {}
+            }
+        }
+
+        Ok(elf)
+    }
+}
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use super::ParseLimits;
+    use crate::{testdata::Fixture, Class, Elf, Structure};
+
+    #[test]
+    fn fixtures_are_within_limits() {
+        for fixture in Fixture::all() {
+            let file = fixture.build();
+            Elf::with_limits(&file, &ParseLimits::DEFAULT).unwrap();
+        }
+    }
+
+    #[test]
+    fn limits_are_located_in_the_file_header() {
+        for fixture in [Fixture::new(), Fixture::new().elf32()] {
+            let file = fixture.build();
+            let limits = ParseLimits {
+                max_sections: 1,
+                ..ParseLimits::UNLIMITED
+            };
+            let error = Elf::with_limits(&file, &limits).unwrap_err();
+            assert_eq!(error.structure, Structure::FileHeader);
+            let shnum = match fixture.class() {
+                Class::Bits32 => 0x30,
+                _ => 0x3c,
+            };
+            assert_eq!(error.offset, shnum);
+
+            let truncated = &file[..file.len() - 1];
+            let error = Elf::with_limits(truncated, &ParseLimits::UNLIMITED).unwrap_err();
+            assert_eq!(error.structure, Structure::FileHeader);
+            let shoff = match fixture.class() {
+                Class::Bits32 => 0x20,
+                _ => 0x28,
+            };
+            assert_eq!(error.offset, shoff);
+        }
+    }
+}
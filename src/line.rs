@@ -0,0 +1,550 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! DWARF line number lookup
+//!
+//! Just enough of the `.debug_line` program is interpreted to map an address to a source
+//! location, without allocating. Files are assumed to be little-endian, and VLIW operation
+//! indices are not supported.
+
+use crate::{
+    leb128::{read_sleb128, read_uleb128},
+    table::Encoding,
+    DebugSectionKind, DwarfSection, Elf,
+};
+use core::fmt;
+
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+const DW_LNS_SET_FILE: u8 = 0x04;
+const DW_LNS_SET_COLUMN: u8 = 0x05;
+const DW_LNS_CONST_ADD_PC: u8 = 0x08;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 0x09;
+
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+
+const DW_LNCT_PATH: u64 = 0x1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 0x2;
+
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+/// A source location
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LineLocation<'a> {
+    /// The directory `file` is relative to, if it is not the compilation directory
+    pub directory: Option<&'a str>,
+    pub file: &'a str,
+    pub line: u32,
+    /// The column, or 0 if unknown
+    pub column: u32,
+}
+
+impl fmt::Display for LineLocation<'_> {
+    /// Formats the location as `directory/file:line:column`, omitting the directory if
+    /// `file` is absolute and the column if it is unknown
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.directory {
+            Some(dir) if !self.file.starts_with('/') => write!(f, "{dir}/")?,
+            _ => {}
+        }
+        write!(f, "{}:{}", self.file, self.line)?;
+        match self.column {
+            0 => Ok(()),
+            column => write!(f, ":{column}"),
+        }
+    }
+}
+
+/// A row of the line number matrix
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LineRow {
+    pub address: u64,
+    /// Index of the file in the program's file table
+    pub file: u64,
+    pub line: u32,
+    pub column: u32,
+    /// This row marks the first address after the end of a sequence
+    pub end_sequence: bool,
+}
+
+/// The contents of `.debug_line`, with the string sections it refers to
+#[derive(Clone, Copy, Debug)]
+pub struct DebugLine<'a> {
+    data: &'a [u8],
+    line_str: &'a [u8],
+    str: &'a [u8],
+    encoding: Encoding,
+}
+
+impl<'a> DebugLine<'a> {
+    /// `line_str` and `str` are the contents of `.debug_line_str` and `.debug_str`, which
+    /// DWARF 5 file tables refer to
+    ///
+    /// The contents are read as in a little-endian file.
+    pub const fn new(data: &'a [u8], line_str: &'a [u8], str: &'a [u8]) -> DebugLine<'a> {
+        Self {
+            data,
+            line_str,
+            str,
+            encoding: Encoding::NATIVE,
+        }
+    }
+
+    /// Returns an iterator over the line number programs, one for each compilation unit
+    ///
+    /// Iteration stops at the first malformed program header.
+    pub fn programs(&self) -> impl Iterator<Item = LineProgram<'a>> {
+        let this = *self;
+        let mut rest = self.data;
+        core::iter::from_fn(move || {
+            let (program, next) = LineProgram::parse(rest, this.line_str, this.str, this.encoding)?;
+            rest = next;
+            Some(program)
+        })
+    }
+
+    /// Returns the program at `offset`, the `DW_AT_stmt_list` of its compilation unit
+    ///
+    /// Together with [`DebugAranges`](crate::DebugAranges), this lets a DWARF reader find
+    /// the program covering an address without interpreting every program.
+    pub fn program_at(&self, offset: u64) -> Option<LineProgram<'a>> {
+        let data = self.data.get(usize::try_from(offset).ok()?..)?;
+        LineProgram::parse(data, self.line_str, self.str, self.encoding).map(|(program, _)| program)
+    }
+
+    /// Returns the source location of the instruction at `addr`
+    pub fn find(&self, addr: u64) -> Option<LineLocation<'a>> {
+        self.programs().find_map(|program| program.find(addr))
+    }
+}
+
+/// The line number program of one compilation unit
+#[derive(Clone, Copy, Debug)]
+pub struct LineProgram<'a> {
+    version: u16,
+    offset_size: usize,
+    address_size: u8,
+    min_inst_length: u8,
+    line_base: i8,
+    line_range: u8,
+    opcode_base: u8,
+    standard_opcode_lengths: &'a [u8],
+    /// The directory and file tables
+    tables: &'a [u8],
+    program: &'a [u8],
+    line_str: &'a [u8],
+    str: &'a [u8],
+    encoding: Encoding,
+}
+
+impl<'a> LineProgram<'a> {
+    /// Parse the program at the start of `data`, returning it and the data following it
+    fn parse(
+        data: &'a [u8],
+        line_str: &'a [u8],
+        str: &'a [u8],
+        encoding: Encoding,
+    ) -> Option<(LineProgram<'a>, &'a [u8])> {
+        let mut cursor = data;
+        let (offset_size, unit_length) = match read_u32(&mut cursor, encoding)? {
+            0xffff_ffff => (8, read_u64(&mut cursor, encoding)?),
+            len => (4, len as u64),
+        };
+        let unit = cursor.get(..usize::try_from(unit_length).ok()?)?;
+        let next = &cursor[unit.len()..];
+
+        let mut cursor = unit;
+        let version = read_u16(&mut cursor, encoding)?;
+        if !(2..=5).contains(&version) {
+            return None;
+        }
+        let mut address_size = 0;
+        if version >= 5 {
+            let [size, _segment_selector_size] = take(&mut cursor)?;
+            address_size = size;
+        }
+        let header_length = read_offset(&mut cursor, offset_size, encoding)?;
+        let mut header = cursor.get(..usize::try_from(header_length).ok()?)?;
+        let program = &cursor[header.len()..];
+
+        let [min_inst_length] = take(&mut header)?;
+        if version >= 4 {
+            let [_max_ops_per_inst] = take(&mut header)?;
+        }
+        let [_default_is_stmt, line_base, line_range, opcode_base] = take(&mut header)?;
+        if line_range == 0 || opcode_base == 0 {
+            return None;
+        }
+        let standard_opcode_lengths = header.get(..opcode_base as usize - 1)?;
+
+        let program = LineProgram {
+            version,
+            offset_size,
+            address_size,
+            min_inst_length,
+            line_base: line_base as i8,
+            line_range,
+            opcode_base,
+            standard_opcode_lengths,
+            tables: &header[standard_opcode_lengths.len()..],
+            program,
+            line_str,
+            str,
+            encoding,
+        };
+        Some((program, next))
+    }
+
+    #[inline]
+    pub const fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Returns the file at `index` in the file table, with its directory
+    ///
+    /// Before DWARF 5 file indices start at 1. The directory is `None` for the compilation
+    /// directory, which only DWARF 5 names in the table.
+    pub fn file(&self, index: u64) -> Option<(Option<&'a str>, &'a str)> {
+        match self.version {
+            5 => self.file_v5(index),
+            _ => self.file_v4(index),
+        }
+    }
+
+    fn file_v4(&self, index: u64) -> Option<(Option<&'a str>, &'a str)> {
+        let mut cursor = self.tables;
+        let mut directories = cursor;
+        while !read_str(&mut cursor)?.is_empty() {}
+        let mut file = index.checked_sub(1)?;
+        loop {
+            let name = read_str(&mut cursor)?;
+            if name.is_empty() {
+                return None;
+            }
+            let dir = read_uleb128(&mut cursor)?;
+            read_uleb128(&mut cursor)?;
+            read_uleb128(&mut cursor)?;
+            if file == 0 {
+                let dir = match dir {
+                    0 => None,
+                    dir => {
+                        for _ in 1..dir {
+                            read_str(&mut directories)?;
+                        }
+                        Some(read_str(&mut directories).filter(|dir| !dir.is_empty())?)
+                    }
+                };
+                return Some((dir, name));
+            }
+            file -= 1;
+        }
+    }
+
+    fn file_v5(&self, index: u64) -> Option<(Option<&'a str>, &'a str)> {
+        let mut cursor = self.tables;
+        let (dir_formats, dir_count) = self.entry_formats(&mut cursor)?;
+        let directories = cursor;
+        for _ in 0..dir_count {
+            self.read_entry(&mut cursor, dir_formats)?;
+        }
+        let (file_formats, file_count) = self.entry_formats(&mut cursor)?;
+        if index >= file_count {
+            return None;
+        }
+        for _ in 0..index {
+            self.read_entry(&mut cursor, file_formats)?;
+        }
+        let (name, dir) = self.read_entry(&mut cursor, file_formats)?;
+
+        let mut cursor = directories;
+        for _ in 0..dir.unwrap_or(0) {
+            self.read_entry(&mut cursor, dir_formats)?;
+        }
+        let (dir, _) = self.read_entry(&mut cursor, dir_formats)?;
+        Some((dir, name?))
+    }
+
+    /// Read the entry format description of a DWARF 5 directory or file table, returning
+    /// it and the number of entries in the table
+    fn entry_formats(&self, cursor: &mut &'a [u8]) -> Option<(&'a [u8], u64)> {
+        let [count] = take(cursor)?;
+        let formats = *cursor;
+        for _ in 0..2 * count {
+            read_uleb128(cursor)?;
+        }
+        let formats = &formats[..formats.len() - cursor.len()];
+        Some((formats, read_uleb128(cursor)?))
+    }
+
+    /// Read a DWARF 5 directory or file entry, returning its path and directory index
+    fn read_entry(
+        &self,
+        cursor: &mut &'a [u8],
+        mut formats: &'a [u8],
+    ) -> Option<(Option<&'a str>, Option<u64>)> {
+        let mut path = None;
+        let mut dir = None;
+        while !formats.is_empty() {
+            let content = read_uleb128(&mut formats)?;
+            let form = read_uleb128(&mut formats)?;
+            let value = match form {
+                DW_FORM_STRING => FormValue::Str(read_str(cursor)?),
+                DW_FORM_LINE_STRP | DW_FORM_STRP => {
+                    let strings = match form {
+                        DW_FORM_LINE_STRP => self.line_str,
+                        _ => self.str,
+                    };
+                    let offset = read_offset(cursor, self.offset_size, self.encoding)?;
+                    let mut s = strings.get(usize::try_from(offset).ok()?..)?;
+                    FormValue::Str(read_str(&mut s)?)
+                }
+                DW_FORM_UDATA => FormValue::Int(read_uleb128(cursor)?),
+                DW_FORM_DATA1 => FormValue::Int(u8::from_le_bytes(take(cursor)?) as u64),
+                DW_FORM_DATA2 => FormValue::Int(read_u16(cursor, self.encoding)? as u64),
+                DW_FORM_DATA4 => FormValue::Int(read_u32(cursor, self.encoding)? as u64),
+                DW_FORM_DATA8 => FormValue::Int(read_u64(cursor, self.encoding)?),
+                DW_FORM_DATA16 => {
+                    take::<16>(cursor)?;
+                    FormValue::Skip
+                }
+                DW_FORM_BLOCK => {
+                    let len = usize::try_from(read_uleb128(cursor)?).ok()?;
+                    *cursor = cursor.get(len..)?;
+                    FormValue::Skip
+                }
+                _ => return None,
+            };
+            match (content, value) {
+                (DW_LNCT_PATH, FormValue::Str(s)) => path = Some(s),
+                (DW_LNCT_DIRECTORY_INDEX, FormValue::Int(i)) => dir = Some(i),
+                _ => {}
+            }
+        }
+        Some((path, dir))
+    }
+
+    /// Returns an iterator over the rows of the line number matrix
+    ///
+    /// Iteration stops at the first malformed instruction.
+    pub fn rows(&self) -> LineRows<'a> {
+        LineRows {
+            program: *self,
+            cursor: self.program,
+            state: LineRows::initial_state(),
+        }
+    }
+
+    /// Returns the source location of the instruction at `addr`
+    pub fn find(&self, addr: u64) -> Option<LineLocation<'a>> {
+        let mut prev: Option<LineRow> = None;
+        for row in self.rows() {
+            if let Some(prev) = prev.filter(|prev| prev.address <= addr && addr < row.address) {
+                let (directory, file) = self.file(prev.file)?;
+                return Some(LineLocation {
+                    directory,
+                    file,
+                    line: prev.line,
+                    column: prev.column,
+                });
+            }
+            prev = (!row.end_sequence).then_some(row);
+        }
+        None
+    }
+}
+
+enum FormValue<'a> {
+    Str(&'a str),
+    Int(u64),
+    Skip,
+}
+
+/// An iterator over the rows of a line number program
+#[derive(Clone, Debug)]
+pub struct LineRows<'a> {
+    program: LineProgram<'a>,
+    cursor: &'a [u8],
+    state: LineRow,
+}
+
+impl LineRows<'_> {
+    const fn initial_state() -> LineRow {
+        LineRow {
+            address: 0,
+            file: 1,
+            line: 1,
+            column: 0,
+            end_sequence: false,
+        }
+    }
+
+    fn advance(&mut self, operation_advance: u64) {
+        let advance = operation_advance.wrapping_mul(self.program.min_inst_length as u64);
+        self.state.address = self.state.address.wrapping_add(advance);
+    }
+}
+
+impl Iterator for LineRows<'_> {
+    type Item = LineRow;
+
+    fn next(&mut self) -> Option<LineRow> {
+        let p = self.program;
+        loop {
+            let [opcode] = take(&mut self.cursor)?;
+            match opcode {
+                0 => {
+                    let len = usize::try_from(read_uleb128(&mut self.cursor)?).ok()?;
+                    let mut inst = self.cursor.get(..len)?;
+                    self.cursor = &self.cursor[len..];
+                    let [sub_opcode] = take(&mut inst)?;
+                    match sub_opcode {
+                        DW_LNE_END_SEQUENCE => {
+                            let row = LineRow {
+                                end_sequence: true,
+                                ..self.state
+                            };
+                            self.state = Self::initial_state();
+                            return Some(row);
+                        }
+                        DW_LNE_SET_ADDRESS => {
+                            if p.address_size != 0 && inst.len() != p.address_size as usize {
+                                return None;
+                            }
+                            self.state.address = match inst.len() {
+                                4 => read_u32(&mut inst, p.encoding)? as u64,
+                                8 => read_u64(&mut inst, p.encoding)?,
+                                _ => return None,
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+                DW_LNS_COPY => return Some(self.state),
+                DW_LNS_ADVANCE_PC => {
+                    let advance = read_uleb128(&mut self.cursor)?;
+                    self.advance(advance);
+                }
+                DW_LNS_ADVANCE_LINE => {
+                    let advance = read_sleb128(&mut self.cursor)?;
+                    self.state.line = (self.state.line as i64).wrapping_add(advance) as u32;
+                }
+                DW_LNS_SET_FILE => self.state.file = read_uleb128(&mut self.cursor)?,
+                DW_LNS_SET_COLUMN => self.state.column = read_uleb128(&mut self.cursor)? as u32,
+                DW_LNS_CONST_ADD_PC => self.advance(((255 - p.opcode_base) / p.line_range) as u64),
+                DW_LNS_FIXED_ADVANCE_PC => {
+                    let advance = read_u16(&mut self.cursor, p.encoding)?;
+                    self.state.address = self.state.address.wrapping_add(advance as u64);
+                }
+                opcode if opcode < p.opcode_base => {
+                    // Skip the operands of opcodes which do not affect the rows reported
+                    for _ in 0..p.standard_opcode_lengths[opcode as usize - 1] {
+                        read_uleb128(&mut self.cursor)?;
+                    }
+                }
+                opcode => {
+                    let adjusted = opcode - p.opcode_base;
+                    self.advance((adjusted / p.line_range) as u64);
+                    let line_advance = p.line_base as i32 + (adjusted % p.line_range) as i32;
+                    self.state.line = (self.state.line as i32).wrapping_add(line_advance) as u32;
+                    return Some(self.state);
+                }
+            }
+        }
+    }
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+    let bytes = data.get(..N)?.try_into().ok()?;
+    *data = &data[N..];
+    Some(bytes)
+}
+
+fn read_u16(data: &mut &[u8], encoding: Encoding) -> Option<u16> {
+    take::<2>(data).and_then(|bytes| encoding.read_u16(&bytes))
+}
+
+fn read_u32(data: &mut &[u8], encoding: Encoding) -> Option<u32> {
+    take::<4>(data).and_then(|bytes| encoding.read_u32(&bytes))
+}
+
+fn read_u64(data: &mut &[u8], encoding: Encoding) -> Option<u64> {
+    take::<8>(data).and_then(|bytes| encoding.read_u64(&bytes))
+}
+
+fn read_offset(data: &mut &[u8], offset_size: usize, encoding: Encoding) -> Option<u64> {
+    match offset_size {
+        8 => read_u64(data, encoding),
+        _ => read_u32(data, encoding).map(u64::from),
+    }
+}
+
+/// Read a NUL-terminated string
+fn read_str<'a>(data: &mut &'a [u8]) -> Option<&'a str> {
+    let len = data.iter().position(|&b| b == 0)?;
+    let s = core::str::from_utf8(&data[..len]).ok()?;
+    *data = &data[len + 1..];
+    Some(s)
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the contents of `.debug_line`, if there are any
+    pub fn debug_line(&self) -> Option<DebugLine<'elf>> {
+        let data = |s| {
+            self.debug_section(DebugSectionKind::Main(s))
+                .and_then(|sect| sect.file_data())
+                .unwrap_or_default()
+        };
+        self.debug_section(DebugSectionKind::Main(DwarfSection::Line))?;
+        Some(DebugLine {
+            encoding: Encoding::of(self),
+            ..DebugLine::new(
+                data(DwarfSection::Line),
+                data(DwarfSection::LineStr),
+                data(DwarfSection::Str),
+            )
+        })
+    }
+
+    /// Returns the source location of the instruction at the link-time address `addr`
+    pub fn find_line(&self, addr: u64) -> Option<LineLocation<'elf>> {
+        self.debug_line()?.find(addr)
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Entries of mergeable sections
+
+use crate::Section;
+
+/// An entry of an `SHF_MERGE` section
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MergeEntry<'elf> {
+    /// Offset of the entry from the start of the section
+    pub offset: usize,
+    /// Contents of the entry, without the terminator of a string
+    pub data: &'elf [u8],
+}
+
+/// Iterator over the entries of an `SHF_MERGE` section
+///
+/// Strings are terminated by a character of the section's entry size whose bytes are all
+/// zero. Iteration stops at an unterminated string or a partial constant at the end of the
+/// section.
+#[derive(Clone, Debug)]
+pub struct MergeEntries<'elf> {
+    data: &'elf [u8],
+    offset: usize,
+    entry_size: usize,
+    strings: bool,
+}
+
+impl<'elf> MergeEntries<'elf> {
+    /// Returns the size of each constant, or of each character of the strings
+    #[inline]
+    pub const fn entry_size(&self) -> usize {
+        self.entry_size
+    }
+
+    /// Returns `true` if the entries are strings
+    #[inline]
+    pub const fn strings(&self) -> bool {
+        self.strings
+    }
+}
+
+impl<'elf> Iterator for MergeEntries<'elf> {
+    type Item = MergeEntry<'elf>;
+
+    fn next(&mut self) -> Option<MergeEntry<'elf>> {
+        let rest = self.data.get(self.offset..)?;
+
+        let (len, advance) = match self.strings {
+            true => {
+                let len = rest
+                    .chunks_exact(self.entry_size)
+                    .position(|c| c.iter().all(|&b| b == 0))?
+                    * self.entry_size;
+                (len, len + self.entry_size)
+            }
+            false if rest.len() >= self.entry_size => (self.entry_size, self.entry_size),
+            false => return None,
+        };
+
+        let entry = MergeEntry {
+            offset: self.offset,
+            data: &rest[..len],
+        };
+        self.offset += advance;
+        Some(entry)
+    }
+}
+
+impl<'elf> Section<'_, 'elf> {
+    /// Returns an iterator over the strings or constants of an `SHF_MERGE` section
+    ///
+    /// Entries are yielded in the order they appear, so duplicates are only removed if the
+    /// linker has already merged the section. Returns `None` if the section is not
+    /// mergeable, its contents lie outside of the file, or it has no entry size. An entry
+    /// size of zero is accepted for strings, and treated as one.
+    pub fn merge_entries(&self) -> Option<MergeEntries<'elf>> {
+        let flags = self.flags();
+        if !flags.merge() {
+            return None;
+        }
+
+        let entry_size = match (self.entry_size(), flags.strings()) {
+            (0, true) => 1,
+            (0, false) => return None,
+            (size, _) => usize::try_from(size).ok()?,
+        };
+
+        Some(MergeEntries {
+            data: self.file_data()?,
+            offset: 0,
+            entry_size,
+            strings: flags.strings(),
+        })
+    }
+}
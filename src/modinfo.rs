@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Module metadata
+//!
+//! A loadable module describes itself in its [`MODINFO_SECTION`] section, which holds a
+//! sequence of `KEY=VALUE` records each terminated by a NUL byte. Records may be separated
+//! by additional NUL bytes, as the linker pads them when they come from different objects.
+//! Keys may repeat; [`modinfo_keys`] lists the ones the module subsystem understands.
+
+use crate::Elf;
+
+/// The name of the section holding module metadata
+pub const MODINFO_SECTION: &str = ".modinfo";
+
+/// Well-known module metadata keys
+pub mod modinfo_keys {
+    /// The name the module is known by, which other modules use to depend on it
+    pub const NAME: &str = "name";
+    /// The license the module is distributed under
+    pub const LICENSE: &str = "license";
+    /// A comma-separated list of the names of modules this module depends on
+    pub const DEPENDS: &str = "depends";
+    /// A parameter, as `NAME:DESCRIPTION`
+    pub const PARM: &str = "parm";
+    /// The type of a parameter, as `NAME:TYPE`
+    pub const PARMTYPE: &str = "parmtype";
+    pub const DESCRIPTION: &str = "description";
+    pub const AUTHOR: &str = "author";
+    pub const VERSION: &str = "version";
+}
+
+/// A parameter accepted by a module
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ModParam<'elf> {
+    pub name: &'elf str,
+    pub description: &'elf str,
+    /// The type given by a `parmtype` record, if there is one
+    pub kind: Option<&'elf str>,
+}
+
+/// The records of a module's metadata section
+#[derive(Clone, Copy, Debug)]
+pub struct ModInfo<'elf> {
+    data: &'elf [u8],
+}
+
+impl<'elf> ModInfo<'elf> {
+    #[inline]
+    pub const fn new(data: &'elf [u8]) -> ModInfo<'elf> {
+        Self { data }
+    }
+
+    /// Returns an iterator over the key/value pairs, in order
+    ///
+    /// Records which are not valid UTF-8 or have no `=` are skipped.
+    pub fn entries(&self) -> impl Iterator<Item = (&'elf str, &'elf str)> {
+        self.data
+            .split(|&b| b == 0)
+            .filter_map(|record| core::str::from_utf8(record).ok()?.split_once('='))
+    }
+
+    /// Returns an iterator over the values of every record with the given key
+    pub fn get_all<'a>(&self, key: &'a str) -> impl Iterator<Item = &'elf str> + 'a
+    where
+        'elf: 'a,
+    {
+        self.entries()
+            .filter(move |&(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value of the first record with the given key
+    pub fn get(&self, key: &str) -> Option<&'elf str> {
+        self.get_all(key).next()
+    }
+
+    #[inline]
+    pub fn name(&self) -> Option<&'elf str> {
+        self.get(modinfo_keys::NAME)
+    }
+
+    #[inline]
+    pub fn license(&self) -> Option<&'elf str> {
+        self.get(modinfo_keys::LICENSE)
+    }
+
+    /// Returns an iterator over the names of the modules this module depends on
+    ///
+    /// Every `depends` record contributes, and empty names are skipped.
+    pub fn dependencies(&self) -> impl Iterator<Item = &'elf str> {
+        self.get_all(modinfo_keys::DEPENDS)
+            .flat_map(|list| list.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Returns an iterator over the parameters the module accepts
+    pub fn parameters(&self) -> impl Iterator<Item = ModParam<'elf>> + '_ {
+        self.get_all(modinfo_keys::PARM).filter_map(move |parm| {
+            let (name, description) = parm.split_once(':').unwrap_or((parm, ""));
+            let kind = self.get_all(modinfo_keys::PARMTYPE).find_map(|parmtype| {
+                let (param, kind) = parmtype.split_once(':')?;
+                (param == name).then_some(kind)
+            });
+            (!name.is_empty()).then_some(ModParam {
+                name,
+                description,
+                kind,
+            })
+        })
+    }
+}
+
+/// Returns the size of the metadata section holding `entries`
+pub fn modinfo_size(entries: &[(&str, &str)]) -> usize {
+    entries.iter().map(|(k, v)| k.len() + v.len() + 2).sum()
+}
+
+/// Write the metadata records for `entries` to the start of `buf`
+///
+/// Returns the number of bytes written, or `None` if `buf` is too small, a key is empty or
+/// contains `=`, or either a key or value contains a NUL byte. The result can be placed in
+/// a [`MODINFO_SECTION`] section, for example with
+/// [`ElfMut::append_section()`](crate::ElfMut::append_section).
+pub fn write_modinfo(buf: &mut [u8], entries: &[(&str, &str)]) -> Option<usize> {
+    let valid = entries
+        .iter()
+        .all(|(k, v)| !k.is_empty() && !k.contains(['=', '\0']) && !v.contains('\0'));
+    let size = modinfo_size(entries);
+    if !valid || buf.len() < size {
+        return None;
+    }
+
+    let mut at = 0;
+    for (k, v) in entries {
+        for s in [k.as_bytes(), b"=", v.as_bytes(), b"\0"] {
+            buf[at..][..s.len()].copy_from_slice(s);
+            at += s.len();
+        }
+    }
+    Some(at)
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the module metadata, if there is any
+    pub fn modinfo(&self) -> Option<ModInfo<'elf>> {
+        Some(ModInfo::new(
+            self.find_section(MODINFO_SECTION)?.file_data()?,
+        ))
+    }
+}
@@ -0,0 +1,753 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Linking relocatable modules in memory
+
+use crate::{
+    slice_from_bytes,
+    table::{Encoding, Table},
+    BigEndian, Binding, Elf, Endian, LittleEndian, Rela, RelocError, Relocator, Section, Sym,
+    SymbolKind, Visibility, SHN_ABS, SHN_COMMON, SHN_UNDEF,
+};
+use core::ops::Range;
+
+/// What to bind an undefined symbol to when it cannot be imported
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum UndefinedPolicy {
+    /// Fail to link the module
+    #[default]
+    Fail,
+    /// Bind weak symbols to the stub at this address, and fail for any other symbol
+    StubWeak(u64),
+    /// Bind all symbols to the stub at this address
+    StubAll(u64),
+}
+
+impl UndefinedPolicy {
+    /// Returns the stub address for an unresolved symbol with the given binding
+    pub const fn stub_for(self, binding: Binding) -> Option<u64> {
+        match (self, binding) {
+            (Self::StubWeak(stub), Binding::Weak) | (Self::StubAll(stub), _) => Some(stub),
+            _ => None,
+        }
+    }
+}
+
+/// An undefined symbol which was bound to the stub
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct StubbedSymbol<'elf> {
+    /// Index of the symbol in the symbol table
+    pub index: u32,
+    pub name: &'elf str,
+    pub binding: Binding,
+}
+
+/// A step taken while linking a module, reported for bookkeeping
+///
+/// Together these describe everything needed to unload the module again: the memory it
+/// occupies, the symbols it depends on, and the sections whose contents were modified. No
+/// GOT or PLT entries are allocated by [`ModuleLinker`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LinkEvent<'elf> {
+    /// The allocated section at `section` occupies `range`
+    Mapped { section: u16, range: Range<u64> },
+    /// An undefined symbol was imported from outside the module
+    Imported {
+        index: u32,
+        name: &'elf str,
+        address: u64,
+    },
+    /// An undefined symbol was bound to the stub
+    Stubbed(StubbedSymbol<'elf>),
+    /// The relocations against the section at `section` were applied
+    Relocated { section: u16, count: usize },
+}
+
+/// Links a relocatable (`ET_REL`) module which has been laid out in memory
+///
+/// `address_of` returns the runtime address assigned to a section, and `import` returns the
+/// address of a symbol defined outside of the module.
+pub struct ModuleLinker<'a, 'elf, A, I> {
+    elf: &'a Elf<'elf>,
+    address_of: A,
+    import: I,
+    policy: UndefinedPolicy,
+}
+
+impl<'a, 'elf, A, I> ModuleLinker<'a, 'elf, A, I>
+where
+    A: Fn(&Section) -> Option<u64>,
+    I: FnMut(&str) -> Option<u64>,
+{
+    pub fn new(elf: &'a Elf<'elf>, address_of: A, import: I) -> ModuleLinker<'a, 'elf, A, I> {
+        Self {
+            elf,
+            address_of,
+            import,
+            policy: UndefinedPolicy::Fail,
+        }
+    }
+
+    /// Set what undefined symbols which cannot be imported are bound to
+    pub fn with_undefined_policy(
+        mut self,
+        policy: UndefinedPolicy,
+    ) -> ModuleLinker<'a, 'elf, A, I> {
+        self.policy = policy;
+        self
+    }
+
+    #[inline]
+    pub fn elf(&self) -> &'a Elf<'elf> {
+        self.elf
+    }
+
+    fn symbols(&self) -> Table<'elf, Sym> {
+        symbols(self.elf)
+    }
+
+    fn symbol_name(&self, sym: &Sym) -> &'elf str {
+        symbol_name(self.elf, sym)
+    }
+
+    /// Returns the table of symbols this module exports under `policy`
+    pub fn export_table(&self, policy: ExportPolicy) -> ExportTable<'a, 'elf, &A> {
+        ExportTable::new(self.elf, &self.address_of, policy)
+    }
+
+    /// Returns the runtime address of the symbol at `index`
+    ///
+    /// Undefined symbols are imported, or bound to the stub according to the
+    /// [`UndefinedPolicy`]. Common symbols must have been allocated by the linker that
+    /// produced the module, and are never resolved.
+    pub fn symbol_address(&mut self, index: u32) -> Option<u64> {
+        let sym = self.symbols().get(index as usize)?;
+
+        match sym.section_index() {
+            SHN_UNDEF => {
+                let name = self.symbol_name(&sym);
+                (self.import)(name).or_else(|| self.policy.stub_for(sym.binding()))
+            }
+            SHN_ABS => Some(sym.value()),
+            SHN_COMMON => None,
+            shndx => {
+                let base = (self.address_of)(&self.elf.section(shndx)?)?;
+                Some(base.wrapping_add(sym.value()))
+            }
+        }
+    }
+
+    /// Apply the relocations of every section which has been given an address
+    ///
+    /// `image` holds the memory the module was laid out in, starting at the runtime address
+    /// `image_base`. Each allocated section which has an address, and then each undefined
+    /// symbol, is reported to `on_event` before any relocations are applied. Undefined
+    /// symbols which cannot be imported are bound to the stub if the policy allows it.
+    ///
+    /// See [`link_with_imports()`](ModuleLinker::link_with_imports).
+    #[cfg(feature = "alloc")]
+    pub fn link<F>(
+        &mut self,
+        image: &mut [u8],
+        image_base: u64,
+        on_event: F,
+    ) -> Result<(), RelocError>
+    where
+        F: FnMut(LinkEvent<'elf>),
+    {
+        let mut imports = alloc::vec![None; self.symbols().len()];
+        self.link_with_imports(&mut imports, image, image_base, on_event)
+    }
+
+    /// Apply the relocations of every section which has been given an address, recording
+    /// the address of each undefined symbol in `imports`
+    ///
+    /// `import` is called once for each undefined symbol, and the address it is bound to is
+    /// stored at the symbol's index in `imports`. Returns an error if a relocation section
+    /// against a section with an address is malformed, or the file's class or byte order is
+    /// not supported.
+    ///
+    /// See [`link()`](ModuleLinker::link).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `imports` has fewer entries than the symbol table.
+    pub fn link_with_imports<F>(
+        &mut self,
+        imports: &mut [Option<u64>],
+        image: &mut [u8],
+        image_base: u64,
+        mut on_event: F,
+    ) -> Result<(), RelocError>
+    where
+        F: FnMut(LinkEvent<'elf>),
+    {
+        let elf = self.elf;
+        assert!(imports.len() >= self.symbols().len());
+
+        for sect in elf.sections().filter(|sect| sect.flags().alloc()) {
+            if let Some(start) = (self.address_of)(&sect) {
+                on_event(LinkEvent::Mapped {
+                    section: sect.index(),
+                    range: start..start.wrapping_add(sect.size() as u64),
+                });
+            }
+        }
+
+        for (index, sym) in self.symbols().iter().enumerate().skip(1) {
+            if sym.section_index() != SHN_UNDEF {
+                continue;
+            }
+
+            let name = self.symbol_name(&sym);
+            if let Some(address) = (self.import)(name) {
+                imports[index] = Some(address);
+                on_event(LinkEvent::Imported {
+                    index: index as u32,
+                    name,
+                    address,
+                });
+                continue;
+            }
+
+            match self.policy.stub_for(sym.binding()) {
+                Some(stub) => {
+                    imports[index] = Some(stub);
+                    on_event(LinkEvent::Stubbed(StubbedSymbol {
+                        index: index as u32,
+                        name,
+                        binding: sym.binding(),
+                    }));
+                }
+                None => return Err(RelocError::UndefinedSymbol(index as u32)),
+            }
+        }
+
+        for sect in elf.sections() {
+            let place_base = match (self.address_of)(&sect) {
+                Some(address) => address,
+                None => continue,
+            };
+            let relas = elf
+                .relas_for_section(sect.index())
+                .map_err(RelocError::Malformed)?;
+
+            let mut count = 0;
+            for rel in relas.sections() {
+                let resolve = |index: u32| match imports.get(index as usize) {
+                    Some(Some(address)) => Some(*address),
+                    _ => self.symbol_address(index),
+                };
+                let relocation = RelocationSection {
+                    elf,
+                    rel: &rel,
+                    target: sect.index(),
+                    place_base,
+                };
+                count += relocation.apply(resolve, image, image_base)?;
+            }
+
+            if count != 0 {
+                on_event(LinkEvent::Relocated {
+                    section: sect.index(),
+                    count,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An `SHT_RELA` section of a module, to be applied in place
+struct RelocationSection<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    rel: &'a Section<'a, 'elf>,
+    target: u16,
+    place_base: u64,
+}
+
+impl RelocationSection<'_, '_> {
+    /// Apply the entries, read in the file's byte order, returning how many there were
+    fn apply<R>(&self, resolve: R, image: &mut [u8], image_base: u64) -> Result<usize, RelocError>
+    where
+        R: FnMut(u32) -> Option<u64>,
+    {
+        // Check the machine, class, and byte order against the file header
+        Relocator::<_, LittleEndian>::for_file(self.elf, 0, |_| None)?;
+
+        let data = self.rel.file_data().unwrap_or_default();
+        let encoding = Encoding::of(self.elf);
+        match (encoding.bits32, encoding.big_endian) {
+            (false, false) => self.apply_in::<LittleEndian, R>(data, resolve, image, image_base),
+            (false, true) => self.apply_in::<BigEndian, R>(data, resolve, image, image_base),
+            _ => Err(RelocError::Incompatible {
+                machine: self.elf.machine(),
+                class: self.elf.class(),
+                data: self.elf.data(),
+            }),
+        }
+    }
+
+    fn apply_in<E: Endian, R>(
+        &self,
+        data: &[u8],
+        resolve: R,
+        image: &mut [u8],
+        image_base: u64,
+    ) -> Result<usize, RelocError>
+    where
+        R: FnMut(u32) -> Option<u64>,
+    {
+        let relas: &[Rela<E>] = slice_from_bytes(data);
+        let mut relocator = Relocator::<R, E>::new(self.elf.machine(), 0, resolve);
+        relocator.set_target_data(self.elf.data());
+        relocator.set_section(Some(self.target));
+        relocator.apply(relas, self.place_base, image, image_base)?;
+        Ok(relas.len())
+    }
+}
+
+/// The bookkeeping of a loaded module, as needed to unload or reload it
+///
+/// Built from the [`LinkEvent`]s reported by [`ModuleLinker::link()`], the module's
+/// [`ExportTable`], and its [`ModInfo`](crate::ModInfo).
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModuleRecord {
+    /// The name the module declares in its metadata
+    pub name: Option<alloc::string::String>,
+    /// The modules named as dependencies in the module's metadata
+    pub dependencies: alloc::vec::Vec<alloc::string::String>,
+    /// The sections of the module and the memory they occupy
+    pub mapped: alloc::vec::Vec<(u16, Range<u64>)>,
+    /// Imported symbols and the addresses they were bound to
+    pub imports: alloc::vec::Vec<(alloc::string::String, u64)>,
+    /// Symbols which were bound to the stub
+    pub stubs: alloc::vec::Vec<alloc::string::String>,
+    /// Sections whose contents were modified by relocations
+    pub relocated: alloc::vec::Vec<u16>,
+    /// Exported symbols and their addresses
+    pub exports: alloc::vec::Vec<(alloc::string::String, u64)>,
+}
+
+#[cfg(feature = "alloc")]
+impl ModuleRecord {
+    pub fn new() -> ModuleRecord {
+        Self::default()
+    }
+
+    /// Record a step of linking the module
+    pub fn record(&mut self, event: LinkEvent<'_>) {
+        use alloc::string::ToString;
+
+        match event {
+            LinkEvent::Mapped { section, range } => self.mapped.push((section, range)),
+            LinkEvent::Imported { name, address, .. } => {
+                self.imports.push((name.to_string(), address))
+            }
+            LinkEvent::Stubbed(sym) => self.stubs.push(sym.name.to_string()),
+            LinkEvent::Relocated { section, .. } => self.relocated.push(section),
+        }
+    }
+
+    /// Record the name and dependencies the module declares in its metadata
+    pub fn record_modinfo(&mut self, info: &crate::ModInfo<'_>) {
+        use alloc::string::ToString;
+
+        self.name = info.name().map(str::to_string);
+        self.dependencies = info.dependencies().map(str::to_string).collect();
+    }
+
+    /// Record the symbols the module exports
+    pub fn record_exports<A>(&mut self, table: &ExportTable<'_, '_, A>)
+    where
+        A: Fn(&Section) -> Option<u64>,
+    {
+        use alloc::string::ToString;
+
+        self.exports.extend(
+            table
+                .exports()
+                .map(|export| (export.name.to_string(), export.address)),
+        );
+    }
+
+    /// Returns `true` if `address` lies within memory occupied by the module
+    pub fn contains(&self, address: u64) -> bool {
+        self.mapped
+            .iter()
+            .any(|(_, range)| range.contains(&address))
+    }
+
+    /// Returns `true` if this module imports any symbol from `other`
+    ///
+    /// A module must not be unloaded while another module depends on it.
+    pub fn depends_on(&self, other: &ModuleRecord) -> bool {
+        self.imports
+            .iter()
+            .any(|(_, address)| other.contains(*address))
+    }
+
+    /// Returns an iterator over the ranges of memory to unmap when unloading the module
+    pub fn ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.mapped
+            .iter()
+            .map(|(_, range)| range.clone())
+            .filter(|range| !range.is_empty())
+    }
+
+    /// Restore the relocated sections of the module to their contents in `elf`
+    ///
+    /// Afterwards the module can be linked again in place, for example against a reloaded
+    /// dependency. `image` and `image_base` are as passed to [`ModuleLinker::link()`].
+    pub fn revert(
+        &self,
+        elf: &Elf,
+        image: &mut [u8],
+        image_base: u64,
+    ) -> Result<(), crate::ParseError> {
+        use crate::{ParseError, Structure};
+
+        for &section in &self.relocated {
+            let structure = Structure::Section(section);
+            let sect = elf.section(section).ok_or(ParseError::new(
+                structure,
+                0,
+                "section index out of range",
+            ))?;
+            let error = |message| ParseError::new(structure, sect.file_offset() as u64, message);
+
+            let data = sect.file_data().ok_or(error("section out of bounds"))?;
+            let (_, range) = self
+                .mapped
+                .iter()
+                .find(|(index, _)| *index == section)
+                .ok_or(error("section was not mapped"))?;
+            let offset = range
+                .start
+                .checked_sub(image_base)
+                .and_then(|offset| usize::try_from(offset).ok())
+                .ok_or(error("section outside of image"))?;
+
+            image
+                .get_mut(offset..)
+                .and_then(|image| image.get_mut(..sect.size()))
+                .ok_or(error("section outside of image"))?
+                .copy_from_slice(data);
+        }
+
+        Ok(())
+    }
+}
+
+/// A dependency of a module which no candidate module provides
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MissingDependency<'elf> {
+    /// Index of the dependent module among the candidates
+    pub module: usize,
+    /// Name of the module depended on
+    pub name: &'elf str,
+}
+
+/// Why a set of modules could not be ordered for loading
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LoadOrderError<'elf> {
+    /// These dependencies are neither candidates nor already loaded
+    Missing(alloc::vec::Vec<MissingDependency<'elf>>),
+    /// More than one candidate declares this name
+    Duplicate(&'elf str),
+    /// These candidates could not be ordered, as they are part of or depend on a cycle
+    Cycle(alloc::vec::Vec<usize>),
+}
+
+/// Order a set of candidate modules so that each is loaded after the modules it depends on
+///
+/// Dependencies are matched against the names the candidates declare in their
+/// [`ModInfo`](crate::ModInfo), and dependencies for which `loaded` returns `true` are
+/// taken to be satisfied already. Returns the indices of the candidates in the order to
+/// load them. Where the dependencies allow it, candidates keep their relative order.
+///
+/// Every missing dependency is reported at once, so that they can all be fixed together.
+#[cfg(feature = "alloc")]
+pub fn load_order<'elf, F>(
+    modules: &[crate::ModInfo<'elf>],
+    mut loaded: F,
+) -> Result<alloc::vec::Vec<usize>, LoadOrderError<'elf>>
+where
+    F: FnMut(&str) -> bool,
+{
+    use alloc::vec::Vec;
+
+    let names: Vec<Option<&str>> = modules.iter().map(|info| info.name()).collect();
+    for (i, name) in names.iter().enumerate() {
+        if let Some(name) = name {
+            if names[..i].contains(&Some(name)) {
+                return Err(LoadOrderError::Duplicate(name));
+            }
+        }
+    }
+    let provider = |dep: &str| names.iter().position(|&name| name == Some(dep));
+
+    // The candidates each module waits on
+    let mut missing = Vec::new();
+    let mut waits_on: Vec<Vec<usize>> = Vec::with_capacity(modules.len());
+    for (module, info) in modules.iter().enumerate() {
+        let mut deps = Vec::new();
+        for name in info.dependencies() {
+            match provider(name) {
+                Some(dep) => deps.push(dep),
+                None if loaded(name) => {}
+                None => missing.push(MissingDependency { module, name }),
+            }
+        }
+        waits_on.push(deps);
+    }
+    if !missing.is_empty() {
+        return Err(LoadOrderError::Missing(missing));
+    }
+
+    let mut order = Vec::with_capacity(modules.len());
+    let mut done = alloc::vec![false; modules.len()];
+    while order.len() < modules.len() {
+        let next =
+            (0..modules.len()).find(|&i| !done[i] && waits_on[i].iter().all(|&dep| done[dep]));
+        match next {
+            Some(i) => {
+                done[i] = true;
+                order.push(i);
+            }
+            None => {
+                let cycle = (0..modules.len()).filter(|&i| !done[i]).collect();
+                return Err(LoadOrderError::Cycle(cycle));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+/// Which defined global and weak symbols of a module are exported
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ExportPolicy {
+    /// Symbols of any visibility
+    All,
+    /// Symbols with default or protected visibility
+    #[default]
+    Visible,
+    /// Symbols with default visibility
+    Default,
+}
+
+impl ExportPolicy {
+    pub const fn allows(self, visibility: Visibility) -> bool {
+        match self {
+            Self::All => true,
+            Self::Visible => matches!(visibility, Visibility::Default | Visibility::Protected),
+            Self::Default => matches!(visibility, Visibility::Default),
+        }
+    }
+}
+
+/// A symbol exported by a loaded module
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Export<'elf> {
+    pub name: &'elf str,
+    /// Runtime address of the symbol
+    pub address: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+    pub binding: Binding,
+}
+
+/// The symbols a loaded module makes available to modules loaded after it
+///
+/// Lookups scan the module's symbol table, so the table needs no storage of its own.
+/// [`resolve()`](ExportTable::resolve) can be used as the `import` function of a
+/// [`ModuleLinker`] for a dependent module.
+#[derive(Clone, Copy)]
+pub struct ExportTable<'a, 'elf, A> {
+    elf: &'a Elf<'elf>,
+    address_of: A,
+    policy: ExportPolicy,
+}
+
+impl<'a, 'elf, A> ExportTable<'a, 'elf, A>
+where
+    A: Fn(&Section) -> Option<u64>,
+{
+    /// Create the export table of a module whose sections were placed by `address_of`
+    pub fn new(
+        elf: &'a Elf<'elf>,
+        address_of: A,
+        policy: ExportPolicy,
+    ) -> ExportTable<'a, 'elf, A> {
+        Self {
+            elf,
+            address_of,
+            policy,
+        }
+    }
+
+    /// Returns an iterator over the exported symbols
+    ///
+    /// Symbols in sections which were not given an address are not exported.
+    pub fn exports(&self) -> impl Iterator<Item = Export<'elf>> + '_ {
+        symbols(self.elf).iter().filter_map(move |sym| {
+            if !matches!(sym.binding(), Binding::Global | Binding::Weak)
+                || !self.policy.allows(sym.visibility())
+                || matches!(sym.kind(), SymbolKind::Section | SymbolKind::File)
+            {
+                return None;
+            }
+
+            let address = match sym.section_index() {
+                SHN_UNDEF | SHN_COMMON => return None,
+                SHN_ABS => sym.value(),
+                shndx => (self.address_of)(&self.elf.section(shndx)?)?.wrapping_add(sym.value()),
+            };
+
+            Some(Export {
+                name: symbol_name(self.elf, &sym),
+                address,
+                size: sym.size(),
+                kind: sym.kind(),
+                binding: sym.binding(),
+            })
+        })
+    }
+
+    /// Returns the exported symbol named `name`
+    pub fn get(&self, name: &str) -> Option<Export<'elf>> {
+        self.exports().find(|export| export.name == name)
+    }
+
+    /// Returns the address of the exported symbol named `name`
+    #[inline]
+    pub fn resolve(&self, name: &str) -> Option<u64> {
+        self.get(name).map(|export| export.address)
+    }
+}
+
+fn symbols<'elf>(elf: &Elf<'elf>) -> Table<'elf, Sym> {
+    elf.symtab_section()
+        .and_then(|shdr| Table::from_section(&shdr).ok())
+        .unwrap_or_default()
+}
+
+fn symbol_name<'elf>(elf: &Elf<'elf>, sym: &Sym) -> &'elf str {
+    elf.string_table()
+        .and_then(|strtab| strtab.get_string(sym.name_index()))
+        .unwrap_or("")
+}
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use super::{LinkEvent, ModuleLinker};
+    use crate::{testdata::Fixture, testdata::POINTER_COUNT, Elf, RelocError};
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+
+    /// Link `file` with only `.data` given an address, of zero, returning the image
+    fn link(file: &[u8], import: impl FnMut(&str) -> Option<u64>) -> Result<Vec<u8>, RelocError> {
+        let elf = Elf::new(file).unwrap();
+        let mut image = alloc::vec![0; file.len()];
+        let mut relocated = 0;
+        ModuleLinker::new(&elf, |sect| (sect.index() == 2).then_some(0), import).link(
+            &mut image,
+            0,
+            |event| {
+                if let LinkEvent::Relocated { section, count } = event {
+                    assert_eq!(section, 2);
+                    relocated += count;
+                }
+            },
+        )?;
+        assert_eq!(relocated, POINTER_COUNT);
+        Ok(image)
+    }
+
+    #[test]
+    fn big_endian_module_is_relocated() {
+        let file = Fixture::new().big_endian().relocating_data().build();
+        let elf = Elf::new(&file).unwrap();
+        let text = elf.section(1).unwrap().addr();
+        let data = elf.section(2).unwrap().file_offset();
+
+        let image = link(&file, |_| None).unwrap();
+        for i in 0..POINTER_COUNT {
+            let word = &image[data + i * 8..][..8];
+            assert_eq!(
+                u64::from_be_bytes(word.try_into().unwrap()),
+                text + i as u64
+            );
+        }
+    }
+
+    #[test]
+    fn elf32_module_is_incompatible() {
+        let file = Fixture::new().elf32().relocating_data().build();
+        let error = link(&file, |_| None).unwrap_err();
+        assert!(matches!(error, RelocError::Incompatible { .. }));
+    }
+
+    #[test]
+    fn undefined_symbols_are_imported_once() {
+        let mut file = Fixture::new().relocating_data().build();
+        let elf = Elf::new(&file).unwrap();
+        let text = elf.section(1).unwrap().addr();
+        let data = elf.section(2).unwrap().file_offset();
+        let rela = elf.section(3).unwrap().file_offset();
+        let symtab = elf.section(6).unwrap().file_offset();
+
+        // Make `pointers` undefined, and relocate every pointer against it with R_X86_64_64
+        file[symtab + 2 * 24 + 6..][..2].copy_from_slice(&0u16.to_le_bytes());
+        for i in 0..POINTER_COUNT {
+            let info = (2 << 32) | 1u64;
+            file[rela + i * 24 + 8..][..8].copy_from_slice(&info.to_le_bytes());
+        }
+
+        let calls = Cell::new(0);
+        let image = link(&file, |name| {
+            calls.set(calls.get() + 1);
+            (name == "pointers").then_some(0x1000)
+        })
+        .unwrap();
+        assert_eq!(calls.get(), 1);
+        for i in 0..POINTER_COUNT {
+            let word = &image[data + i * 8..][..8];
+            assert_eq!(
+                u64::from_le_bytes(word.try_into().unwrap()),
+                0x1000 + text + i as u64
+            );
+        }
+    }
+}
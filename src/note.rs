@@ -0,0 +1,259 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+use crate::{Data, Elf, Section, SectionType, Segment, SegmentKind};
+use core::fmt;
+
+pub const NT_GNU_ABI_TAG: u32 = 1;
+pub const NT_GNU_HWCAP: u32 = 2;
+pub const NT_GNU_BUILD_ID: u32 = 3;
+pub const NT_GNU_GOLD_VERSION: u32 = 4;
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+
+/// An entry of a note section or segment
+#[derive(Clone, Copy)]
+pub struct Note<'elf> {
+    kind: u32,
+    name: &'elf [u8],
+    desc: &'elf [u8],
+}
+
+impl<'elf> Note<'elf> {
+    /// Returns the type of the note, which is interpreted relative to its owner
+    #[inline]
+    pub const fn kind(&self) -> u32 {
+        self.kind
+    }
+
+    /// Returns the name of the note's owner, without the terminating NUL
+    #[inline]
+    pub const fn name(&self) -> &'elf [u8] {
+        self.name
+    }
+
+    /// Returns the note's descriptor
+    #[inline]
+    pub const fn desc(&self) -> &'elf [u8] {
+        self.desc
+    }
+
+    /// Returns `true` if this note has the given owner and type
+    #[inline]
+    pub fn is(&self, name: &[u8], kind: u32) -> bool {
+        self.name == name && self.kind == kind
+    }
+}
+
+impl fmt::Debug for Note<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Note")
+            .field(
+                "name",
+                &core::str::from_utf8(self.name).unwrap_or("<invalid>"),
+            )
+            .field("kind", &self.kind)
+            .field("desc_size", &self.desc.len())
+            .finish()
+    }
+}
+
+/// Iterator over the notes stored in a single section or segment
+///
+/// Iteration stops at the first malformed note.
+#[derive(Clone, Debug)]
+pub struct NoteIter<'elf> {
+    data: &'elf [u8],
+    align: usize,
+    big_endian: bool,
+}
+
+impl<'elf> NoteIter<'elf> {
+    /// Create an iterator over the notes in `data`
+    ///
+    /// `align` is the alignment of the containing section or segment; notes are padded
+    /// to 8 bytes when it is 8, and to 4 bytes otherwise.
+    pub fn new(data: &'elf [u8], align: u64) -> NoteIter<'elf> {
+        Self {
+            data,
+            align: if align == 8 { 8 } else { 4 },
+            big_endian: false,
+        }
+    }
+
+    /// Read the note headers in big-endian byte order
+    pub fn with_big_endian(mut self, big_endian: bool) -> NoteIter<'elf> {
+        self.big_endian = big_endian;
+        self
+    }
+}
+
+impl<'elf> Iterator for NoteIter<'elf> {
+    type Item = Note<'elf>;
+
+    fn next(&mut self) -> Option<Note<'elf>> {
+        let word = |i: usize| -> Option<u32> {
+            let word = self.data.get(i..i + 4)?.try_into().ok()?;
+            Some(match self.big_endian {
+                true => u32::from_be_bytes(word),
+                false => u32::from_le_bytes(word),
+            })
+        };
+        let align_up = |x: usize| x.checked_add(self.align - 1).map(|x| x & !(self.align - 1));
+
+        let result = (|| {
+            let name_size = word(0)? as usize;
+            let desc_size = word(4)? as usize;
+            let kind = word(8)?;
+
+            let name_start: usize = 12;
+            let desc_start = align_up(name_start.checked_add(name_size)?)?;
+            let end = align_up(desc_start.checked_add(desc_size)?)?;
+
+            let name = self.data.get(name_start..name_start + name_size)?;
+            let desc = self.data.get(desc_start..desc_start + desc_size)?;
+            let name = match name.split_last() {
+                Some((0, name)) => name,
+                _ => name,
+            };
+
+            Some((Note { kind, name, desc }, end.min(self.data.len())))
+        })();
+
+        match result {
+            Some((note, end)) => {
+                self.data = &self.data[end..];
+                Some(note)
+            }
+            None => {
+                self.data = &[];
+                None
+            }
+        }
+    }
+}
+
+/// Returns the size of a 4-byte aligned note with the given name and descriptor sizes
+pub const fn note_size(name_len: usize, desc_len: usize) -> usize {
+    12 + (name_len + 1).next_multiple_of(4) + desc_len.next_multiple_of(4)
+}
+
+/// Write a 4-byte aligned note to the start of `buf`, returning the number of bytes written
+///
+/// `name` is written with a terminating NUL. The descriptor is written by `write_desc`,
+/// which receives a zeroed buffer of `desc_len` bytes. Returns `None` if `buf` is too small.
+pub fn write_note<F>(
+    buf: &mut [u8],
+    name: &[u8],
+    kind: u32,
+    desc_len: usize,
+    write_desc: F,
+) -> Option<usize>
+where
+    F: FnOnce(&mut [u8]),
+{
+    let size = note_size(name.len(), desc_len);
+    let buf = buf.get_mut(..size)?;
+    let name_size = u32::try_from(name.len() + 1).ok()?;
+    let desc_size = u32::try_from(desc_len).ok()?;
+
+    buf.fill(0);
+    buf[0..4].copy_from_slice(&name_size.to_le_bytes());
+    buf[4..8].copy_from_slice(&desc_size.to_le_bytes());
+    buf[8..12].copy_from_slice(&kind.to_le_bytes());
+    buf[12..][..name.len()].copy_from_slice(name);
+
+    let desc_start = 12 + (name.len() + 1).next_multiple_of(4);
+    write_desc(&mut buf[desc_start..][..desc_len]);
+
+    Some(size)
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over every note in the file
+    ///
+    /// Notes are read from `SHT_NOTE` sections, or from `PT_NOTE` segments if the file has
+    /// no section headers.
+    pub fn notes(&self) -> impl Iterator<Item = Note<'elf>> + '_ {
+        let use_sections = self.shdr_num() != 0;
+
+        let sections = self
+            .sections()
+            .filter(move |_| use_sections)
+            .flat_map(|s| s.notes());
+        let segments = self
+            .segments()
+            .filter(move |_| !use_sections)
+            .flat_map(|s| s.notes());
+
+        sections.chain(segments)
+    }
+
+    /// Returns the first note with the given owner and type
+    pub fn find_note(&self, name: &[u8], kind: u32) -> Option<Note<'elf>> {
+        self.notes().find(|note| note.is(name, kind))
+    }
+
+    /// Returns the contents of the GNU build ID note, if there is one
+    pub fn build_id(&self) -> Option<&'elf [u8]> {
+        Some(self.find_note(b"GNU", NT_GNU_BUILD_ID)?.desc())
+    }
+}
+
+impl<'elf> Section<'_, 'elf> {
+    /// Returns an iterator over the notes in this section
+    ///
+    /// The iterator is empty unless this is an `SHT_NOTE` section whose contents lie within
+    /// the file.
+    pub fn notes(&self) -> NoteIter<'elf> {
+        match self.section_type() {
+            SectionType::Note => {
+                NoteIter::new(self.file_data().unwrap_or_default(), self.addr_align())
+                    .with_big_endian(self.elf().data() == Data::TwosCompBig)
+            }
+            _ => NoteIter::new(&[], 4),
+        }
+    }
+}
+
+impl<'elf> Segment<'_, 'elf> {
+    /// Returns an iterator over the notes in this segment
+    ///
+    /// The iterator is empty unless this is a `PT_NOTE` segment whose contents lie within
+    /// the file.
+    pub fn notes(&self) -> NoteIter<'elf> {
+        match self.kind() {
+            SegmentKind::Note => {
+                NoteIter::new(self.file_data().unwrap_or_default(), self.alignment())
+                    .with_big_endian(self.elf().data() == Data::TwosCompBig)
+            }
+            _ => NoteIter::new(&[], 4),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Files which own their contents
+
+use crate::{Elf, ParseError};
+use alloc::boxed::Box;
+use core::{fmt, mem::MaybeUninit, ptr::NonNull};
+
+/// A parsed ELF file which owns the buffer holding it
+///
+/// This can be returned from the function which read the file, unlike [`Elf`], which
+/// borrows its contents. The file is accessed through [`elf()`](ElfOwned::elf).
+pub struct ElfOwned {
+    /// Borrows from `data`, which outlives it
+    ///
+    /// This is always initialized. Wrapping it in `MaybeUninit` stops the borrow from being
+    /// treated as valid for as long as `self` is passed by value, which would forbid freeing
+    /// the contents when `self` is dropped inside such a function.
+    elf: MaybeUninit<Elf<'static>>,
+    /// The contents of the file, allocated by a `Box<[u8]>`
+    ///
+    /// This is kept as a pointer rather than a `Box`, since moving a `Box` would assert
+    /// unique access to the contents which `elf` borrows.
+    data: NonNull<[u8]>,
+}
+
+// SAFETY: The contents are never modified, and `Elf` is `Send` and `Sync`.
+unsafe impl Send for ElfOwned {}
+unsafe impl Sync for ElfOwned {}
+
+impl ElfOwned {
+    /// Parse a file, taking ownership of its contents
+    pub fn new(data: impl Into<Box<[u8]>>) -> Result<ElfOwned, ParseError> {
+        let data = NonNull::from(Box::leak(data.into()));
+
+        // SAFETY: The contents are freed only when `self` is dropped, after which the
+        // borrow cannot be observed, since `elf()` ties it to the lifetime of `self`.
+        match Elf::new(unsafe { data.as_ref() }) {
+            Ok(elf) => Ok(ElfOwned {
+                elf: MaybeUninit::new(elf),
+                data,
+            }),
+            Err(error) => {
+                // SAFETY: `data` was allocated by a `Box`, and `elf` was never created.
+                drop(unsafe { Box::from_raw(data.as_ptr()) });
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns the parsed file
+    #[inline]
+    pub fn elf(&self) -> &Elf<'_> {
+        // SAFETY: `elf` is always initialized.
+        unsafe { self.elf.assume_init_ref() }
+    }
+
+    /// Returns the contents of the file
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        // SAFETY: The contents live until `self` is dropped.
+        unsafe { self.data.as_ref() }
+    }
+
+    /// Returns the buffer holding the file, discarding the parsed view
+    pub fn into_inner(self) -> Box<[u8]> {
+        let data = self.data;
+        core::mem::forget(self);
+        // SAFETY: `data` was allocated by a `Box`, and `self` will not free it.
+        unsafe { Box::from_raw(data.as_ptr()) }
+    }
+}
+
+impl Drop for ElfOwned {
+    fn drop(&mut self) {
+        // SAFETY: `data` was allocated by a `Box`, and is no longer borrowed by `elf`.
+        drop(unsafe { Box::from_raw(self.data.as_ptr()) });
+    }
+}
+
+impl fmt::Debug for ElfOwned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ElfOwned").field(self.elf()).finish()
+    }
+}
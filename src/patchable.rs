@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Patchable function entries
+
+use crate::{
+    backend_for,
+    table::{Encoding, Table},
+    Elf, ElfType, ParseError, Rel, Rela, Section, SectionType, Sym, SymbolKind,
+};
+use core::ops::Range;
+
+/// The name of the section listing patchable function entries
+pub const PATCHABLE_FUNCTION_ENTRIES: &str = "__patchable_function_entries";
+
+/// A NOP pad emitted by `-fpatchable-function-entry`, which can be patched at runtime
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PatchSite<'elf> {
+    /// Address of the first NOP in the pad
+    ///
+    /// In relocatable files this is the offset of the pad within [`section`].
+    ///
+    /// [`section`]: PatchSite::section
+    pub address: u64,
+    /// Index of the section containing the pad, if known
+    pub section: Option<u16>,
+    /// Index of the function's symbol in the symbol table, if found
+    pub symbol: Option<usize>,
+    /// Name of the function, if found
+    pub name: Option<&'elf str>,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over the patchable function entries
+    ///
+    /// Each entry is matched to the nearest function symbol at or after the start of the
+    /// pad, since a pad may begin before the function's entry point. Sections whose
+    /// relocations are malformed are skipped.
+    pub fn patch_sites(&self) -> impl Iterator<Item = PatchSite<'elf>> + '_ {
+        self.sections_in_link_order(PATCHABLE_FUNCTION_ENTRIES)
+            .filter_map(move |sect| Some((self.relas_for_section(sect.index()).ok()?, sect)))
+            .flat_map(move |(relas, sect)| {
+                let section = sect.link_order_section().map(|text| text.index());
+                let encoding = Encoding::of(self);
+                let word_size = encoding.word_size();
+
+                sect.file_data()
+                    .unwrap_or_default()
+                    .chunks_exact(word_size)
+                    .enumerate()
+                    .map(move |(i, entry)| {
+                        let raw = encoding.read_word(entry).unwrap_or(0);
+                        let offset = (i * word_size) as u64;
+                        let (address, reloc_section) = self.relocated_address(relas, offset, raw);
+                        let section = reloc_section.or(section);
+                        let (symbol, name) = self.patch_site_function(address, section);
+
+                        PatchSite {
+                            address,
+                            section,
+                            symbol,
+                            name,
+                        }
+                    })
+            })
+    }
+
+    fn patch_site_function(
+        &self,
+        address: u64,
+        section: Option<u16>,
+    ) -> (Option<usize>, Option<&'elf str>) {
+        let is_relocatable = self.file_type() == ElfType::Rel;
+        let strtab = self.string_table();
+
+        let found = self.symtab().and_then(|symtab| {
+            symtab
+                .enumerate()
+                .filter(|(_, sym)| {
+                    sym.kind() == SymbolKind::Func
+                        && sym.value() >= address
+                        && (!is_relocatable || Some(sym.section_index()) == section)
+                })
+                .min_by_key(|(_, sym)| sym.value())
+        });
+
+        match found {
+            Some((index, sym)) => (
+                Some(index),
+                strtab.and_then(|strtab| strtab.get_string(sym.name_index())),
+            ),
+            None => (None, None),
+        }
+    }
+}
+
+/// How a patch point is known to lie on an instruction boundary
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PatchBoundary {
+    /// The patch begins at the entry point of a function
+    FunctionEntry,
+    /// The patch begins at a patchable function entry
+    PatchableEntry,
+}
+
+/// A patch point accepted by [`Elf::check_patch_point()`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PatchPoint<'elf> {
+    /// Index of the section containing the patch
+    pub section: u16,
+    /// Index of the function's symbol in the symbol table
+    pub symbol: usize,
+    pub name: Option<&'elf str>,
+    /// Addresses which may be patched, from the start of the patchable entry or the
+    /// function to the end of the function
+    pub function: Range<u64>,
+    pub boundary: PatchBoundary,
+}
+
+/// A reason a proposed patch point is unsafe to write
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PatchPointError {
+    /// The address does not lie in an executable section, or no section was given for a
+    /// relocatable file
+    NoSection,
+    /// The address is not inside any function symbol
+    NotInFunction,
+    /// The patch extends past the end of its function, at this address
+    CrossesFunctionEnd(u64),
+    /// The patch does not begin at a function entry or a patchable function entry
+    NotAtBoundary,
+    /// The patch overlaps a field written by the relocation at this offset
+    Relocation(u64),
+    /// A relocation table could not be read
+    Malformed(ParseError),
+}
+
+impl<'elf> Elf<'elf> {
+    /// Check that `len` bytes at `address` may be overwritten by a live patch
+    ///
+    /// In relocatable files `address` is an offset within `section`, which must be given;
+    /// otherwise it is a virtual address, and `section` defaults to the executable section
+    /// containing it.
+    ///
+    /// The patch must begin at the entry point of a function or at a patchable function
+    /// entry, since these are the only instruction boundaries known without disassembling
+    /// the code. It must end within the same function, and must not overlap any field
+    /// patched by a relocation.
+    pub fn check_patch_point(
+        &self,
+        section: Option<u16>,
+        address: u64,
+        len: u64,
+    ) -> Result<PatchPoint<'elf>, PatchPointError> {
+        let is_relocatable = self.file_type() == ElfType::Rel;
+        let section = match section {
+            Some(index) => self.section(index),
+            None if is_relocatable => None,
+            None => self.sections().find(|sect| {
+                sect.flags().alloc()
+                    && (sect.addr()..sect.addr().saturating_add(sect.size() as u64))
+                        .contains(&address)
+            }),
+        };
+        let section = section
+            .filter(|sect| sect.flags().execinstr())
+            .ok_or(PatchPointError::NoSection)?;
+        let end = address.saturating_add(len);
+
+        let site = self.patch_sites().find(|site| {
+            site.address == address && (!is_relocatable || site.section == Some(section.index()))
+        });
+        let in_section = |sym: &Sym| !is_relocatable || sym.section_index() == section.index();
+
+        let (symbol, boundary) = match site.and_then(|site| site.symbol) {
+            Some(symbol) => (symbol, PatchBoundary::PatchableEntry),
+            None => {
+                let (symbol, sym) = self
+                    .symtab()
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                    .find(|(_, sym)| {
+                        sym.kind() == SymbolKind::Func
+                            && in_section(sym)
+                            && (sym.value()..sym.value().saturating_add(sym.size()))
+                                .contains(&address)
+                    })
+                    .ok_or(PatchPointError::NotInFunction)?;
+                if sym.value() != address {
+                    return Err(PatchPointError::NotAtBoundary);
+                }
+                (symbol, PatchBoundary::FunctionEntry)
+            }
+        };
+
+        let sym = self
+            .symtab()
+            .and_then(|mut symtab| symtab.nth(symbol))
+            .ok_or(PatchPointError::NotInFunction)?;
+        let function_end = sym.value().saturating_add(sym.size());
+        if end > function_end {
+            return Err(PatchPointError::CrossesFunctionEnd(function_end));
+        }
+
+        self.check_patch_relocations(&section, address..end)?;
+
+        Ok(PatchPoint {
+            section: section.index(),
+            symbol,
+            name: self
+                .string_table()
+                .and_then(|strtab| strtab.get_string(sym.name_index())),
+            function: address..function_end,
+            boundary,
+        })
+    }
+
+    /// Check that no relocation which applies to `section` patches any of `range`
+    fn check_patch_relocations(
+        &self,
+        section: &Section,
+        range: Range<u64>,
+    ) -> Result<(), PatchPointError> {
+        let is_relocatable = self.file_type() == ElfType::Rel;
+        let backend = backend_for(self.machine());
+        let word_size = Encoding::of(self).word_size() as u64;
+
+        // Dynamic relocations are not tied to a section, and hold addresses.
+        let applies = |rel: &Section| {
+            rel.info() == section.index() as u32 || (!is_relocatable && rel.info() == 0)
+        };
+        let overlaps = |offset: u64, kind| {
+            let fields = backend.and_then(|backend| backend.fields(kind, offset));
+            match fields {
+                Some(fields) => fields.into_iter().any(|field| {
+                    field.address < range.end
+                        && range.start < field.address.saturating_add(field.size as u64)
+                }),
+                None => offset < range.end && range.start < offset.saturating_add(word_size),
+            }
+        };
+
+        for rel in self.sections().filter(applies) {
+            let hit = match rel.section_type() {
+                SectionType::Rel => Table::<Rel>::from_section(&rel)
+                    .map_err(PatchPointError::Malformed)?
+                    .iter()
+                    .map(|entry| (entry.offset(), entry.kind()))
+                    .find(|&(offset, kind)| overlaps(offset, kind)),
+                SectionType::Rela => Table::<Rela>::from_section(&rel)
+                    .map_err(PatchPointError::Malformed)?
+                    .iter()
+                    .map(|entry| (entry.offset(), entry.kind()))
+                    .find(|&(offset, kind)| overlaps(offset, kind)),
+                _ => None,
+            };
+            if let Some((offset, _)) = hit {
+                return Err(PatchPointError::Relocation(offset));
+            }
+        }
+
+        Ok(())
+    }
+}
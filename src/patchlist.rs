@@ -0,0 +1,317 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Pre-computed relocation patch lists
+//!
+//! A patch list captures everything needed to relocate a position-independent image to
+//! an arbitrary load bias, without parsing any ELF structures at load time. Lists are
+//! generated ahead of time (e.g. by a build tool) and serialized into a compact byte
+//! format suitable for embedding in a bootloader.
+
+use crate::{
+    slice_from_bytes,
+    table::{Encoding, Table},
+    BigEndian, Data, Elf, Endian, LittleEndian, PatchOp, Rela, RelocError, Relocator, Section,
+    SectionType, Sym, SHN_UNDEF,
+};
+
+/// How the value of a [`PatchEntry`] is computed at load time
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Formula {
+    /// The value does not depend on the load bias
+    Absolute,
+    /// The load bias is added to the value
+    Biased,
+}
+
+/// One write of a pre-computed relocation patch list
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PatchEntry {
+    /// Link-time virtual address of the storage unit
+    pub vaddr: u64,
+    pub value: u64,
+    /// Size of the storage unit, in bytes
+    pub size: u8,
+    pub formula: Formula,
+    /// Whether the value is stored most significant byte first
+    pub big_endian: bool,
+}
+
+impl PatchEntry {
+    /// Size of an encoded entry, in bytes
+    pub const ENCODED_SIZE: usize = 17;
+
+    /// Returns the value to store when the image is loaded with `bias`
+    #[inline]
+    pub const fn value_for(&self, bias: u64) -> u64 {
+        match self.formula {
+            Formula::Absolute => self.value,
+            Formula::Biased => self.value.wrapping_add(bias),
+        }
+    }
+
+    /// Encode the entry as `vaddr` and `value` (little-endian), followed by a byte
+    /// holding the size in the low nibble, the formula in the next three bits, and the byte
+    /// order of the storage unit in the top bit
+    pub const fn encode(&self) -> [u8; Self::ENCODED_SIZE] {
+        let mut buf = [0; Self::ENCODED_SIZE];
+        let vaddr = self.vaddr.to_le_bytes();
+        let value = self.value.to_le_bytes();
+        let mut i = 0;
+        while i < 8 {
+            buf[i] = vaddr[i];
+            buf[8 + i] = value[i];
+            i += 1;
+        }
+        let formula = match self.formula {
+            Formula::Absolute => 0,
+            Formula::Biased => 1,
+        };
+        buf[16] = (self.size & 0xf) | formula << 4 | (self.big_endian as u8) << 7;
+        buf
+    }
+
+    /// Decode an entry produced by [`encode()`](Self::encode)
+    pub fn decode(buf: &[u8]) -> Option<PatchEntry> {
+        let buf = buf.get(..Self::ENCODED_SIZE)?;
+        let size = buf[16] & 0xf;
+        let formula = match (buf[16] >> 4) & 0x7 {
+            0 => Formula::Absolute,
+            1 => Formula::Biased,
+            _ => return None,
+        };
+
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return None;
+        }
+
+        Some(PatchEntry {
+            vaddr: u64::from_le_bytes(buf[..8].try_into().ok()?),
+            value: u64::from_le_bytes(buf[8..16].try_into().ok()?),
+            size,
+            formula,
+            big_endian: buf[16] & 0x80 != 0,
+        })
+    }
+
+    /// Decode a serialized patch list, stopping at the first malformed entry
+    pub fn decode_all(buf: &[u8]) -> impl Iterator<Item = PatchEntry> + '_ {
+        buf.chunks_exact(Self::ENCODED_SIZE)
+            .map_while(PatchEntry::decode)
+    }
+}
+
+/// Apply a pre-computed patch list
+///
+/// `image` holds the loaded image, whose first byte has the link-time virtual address
+/// `image_vaddr`.
+pub fn apply_patch_list<I>(
+    entries: I,
+    image: &mut [u8],
+    image_vaddr: u64,
+    bias: u64,
+) -> Result<(), RelocError>
+where
+    I: IntoIterator<Item = PatchEntry>,
+{
+    for entry in entries {
+        let value = entry.value_for(bias);
+        let buf = entry
+            .vaddr
+            .checked_sub(image_vaddr)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .and_then(|offset| image.get_mut(offset..)?.get_mut(..entry.size as usize))
+            .ok_or(RelocError::OutOfBounds(entry.vaddr))?;
+
+        match entry.big_endian {
+            true => buf.copy_from_slice(&value.to_be_bytes()[8 - buf.len()..]),
+            false => buf.copy_from_slice(&value.to_le_bytes()[..buf.len()]),
+        }
+    }
+
+    Ok(())
+}
+
+impl<'elf> Elf<'elf> {
+    /// Pre-compute the patch list for the dynamic relocations of this image
+    ///
+    /// Every relocation is evaluated at two different load biases; the difference tells
+    /// whether the result depends on the bias. Symbols must be defined within the image
+    /// itself, and only relocations which overwrite a whole storage unit can be
+    /// represented. Entries are read in the file's byte order; returns
+    /// [`RelocError::Incompatible`] for ELF32 files.
+    pub fn precompute_relocations<F>(&self, mut f: F) -> Result<(), RelocError>
+    where
+        F: FnMut(PatchEntry),
+    {
+        // Check the machine, class, and byte order against the file header
+        Relocator::<_, LittleEndian>::for_file(self, 0, |_| None)?;
+
+        let dynsym = match self.dynsym_section() {
+            Some(shdr) => Table::<Sym>::from_section(&shdr).map_err(RelocError::Malformed)?,
+            None => Table::default(),
+        };
+        let encoding = Encoding::of(self);
+        for shdr in self
+            .sections()
+            .filter(|s| s.section_type() == SectionType::Rela && s.flags().alloc())
+        {
+            Table::<Rela>::from_section(&shdr).map_err(RelocError::Malformed)?;
+            match (encoding.bits32, encoding.big_endian) {
+                (false, false) => {
+                    self.precompute_section::<LittleEndian, F>(&shdr, dynsym, &mut f)?
+                }
+                (false, true) => self.precompute_section::<BigEndian, F>(&shdr, dynsym, &mut f)?,
+                _ => {
+                    return Err(RelocError::Incompatible {
+                        machine: self.machine(),
+                        class: self.class(),
+                        data: self.data(),
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pre-compute the patches of the `SHT_RELA` section `shdr`, whose entries are in the
+    /// byte order `E`, resolving symbols through `dynsym`
+    fn precompute_section<E: Endian, F>(
+        &self,
+        shdr: &Section<'_, 'elf>,
+        dynsym: Table<'elf, Sym>,
+        f: &mut F,
+    ) -> Result<(), RelocError>
+    where
+        F: FnMut(PatchEntry),
+    {
+        const PROBE_BIAS: u64 = 0x5a5a_5000_0000;
+
+        let relocator = |bias: u64| {
+            let mut relocator = Relocator::<_, E>::new(self.machine(), bias, move |index: u32| {
+                let sym = dynsym.get(index as usize)?;
+                match sym.section_index() {
+                    SHN_UNDEF => None,
+                    _ => Some(sym.value().wrapping_add(bias)),
+                }
+            });
+            relocator.set_target_data(self.data());
+            relocator
+        };
+        let mut base = relocator(0);
+        let mut probe = relocator(PROBE_BIAS);
+        let big_endian = self.data() == Data::TwosCompBig;
+
+        let relas: &[Rela<E>] = slice_from_bytes(shdr.file_data().unwrap_or_default());
+        for index in 0..relas.len() {
+            let patches = base.compute(relas, index, 0)?;
+            let probes = probe.compute(relas, index, PROBE_BIAS)?;
+
+            for (patch, probed) in patches.into_iter().zip(probes) {
+                let unsupported = RelocError::Unsupported {
+                    machine: self.machine(),
+                    kind: relas[index].kind(),
+                };
+                if patch.op != PatchOp::Write {
+                    return Err(unsupported);
+                }
+
+                let delta = probed.value.wrapping_sub(patch.value) & patch.size_mask();
+                let formula = if delta == 0 {
+                    Formula::Absolute
+                } else if delta == PROBE_BIAS & patch.size_mask() {
+                    Formula::Biased
+                } else {
+                    return Err(unsupported);
+                };
+
+                f(PatchEntry {
+                    vaddr: patch.address,
+                    value: patch.value,
+                    size: patch.size,
+                    formula,
+                    big_endian,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use super::{apply_patch_list, Formula, PatchEntry};
+    use crate::{testdata::Fixture, testdata::POINTER_COUNT, Data, Elf, RelocError};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn patches_are_stored_in_the_file_byte_order() {
+        const BIAS: u64 = 0x10_0000;
+
+        for fixture in [Fixture::new(), Fixture::new().big_endian()] {
+            let file = fixture.build();
+            let elf = Elf::new(&file).unwrap();
+            let text = elf.section(1).unwrap().addr();
+            let data = elf.section(2).unwrap().file_offset();
+            let big_endian = fixture.data() == Data::TwosCompBig;
+
+            let mut entries = Vec::new();
+            elf.precompute_relocations(|entry| entries.push(entry))
+                .unwrap();
+            assert_eq!(entries.len(), POINTER_COUNT);
+            for entry in &entries {
+                assert_eq!(entry.formula, Formula::Biased);
+                assert_eq!(entry.big_endian, big_endian);
+                assert_eq!(PatchEntry::decode(&entry.encode()), Some(*entry));
+            }
+
+            let mut image = alloc::vec![0; file.len()];
+            apply_patch_list(entries, &mut image, 0, BIAS).unwrap();
+            for i in 0..POINTER_COUNT {
+                let word = image[data + i * 8..][..8].try_into().unwrap();
+                let value = match big_endian {
+                    true => u64::from_be_bytes(word),
+                    false => u64::from_le_bytes(word),
+                };
+                assert_eq!(value, text + i as u64 + BIAS);
+            }
+        }
+    }
+
+    #[test]
+    fn elf32_is_incompatible() {
+        let file = Fixture::new().elf32().build();
+        let elf = Elf::new(&file).unwrap();
+        let error = elf.precompute_relocations(|_| {}).unwrap_err();
+        assert!(matches!(error, RelocError::Incompatible { .. }));
+    }
+}
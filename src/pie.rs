@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Checks for self-relocating position-independent images
+
+use crate::{
+    backend_for,
+    table::{Encoding, Table},
+    DynTag, Elf, ElfType, Rel, Rela, RelocClass, RelocKind, SegmentKind,
+};
+
+/// A reason an image cannot be relocated by an early-boot self-relocator
+///
+/// Such a relocator applies only `RELATIVE` and RELR relocations, which need nothing but
+/// the load bias.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PieViolation {
+    /// The file is not `ET_DYN`
+    NotDynamic(ElfType),
+    /// The machine has no known relocation types
+    UnsupportedMachine,
+    /// The image requests a program interpreter
+    Interpreter,
+    /// The image depends on a shared object
+    Needed,
+    /// The image requires relocations in read-only segments (`DT_TEXTREL`)
+    TextRel,
+    /// A relocation table referenced by the dynamic table could not be read
+    MalformedTable(DynTag),
+    /// A relocation other than a relative relocation
+    Symbolic {
+        address: u64,
+        kind: RelocKind,
+        symbol: u32,
+    },
+}
+
+/// The outcome of [`Elf::check_pie()`]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PieReport {
+    /// Number of `RELATIVE` relocations in `DT_RELA`, `DT_REL`, and `DT_JMPREL`
+    pub relative: usize,
+    /// Number of addresses relocated by `DT_RELR`
+    pub relr: usize,
+    /// Number of problems found
+    pub violations: usize,
+}
+
+impl PieReport {
+    /// Returns `true` if the image can be relocated using only the load bias
+    #[inline]
+    pub const fn passed(&self) -> bool {
+        self.violations == 0
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Check that this image can be relocated by an early-boot self-relocator
+    ///
+    /// Every problem found is passed to `on_violation`, and the returned report counts the
+    /// relocations which would be applied.
+    pub fn check_pie<F>(&self, mut on_violation: F) -> PieReport
+    where
+        F: FnMut(PieViolation),
+    {
+        let mut report = PieReport::default();
+        let mut violation = |report: &mut PieReport, v| {
+            report.violations += 1;
+            on_violation(v);
+        };
+
+        if self.file_type() != ElfType::Dyn {
+            violation(&mut report, PieViolation::NotDynamic(self.file_type()));
+        }
+        if self
+            .segments()
+            .any(|sgmt| sgmt.kind() == SegmentKind::Interp)
+        {
+            violation(&mut report, PieViolation::Interpreter);
+        }
+
+        let backend = backend_for(self.machine());
+        if backend.is_none() {
+            violation(&mut report, PieViolation::UnsupportedMachine);
+        }
+
+        let Some(dyntab) = self.dynamic_table() else {
+            return report;
+        };
+        let value = |tag| dyntab.all(tag).next().map(|entry| entry.value());
+        let encoding = Encoding::of(self);
+
+        if dyntab.all(DynTag::NEEDED).next().is_some() {
+            violation(&mut report, PieViolation::Needed);
+        }
+        // `DF_TEXTREL` in `DT_FLAGS` is equivalent to `DT_TEXTREL`.
+        if value(DynTag::TEXTREL).is_some() || value(DynTag::FLAGS).is_some_and(|f| f & 0x4 != 0) {
+            violation(&mut report, PieViolation::TextRel);
+        }
+
+        // `DT_PLTREL` holds the tag of the table type, `DT_REL` or `DT_RELA`.
+        let plt_is_rela = value(DynTag::PLTREL) != Some(17);
+        let tables = [
+            (DynTag::RELA, DynTag::RELASZ, true),
+            (DynTag::REL, DynTag::RELSZ, false),
+            (DynTag::JMPREL, DynTag::PLTRELSZ, plt_is_rela),
+        ];
+
+        for (tag, size_tag, is_rela) in tables {
+            let Some(addr) = value(tag) else {
+                continue;
+            };
+            let size = value(size_tag).unwrap_or(0) as usize;
+            let Some(data) = self.data_at(addr, size) else {
+                violation(&mut report, PieViolation::MalformedTable(tag));
+                continue;
+            };
+
+            let entries = match is_rela {
+                true => RelocEntries::Rela(Table::new(data, encoding)),
+                false => RelocEntries::Rel(Table::new(data, encoding)),
+            };
+
+            for (address, kind, symbol) in entries.iter() {
+                match backend.and_then(|b| b.classify(kind)) {
+                    Some(RelocClass::Relative) => report.relative += 1,
+                    Some(RelocClass::None) => {}
+                    _ => violation(
+                        &mut report,
+                        PieViolation::Symbolic {
+                            address,
+                            kind,
+                            symbol,
+                        },
+                    ),
+                }
+            }
+        }
+
+        if let Some(addr) = value(DynTag::RELR) {
+            let size = value(DynTag::RELRSZ).unwrap_or(0) as usize;
+            match self.data_at(addr, size) {
+                Some(data) => report.relr += relr_count(data, encoding),
+                None => violation(&mut report, PieViolation::MalformedTable(DynTag::RELR)),
+            }
+        }
+
+        report
+    }
+}
+
+enum RelocEntries<'elf> {
+    Rel(Table<'elf, Rel>),
+    Rela(Table<'elf, Rela>),
+}
+
+impl<'elf> RelocEntries<'elf> {
+    /// Returns an iterator over the offset, type and symbol of each entry
+    fn iter(&self) -> impl Iterator<Item = (u64, RelocKind, u32)> + 'elf {
+        let (rels, relas) = match *self {
+            Self::Rel(rels) => (Some(rels), None),
+            Self::Rela(relas) => (None, Some(relas)),
+        };
+
+        rels.into_iter()
+            .flat_map(Table::iter)
+            .map(|rel| (rel.offset(), rel.kind(), rel.sym()))
+            .chain(
+                relas
+                    .into_iter()
+                    .flat_map(Table::iter)
+                    .map(|rela| (rela.offset(), rela.kind(), rela.sym())),
+            )
+    }
+}
+
+/// Returns the number of addresses relocated by a `DT_RELR` table
+fn relr_count(data: &[u8], encoding: Encoding) -> usize {
+    let word_size = match encoding.bits32 {
+        true => 4,
+        false => 8,
+    };
+
+    data.chunks_exact(word_size)
+        .map(|entry| {
+            // Bit 0 marks a bitmap, and lies in the least significant byte.
+            let low = match encoding.big_endian {
+                true => entry[word_size - 1],
+                false => entry[0],
+            };
+            match low & 1 {
+                0 => 1,
+                _ => entry.iter().map(|b| b.count_ones() as usize).sum::<usize>() - 1,
+            }
+        })
+        .sum()
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+//! Parsing the headers from a prefix of a file
+//!
+//! A boot loader fetching a file over the network usually requests a fixed-size prefix
+//! first. [`ElfPrefix`] parses the file header from such a prefix, and reports how many
+//! more bytes must be fetched before each header table can be read.
+
+use crate::{
+    parse_file_header,
+    table::{Encoding, Entry, Table},
+    FileHeader, ParseError, ProgramHeader, SectionHeader, Structure,
+};
+use core::{fmt, mem::size_of};
+
+/// An error parsing the file header from a prefix
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PrefixError {
+    /// The prefix ends before the file header does
+    ///
+    /// `needed` is the number of bytes missing from the prefix. If the prefix is too short
+    /// to hold the class, this assumes an ELF32 header, the smaller of the two.
+    Incomplete { needed: usize },
+    /// The file header is malformed
+    Parse(ParseError),
+}
+
+impl fmt::Display for PrefixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete { needed } => write!(f, "file header needs {needed} more bytes"),
+            Self::Parse(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<ParseError> for PrefixError {
+    #[inline]
+    fn from(error: ParseError) -> PrefixError {
+        Self::Parse(error)
+    }
+}
+
+/// A structure which extends past the end of the prefix
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Deferred {
+    /// File offset of the structure
+    pub offset: u64,
+    /// Size of the structure, in bytes
+    pub size: u64,
+    /// Number of bytes which must follow the prefix before the structure can be read
+    pub needed: u64,
+}
+
+/// The headers of a file, parsed from a prefix of it
+#[derive(Clone, Copy, Debug)]
+pub struct ElfPrefix<'a> {
+    data: &'a [u8],
+    ehdr: FileHeader,
+    encoding: Encoding,
+}
+
+impl<'a> ElfPrefix<'a> {
+    /// Parse the file header at the start of `data`, which need not hold the whole file
+    ///
+    /// Returns [`PrefixError::Incomplete`] if `data` ends before the file header does, and
+    /// an error if the magic bytes are wrong even in a shorter prefix.
+    pub fn parse(data: &'a [u8]) -> Result<ElfPrefix<'a>, PrefixError> {
+        let magic = &b"\x7fELF"[..data.len().min(4)];
+        if data[..magic.len()] != *magic {
+            return Err(PrefixError::Parse(ParseError::new(
+                Structure::FileHeader,
+                0,
+                "invalid ELF",
+            )));
+        }
+
+        let size = match data.get(4) {
+            Some(2) => size_of::<FileHeader>(),
+            _ => FileHeader::SIZE32,
+        };
+        if data.len() < size {
+            return Err(PrefixError::Incomplete {
+                needed: size - data.len(),
+            });
+        }
+
+        let (ehdr, encoding) = parse_file_header(data)?;
+        Ok(ElfPrefix {
+            data,
+            ehdr,
+            encoding,
+        })
+    }
+
+    /// Returns the file header, in the little-endian ELF64 layout
+    #[inline]
+    pub fn header(&self) -> &FileHeader {
+        &self.ehdr
+    }
+
+    /// Returns the prefix the headers were parsed from
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the entries of a header table, or where it lies if the prefix does not
+    /// hold all of it
+    fn table<T: Entry>(&self, offset: usize, num: u16) -> Result<Table<'a, T>, Deferred> {
+        let size = num as u64 * T::encoded_size(self.encoding) as u64;
+        let end = (offset as u64).saturating_add(size);
+        match self
+            .data
+            .get(offset..)
+            .and_then(|data| data.get(..size as usize))
+        {
+            Some(data) => Ok(Table::new(data, self.encoding)),
+            None => Err(Deferred {
+                offset: offset as u64,
+                size,
+                needed: end - self.data.len() as u64,
+            }),
+        }
+    }
+
+    /// Returns the program headers, or the bytes still needed to read them
+    pub fn program_headers(&self) -> Result<impl Iterator<Item = ProgramHeader> + 'a, Deferred> {
+        let table = self.table(self.ehdr.phdr_offset(), self.ehdr.phdr_num())?;
+        Ok(table.iter())
+    }
+
+    /// Returns the section headers, or the bytes still needed to read them
+    pub fn section_headers(&self) -> Result<impl Iterator<Item = SectionHeader> + 'a, Deferred> {
+        let table = self.table(self.ehdr.shdr_offset(), self.ehdr.shdr_num())?;
+        Ok(table.iter())
+    }
+
+    /// Returns the number of bytes which must follow the prefix before both header tables
+    /// can be read
+    pub fn needed(&self) -> u64 {
+        let phdrs = self.program_headers().err().map_or(0, |d| d.needed);
+        let shdrs = self.section_headers().err().map_or(0, |d| d.needed);
+        phdrs.max(shdrs)
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Initialization priority suffixes
+//!
+//! Constructor and destructor sections may carry a priority suffix, as in
+//! `.init_array.00101`, which the linker uses to order them (`SORT_BY_INIT_PRIORITY`).
+//! `.ctors` and `.dtors` tables run backwards, so their suffixes are inverted to give the
+//! equivalent `.init_array` or `.fini_array` priority.
+
+use core::cmp::Ordering;
+
+/// Split a trailing `.NNNNN` numeric suffix from a section name
+///
+/// Returns the name without the suffix, and the suffix, if present. A suffix too large
+/// for a priority is still removed, but gives the default priority (`None`), so the
+/// section is not mistaken for one of another name.
+pub fn split_priority_suffix(name: &str) -> (&str, Option<u16>) {
+    match name.rsplit_once('.') {
+        Some((base, suffix))
+            if !base.is_empty()
+                && !suffix.is_empty()
+                && suffix.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (base, suffix.parse().ok())
+        }
+        _ => (name, None),
+    }
+}
+
+/// Returns the initialization priority of a constructor or destructor section
+///
+/// Suffixes of `.init_array` and `.fini_array` sections are used as-is, while those of
+/// `.ctors` and `.dtors` sections are subtracted from 65535. Returns `None` if the section
+/// has no suffix or is not one of these.
+pub fn init_priority(name: &str) -> Option<u16> {
+    let (base, priority) = split_priority_suffix(name);
+
+    match base {
+        ".init_array" | ".fini_array" | ".preinit_array" => priority,
+        ".ctors" | ".dtors" => priority.map(|p| u16::MAX - p),
+        _ => None,
+    }
+}
+
+/// Compare two section names in the order the linker places them
+///
+/// Sections with a priority come first, from lowest to highest. Sections without one
+/// compare equal to each other, and so keep their relative order when sorted stably.
+pub fn compare_init_priority(a: &str, b: &str) -> Ordering {
+    match (init_priority(a), init_priority(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Stably sort `items` by the initialization priority of their section names
+///
+/// This does not allocate, and is intended for the handful of sections found in a file.
+pub fn sort_by_init_priority<T, F>(items: &mut [T], mut name: F)
+where
+    F: FnMut(&T) -> &str,
+{
+    // Insertion sort
+    for i in 1..items.len() {
+        let mut j = i;
+        while j > 0 && compare_init_priority(name(&items[j - 1]), name(&items[j])).is_gt() {
+            items.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_suffix() {
+        assert_eq!(split_priority_suffix(".init_array"), (".init_array", None));
+        assert_eq!(
+            split_priority_suffix(".init_array.00101"),
+            (".init_array", Some(101))
+        );
+        assert_eq!(
+            split_priority_suffix(".init_array.65535"),
+            (".init_array", Some(65535))
+        );
+        assert_eq!(split_priority_suffix(".text.hot"), (".text.hot", None));
+        assert_eq!(split_priority_suffix(".123"), (".123", None));
+    }
+
+    #[test]
+    fn overflowing_suffix_keeps_section() {
+        assert_eq!(
+            split_priority_suffix(".init_array.65536"),
+            (".init_array", None)
+        );
+        assert_eq!(
+            split_priority_suffix(".ctors.99999999999"),
+            (".ctors", None)
+        );
+        assert_eq!(init_priority(".init_array.70000"), None);
+        assert_eq!(
+            compare_init_priority(".init_array.70000", ".init_array.00100"),
+            Ordering::Greater
+        );
+    }
+}
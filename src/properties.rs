@@ -0,0 +1,297 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Properties of the target a program was built for
+//!
+//! Requirements of the code are recorded in three places: GNU property notes, attribute
+//! sections, and the machine-specific flags of the file header. [`Elf::target_properties()`]
+//! gathers them into one structure.
+
+use crate::{
+    leb128::read_uleb128, table::Encoding, Elf, Machine, SectionType, NT_GNU_PROPERTY_TYPE_0,
+};
+
+pub const GNU_PROPERTY_STACK_SIZE: u32 = 1;
+pub const GNU_PROPERTY_NO_COPY_ON_PROTECTED: u32 = 2;
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc000_0000;
+pub const GNU_PROPERTY_RISCV_FEATURE_1_AND: u32 = 0xc000_0000;
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc000_0002;
+pub const GNU_PROPERTY_X86_ISA_1_NEEDED: u32 = 0xc000_8002;
+
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_BTI: u32 = 0x1;
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_PAC: u32 = 0x2;
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_GCS: u32 = 0x4;
+pub const GNU_PROPERTY_RISCV_FEATURE_1_CFI_LP_UNLABELED: u32 = 0x1;
+pub const GNU_PROPERTY_RISCV_FEATURE_1_CFI_SS: u32 = 0x2;
+pub const GNU_PROPERTY_X86_FEATURE_1_IBT: u32 = 0x1;
+pub const GNU_PROPERTY_X86_FEATURE_1_SHSTK: u32 = 0x2;
+
+pub const EF_RISCV_RVC: u32 = 0x1;
+pub const EF_RISCV_FLOAT_ABI: u32 = 0x6;
+pub const EF_RISCV_RVE: u32 = 0x8;
+pub const EF_RISCV_TSO: u32 = 0x10;
+
+pub const TAG_RISCV_STACK_ALIGN: u64 = 4;
+pub const TAG_RISCV_ARCH: u64 = 5;
+pub const TAG_RISCV_UNALIGNED_ACCESS: u64 = 6;
+
+impl SectionType {
+    pub const RISCV_ATTRIBUTES: Self = Self::CpuSpecific(0x70000003);
+}
+
+/// An entry of the GNU property note
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GnuProperty<'elf> {
+    pub kind: u32,
+    pub data: &'elf [u8],
+}
+
+/// An attribute from an attribute section, such as `.riscv.attributes`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Attribute<'elf> {
+    Int(u64, u64),
+    Str(u64, &'elf str),
+}
+
+/// The calling convention for floating-point arguments
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FloatAbi {
+    /// Passed in integer registers
+    Soft,
+    Single,
+    Double,
+    Quad,
+}
+
+/// The requirements of a program's code, as returned by [`Elf::target_properties()`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TargetProperties<'elf> {
+    /// Indirect branches only land on marked instructions (AArch64 BTI, x86 IBT, RISC-V
+    /// Zicfilp)
+    pub branch_targets: bool,
+    /// Return addresses are signed (AArch64 PAC)
+    pub pointer_auth: bool,
+    /// The code is compatible with a shadow stack (AArch64 GCS, x86 SHSTK, RISC-V Zicfiss)
+    pub shadow_stack: bool,
+    /// x86-64 microarchitecture levels required, as `GNU_PROPERTY_X86_ISA_1_*` bits
+    pub x86_isa_needed: u32,
+    /// Compressed instructions are used (RISC-V C extension)
+    pub compressed: bool,
+    /// Only 16 integer registers are used (RISC-V E extension)
+    pub reduced_registers: bool,
+    /// The total store ordering memory model is required (RISC-V Ztso)
+    pub total_store_order: bool,
+    pub float_abi: Option<FloatAbi>,
+    /// ISA string, such as `rv64i2p1_m2p0_a2p1_c2p0` (RISC-V `Tag_RISCV_arch`)
+    pub arch: Option<&'elf str>,
+    /// Required alignment of the stack, in bytes
+    ///
+    /// This is taken from the attribute section if present, or else is the default of the
+    /// machine's psABI.
+    pub stack_align: Option<u64>,
+    /// Misaligned memory accesses may be made
+    pub unaligned_access: bool,
+    /// Size of the stack requested in `GNU_PROPERTY_STACK_SIZE`
+    pub stack_size: Option<u64>,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over the entries of the GNU property note
+    pub fn gnu_properties(&self) -> impl Iterator<Item = GnuProperty<'elf>> {
+        let encoding = Encoding::of(self);
+        let align = encoding.word_size();
+        let mut data = self
+            .find_note(b"GNU", NT_GNU_PROPERTY_TYPE_0)
+            .map(|note| note.desc())
+            .unwrap_or_default();
+
+        core::iter::from_fn(move || {
+            let kind = encoding.read_u32(data)?;
+            let size = encoding.read_u32(data.get(4..)?)? as usize;
+            let property = data.get(8..)?.get(..size)?;
+            data = data
+                .get((8 + size).next_multiple_of(align)..)
+                .unwrap_or_default();
+            Some(GnuProperty {
+                kind,
+                data: property,
+            })
+        })
+    }
+
+    /// Returns the `u32` value of the GNU property of type `kind`, if present
+    fn gnu_property_u32(&self, kind: u32) -> Option<u32> {
+        let property = self.gnu_properties().find(|p| p.kind == kind)?;
+        Encoding::of(self).read_u32(property.data)
+    }
+
+    /// Returns an iterator over the attributes which apply to the whole file, from the
+    /// `vendor` subsection of the first section of type `section_type`
+    ///
+    /// Odd-numbered tags have string values and even-numbered tags have integer values,
+    /// following the generic attribute rules used by RISC-V. Iteration stops at the first
+    /// malformed attribute.
+    pub fn file_attributes(
+        &self,
+        section_type: SectionType,
+        vendor: &str,
+    ) -> impl Iterator<Item = Attribute<'elf>> {
+        const TAG_FILE: u64 = 1;
+
+        let encoding = Encoding::of(self);
+        let data = self
+            .sections()
+            .find(|sect| sect.section_type() == section_type)
+            .and_then(|sect| sect.file_data())
+            .and_then(|data| data.strip_prefix(b"A"))
+            .unwrap_or_default();
+
+        // Find the vendor's subsection, then the file's sub-subsection within it.
+        let find = |mut data: &'elf [u8], matches: &dyn Fn(&'elf [u8]) -> Option<usize>| {
+            while !data.is_empty() {
+                let len = encoding.read_u32(data)? as usize;
+                let (block, rest) = data.split_at_checked(len)?;
+                if let Some(start) = matches(block) {
+                    return block.get(start..);
+                }
+                data = rest;
+            }
+            None
+        };
+        let vendor_data = find(data, &|block| {
+            let name = block.get(4..)?;
+            let end = name.iter().position(|&b| b == 0)?;
+            (name[..end] == *vendor.as_bytes()).then_some(4 + end + 1)
+        });
+        let mut attrs = vendor_data
+            .and_then(|data| {
+                let mut tagged = data;
+                while !tagged.is_empty() {
+                    let mut cursor = tagged;
+                    let tag = read_uleb128(&mut cursor)?;
+                    let header = tagged.len() - cursor.len();
+                    let len = encoding.read_u32(cursor)? as usize;
+                    let (block, rest) = tagged.split_at_checked(len)?;
+                    if tag == TAG_FILE {
+                        return block.get(header + 4..);
+                    }
+                    tagged = rest;
+                }
+                None
+            })
+            .unwrap_or_default();
+
+        core::iter::from_fn(move || {
+            let tag = read_uleb128(&mut attrs)?;
+            match tag % 2 {
+                0 => Some(Attribute::Int(tag, read_uleb128(&mut attrs)?)),
+                _ => {
+                    let end = attrs.iter().position(|&b| b == 0)?;
+                    let value = core::str::from_utf8(&attrs[..end]).ok()?;
+                    attrs = &attrs[end + 1..];
+                    Some(Attribute::Str(tag, value))
+                }
+            }
+        })
+    }
+
+    /// Returns the requirements of this file's code
+    pub fn target_properties(&self) -> TargetProperties<'elf> {
+        let mut props = TargetProperties::default();
+        let flags = self.flags();
+
+        match self.machine() {
+            Machine::X86_64 => {
+                let features = self
+                    .gnu_property_u32(GNU_PROPERTY_X86_FEATURE_1_AND)
+                    .unwrap_or(0);
+                props.branch_targets = features & GNU_PROPERTY_X86_FEATURE_1_IBT != 0;
+                props.shadow_stack = features & GNU_PROPERTY_X86_FEATURE_1_SHSTK != 0;
+                props.x86_isa_needed = self
+                    .gnu_property_u32(GNU_PROPERTY_X86_ISA_1_NEEDED)
+                    .unwrap_or(0);
+                props.stack_align = Some(16);
+                props.unaligned_access = true;
+            }
+            Machine::Aarch64 => {
+                let features = self
+                    .gnu_property_u32(GNU_PROPERTY_AARCH64_FEATURE_1_AND)
+                    .unwrap_or(0);
+                props.branch_targets = features & GNU_PROPERTY_AARCH64_FEATURE_1_BTI != 0;
+                props.pointer_auth = features & GNU_PROPERTY_AARCH64_FEATURE_1_PAC != 0;
+                props.shadow_stack = features & GNU_PROPERTY_AARCH64_FEATURE_1_GCS != 0;
+                props.stack_align = Some(16);
+            }
+            Machine::Riscv => {
+                let features = self
+                    .gnu_property_u32(GNU_PROPERTY_RISCV_FEATURE_1_AND)
+                    .unwrap_or(0);
+                props.branch_targets =
+                    features & GNU_PROPERTY_RISCV_FEATURE_1_CFI_LP_UNLABELED != 0;
+                props.shadow_stack = features & GNU_PROPERTY_RISCV_FEATURE_1_CFI_SS != 0;
+                props.compressed = flags & EF_RISCV_RVC != 0;
+                props.reduced_registers = flags & EF_RISCV_RVE != 0;
+                props.total_store_order = flags & EF_RISCV_TSO != 0;
+                props.float_abi = Some(match flags & EF_RISCV_FLOAT_ABI {
+                    0x0 => FloatAbi::Soft,
+                    0x2 => FloatAbi::Single,
+                    0x4 => FloatAbi::Double,
+                    _ => FloatAbi::Quad,
+                });
+                // The ILP32E and LP64E ABIs only align the stack to 4 and 8 bytes.
+                props.stack_align = Some(match props.reduced_registers {
+                    true => 4,
+                    false => 16,
+                });
+
+                for attr in self.file_attributes(SectionType::RISCV_ATTRIBUTES, "riscv") {
+                    match attr {
+                        Attribute::Int(TAG_RISCV_STACK_ALIGN, align) => {
+                            props.stack_align = Some(align)
+                        }
+                        Attribute::Int(TAG_RISCV_UNALIGNED_ACCESS, value) => {
+                            props.unaligned_access = value != 0
+                        }
+                        Attribute::Str(TAG_RISCV_ARCH, arch) => props.arch = Some(arch),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        props.stack_size = self
+            .gnu_properties()
+            .find(|p| p.kind == GNU_PROPERTY_STACK_SIZE)
+            .and_then(|p| Encoding::of(self).read_word(p.data));
+
+        props
+    }
+}
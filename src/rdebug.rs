@@ -0,0 +1,305 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! The dynamic linker's debugger interface
+//!
+//! The dynamic linker publishes the list of loaded objects through an `r_debug` structure,
+//! whose address it stores in the `DT_DEBUG` entry of the executable's dynamic table.
+//! These types are read from the memory of a live process or a core dump through a
+//! caller-provided function, `read(address, buf) -> bool`, which fills `buf` with the
+//! memory at `address` and returns `false` if it is not mapped.
+
+use crate::{
+    assert_struct_size, impl_pod,
+    table::{Encoding, Entry},
+    Dyn, DynTag, DynamicTable, Elf, Endian, LittleEndian, SegmentKind, I32, U64,
+};
+use core::marker::PhantomData;
+
+/// The state of the loaded-object list, as published in [`RDebug::state()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RState {
+    /// The list is consistent
+    Consistent,
+    /// An object is being added
+    Add,
+    /// An object is being removed
+    Delete,
+    Unknown(i32),
+}
+
+impl RState {
+    pub const fn from_i32(x: i32) -> RState {
+        match x {
+            0 => RState::Consistent,
+            1 => RState::Add,
+            2 => RState::Delete,
+            x => RState::Unknown(x),
+        }
+    }
+}
+
+/// The `r_debug` structure
+#[repr(C)]
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub struct RDebug<E: Endian = LittleEndian> {
+    version: I32<E>,
+    _padding0: [u8; 4],
+    map: U64<E>,
+    brk: U64<E>,
+    state: I32<E>,
+    _padding1: [u8; 4],
+    ldbase: U64<E>,
+}
+
+assert_struct_size!(RDebug, 40);
+impl_pod!(RDebug);
+
+impl<E: Endian> RDebug<E> {
+    /// Read the structure at `address`
+    pub fn read<R>(mut read: R, address: u64) -> Option<RDebug<E>>
+    where
+        R: FnMut(u64, &mut [u8]) -> bool,
+    {
+        let mut r_debug = RDebug::default();
+        read(address, crate::bytes_of_mut(&mut r_debug)).then_some(r_debug)
+    }
+
+    /// Returns the protocol version, which is `0` until the dynamic linker has started
+    #[inline]
+    pub const fn version(&self) -> i32 {
+        self.version.get()
+    }
+
+    /// Returns the address of the first [`LinkMap`]
+    #[inline]
+    pub const fn map(&self) -> u64 {
+        self.map.get()
+    }
+
+    /// Returns the address of the function the dynamic linker calls when the list changes
+    ///
+    /// Debuggers place a breakpoint here to be notified of `dlopen()` and `dlclose()`.
+    #[inline]
+    pub const fn brk(&self) -> u64 {
+        self.brk.get()
+    }
+
+    #[inline]
+    pub const fn state(&self) -> RState {
+        RState::from_i32(self.state.get())
+    }
+
+    /// Returns the base address of the dynamic linker
+    #[inline]
+    pub const fn ldbase(&self) -> u64 {
+        self.ldbase.get()
+    }
+
+    /// Returns an iterator over the loaded objects, yielding each entry's address
+    pub fn link_maps<R>(&self, read: R) -> LinkMaps<E, R>
+    where
+        R: FnMut(u64, &mut [u8]) -> bool,
+    {
+        LinkMaps {
+            read,
+            next: self.map(),
+            prev: 0,
+            _endian: PhantomData,
+        }
+    }
+}
+
+impl<E: Endian> core::fmt::Debug for RDebug<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RDebug")
+            .field("version", &self.version())
+            .field("map", &format_args!("{:#018x}", self.map()))
+            .field("brk", &format_args!("{:#018x}", self.brk()))
+            .field("state", &self.state())
+            .field("ldbase", &format_args!("{:#018x}", self.ldbase()))
+            .finish()
+    }
+}
+
+/// The `link_map` structure which describes one loaded object
+///
+/// The dynamic linker's private fields which follow the public ones are not included.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub struct LinkMap<E: Endian = LittleEndian> {
+    addr: U64<E>,
+    name: U64<E>,
+    ld: U64<E>,
+    next: U64<E>,
+    prev: U64<E>,
+}
+
+assert_struct_size!(LinkMap, 40);
+impl_pod!(LinkMap);
+
+impl<E: Endian> LinkMap<E> {
+    /// Read the structure at `address`
+    pub fn read<R>(mut read: R, address: u64) -> Option<LinkMap<E>>
+    where
+        R: FnMut(u64, &mut [u8]) -> bool,
+    {
+        let mut link_map = LinkMap::default();
+        read(address, crate::bytes_of_mut(&mut link_map)).then_some(link_map)
+    }
+
+    /// Returns the load bias of the object
+    #[inline]
+    pub const fn addr(&self) -> u64 {
+        self.addr.get()
+    }
+
+    /// Returns the address of the object's NUL-terminated path
+    #[inline]
+    pub const fn name(&self) -> u64 {
+        self.name.get()
+    }
+
+    /// Returns the address of the object's dynamic table
+    #[inline]
+    pub const fn ld(&self) -> u64 {
+        self.ld.get()
+    }
+
+    #[inline]
+    pub const fn next(&self) -> u64 {
+        self.next.get()
+    }
+
+    #[inline]
+    pub const fn prev(&self) -> u64 {
+        self.prev.get()
+    }
+
+    /// Read the object's path into `buf`
+    ///
+    /// Returns the path without its NUL terminator, or `None` if it could not be read or
+    /// does not fit in `buf`.
+    pub fn read_name<'b, R>(&self, mut read: R, buf: &'b mut [u8]) -> Option<&'b [u8]>
+    where
+        R: FnMut(u64, &mut [u8]) -> bool,
+    {
+        // Read a byte at a time, so the read never runs into an unmapped page.
+        for i in 0..buf.len() {
+            if !read(self.name().checked_add(i as u64)?, &mut buf[i..][..1]) {
+                return None;
+            }
+            if buf[i] == 0 {
+                return Some(&buf[..i]);
+            }
+        }
+        None
+    }
+}
+
+impl<E: Endian> core::fmt::Debug for LinkMap<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LinkMap")
+            .field("addr", &format_args!("{:#018x}", self.addr()))
+            .field("name", &format_args!("{:#018x}", self.name()))
+            .field("ld", &format_args!("{:#018x}", self.ld()))
+            .field("next", &format_args!("{:#018x}", self.next()))
+            .field("prev", &format_args!("{:#018x}", self.prev()))
+            .finish()
+    }
+}
+
+/// An iterator over the loaded-object list, returned by [`RDebug::link_maps()`]
+///
+/// Iteration stops at the end of the list, when an entry cannot be read, or when an
+/// entry's `l_prev` does not point back at the previous entry, which guards against
+/// cycles in a corrupted list.
+pub struct LinkMaps<E: Endian, R> {
+    read: R,
+    next: u64,
+    prev: u64,
+    _endian: PhantomData<E>,
+}
+
+impl<E, R> Iterator for LinkMaps<E, R>
+where
+    E: Endian,
+    R: FnMut(u64, &mut [u8]) -> bool,
+{
+    type Item = (u64, LinkMap<E>);
+
+    fn next(&mut self) -> Option<(u64, LinkMap<E>)> {
+        let address = self.next;
+        if address == 0 {
+            return None;
+        }
+
+        self.next = 0;
+        let link_map = LinkMap::read(&mut self.read, address)?;
+        if link_map.prev() != self.prev {
+            return None;
+        }
+
+        self.prev = address;
+        self.next = link_map.next();
+        Some((address, link_map))
+    }
+}
+
+impl DynamicTable<'_, '_> {
+    /// Returns the value of the `DT_DEBUG` entry
+    ///
+    /// In the memory of a running process this is the address of the [`RDebug`]
+    /// structure; in the file it is zero.
+    pub fn debug_address(&self) -> Option<u64> {
+        Some(self.get_unique(DynTag::DEBUG).ok()??.value())
+    }
+}
+
+impl Elf<'_> {
+    /// Returns the link-time virtual address of the `DT_DEBUG` entry's value
+    ///
+    /// Add the load bias to get the address at which the dynamic linker stores the address
+    /// of [`RDebug`] in a running process.
+    pub fn debug_entry_address(&self) -> Option<u64> {
+        let dyntab = self.dynamic_table()?;
+        let index = dyntab
+            .entries()
+            .position(|entry| entry.tag() == DynTag::DEBUG)?;
+
+        let sgmt = self
+            .segments()
+            .find(|sgmt| sgmt.kind() == SegmentKind::Dynamic)?;
+        // The value follows the tag, which takes up half of the entry.
+        let entry_size = Dyn::encoded_size(Encoding::of(self)) as u64;
+        let offset = index as u64 * entry_size + entry_size / 2;
+        sgmt.virtual_address().checked_add(offset)
+    }
+}
@@ -0,0 +1,388 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Floating-point and vector register sets in core dumps
+
+use crate::{Machine, Thread, NT_FPREGSET};
+
+/// The x86 `XSAVE` area, in standard format
+pub const NT_X86_XSTATE: u32 = 0x202;
+/// The offsets and sizes of the components of [`NT_X86_XSTATE`]
+pub const NT_X86_XSAVE_LAYOUT: u32 = 0x205;
+/// AArch64 SVE registers
+pub const NT_ARM_SVE: u32 = 0x405;
+
+/// `XSAVE` state components
+pub mod xfeature {
+    pub const X87: u32 = 0;
+    pub const SSE: u32 = 1;
+    /// The upper halves of `ymm0` to `ymm15`
+    pub const YMM: u32 = 2;
+    pub const BNDREGS: u32 = 3;
+    pub const BNDCSR: u32 = 4;
+    pub const OPMASK: u32 = 5;
+    /// The upper halves of `zmm0` to `zmm15`
+    pub const ZMM_HI256: u32 = 6;
+    /// `zmm16` to `zmm31`
+    pub const HI16_ZMM: u32 = 7;
+    pub const PKRU: u32 = 9;
+    pub const XTILE_CFG: u32 = 17;
+    pub const XTILE_DATA: u32 = 18;
+}
+
+/// The location of an extended `XSAVE` component
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct XSaveComponent {
+    pub feature: u32,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl XSaveComponent {
+    /// Returns the usual location of a component in the standard format
+    ///
+    /// Offsets in the standard format are defined by the CPU; these are the ones used by
+    /// Intel processors. Prefer the [`XSaveLayout`] when the core has one.
+    pub const fn standard(feature: u32) -> Option<XSaveComponent> {
+        let (offset, size) = match feature {
+            xfeature::YMM => (576, 256),
+            xfeature::BNDREGS => (960, 64),
+            xfeature::BNDCSR => (1024, 64),
+            xfeature::OPMASK => (1088, 64),
+            xfeature::ZMM_HI256 => (1152, 512),
+            xfeature::HI16_ZMM => (1664, 1024),
+            xfeature::PKRU => (2688, 8),
+            _ => return None,
+        };
+        Some(XSaveComponent {
+            feature,
+            offset,
+            size,
+        })
+    }
+}
+
+/// The descriptor of an `NT_X86_XSAVE_LAYOUT` note
+#[derive(Clone, Copy, Debug)]
+pub struct XSaveLayout<'elf> {
+    desc: &'elf [u8],
+}
+
+impl<'elf> XSaveLayout<'elf> {
+    #[inline]
+    pub const fn new(desc: &'elf [u8]) -> XSaveLayout<'elf> {
+        Self { desc }
+    }
+
+    /// Returns an iterator over the components described by the note
+    pub fn components(&self) -> impl Iterator<Item = XSaveComponent> + 'elf {
+        self.desc.chunks_exact(16).map(|entry| XSaveComponent {
+            feature: read_u32(entry, 0).unwrap(),
+            size: read_u32(entry, 4).unwrap(),
+            offset: read_u32(entry, 8).unwrap(),
+        })
+    }
+
+    pub fn component(&self, feature: u32) -> Option<XSaveComponent> {
+        self.components().find(|c| c.feature == feature)
+    }
+}
+
+/// An x86 `XSAVE` area, from an `NT_X86_XSTATE` note
+///
+/// The first 512 bytes are the legacy `FXSAVE` area, which is also the contents of the
+/// x86-64 `NT_FPREGSET` note.
+#[derive(Clone, Copy, Debug)]
+pub struct XSaveArea<'elf> {
+    data: &'elf [u8],
+    layout: Option<XSaveLayout<'elf>>,
+}
+
+impl<'elf> XSaveArea<'elf> {
+    /// Returns `None` if `data` is shorter than the legacy area
+    pub fn new(data: &'elf [u8], layout: Option<XSaveLayout<'elf>>) -> Option<XSaveArea<'elf>> {
+        (data.len() >= 512).then_some(Self { data, layout })
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &'elf [u8] {
+        self.data
+    }
+
+    /// Returns the x87 control word
+    #[inline]
+    pub fn fcw(&self) -> u16 {
+        u16::from_le_bytes([self.data[0], self.data[1]])
+    }
+
+    /// Returns the x87 status word
+    #[inline]
+    pub fn fsw(&self) -> u16 {
+        u16::from_le_bytes([self.data[2], self.data[3]])
+    }
+
+    /// Returns the abridged x87 tag word
+    #[inline]
+    pub fn ftw(&self) -> u8 {
+        self.data[4]
+    }
+
+    #[inline]
+    pub fn mxcsr(&self) -> u32 {
+        read_u32(self.data, 24).unwrap()
+    }
+
+    /// Returns the 80-bit x87 register `st(index)`
+    pub fn st(&self, index: usize) -> Option<&'elf [u8]> {
+        (index < 8).then(|| &self.data[32 + index * 16..][..10])
+    }
+
+    /// Returns the low 128 bits of vector register `index`
+    pub fn xmm(&self, index: usize) -> Option<&'elf [u8]> {
+        (index < 16).then(|| &self.data[160 + index * 16..][..16])
+    }
+
+    /// Returns the state components enabled in `XCR0`, which Linux stores in the
+    /// software-reserved bytes of the legacy area
+    #[inline]
+    pub fn xcr0(&self) -> u64 {
+        read_u64(self.data, 464).unwrap()
+    }
+
+    /// Returns the state components which were saved, or `0` if there is no `XSAVE`
+    /// header
+    pub fn xstate_bv(&self) -> u64 {
+        read_u64(self.data, 512).unwrap_or(0)
+    }
+
+    /// Returns the location of a component
+    ///
+    /// The layout note is used when present, and the standard offsets otherwise.
+    pub fn component_location(&self, feature: u32) -> Option<XSaveComponent> {
+        match &self.layout {
+            Some(layout) => layout.component(feature),
+            None => XSaveComponent::standard(feature),
+        }
+    }
+
+    /// Returns the contents of a component
+    ///
+    /// Returns `None` if the component is not enabled in `XCR0` or lies outside the area.
+    /// A component which was not saved (see [`xstate_bv()`](XSaveArea::xstate_bv)) is in
+    /// its initial state, which is usually all zeros.
+    pub fn component(&self, feature: u32) -> Option<&'elf [u8]> {
+        if feature >= 64 || self.xcr0() & (1 << feature) == 0 {
+            return None;
+        }
+        let location = self.component_location(feature)?;
+        self.data
+            .get(location.offset as usize..)?
+            .get(..location.size as usize)
+    }
+
+    /// Returns the upper 128 bits of `ymm(index)`
+    pub fn ymm_hi(&self, index: usize) -> Option<&'elf [u8]> {
+        let ymm = self.component(xfeature::YMM)?;
+        ymm.get(index * 16..)?.get(..16)
+    }
+}
+
+/// AArch64 SIMD registers, from `NT_FPREGSET` or the FPSIMD form of `NT_ARM_SVE`
+#[derive(Clone, Copy, Debug)]
+pub struct FpsimdRegisters<'elf> {
+    data: &'elf [u8],
+}
+
+impl<'elf> FpsimdRegisters<'elf> {
+    /// Returns `None` if `data` is too short for `user_fpsimd_state`
+    pub fn new(data: &'elf [u8]) -> Option<FpsimdRegisters<'elf>> {
+        (data.len() >= 520).then_some(Self { data })
+    }
+
+    /// Returns the 128-bit register `v(index)`
+    pub fn v(&self, index: usize) -> Option<&'elf [u8]> {
+        (index < 32).then(|| &self.data[index * 16..][..16])
+    }
+
+    #[inline]
+    pub fn fpsr(&self) -> u32 {
+        read_u32(self.data, 512).unwrap()
+    }
+
+    #[inline]
+    pub fn fpcr(&self) -> u32 {
+        read_u32(self.data, 516).unwrap()
+    }
+}
+
+/// AArch64 SVE state, from an `NT_ARM_SVE` note
+#[derive(Clone, Copy, Debug)]
+pub struct SveRegisters<'elf> {
+    desc: &'elf [u8],
+}
+
+impl<'elf> SveRegisters<'elf> {
+    const HEADER_SIZE: usize = 16;
+    /// `SVE_PT_REGS_SVE`: the registers are stored in SVE form rather than FPSIMD form
+    const REGS_SVE: u16 = 1;
+
+    /// Returns `None` if `desc` is too short for its header
+    pub fn new(desc: &'elf [u8]) -> Option<SveRegisters<'elf>> {
+        (desc.len() >= Self::HEADER_SIZE).then_some(Self { desc })
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.desc[offset], self.desc[offset + 1]])
+    }
+
+    /// Returns the vector length in bytes
+    #[inline]
+    pub fn vl(&self) -> u16 {
+        self.u16_at(8)
+    }
+
+    #[inline]
+    pub fn max_vl(&self) -> u16 {
+        self.u16_at(10)
+    }
+
+    #[inline]
+    pub fn flags(&self) -> u16 {
+        self.u16_at(12)
+    }
+
+    /// Returns `true` if the registers are stored in SVE form
+    ///
+    /// Otherwise only the FPSIMD registers were live, and they are stored in that form.
+    #[inline]
+    pub fn is_sve(&self) -> bool {
+        self.flags() & Self::REGS_SVE != 0
+    }
+
+    /// Returns the registers in FPSIMD form, if they are not stored in SVE form
+    pub fn fpsimd(&self) -> Option<FpsimdRegisters<'elf>> {
+        if self.is_sve() {
+            return None;
+        }
+        FpsimdRegisters::new(&self.desc[Self::HEADER_SIZE..])
+    }
+
+    fn get(&self, offset: usize, len: usize) -> Option<&'elf [u8]> {
+        if !self.is_sve() {
+            return None;
+        }
+        self.desc.get(offset..)?.get(..len)
+    }
+
+    fn pregs_offset(&self) -> usize {
+        Self::HEADER_SIZE + 32 * self.vl() as usize
+    }
+
+    fn ffr_offset(&self) -> usize {
+        self.pregs_offset() + 16 * (self.vl() as usize / 8)
+    }
+
+    fn fpsr_offset(&self) -> usize {
+        (self.ffr_offset() + self.vl() as usize / 8).next_multiple_of(16)
+    }
+
+    /// Returns the vector register `z(index)`
+    pub fn z(&self, index: usize) -> Option<&'elf [u8]> {
+        let vl = self.vl() as usize;
+        (index < 32).then_some(())?;
+        self.get(Self::HEADER_SIZE + index * vl, vl)
+    }
+
+    /// Returns the predicate register `p(index)`
+    pub fn p(&self, index: usize) -> Option<&'elf [u8]> {
+        let len = self.vl() as usize / 8;
+        (index < 16).then_some(())?;
+        self.get(self.pregs_offset() + index * len, len)
+    }
+
+    /// Returns the first-fault register
+    pub fn ffr(&self) -> Option<&'elf [u8]> {
+        self.get(self.ffr_offset(), self.vl() as usize / 8)
+    }
+
+    pub fn fpsr(&self) -> Option<u32> {
+        read_u32(self.get(self.fpsr_offset(), 4)?, 0)
+    }
+
+    pub fn fpcr(&self) -> Option<u32> {
+        read_u32(self.get(self.fpsr_offset() + 4, 4)?, 0)
+    }
+}
+
+impl<'elf> Thread<'_, 'elf> {
+    /// Returns the thread's `XSAVE` area, on x86-64
+    ///
+    /// Falls back to the legacy area from `NT_FPREGSET` if there is no `NT_X86_XSTATE`.
+    pub fn xsave(&self) -> Option<XSaveArea<'elf>> {
+        if self.core().elf().machine() != Machine::X86_64 {
+            return None;
+        }
+        let layout = self
+            .core()
+            .notes()
+            .find(|note| note.is(b"LINUX", NT_X86_XSAVE_LAYOUT))
+            .map(|note| XSaveLayout::new(note.desc()));
+        let note = self
+            .note(NT_X86_XSTATE)
+            .or_else(|| self.note(NT_FPREGSET))?;
+        XSaveArea::new(note.desc(), layout)
+    }
+
+    /// Returns the thread's SIMD registers, on AArch64
+    pub fn fpsimd(&self) -> Option<FpsimdRegisters<'elf>> {
+        if self.core().elf().machine() != Machine::Aarch64 {
+            return None;
+        }
+        FpsimdRegisters::new(self.note(NT_FPREGSET)?.desc())
+    }
+
+    /// Returns the thread's SVE registers, on AArch64
+    pub fn sve(&self) -> Option<SveRegisters<'elf>> {
+        if self.core().elf().machine() != Machine::Aarch64 {
+            return None;
+        }
+        SveRegisters::new(self.note(NT_ARM_SVE)?.desc())
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..)?.get(..4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..)?.get(..8)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
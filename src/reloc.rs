@@ -28,7 +28,338 @@
  * SPDX-License-Identifier: BSD-3-Clause
  */
 
-use crate::assert_struct_size;
+use crate::{
+    assert_struct_size,
+    table::{impl_entry, Table},
+    Elf, Endian, LittleEndian, ParseError, Section, SectionType, StringTable, Structure, Sym,
+    Symbol, I32, I64, U32, U64,
+};
+use core::fmt;
+
+impl<'elf> Elf<'elf> {
+    /// Returns the `SHT_RELA` entries which apply to the section at `index`
+    ///
+    /// The entries of every relocation section whose `sh_info` refers to the section are
+    /// decoded in the file's class and byte order. Returns an error if any of those
+    /// sections is malformed.
+    pub(crate) fn relas_for_section(
+        &self,
+        index: u16,
+    ) -> Result<SectionRelas<'_, 'elf>, ParseError> {
+        let relas = SectionRelas {
+            elf: self,
+            target: index,
+        };
+        for rel in relas.sections() {
+            Table::<Rela>::from_section(&rel)?;
+        }
+        Ok(relas)
+    }
+
+    /// Resolve an address stored at `offset` in a section of a relocatable file
+    ///
+    /// Sections of metadata such as `.stack_sizes` hold absolute addresses which are filled
+    /// in by relocations against their text sections. Returns the address along with the
+    /// index of the section it is relative to, or the stored value `raw` and `None` if no
+    /// relocation applies at `offset`.
+    pub(crate) fn relocated_address(
+        &self,
+        relas: SectionRelas<'_, 'elf>,
+        offset: u64,
+        raw: u64,
+    ) -> (u64, Option<u16>) {
+        relas
+            .iter()
+            .find(|rela| rela.offset() == offset)
+            .and_then(|rela| {
+                let sym = self.symtab()?.nth(rela.sym() as usize)?;
+                let address = sym.value().wrapping_add(rela.addend() as u64);
+                Some((address, Some(sym.section_index())))
+            })
+            .unwrap_or((raw, None))
+    }
+}
+
+/// The `SHT_RELA` entries which apply to a section
+///
+/// Returned by [`Elf::relas_for_section()`], which has checked that every relocation
+/// section can be decoded.
+#[derive(Clone, Copy)]
+pub(crate) struct SectionRelas<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    target: u16,
+}
+
+impl<'a, 'elf> SectionRelas<'a, 'elf> {
+    /// Returns the `SHT_RELA` sections which apply to the section, in section header order
+    pub(crate) fn sections(self) -> impl Iterator<Item = Section<'a, 'elf>> {
+        let target = self.target;
+        self.elf.sections().filter(move |rel| {
+            rel.section_type() == SectionType::Rela && rel.info() == target as u32
+        })
+    }
+
+    /// Returns the entries of all of the sections, in the crate's layout
+    pub(crate) fn iter(self) -> impl Iterator<Item = Rela> + 'a {
+        self.sections()
+            .filter_map(|rel| Table::<Rela>::from_section(&rel).ok())
+            .flat_map(Table::iter)
+    }
+}
+
+impl<'a, 'elf> Section<'a, 'elf> {
+    /// Returns an iterator over the relocations which apply to this section
+    ///
+    /// These are the entries of every `SHT_REL` and `SHT_RELA` section whose `sh_info`
+    /// refers to this section, in section header order. Each entry is joined with the
+    /// symbol table given by its relocation section's `sh_link`.
+    pub fn relocations(&self) -> Relocations<'a, 'elf> {
+        Relocations {
+            elf: self.elf(),
+            target: self.index(),
+            next_section: 1,
+            current: None,
+        }
+    }
+
+    /// Returns an iterator over the entries of this `SHT_REL` or `SHT_RELA` section
+    ///
+    /// Each entry is joined with the symbol table given by `sh_link`. The section they
+    /// apply to is returned by [`relocated_section()`](Section::relocated_section).
+    ///
+    /// Returns an error if this is not a relocation section, its entry size does not match
+    /// its type, or its contents lie outside of the file.
+    pub fn relocation_entries(&self) -> Result<RelocationEntries<'a, 'elf>, ParseError> {
+        let section = match self.section_type() {
+            SectionType::Rel | SectionType::Rela => RelocSection::new(self)?,
+            _ => {
+                return Err(ParseError::new(
+                    Structure::Section(self.index()),
+                    self.file_offset() as u64,
+                    "not a relocation section",
+                ))
+            }
+        };
+
+        Ok(RelocationEntries {
+            elf: self.elf(),
+            section,
+        })
+    }
+
+    /// Returns the section the entries of this `SHT_REL` or `SHT_RELA` section apply to
+    ///
+    /// This is the section given by `sh_info`. Returns `None` if this is not a relocation
+    /// section, or it applies to no section in particular, as with `.rela.dyn`.
+    pub fn relocated_section(&self) -> Option<Section<'a, 'elf>> {
+        self.elf().section(self.info_as_section_index()?)
+    }
+}
+
+/// The entries of one relocation section
+#[derive(Clone, Copy)]
+enum RelocEntries<'elf> {
+    Rel(Table<'elf, Rel>),
+    Rela(Table<'elf, Rela>),
+}
+
+impl RelocEntries<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Rel(table) => table.len(),
+            Self::Rela(table) => table.len(),
+        }
+    }
+
+    /// Returns the offset, info, and addend of the entry at `i`
+    fn get(&self, i: usize) -> Option<(u64, RelocInfo, Option<i64>)> {
+        match self {
+            Self::Rel(table) => table.get(i).map(|rel| (rel.offset(), rel.info(), None)),
+            Self::Rela(table) => table
+                .get(i)
+                .map(|rela| (rela.offset(), rela.info(), Some(rela.addend()))),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct RelocSection<'elf> {
+    index: u16,
+    entries: RelocEntries<'elf>,
+    symbols: Option<Table<'elf, Sym>>,
+    strtab: Option<StringTable<'elf>>,
+    next: usize,
+}
+
+impl<'elf> RelocSection<'elf> {
+    /// Returns the entries of `sect`, which must be an `SHT_REL` or `SHT_RELA` section
+    fn new(sect: &Section<'_, 'elf>) -> Result<RelocSection<'elf>, ParseError> {
+        let entries = match sect.section_type() {
+            SectionType::Rel => RelocEntries::Rel(Table::from_section(sect)?),
+            _ => RelocEntries::Rela(Table::from_section(sect)?),
+        };
+
+        let symtab = sect.linked_section();
+        let symbols = symtab
+            .as_ref()
+            .and_then(|symtab| Table::from_section(symtab).ok());
+        let strtab = symtab.and_then(|symtab| symtab.linked_string_table());
+
+        Ok(RelocSection {
+            index: sect.index(),
+            entries,
+            symbols,
+            strtab,
+            next: 0,
+        })
+    }
+
+    /// Returns the next entry of the section, joined with its symbol table
+    fn next_entry<'a>(&mut self, elf: &'a Elf<'elf>) -> Option<Relocation<'a, 'elf>> {
+        while self.next < self.entries.len() {
+            let i = self.next;
+            self.next += 1;
+
+            if let Some((offset, info, addend)) = self.entries.get(i) {
+                return Some(Relocation {
+                    elf,
+                    section: self.index,
+                    symbols: self.symbols,
+                    strtab: self.strtab,
+                    offset,
+                    info,
+                    addend,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the relocations which apply to a section
+///
+/// Returned by [`Section::relocations()`].
+#[derive(Clone)]
+pub struct Relocations<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    target: u16,
+    next_section: u16,
+    current: Option<RelocSection<'elf>>,
+}
+
+impl<'a, 'elf> Iterator for Relocations<'a, 'elf> {
+    type Item = Relocation<'a, 'elf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.target == 0 {
+            return None;
+        }
+
+        loop {
+            if let Some(current) = &mut self.current {
+                if let Some(rel) = current.next_entry(self.elf) {
+                    return Some(rel);
+                }
+            }
+
+            let sect = self.elf.section(self.next_section)?;
+            self.next_section += 1;
+            self.current = match sect.info() == self.target as u32 {
+                true => match sect.section_type() {
+                    SectionType::Rel | SectionType::Rela => RelocSection::new(&sect).ok(),
+                    _ => None,
+                },
+                false => None,
+            };
+        }
+    }
+}
+
+/// An iterator over the entries of one relocation section
+///
+/// Returned by [`Section::relocation_entries()`].
+#[derive(Clone)]
+pub struct RelocationEntries<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    section: RelocSection<'elf>,
+}
+
+impl<'a, 'elf> Iterator for RelocationEntries<'a, 'elf> {
+    type Item = Relocation<'a, 'elf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.section.next_entry(self.elf)
+    }
+}
+
+/// A relocation entry, joined with its symbol table
+#[derive(Clone, Copy)]
+pub struct Relocation<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    section: u16,
+    symbols: Option<Table<'elf, Sym>>,
+    strtab: Option<StringTable<'elf>>,
+    offset: u64,
+    info: RelocInfo,
+    addend: Option<i64>,
+}
+
+impl<'a, 'elf> Relocation<'a, 'elf> {
+    /// Returns the index of the `SHT_REL` or `SHT_RELA` section containing the entry
+    #[inline]
+    pub fn section_index(&self) -> u16 {
+        self.section
+    }
+
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[inline]
+    pub fn info(&self) -> RelocInfo {
+        self.info
+    }
+
+    #[inline]
+    pub fn kind(&self) -> RelocKind {
+        self.info.kind()
+    }
+
+    /// Returns the index of the symbol in the associated symbol table
+    #[inline]
+    pub fn symbol_index(&self) -> u32 {
+        self.info.symbol()
+    }
+
+    /// Returns the explicit addend, or `None` for `SHT_REL` entries
+    #[inline]
+    pub fn addend(&self) -> Option<i64> {
+        self.addend
+    }
+
+    /// Returns the symbol the relocation refers to, or `None` if it has no symbol
+    pub fn symbol(&self) -> Option<Symbol<'a, 'elf>> {
+        match self.symbol_index() {
+            0 => None,
+            index => {
+                let sym = self.symbols?.get(index as usize)?;
+                Some(Symbol::new(self.elf, self.strtab, sym))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Relocation<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Relocation")
+            .field("offset", &format_args!("{:#x}", self.offset))
+            .field("kind", &self.kind())
+            .field("symbol", &self.symbol().and_then(|sym| sym.name()))
+            .field("addend", &self.addend)
+            .finish()
+    }
+}
 
 #[repr(transparent)]
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
@@ -52,48 +383,234 @@ impl RelocInfo {
 }
 
 #[repr(C)]
-pub struct Rel {
-    offset: u64,
-    info: RelocInfo,
+#[derive(Clone, Copy)]
+pub struct Rel<E: Endian = LittleEndian> {
+    offset: U64<E>,
+    info: U64<E>,
+}
+
+assert_struct_size!(Rel, 16);
+
+impl<E: Endian> Rel<E> {
+    /// Re-encode the entry in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> Rel<F> {
+        Rel {
+            offset: self.offset.convert(),
+            info: self.info.convert(),
+        }
+    }
+
+    pub const fn new(offset: u64, info: RelocInfo) -> Rel<E> {
+        Rel {
+            offset: U64::new(offset),
+            info: U64::new(info.0),
+        }
+    }
+
+    /// Returns the entry as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    pub fn set_info(&mut self, info: RelocInfo) {
+        self.info.set(info.0);
+    }
+
+    #[inline]
+    pub const fn offset(&self) -> u64 {
+        self.offset.get()
+    }
+
+    #[inline]
+    pub const fn info(&self) -> RelocInfo {
+        RelocInfo(self.info.get())
+    }
+
+    #[inline]
+    pub const fn sym(&self) -> u32 {
+        self.info().symbol()
+    }
+
+    #[inline]
+    pub const fn kind(&self) -> RelocKind {
+        self.info().kind()
+    }
 }
 
 #[repr(C)]
-#[derive(Clone, Default, Eq, Hash, PartialEq)]
-pub struct Rela {
-    pub offset: u64,
-    pub info: RelocInfo,
-    pub addend: i64,
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub struct Rela<E: Endian = LittleEndian> {
+    offset: U64<E>,
+    info: U64<E>,
+    addend: I64<E>,
 }
 
 assert_struct_size!(Rela, 24);
 
-impl core::fmt::Debug for Rela {
+impl<E: Endian> core::fmt::Debug for Rela<E> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Rela")
-            .field("offset", &format_args!("{:#018x}", self.offset))
+            .field("offset", &format_args!("{:#018x}", self.offset()))
             .field("symbol", &format_args!("{:#018x}", self.sym()))
             .field("kind", &self.kind())
-            .field("addend", &format_args!("{:#018x}", self.addend))
+            .field("addend", &format_args!("{:#018x}", self.addend()))
             .finish()
     }
 }
 
-impl Rela {
+impl<E: Endian> Rela<E> {
+    /// Re-encode the entry in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> Rela<F> {
+        Rela {
+            offset: self.offset.convert(),
+            info: self.info.convert(),
+            addend: self.addend.convert(),
+        }
+    }
+
+    pub const fn new(offset: u64, info: RelocInfo, addend: i64) -> Rela<E> {
+        Rela {
+            offset: U64::new(offset),
+            info: U64::new(info.0),
+            addend: I64::new(addend),
+        }
+    }
+
+    /// Returns the entry as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    pub fn set_info(&mut self, info: RelocInfo) {
+        self.info.set(info.0);
+    }
+
+    #[inline]
+    pub const fn offset(&self) -> u64 {
+        self.offset.get()
+    }
+
+    #[inline]
+    pub const fn info(&self) -> RelocInfo {
+        RelocInfo(self.info.get())
+    }
+
+    #[inline]
+    pub const fn addend(&self) -> i64 {
+        self.addend.get()
+    }
+
     #[inline]
     pub const fn sym(&self) -> u32 {
-        self.info.symbol()
+        self.info().symbol()
     }
 
     #[inline]
     pub const fn kind(&self) -> RelocKind {
-        self.info.kind()
+        self.info().kind()
+    }
+}
+
+/// Widen the `r_info` field of an ELF32 entry, which packs the symbol index into 24 bits
+/// and the type into 8
+const fn widen_info(info: u32) -> RelocInfo {
+    RelocInfo::new(info >> 8, RelocKind(info & 0xff))
+}
+
+/// A relocation entry of an ELF32 file, without an addend
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Rel32<E: Endian = LittleEndian> {
+    offset: U32<E>,
+    info: U32<E>,
+}
+
+assert_struct_size!(Rel32, 8);
+
+impl<E: Endian> From<Rel32<E>> for Rel<E> {
+    fn from(rel: Rel32<E>) -> Rel<E> {
+        Rel::new(rel.offset(), rel.info())
+    }
+}
+
+impl_entry!(Rel, Rel32);
+
+impl<E: Endian> Rel32<E> {
+    /// Returns the entry as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    #[inline]
+    pub const fn offset(&self) -> u64 {
+        self.offset.get() as u64
+    }
+
+    /// Returns the symbol index and type, in the ELF64 encoding
+    #[inline]
+    pub const fn info(&self) -> RelocInfo {
+        widen_info(self.info.get())
+    }
+}
+
+/// A relocation entry of an ELF32 file, with an explicit addend
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Rela32<E: Endian = LittleEndian> {
+    offset: U32<E>,
+    info: U32<E>,
+    addend: I32<E>,
+}
+
+assert_struct_size!(Rela32, 12);
+
+impl<E: Endian> From<Rela32<E>> for Rela<E> {
+    fn from(rela: Rela32<E>) -> Rela<E> {
+        Rela::new(rela.offset(), rela.info(), rela.addend())
+    }
+}
+
+impl_entry!(Rela, Rela32);
+
+impl<E: Endian> Rela32<E> {
+    /// Returns the entry as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    #[inline]
+    pub const fn offset(&self) -> u64 {
+        self.offset.get() as u64
+    }
+
+    /// Returns the symbol index and type, in the ELF64 encoding
+    #[inline]
+    pub const fn info(&self) -> RelocInfo {
+        widen_info(self.info.get())
+    }
+
+    #[inline]
+    pub const fn addend(&self) -> i64 {
+        self.addend.get() as i64
     }
 }
 
 #[repr(transparent)]
-#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
 pub struct RelocKind(u32);
 
+impl RelocKind {
+    #[inline]
+    pub const fn from_u32(x: u32) -> RelocKind {
+        Self(x)
+    }
+
+    #[inline]
+    pub const fn to_u32(self) -> u32 {
+        self.0
+    }
+}
+
 macro_rules! reloc_kinds {
     ($(
         $(#[$meta:meta])*
@@ -220,3 +737,101 @@ impl RelocKind {
         const X86_64_REX_GOTPCRELX   = 42;
     }
 }
+
+/// AArch64
+///
+/// - `A`       - the addend used to compute the value of the relocatable field
+/// - `B`       - the base address at which the object has been loaded into memory
+/// - `P`       - the place of the storage unit being relocated
+/// - `S`       - the value of the symbol referenced by the relocation entry
+/// - `Page(x)` - the address `x` with the low 12 bits cleared
+impl RelocKind {
+    reloc_kinds! {
+        const AARCH64_NONE                  = 0;
+        const AARCH64_ABS64                 = 257;
+        const AARCH64_ABS32                 = 258;
+        const AARCH64_ABS16                 = 259;
+        const AARCH64_PREL64                = 260;
+        const AARCH64_PREL32                = 261;
+        const AARCH64_PREL16                = 262;
+        const AARCH64_ADR_PREL_LO21         = 274;
+        const AARCH64_ADR_PREL_PG_HI21      = 275;
+        const AARCH64_ADR_PREL_PG_HI21_NC   = 276;
+        const AARCH64_ADD_ABS_LO12_NC       = 277;
+        const AARCH64_LDST8_ABS_LO12_NC     = 278;
+        const AARCH64_TSTBR14               = 279;
+        const AARCH64_CONDBR19              = 280;
+        const AARCH64_JUMP26                = 282;
+        const AARCH64_CALL26                = 283;
+        const AARCH64_LDST16_ABS_LO12_NC    = 284;
+        const AARCH64_LDST32_ABS_LO12_NC    = 285;
+        const AARCH64_LDST64_ABS_LO12_NC    = 286;
+        const AARCH64_LDST128_ABS_LO12_NC   = 299;
+        const AARCH64_COPY                  = 1024;
+        const AARCH64_GLOB_DAT              = 1025;
+        const AARCH64_JUMP_SLOT             = 1026;
+        const AARCH64_RELATIVE              = 1027;
+        const AARCH64_TLS_DTPMOD            = 1028;
+        const AARCH64_TLS_DTPREL            = 1029;
+        const AARCH64_TLS_TPREL             = 1030;
+        const AARCH64_TLSDESC               = 1031;
+        const AARCH64_IRELATIVE             = 1032;
+    }
+}
+
+/// BPF
+///
+/// BPF objects use `SHT_REL` sections, with the addend stored in the relocated field.
+impl RelocKind {
+    reloc_kinds! {
+        const BPF_NONE                      = 0;
+        /// 64-bit immediate of an `ld_imm64` instruction, e.g. a map reference
+        const BPF_64_64                     = 1;
+        const BPF_64_ABS64                  = 2;
+        const BPF_64_ABS32                  = 3;
+        const BPF_64_NODYLD32               = 4;
+        /// 32-bit immediate of a call instruction, in instructions
+        const BPF_64_32                     = 10;
+    }
+}
+
+#[cfg(all(test, feature = "testdata"))]
+mod tests {
+    use crate::{testdata::Fixture, Elf, SectionType};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn relas_are_decoded_in_the_file_encoding() {
+        for little in [Fixture::new(), Fixture::new().elf32()] {
+            let little = little.relocating_data();
+            let big = little.big_endian().with_machine(little.machine());
+
+            let little_file = little.build();
+            let little_elf = Elf::new(&little_file).unwrap();
+            let expected: Vec<_> = little_elf.relas_for_section(2).unwrap().iter().collect();
+            assert!(!expected.is_empty());
+
+            let big_file = big.build();
+            let big_elf = Elf::new(&big_file).unwrap();
+            let relas: Vec<_> = big_elf.relas_for_section(2).unwrap().iter().collect();
+            assert_eq!(relas, expected);
+        }
+    }
+
+    #[test]
+    fn malformed_relas_are_an_error() {
+        let mut file = Fixture::new().relocating_data().build();
+        let elf = Elf::new(&file).unwrap();
+        let rela = elf
+            .sections()
+            .find(|sect| sect.section_type() == SectionType::Rela)
+            .unwrap();
+        // Make sh_size no longer a multiple of sh_entsize
+        let offset = elf.shdr_offset() + rela.index() as usize * elf.shdr_size() as usize + 32;
+        let size = rela.size() as u64 - 1;
+        file[offset..offset + 8].copy_from_slice(&size.to_le_bytes());
+
+        let elf = Elf::new(&file).unwrap();
+        assert!(elf.relas_for_section(2).is_err());
+    }
+}
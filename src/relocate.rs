@@ -0,0 +1,1350 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Relocation application
+
+use crate::{
+    Class, Data, Elf, Endian, FileHeader, LittleEndian, Machine, ParseError, Rela, RelocKind,
+};
+use core::marker::PhantomData;
+
+/// How a [`Patch`] combines its value with the current contents of the storage unit
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PatchOp {
+    /// Overwrite the storage unit
+    Write,
+    /// Replace only the bits selected by the mask (used for instruction immediates)
+    Insert(u64),
+    /// Add the value to the storage unit
+    Add,
+    /// Subtract the value from the storage unit
+    Sub,
+}
+
+/// A single modification of memory produced by a relocation
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Patch {
+    /// Runtime address of the storage unit
+    pub address: u64,
+    /// Size of the storage unit, in bytes
+    pub size: u8,
+    pub value: u64,
+    pub op: PatchOp,
+}
+
+impl Patch {
+    #[inline]
+    pub const fn new(address: u64, size: u8, value: u64, op: PatchOp) -> Patch {
+        Self {
+            address,
+            size,
+            value,
+            op,
+        }
+    }
+
+    /// Returns a mask of the bits covered by the storage unit
+    #[inline]
+    pub const fn size_mask(&self) -> u64 {
+        match self.size {
+            8.. => u64::MAX,
+            n => (1 << (n * 8)) - 1,
+        }
+    }
+
+    /// Returns the new contents of the storage unit, given its current contents
+    pub const fn apply(&self, old: u64) -> u64 {
+        let new = match self.op {
+            PatchOp::Write => self.value,
+            PatchOp::Insert(mask) => (old & !mask) | (self.value & mask),
+            PatchOp::Add => old.wrapping_add(self.value),
+            PatchOp::Sub => old.wrapping_sub(self.value),
+        };
+
+        new & self.size_mask()
+    }
+
+    /// Returns `true` if each byte of the result depends only on the same byte of the
+    /// current contents, i.e. the patch may be applied one byte at a time
+    #[inline]
+    pub const fn is_bytewise(&self) -> bool {
+        matches!(self.op, PatchOp::Write | PatchOp::Insert(_))
+    }
+}
+
+/// The (at most two) patches produced by a single relocation
+#[derive(Clone, Copy, Debug)]
+pub struct Patches {
+    patches: [Patch; 2],
+    len: usize,
+}
+
+impl Patches {
+    const EMPTY: Patch = Patch::new(0, 0, 0, PatchOp::Write);
+
+    /// A relocation which does not modify memory
+    pub const NONE: Patches = Patches {
+        patches: [Self::EMPTY; 2],
+        len: 0,
+    };
+
+    #[inline]
+    pub const fn one(patch: Patch) -> Patches {
+        Self {
+            patches: [patch, Self::EMPTY],
+            len: 1,
+        }
+    }
+
+    #[inline]
+    pub const fn two(first: Patch, second: Patch) -> Patches {
+        Self {
+            patches: [first, second],
+            len: 2,
+        }
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[Patch] {
+        &self.patches[..self.len]
+    }
+}
+
+impl IntoIterator for Patches {
+    type Item = Patch;
+    type IntoIter = core::iter::Take<core::array::IntoIter<Patch, 2>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.patches.into_iter().take(self.len)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RelocError {
+    /// The relocation type is not supported for this machine
+    Unsupported { machine: Machine, kind: RelocKind },
+    /// The resolver could not provide a value for the symbol with this index
+    UndefinedSymbol(u32),
+    /// The storage unit at this address lies outside the memory being relocated
+    OutOfBounds(u64),
+    /// A RISC-V `PCREL_LO12` relocation refers to an address with no `PCREL_HI20` relocation
+    MissingHi20(u64),
+    /// An arithmetic patch at this address crosses a page boundary
+    Straddle(u64),
+    /// The computed value does not fit in the relocated field
+    Overflow(RelocOverflow),
+    /// The file's machine, class, or byte order does not match the relocator
+    Incompatible {
+        machine: Machine,
+        class: Class,
+        data: Data,
+    },
+    /// A relocation or symbol table is malformed
+    Malformed(ParseError),
+}
+
+/// Details of a relocation whose value does not fit in its field
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RelocOverflow {
+    /// Index of the relocation within its table
+    pub index: usize,
+    /// Index of the section being relocated, if known
+    pub section: Option<u16>,
+    pub kind: RelocKind,
+    /// Index of the symbol in the associated symbol table
+    pub symbol: u32,
+    /// Runtime address of the storage unit
+    pub address: u64,
+    /// The value which was to be stored, before truncation
+    pub value: u64,
+    /// Width of the field, in bits
+    pub bits: u8,
+}
+
+/// The values a relocated field can represent
+///
+/// A field of zero bits can only hold zero, and a field of 64 bits or more can hold any
+/// value.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FieldRange {
+    /// A two's complement value of this many bits
+    Signed(u8),
+    /// An unsigned value of this many bits
+    Unsigned(u8),
+    /// Either a signed or an unsigned value of this many bits
+    Any(u8),
+}
+
+impl FieldRange {
+    /// Returns the width of the field, in bits
+    pub const fn bits(self) -> u8 {
+        match self {
+            Self::Signed(n) | Self::Unsigned(n) | Self::Any(n) => n,
+        }
+    }
+
+    /// Returns `true` if `x` can be stored in the field without loss
+    pub fn contains(self, x: u64) -> bool {
+        let signed = |n: u8| match n {
+            0 => x == 0,
+            64.. => true,
+            _ => {
+                let shift = 64 - n as u32;
+                (((x << shift) as i64) >> shift) as u64 == x
+            }
+        };
+        let unsigned = |n: u8| match n {
+            0 => x == 0,
+            64.. => true,
+            _ => x >> n == 0,
+        };
+
+        match self {
+            Self::Signed(n) => signed(n),
+            Self::Unsigned(n) => unsigned(n),
+            Self::Any(n) => signed(n) || unsigned(n),
+        }
+    }
+}
+
+/// How the value of a relocation is computed
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RelocClass {
+    /// The relocation does not modify memory
+    None,
+    /// `S + A`, possibly only part of it
+    Absolute,
+    /// `S + A - P`, possibly only part of it
+    PcRelative,
+    /// `B + A`, which does not depend on a symbol
+    Relative,
+    /// `S`, usually the address of a symbol defined by another object
+    Import,
+    /// `S + A` is added to or subtracted from the current contents
+    Arithmetic,
+    /// The symbol refers to the place of another relocation, whose result is used
+    Paired,
+}
+
+/// The inputs to a relocation's computation
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RelocValues {
+    /// `S`: the value of the symbol
+    pub symbol: u64,
+    /// `A`
+    pub addend: u64,
+    /// `P`: the runtime address of the storage unit
+    pub place: u64,
+    /// `B`
+    pub load_bias: u64,
+}
+
+impl RelocValues {
+    /// Returns `S + A`
+    #[inline]
+    pub const fn absolute(&self) -> u64 {
+        self.symbol.wrapping_add(self.addend)
+    }
+
+    /// Returns `S + A - P`
+    #[inline]
+    pub const fn pc_relative(&self) -> u64 {
+        self.absolute().wrapping_sub(self.place)
+    }
+}
+
+/// Relocation support for one architecture
+///
+/// [`Relocator`] handles symbol resolution, range checking, and writing to memory, and
+/// defers to a backend for everything specific to the architecture. Backends for the
+/// machines supported by this crate are returned by [`backend_for()`]; others can be used
+/// with [`Relocator::with_backend()`].
+pub trait RelocationBackend: Sync {
+    fn machine(&self) -> Machine;
+
+    /// Returns the class of files whose relocations this backend applies
+    fn class(&self) -> Class {
+        Class::Bits64
+    }
+
+    /// Returns how `kind` is computed, or `None` if it is not supported
+    fn classify(&self, kind: RelocKind) -> Option<RelocClass>;
+
+    /// Returns `true` if computing `kind` requires the value of its symbol
+    fn needs_symbol(&self, kind: RelocKind) -> bool {
+        !matches!(
+            self.classify(kind),
+            None | Some(RelocClass::None | RelocClass::Relative)
+        )
+    }
+
+    /// Returns the sizes, in bytes, of the storage units patched by this backend
+    fn widths(&self) -> &'static [u8];
+
+    /// Compute the patches for a relocation, or `None` if it is not supported
+    fn compute(&self, kind: RelocKind, values: RelocValues) -> Option<Patches>;
+
+    /// Returns the storage units patched by a relocation at `place`, whose values are
+    /// placeholders, or `None` if it is not supported
+    fn fields(&self, kind: RelocKind, place: u64) -> Option<Patches> {
+        match self.classify(kind)? {
+            RelocClass::Paired => self.compute_paired(kind, place, 0),
+            _ => self.compute(
+                kind,
+                RelocValues {
+                    place,
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+
+    /// Returns the value stored in the field of a relocation whose field has a limited
+    /// range, along with that range
+    ///
+    /// Values which are stored shifted, such as branch offsets, are returned unshifted.
+    fn field(&self, kind: RelocKind, values: RelocValues) -> Option<(u64, FieldRange)> {
+        let _ = (kind, values);
+        None
+    }
+
+    /// Returns the kind of relocation referred to by a [`RelocClass::Paired`] relocation
+    fn pair_kind(&self, kind: RelocKind) -> Option<RelocKind> {
+        let _ = kind;
+        None
+    }
+
+    /// Compute the patches for a [`RelocClass::Paired`] relocation at `place`, given the
+    /// PC-relative `offset` computed by the relocation it refers to
+    fn compute_paired(&self, kind: RelocKind, place: u64, offset: u64) -> Option<Patches> {
+        let _ = (kind, place, offset);
+        None
+    }
+
+    /// Returns `true` if instructions are little-endian regardless of the byte order of
+    /// data, in which case [`PatchOp::Insert`] patches are always made little-endian
+    fn little_endian_code(&self) -> bool {
+        false
+    }
+}
+
+/// Returns the built-in backend for `machine`
+pub fn backend_for(machine: Machine) -> Option<&'static dyn RelocationBackend> {
+    match machine {
+        Machine::X86_64 => Some(&X86_64Backend),
+        Machine::Aarch64 => Some(&Aarch64Backend),
+        Machine::Riscv => Some(&RiscvBackend),
+        _ => None,
+    }
+}
+
+/// Applies relocations for one machine
+///
+/// Symbol values are provided by the `resolve` callback, which receives the index of the
+/// symbol in the associated symbol table and returns its runtime address.
+///
+/// `E` is the byte order of the relocation entries and file header passed in. Memory is
+/// patched in the byte order of the target, regardless of the byte order of the host.
+/// This is `E` unless set otherwise, but the two differ for big-endian files read through
+/// [`Elf`], whose structures are re-encoded in little-endian byte order.
+pub struct Relocator<R, E: Endian = LittleEndian> {
+    machine: Machine,
+    backend: Option<&'static dyn RelocationBackend>,
+    load_bias: u64,
+    section: Option<u16>,
+    target_big_endian: bool,
+    resolve: R,
+    _endian: PhantomData<E>,
+}
+
+impl<R, E: Endian> Relocator<R, E>
+where
+    R: FnMut(u32) -> Option<u64>,
+{
+    /// Create a relocator using the built-in backend for `machine`
+    ///
+    /// `load_bias` is the value `B`: the difference between the runtime and link-time
+    /// addresses of the object.
+    #[inline]
+    pub fn new(machine: Machine, load_bias: u64, resolve: R) -> Relocator<R, E> {
+        Self {
+            machine,
+            backend: backend_for(machine),
+            load_bias,
+            section: None,
+            target_big_endian: E::BIG_ENDIAN,
+            resolve,
+            _endian: PhantomData,
+        }
+    }
+
+    /// Create a relocator using `backend`
+    #[inline]
+    pub fn with_backend(
+        backend: &'static dyn RelocationBackend,
+        load_bias: u64,
+        resolve: R,
+    ) -> Relocator<R, E> {
+        Self {
+            machine: backend.machine(),
+            backend: Some(backend),
+            load_bias,
+            section: None,
+            target_big_endian: E::BIG_ENDIAN,
+            resolve,
+            _endian: PhantomData,
+        }
+    }
+
+    /// Create a relocator for the machine of the file described by `ehdr`
+    ///
+    /// Memory is patched in the byte order given by the file's identification bytes.
+    /// Returns [`RelocError::Incompatible`] if relocations for the file cannot be applied.
+    pub fn for_file(
+        ehdr: &FileHeader<E>,
+        load_bias: u64,
+        resolve: R,
+    ) -> Result<Relocator<R, E>, RelocError> {
+        let mut relocator = Self::new(ehdr.machine(), load_bias, resolve);
+        relocator.set_target_data(ehdr.data());
+        relocator.check_compatible(ehdr)?;
+        Ok(relocator)
+    }
+
+    /// Returns `true` if there is a built-in backend for `machine`
+    pub fn supports(machine: Machine) -> bool {
+        backend_for(machine).is_some()
+    }
+
+    /// Check that relocations from the file described by `ehdr` can be applied by this
+    /// relocator
+    ///
+    /// The file must be for this relocator's machine, of the backend's class, and in the
+    /// byte order of the memory the relocator patches.
+    pub fn check_compatible(&self, ehdr: &FileHeader<E>) -> Result<(), RelocError> {
+        let data = self.target_data();
+        let compatible = self.backend.is_some_and(|backend| {
+            ehdr.machine() == backend.machine() && ehdr.class() == backend.class()
+        });
+
+        if !compatible || ehdr.data() != data {
+            return Err(RelocError::Incompatible {
+                machine: ehdr.machine(),
+                class: ehdr.class(),
+                data: ehdr.data(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Set the index of the section being relocated, which is reported on overflow
+    #[inline]
+    pub fn set_section(&mut self, section: Option<u16>) {
+        self.section = section;
+    }
+
+    /// Set the byte order of the memory being patched
+    ///
+    /// Encodings other than [`Data::TwosCompBig`] are taken to be little-endian.
+    #[inline]
+    pub fn set_target_data(&mut self, data: Data) {
+        self.target_big_endian = data == Data::TwosCompBig;
+    }
+
+    /// Returns the byte order of the memory being patched
+    #[inline]
+    pub fn target_data(&self) -> Data {
+        match self.target_big_endian {
+            true => Data::TwosCompBig,
+            false => Data::TwosCompLittle,
+        }
+    }
+
+    #[inline]
+    pub fn machine(&self) -> Machine {
+        self.machine
+    }
+
+    #[inline]
+    pub fn load_bias(&self) -> u64 {
+        self.load_bias
+    }
+
+    /// Returns `true` if the storage unit of `patch` is stored most significant byte first
+    fn is_big_endian(&self, patch: &Patch) -> bool {
+        let le_code = self.backend.is_some_and(|b| b.little_endian_code());
+        self.target_big_endian && !(le_code && matches!(patch.op, PatchOp::Insert(_)))
+    }
+
+    fn symbol_value(&mut self, sym: u32) -> Result<u64, RelocError> {
+        match sym {
+            0 => Ok(0),
+            _ => (self.resolve)(sym).ok_or(RelocError::UndefinedSymbol(sym)),
+        }
+    }
+
+    /// Compute the patches for `relas[index]`
+    ///
+    /// `place_base` is added to each `r_offset` to find the runtime address of the storage
+    /// unit: this is the load bias for executables and shared objects, or the runtime address
+    /// of the target section for relocatable files. The whole table is needed because some
+    /// relocations refer to one another.
+    ///
+    /// Returns [`RelocError::Overflow`] if the value does not fit in the relocated field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds for `relas`.
+    pub fn compute(
+        &mut self,
+        relas: &[Rela<E>],
+        index: usize,
+        place_base: u64,
+    ) -> Result<Patches, RelocError> {
+        let rela = &relas[index];
+        let kind = rela.kind();
+        let unsupported = RelocError::Unsupported {
+            machine: self.machine,
+            kind,
+        };
+        let backend = self.backend.ok_or(unsupported)?;
+        let class = backend.classify(kind).ok_or(unsupported)?;
+        let mut values = RelocValues {
+            symbol: 0,
+            addend: rela.addend() as u64,
+            place: place_base.wrapping_add(rela.offset()),
+            load_bias: self.load_bias,
+        };
+
+        if class == RelocClass::Paired {
+            // The symbol refers to the place of the relocation which computed the full
+            // offset, such as the `auipc` of a RISC-V `PCREL_HI20` relocation.
+            let pair_kind = backend.pair_kind(kind).ok_or(unsupported)?;
+            let hi_place = self.symbol_value(rela.sym())?.wrapping_add(values.addend);
+            let hi = relas
+                .iter()
+                .find(|r| r.kind() == pair_kind && place_base.wrapping_add(r.offset()) == hi_place)
+                .ok_or(RelocError::MissingHi20(hi_place))?;
+            let s = self.symbol_value(hi.sym())?;
+            let offset = s.wrapping_add(hi.addend() as u64).wrapping_sub(hi_place);
+
+            return backend
+                .compute_paired(kind, values.place, offset)
+                .ok_or(unsupported);
+        }
+
+        if backend.needs_symbol(kind) {
+            values.symbol = self.symbol_value(rela.sym())?;
+        }
+
+        if let Some((value, range)) = backend.field(kind, values) {
+            if !range.contains(value) {
+                return Err(RelocError::Overflow(RelocOverflow {
+                    index,
+                    section: self.section,
+                    kind,
+                    symbol: rela.sym(),
+                    address: values.place,
+                    value,
+                    bits: range.bits(),
+                }));
+            }
+        }
+
+        let patches = backend.compute(kind, values).ok_or(unsupported)?;
+        match patches
+            .as_slice()
+            .iter()
+            .all(|patch| backend.widths().contains(&patch.size))
+        {
+            true => Ok(patches),
+            false => Err(unsupported),
+        }
+    }
+
+    /// Apply `relas` to `image`, which is mapped at the runtime address `image_base`
+    pub fn apply(
+        &mut self,
+        relas: &[Rela<E>],
+        place_base: u64,
+        image: &mut [u8],
+        image_base: u64,
+    ) -> Result<(), RelocError> {
+        for index in 0..relas.len() {
+            for patch in self.compute(relas, index, place_base)? {
+                let buf = patch
+                    .address
+                    .checked_sub(image_base)
+                    .and_then(|offset| usize::try_from(offset).ok())
+                    .and_then(|offset| image.get_mut(offset..)?.get_mut(..patch.size as usize))
+                    .ok_or(RelocError::OutOfBounds(patch.address))?;
+
+                let big = self.is_big_endian(&patch);
+                write_unit(buf, patch.apply(read_unit(buf, big)), big);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the patches `apply()` would make to `image`, without writing to it
+    ///
+    /// Symbols are resolved and every patch is checked as for [`apply()`](Self::apply),
+    /// which fails with the same error, and `f` is called with each patch in the order it
+    /// would be made.
+    ///
+    /// As nothing is written, a storage unit patched by more than one relocation is
+    /// reported with its contents in `image`, unless the relocations are consecutive, as
+    /// with the paired `ADD` and `SUB` relocations of RISC-V.
+    pub fn dry_run<F>(
+        &mut self,
+        relas: &[Rela<E>],
+        place_base: u64,
+        image: &[u8],
+        image_base: u64,
+        mut f: F,
+    ) -> Result<(), RelocError>
+    where
+        F: FnMut(PatchReport),
+    {
+        let mut last: Option<PatchReport> = None;
+
+        for index in 0..relas.len() {
+            for patch in self.compute(relas, index, place_base)? {
+                let buf = patch
+                    .address
+                    .checked_sub(image_base)
+                    .and_then(|offset| usize::try_from(offset).ok())
+                    .and_then(|offset| image.get(offset..)?.get(..patch.size as usize))
+                    .ok_or(RelocError::OutOfBounds(patch.address))?;
+
+                let old = match last {
+                    Some(last) if last.address == patch.address && last.size == patch.size => {
+                        last.new
+                    }
+                    _ => read_unit(buf, self.is_big_endian(&patch)),
+                };
+                let report = PatchReport {
+                    index,
+                    section: self.section,
+                    kind: relas[index].kind(),
+                    address: patch.address,
+                    size: patch.size,
+                    old,
+                    new: patch.apply(old),
+                };
+
+                f(report);
+                last = Some(report);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply `relas` to memory which can only be accessed a page at a time
+    ///
+    /// Patches are collected into the page buffer `page`, whose length is the page size,
+    /// and each page is read from and written back to `store` as a whole. A page is only
+    /// written back once all consecutive patches to it have been made, so applying
+    /// relocations sorted by offset touches each page exactly once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `page` is not a power of two.
+    pub fn apply_batched<S>(
+        &mut self,
+        relas: &[Rela<E>],
+        place_base: u64,
+        store: &mut S,
+        page: &mut [u8],
+    ) -> Result<(), BatchError<S::Error>>
+    where
+        S: PageStore,
+    {
+        assert!(page.len().is_power_of_two());
+
+        let mut window = PageWindow {
+            store,
+            mask: !(page.len() as u64 - 1),
+            page,
+            addr: None,
+            dirty: false,
+        };
+
+        for index in 0..relas.len() {
+            for patch in self.compute(relas, index, place_base)? {
+                let size = patch.size as u64;
+                let first = patch.address & window.mask;
+                let last = patch.address.wrapping_add(size - 1) & window.mask;
+
+                let big = self.is_big_endian(&patch);
+
+                if first == last {
+                    let buf = window.bytes(patch.address, size)?;
+                    write_unit(buf, patch.apply(read_unit(buf, big)), big);
+                } else if patch.is_bytewise() {
+                    for i in 0..size {
+                        let byte = &mut window.bytes(patch.address + i, 1)?[0];
+                        let shift = match big {
+                            true => (size - 1 - i) * 8,
+                            false => i * 8,
+                        };
+                        *byte = (patch.apply((*byte as u64) << shift) >> shift) as u8;
+                    }
+                } else {
+                    return Err(RelocError::Straddle(patch.address).into());
+                }
+            }
+        }
+
+        window.flush().map_err(BatchError::Store)
+    }
+}
+
+/// Apply `relas` from `elf` to `image`, which is mapped at the runtime address `image_base`
+///
+/// The relocator is chosen according to [`Elf::machine()`]; see [`Relocator::for_file()`]
+/// and [`Relocator::apply()`].
+pub fn apply_for<R>(
+    elf: &Elf,
+    relas: &[Rela],
+    load_bias: u64,
+    place_base: u64,
+    image: &mut [u8],
+    image_base: u64,
+    resolve: R,
+) -> Result<(), RelocError>
+where
+    R: FnMut(u32) -> Option<u64>,
+{
+    Relocator::for_file(elf, load_bias, resolve)?.apply(relas, place_base, image, image_base)
+}
+
+/// A patch which would be made by [`Relocator::apply()`], as reported by
+/// [`Relocator::dry_run()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PatchReport {
+    /// Index of the relocation within its table
+    pub index: usize,
+    /// Index of the section being relocated, if known
+    pub section: Option<u16>,
+    pub kind: RelocKind,
+    /// Runtime address of the storage unit
+    pub address: u64,
+    /// Size of the storage unit, in bytes
+    pub size: u8,
+    /// Contents of the storage unit before the patch
+    pub old: u64,
+    /// Contents of the storage unit after the patch
+    pub new: u64,
+}
+
+/// Backing storage for [`Relocator::apply_batched()`], accessed a page at a time
+pub trait PageStore {
+    type Error;
+
+    /// Read the page at runtime address `addr` into `page`
+    fn read_page(&mut self, addr: u64, page: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Write back the modified page at runtime address `addr`
+    fn write_page(&mut self, addr: u64, page: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BatchError<E> {
+    Reloc(RelocError),
+    Store(E),
+}
+
+impl<E> From<RelocError> for BatchError<E> {
+    #[inline]
+    fn from(error: RelocError) -> Self {
+        Self::Reloc(error)
+    }
+}
+
+struct PageWindow<'a, S> {
+    store: &'a mut S,
+    page: &'a mut [u8],
+    mask: u64,
+    addr: Option<u64>,
+    dirty: bool,
+}
+
+impl<S: PageStore> PageWindow<'_, S> {
+    fn flush(&mut self) -> Result<(), S::Error> {
+        if let (Some(addr), true) = (self.addr, self.dirty) {
+            self.store.write_page(addr, self.page)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Returns `len` bytes at `addr`, which must not cross a page boundary
+    fn bytes(&mut self, addr: u64, len: u64) -> Result<&mut [u8], BatchError<S::Error>> {
+        let page_addr = addr & self.mask;
+
+        if self.addr != Some(page_addr) {
+            self.flush().map_err(BatchError::Store)?;
+            self.addr = None;
+            self.store
+                .read_page(page_addr, self.page)
+                .map_err(BatchError::Store)?;
+            self.addr = Some(page_addr);
+        }
+
+        self.dirty = true;
+        let offset = (addr - page_addr) as usize;
+        Ok(&mut self.page[offset..][..len as usize])
+    }
+}
+
+fn read_unit(buf: &[u8], big: bool) -> u64 {
+    match big {
+        true => buf.iter().fold(0, |acc, &b| acc << 8 | b as u64),
+        false => buf.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64),
+    }
+}
+
+fn write_unit(buf: &mut [u8], value: u64, big: bool) {
+    let len = buf.len();
+    for (i, b) in buf.iter_mut().enumerate() {
+        let shift = match big {
+            true => (len - 1 - i) * 8,
+            false => i * 8,
+        };
+        *b = (value >> shift) as u8;
+    }
+}
+
+#[inline]
+const fn write(p: u64, size: u8, value: u64) -> Option<Patches> {
+    Some(Patches::one(Patch::new(p, size, value, PatchOp::Write)))
+}
+
+#[inline]
+const fn insert(p: u64, size: u8, value: u64, mask: u64) -> Option<Patches> {
+    Some(Patches::one(Patch::new(
+        p,
+        size,
+        value,
+        PatchOp::Insert(mask),
+    )))
+}
+
+fn x86_64(kind: RelocKind, s: u64, a: u64, p: u64, b: u64) -> Option<Patches> {
+    let sa = s.wrapping_add(a);
+    let pcrel = sa.wrapping_sub(p);
+
+    match kind {
+        RelocKind::X86_64_NONE => Some(Patches::NONE),
+        RelocKind::X86_64_64 => write(p, 8, sa),
+        RelocKind::X86_64_PC32 | RelocKind::X86_64_PLT32 => write(p, 4, pcrel),
+        RelocKind::X86_64_GLOB_DAT | RelocKind::X86_64_JUMP_SLOT => write(p, 8, s),
+        RelocKind::X86_64_RELATIVE => write(p, 8, b.wrapping_add(a)),
+        RelocKind::X86_64_32 | RelocKind::X86_64_32S => write(p, 4, sa),
+        RelocKind::X86_64_16 => write(p, 2, sa),
+        RelocKind::X86_64_PC16 => write(p, 2, pcrel),
+        RelocKind::X86_64_8 => write(p, 1, sa),
+        RelocKind::X86_64_PC8 => write(p, 1, pcrel),
+        RelocKind::X86_64_PC64 => write(p, 8, pcrel),
+        _ => None,
+    }
+}
+
+fn aarch64(kind: RelocKind, s: u64, a: u64, p: u64, b: u64) -> Option<Patches> {
+    let sa = s.wrapping_add(a);
+    let pcrel = sa.wrapping_sub(p);
+    let adr = |x: u64| ((x & 3) << 29) | (((x >> 2) & 0x7ffff) << 5);
+    let lo12 = |shift: u32| ((sa & 0xfff) >> shift) << 10;
+
+    match kind {
+        RelocKind::AARCH64_NONE => Some(Patches::NONE),
+        RelocKind::AARCH64_ABS64 => write(p, 8, sa),
+        RelocKind::AARCH64_ABS32 => write(p, 4, sa),
+        RelocKind::AARCH64_ABS16 => write(p, 2, sa),
+        RelocKind::AARCH64_PREL64 => write(p, 8, pcrel),
+        RelocKind::AARCH64_PREL32 => write(p, 4, pcrel),
+        RelocKind::AARCH64_PREL16 => write(p, 2, pcrel),
+        RelocKind::AARCH64_ADR_PREL_LO21 => insert(p, 4, adr(pcrel), 0x60ffffe0),
+        RelocKind::AARCH64_ADR_PREL_PG_HI21 | RelocKind::AARCH64_ADR_PREL_PG_HI21_NC => {
+            let pages = (sa & !0xfff).wrapping_sub(p & !0xfff) >> 12;
+            insert(p, 4, adr(pages), 0x60ffffe0)
+        }
+        RelocKind::AARCH64_ADD_ABS_LO12_NC | RelocKind::AARCH64_LDST8_ABS_LO12_NC => {
+            insert(p, 4, lo12(0), 0x3ffc00)
+        }
+        RelocKind::AARCH64_LDST16_ABS_LO12_NC => insert(p, 4, lo12(1), 0x3ffc00),
+        RelocKind::AARCH64_LDST32_ABS_LO12_NC => insert(p, 4, lo12(2), 0x3ffc00),
+        RelocKind::AARCH64_LDST64_ABS_LO12_NC => insert(p, 4, lo12(3), 0x3ffc00),
+        RelocKind::AARCH64_LDST128_ABS_LO12_NC => insert(p, 4, lo12(4), 0x3ffc00),
+        RelocKind::AARCH64_TSTBR14 => insert(p, 4, (pcrel >> 2) << 5, 0x7ffe0),
+        RelocKind::AARCH64_CONDBR19 => insert(p, 4, (pcrel >> 2) << 5, 0xffffe0),
+        RelocKind::AARCH64_JUMP26 | RelocKind::AARCH64_CALL26 => {
+            insert(p, 4, pcrel >> 2, 0x3ffffff)
+        }
+        RelocKind::AARCH64_GLOB_DAT | RelocKind::AARCH64_JUMP_SLOT => write(p, 8, sa),
+        RelocKind::AARCH64_RELATIVE => write(p, 8, b.wrapping_add(a)),
+        _ => None,
+    }
+}
+
+/// Patch the immediate of a U-type instruction with the high 20 bits of `x`
+#[inline]
+const fn riscv_u_type(p: u64, x: u64) -> Patch {
+    Patch::new(p, 4, x.wrapping_add(0x800), PatchOp::Insert(0xfffff000))
+}
+
+/// Patch the immediate of an I-type instruction with the low 12 bits of `x`
+#[inline]
+const fn riscv_i_type(p: u64, x: u64) -> Patch {
+    Patch::new(p, 4, x << 20, PatchOp::Insert(0xfff00000))
+}
+
+/// Patch the immediate of an S-type instruction with the low 12 bits of `x`
+#[inline]
+const fn riscv_s_type(p: u64, x: u64) -> Patch {
+    let imm = ((x >> 5) & 0x7f) << 25 | (x & 0x1f) << 7;
+    Patch::new(p, 4, imm, PatchOp::Insert(0xfe000f80))
+}
+
+fn riscv(kind: RelocKind, s: u64, a: u64, p: u64, b: u64) -> Option<Patches> {
+    let sa = s.wrapping_add(a);
+    let pcrel = sa.wrapping_sub(p);
+    let bit = |x: u64, n: u32| (x >> n) & 1;
+
+    match kind {
+        RelocKind::RISCV_NONE | RelocKind::RISCV_RELAX | RelocKind::RISCV_ALIGN => {
+            Some(Patches::NONE)
+        }
+        RelocKind::RISCV_32 => write(p, 4, sa),
+        RelocKind::RISCV_64 => write(p, 8, sa),
+        RelocKind::RISCV_RELATIVE => write(p, 8, b.wrapping_add(a)),
+        RelocKind::RISCV_JUMP_SLOT => write(p, 8, s),
+        RelocKind::RISCV_32_PCREL => write(p, 4, pcrel),
+        RelocKind::RISCV_BRANCH => {
+            let x = pcrel;
+            let imm = bit(x, 12) << 31
+                | ((x >> 5) & 0x3f) << 25
+                | ((x >> 1) & 0xf) << 8
+                | bit(x, 11) << 7;
+            insert(p, 4, imm, 0xfe000f80)
+        }
+        RelocKind::RISCV_JAL => {
+            let x = pcrel;
+            let imm = bit(x, 20) << 31
+                | ((x >> 1) & 0x3ff) << 21
+                | bit(x, 11) << 20
+                | ((x >> 12) & 0xff) << 12;
+            insert(p, 4, imm, 0xfffff000)
+        }
+        RelocKind::RISCV_CALL | RelocKind::RISCV_CALL_PLT => Some(Patches::two(
+            riscv_u_type(p, pcrel),
+            riscv_i_type(p.wrapping_add(4), pcrel),
+        )),
+        RelocKind::RISCV_PCREL_HI20 => Some(Patches::one(riscv_u_type(p, pcrel))),
+        RelocKind::RISCV_HI20 => Some(Patches::one(riscv_u_type(p, sa))),
+        RelocKind::RISCV_LO12_I => Some(Patches::one(riscv_i_type(p, sa))),
+        RelocKind::RISCV_LO12_S => Some(Patches::one(riscv_s_type(p, sa))),
+        RelocKind::RISCV_RVC_BRANCH => {
+            let x = pcrel;
+            let imm = bit(x, 8) << 12
+                | ((x >> 3) & 3) << 10
+                | ((x >> 6) & 3) << 5
+                | ((x >> 1) & 3) << 3
+                | bit(x, 5) << 2;
+            insert(p, 2, imm, 0x1c7c)
+        }
+        RelocKind::RISCV_RVC_JUMP => {
+            let x = pcrel;
+            let imm = bit(x, 11) << 12
+                | bit(x, 4) << 11
+                | ((x >> 8) & 3) << 9
+                | bit(x, 10) << 8
+                | bit(x, 6) << 7
+                | bit(x, 7) << 6
+                | ((x >> 1) & 7) << 3
+                | bit(x, 5) << 2;
+            insert(p, 2, imm, 0x1ffc)
+        }
+        RelocKind::RISCV_ADD8 => Some(Patches::one(Patch::new(p, 1, sa, PatchOp::Add))),
+        RelocKind::RISCV_ADD16 => Some(Patches::one(Patch::new(p, 2, sa, PatchOp::Add))),
+        RelocKind::RISCV_ADD32 => Some(Patches::one(Patch::new(p, 4, sa, PatchOp::Add))),
+        RelocKind::RISCV_ADD64 => Some(Patches::one(Patch::new(p, 8, sa, PatchOp::Add))),
+        RelocKind::RISCV_SUB8 => Some(Patches::one(Patch::new(p, 1, sa, PatchOp::Sub))),
+        RelocKind::RISCV_SUB16 => Some(Patches::one(Patch::new(p, 2, sa, PatchOp::Sub))),
+        RelocKind::RISCV_SUB32 => Some(Patches::one(Patch::new(p, 4, sa, PatchOp::Sub))),
+        RelocKind::RISCV_SUB64 => Some(Patches::one(Patch::new(p, 8, sa, PatchOp::Sub))),
+        RelocKind::RISCV_SET6 => insert(p, 1, sa, 0x3f),
+        RelocKind::RISCV_SET8 => write(p, 1, sa),
+        RelocKind::RISCV_SET16 => write(p, 2, sa),
+        RelocKind::RISCV_SET32 => write(p, 4, sa),
+        _ => None,
+    }
+}
+
+/// Relocations for x86-64
+#[derive(Clone, Copy, Debug, Default)]
+pub struct X86_64Backend;
+
+impl RelocationBackend for X86_64Backend {
+    fn machine(&self) -> Machine {
+        Machine::X86_64
+    }
+
+    fn classify(&self, kind: RelocKind) -> Option<RelocClass> {
+        Some(match kind {
+            RelocKind::X86_64_NONE => RelocClass::None,
+            RelocKind::X86_64_64
+            | RelocKind::X86_64_32
+            | RelocKind::X86_64_32S
+            | RelocKind::X86_64_16
+            | RelocKind::X86_64_8 => RelocClass::Absolute,
+            RelocKind::X86_64_PC32
+            | RelocKind::X86_64_PLT32
+            | RelocKind::X86_64_PC16
+            | RelocKind::X86_64_PC8
+            | RelocKind::X86_64_PC64 => RelocClass::PcRelative,
+            RelocKind::X86_64_GLOB_DAT | RelocKind::X86_64_JUMP_SLOT => RelocClass::Import,
+            RelocKind::X86_64_RELATIVE => RelocClass::Relative,
+            _ => return None,
+        })
+    }
+
+    fn widths(&self) -> &'static [u8] {
+        &[1, 2, 4, 8]
+    }
+
+    fn compute(&self, kind: RelocKind, v: RelocValues) -> Option<Patches> {
+        x86_64(kind, v.symbol, v.addend, v.place, v.load_bias)
+    }
+
+    fn field(&self, kind: RelocKind, v: RelocValues) -> Option<(u64, FieldRange)> {
+        let (sa, pcrel) = (v.absolute(), v.pc_relative());
+
+        Some(match kind {
+            RelocKind::X86_64_PC32 | RelocKind::X86_64_PLT32 => (pcrel, FieldRange::Signed(32)),
+            RelocKind::X86_64_32 => (sa, FieldRange::Unsigned(32)),
+            RelocKind::X86_64_32S => (sa, FieldRange::Signed(32)),
+            RelocKind::X86_64_16 => (sa, FieldRange::Any(16)),
+            RelocKind::X86_64_PC16 => (pcrel, FieldRange::Signed(16)),
+            RelocKind::X86_64_8 => (sa, FieldRange::Any(8)),
+            RelocKind::X86_64_PC8 => (pcrel, FieldRange::Signed(8)),
+            _ => return None,
+        })
+    }
+}
+
+/// Relocations for AArch64
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aarch64Backend;
+
+impl RelocationBackend for Aarch64Backend {
+    fn machine(&self) -> Machine {
+        Machine::Aarch64
+    }
+
+    fn classify(&self, kind: RelocKind) -> Option<RelocClass> {
+        Some(match kind {
+            RelocKind::AARCH64_NONE => RelocClass::None,
+            RelocKind::AARCH64_ABS64
+            | RelocKind::AARCH64_ABS32
+            | RelocKind::AARCH64_ABS16
+            | RelocKind::AARCH64_ADD_ABS_LO12_NC
+            | RelocKind::AARCH64_LDST8_ABS_LO12_NC
+            | RelocKind::AARCH64_LDST16_ABS_LO12_NC
+            | RelocKind::AARCH64_LDST32_ABS_LO12_NC
+            | RelocKind::AARCH64_LDST64_ABS_LO12_NC
+            | RelocKind::AARCH64_LDST128_ABS_LO12_NC => RelocClass::Absolute,
+            RelocKind::AARCH64_PREL64
+            | RelocKind::AARCH64_PREL32
+            | RelocKind::AARCH64_PREL16
+            | RelocKind::AARCH64_ADR_PREL_LO21
+            | RelocKind::AARCH64_ADR_PREL_PG_HI21
+            | RelocKind::AARCH64_ADR_PREL_PG_HI21_NC
+            | RelocKind::AARCH64_TSTBR14
+            | RelocKind::AARCH64_CONDBR19
+            | RelocKind::AARCH64_JUMP26
+            | RelocKind::AARCH64_CALL26 => RelocClass::PcRelative,
+            RelocKind::AARCH64_GLOB_DAT | RelocKind::AARCH64_JUMP_SLOT => RelocClass::Import,
+            RelocKind::AARCH64_RELATIVE => RelocClass::Relative,
+            _ => return None,
+        })
+    }
+
+    fn widths(&self) -> &'static [u8] {
+        &[2, 4, 8]
+    }
+
+    fn compute(&self, kind: RelocKind, v: RelocValues) -> Option<Patches> {
+        aarch64(kind, v.symbol, v.addend, v.place, v.load_bias)
+    }
+
+    fn field(&self, kind: RelocKind, v: RelocValues) -> Option<(u64, FieldRange)> {
+        let (sa, pcrel) = (v.absolute(), v.pc_relative());
+
+        Some(match kind {
+            RelocKind::AARCH64_ABS32 => (sa, FieldRange::Any(32)),
+            RelocKind::AARCH64_ABS16 => (sa, FieldRange::Any(16)),
+            RelocKind::AARCH64_PREL32 => (pcrel, FieldRange::Signed(32)),
+            RelocKind::AARCH64_PREL16 => (pcrel, FieldRange::Signed(16)),
+            RelocKind::AARCH64_ADR_PREL_LO21 => (pcrel, FieldRange::Signed(21)),
+            RelocKind::AARCH64_ADR_PREL_PG_HI21 => {
+                let pages = (sa & !0xfff).wrapping_sub(v.place & !0xfff);
+                (pages, FieldRange::Signed(33))
+            }
+            RelocKind::AARCH64_TSTBR14 => (pcrel, FieldRange::Signed(16)),
+            RelocKind::AARCH64_CONDBR19 => (pcrel, FieldRange::Signed(21)),
+            RelocKind::AARCH64_JUMP26 | RelocKind::AARCH64_CALL26 => {
+                (pcrel, FieldRange::Signed(28))
+            }
+            _ => return None,
+        })
+    }
+
+    fn little_endian_code(&self) -> bool {
+        true
+    }
+}
+
+/// Relocations for 64-bit RISC-V
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RiscvBackend;
+
+impl RelocationBackend for RiscvBackend {
+    fn machine(&self) -> Machine {
+        Machine::Riscv
+    }
+
+    fn classify(&self, kind: RelocKind) -> Option<RelocClass> {
+        Some(match kind {
+            RelocKind::RISCV_NONE | RelocKind::RISCV_RELAX | RelocKind::RISCV_ALIGN => {
+                RelocClass::None
+            }
+            RelocKind::RISCV_32
+            | RelocKind::RISCV_64
+            | RelocKind::RISCV_HI20
+            | RelocKind::RISCV_LO12_I
+            | RelocKind::RISCV_LO12_S
+            | RelocKind::RISCV_SET6
+            | RelocKind::RISCV_SET8
+            | RelocKind::RISCV_SET16
+            | RelocKind::RISCV_SET32 => RelocClass::Absolute,
+            RelocKind::RISCV_32_PCREL
+            | RelocKind::RISCV_BRANCH
+            | RelocKind::RISCV_JAL
+            | RelocKind::RISCV_CALL
+            | RelocKind::RISCV_CALL_PLT
+            | RelocKind::RISCV_PCREL_HI20
+            | RelocKind::RISCV_RVC_BRANCH
+            | RelocKind::RISCV_RVC_JUMP => RelocClass::PcRelative,
+            RelocKind::RISCV_JUMP_SLOT => RelocClass::Import,
+            RelocKind::RISCV_RELATIVE => RelocClass::Relative,
+            RelocKind::RISCV_ADD8
+            | RelocKind::RISCV_ADD16
+            | RelocKind::RISCV_ADD32
+            | RelocKind::RISCV_ADD64
+            | RelocKind::RISCV_SUB8
+            | RelocKind::RISCV_SUB16
+            | RelocKind::RISCV_SUB32
+            | RelocKind::RISCV_SUB64 => RelocClass::Arithmetic,
+            RelocKind::RISCV_PCREL_LO12_I | RelocKind::RISCV_PCREL_LO12_S => RelocClass::Paired,
+            _ => return None,
+        })
+    }
+
+    fn widths(&self) -> &'static [u8] {
+        &[1, 2, 4, 8]
+    }
+
+    fn compute(&self, kind: RelocKind, v: RelocValues) -> Option<Patches> {
+        riscv(kind, v.symbol, v.addend, v.place, v.load_bias)
+    }
+
+    fn field(&self, kind: RelocKind, v: RelocValues) -> Option<(u64, FieldRange)> {
+        let (sa, pcrel) = (v.absolute(), v.pc_relative());
+
+        Some(match kind {
+            RelocKind::RISCV_32 => (sa, FieldRange::Any(32)),
+            RelocKind::RISCV_32_PCREL => (pcrel, FieldRange::Signed(32)),
+            RelocKind::RISCV_BRANCH => (pcrel, FieldRange::Signed(13)),
+            RelocKind::RISCV_JAL => (pcrel, FieldRange::Signed(21)),
+            RelocKind::RISCV_RVC_BRANCH => (pcrel, FieldRange::Signed(9)),
+            RelocKind::RISCV_RVC_JUMP => (pcrel, FieldRange::Signed(12)),
+            // The low 12 bits are sign-extended, so the high part is rounded.
+            RelocKind::RISCV_CALL | RelocKind::RISCV_CALL_PLT | RelocKind::RISCV_PCREL_HI20 => {
+                (pcrel.wrapping_add(0x800), FieldRange::Signed(32))
+            }
+            RelocKind::RISCV_HI20 => (sa.wrapping_add(0x800), FieldRange::Signed(32)),
+            _ => return None,
+        })
+    }
+
+    fn pair_kind(&self, kind: RelocKind) -> Option<RelocKind> {
+        match kind {
+            RelocKind::RISCV_PCREL_LO12_I | RelocKind::RISCV_PCREL_LO12_S => {
+                Some(RelocKind::RISCV_PCREL_HI20)
+            }
+            _ => None,
+        }
+    }
+
+    fn compute_paired(&self, kind: RelocKind, place: u64, offset: u64) -> Option<Patches> {
+        match kind {
+            RelocKind::RISCV_PCREL_LO12_I => Some(Patches::one(riscv_i_type(place, offset))),
+            RelocKind::RISCV_PCREL_LO12_S => Some(Patches::one(riscv_s_type(place, offset))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_file_header, BigEndian, RelocInfo};
+
+    /// Returns an ELF64 file header for AArch64, big-endian if `big`
+    fn aarch64_header(big: bool) -> [u8; 64] {
+        let mut buf = [0; 64];
+        buf[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1 + big as u8, 1]);
+        let (machine, version) = match big {
+            true => (183u16.to_be_bytes(), 1u32.to_be_bytes()),
+            false => (183u16.to_le_bytes(), 1u32.to_le_bytes()),
+        };
+        buf[0x12..0x14].copy_from_slice(&machine);
+        buf[0x14..0x18].copy_from_slice(&version);
+        buf
+    }
+
+    fn relas<E: Endian>() -> [Rela<E>; 2] {
+        [
+            Rela::new(0x1000, RelocInfo::new(1, RelocKind::AARCH64_ABS64), 0x10),
+            Rela::new(0x1008, RelocInfo::new(1, RelocKind::AARCH64_CALL26), 0),
+        ]
+    }
+
+    /// A `bl` instruction with a zero offset
+    const BL: u32 = 0x9400_0000;
+
+    #[test]
+    fn big_endian_target_from_little_endian_relas() {
+        let header = aarch64_header(true);
+        let (ehdr, _) = parse_file_header(&header).unwrap();
+
+        let mut relocator = Relocator::for_file(&ehdr, 0, |_| Some(0x2000)).unwrap();
+        assert_eq!(relocator.target_data(), Data::TwosCompBig);
+
+        let mut image = [0; 12];
+        image[8..].copy_from_slice(&BL.to_le_bytes());
+        relocator
+            .apply(&relas::<LittleEndian>(), 0, &mut image, 0x1000)
+            .unwrap();
+
+        // Data is stored big-endian, while instructions are always little-endian.
+        assert_eq!(image[..8], 0x2010u64.to_be_bytes());
+        assert_eq!(image[8..], (BL | 0x3fe).to_le_bytes());
+    }
+
+    #[test]
+    fn little_endian_target_from_big_endian_relas() {
+        let mut relocator = Relocator::<_, BigEndian>::new(Machine::Aarch64, 0, |_| Some(0x2000));
+        relocator.set_target_data(Data::TwosCompLittle);
+
+        let mut image = [0; 12];
+        image[8..].copy_from_slice(&BL.to_le_bytes());
+        relocator
+            .apply(&relas::<BigEndian>(), 0, &mut image, 0x1000)
+            .unwrap();
+
+        assert_eq!(image[..8], 0x2010u64.to_le_bytes());
+        assert_eq!(image[8..], (BL | 0x3fe).to_le_bytes());
+    }
+
+    #[test]
+    fn apply_for_big_endian_file() {
+        let header = aarch64_header(true);
+        let elf = Elf::new(&header).unwrap();
+
+        let mut image = [0; 8];
+        let relas = &relas::<LittleEndian>()[..1];
+        apply_for(&elf, relas, 0, 0, &mut image, 0x1000, |_| Some(0x2000)).unwrap();
+        assert_eq!(image, 0x2010u64.to_be_bytes());
+    }
+
+    #[test]
+    fn target_byte_order_must_match_file() {
+        let header = aarch64_header(true);
+        let (ehdr, _) = parse_file_header(&header).unwrap();
+
+        let relocator = Relocator::new(Machine::Aarch64, 0, |_| None);
+        assert!(matches!(
+            relocator.check_compatible(&ehdr),
+            Err(RelocError::Incompatible {
+                data: Data::TwosCompBig,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn field_range_widths() {
+        assert!(FieldRange::Signed(8).contains(-128i64 as u64));
+        assert!(!FieldRange::Signed(8).contains(128));
+        assert!(FieldRange::Unsigned(8).contains(255));
+        assert!(!FieldRange::Unsigned(8).contains(256));
+        assert!(FieldRange::Any(8).contains(255));
+        assert!(FieldRange::Any(8).contains(-1i64 as u64));
+
+        for range in [
+            FieldRange::Signed(0),
+            FieldRange::Unsigned(0),
+            FieldRange::Any(0),
+        ] {
+            assert!(range.contains(0));
+            assert!(!range.contains(1));
+            assert!(!range.contains(u64::MAX));
+        }
+        for n in [64, 65, u8::MAX] {
+            for range in [
+                FieldRange::Signed(n),
+                FieldRange::Unsigned(n),
+                FieldRange::Any(n),
+            ] {
+                assert!(range.contains(0));
+                assert!(range.contains(u64::MAX));
+            }
+        }
+        assert!(FieldRange::Signed(63).contains(-1i64 as u64));
+        assert!(!FieldRange::Unsigned(63).contains(u64::MAX));
+    }
+}
@@ -0,0 +1,221 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Rust and LLVM toolchain artifacts
+
+use crate::Elf;
+
+/// The header which starts rustc's crate metadata, minus the version byte
+const METADATA_MAGIC: &[u8; 7] = b"rust\0\0\0";
+
+/// The stream identifier chunk which starts a snappy framed stream
+const SNAPPY_STREAM_ID: &[u8; 10] = b"\xff\x06\x00\x00sNaPpY";
+
+/// Crate metadata embedded by rustc in the `.rustc` section of a dylib
+#[derive(Clone, Copy, Debug)]
+pub struct RustMetadata<'elf> {
+    version: u8,
+    payload: &'elf [u8],
+    compressed: bool,
+}
+
+impl<'elf> RustMetadata<'elf> {
+    /// Parse the contents of a `.rustc` section
+    ///
+    /// Depending on the compiler version, the metadata is stored either raw or as a
+    /// snappy framed stream, optionally preceded by its 64-bit length.
+    pub fn parse(data: &'elf [u8]) -> Option<RustMetadata<'elf>> {
+        if data.get(..METADATA_MAGIC.len())? != METADATA_MAGIC {
+            return None;
+        }
+
+        let version = *data.get(METADATA_MAGIC.len())?;
+        let rest = &data[METADATA_MAGIC.len() + 1..];
+
+        // Newer compilers prefix the payload with its length, which rustc always writes
+        // in little-endian byte order, whatever the target's.
+        let prefix = rest
+            .get(..8)
+            .map(|len| u64::from_le_bytes(len.try_into().unwrap()));
+        let payload = match prefix {
+            Some(len) if len == rest.len() as u64 - 8 => &rest[8..],
+            Some(_) if rest[8..].starts_with(SNAPPY_STREAM_ID) => &rest[8..],
+            _ => rest,
+        };
+        let compressed = payload.starts_with(SNAPPY_STREAM_ID);
+
+        Some(RustMetadata {
+            version,
+            payload,
+            compressed,
+        })
+    }
+
+    /// Returns the metadata format version
+    #[inline]
+    pub const fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns `true` if the payload is snappy-compressed
+    #[inline]
+    pub const fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Returns the payload following the header, which may be compressed
+    #[inline]
+    pub const fn payload(&self) -> &'elf [u8] {
+        self.payload
+    }
+
+    /// Returns the uncompressed metadata
+    ///
+    /// Chunk checksums are not verified. Returns `None` if the stream is malformed.
+    #[cfg(feature = "alloc")]
+    pub fn decompress(&self) -> Option<alloc::vec::Vec<u8>> {
+        if self.compressed {
+            snappy::decompress_framed(self.payload)
+        } else {
+            Some(self.payload.to_vec())
+        }
+    }
+}
+
+/// Returns `true` if `data` starts with an LLVM bitcode or bitcode wrapper magic number
+pub fn is_llvm_bitcode(data: &[u8]) -> bool {
+    data.starts_with(b"BC\xc0\xde") || data.starts_with(&0x0b17c0de_u32.to_le_bytes())
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the rustc crate metadata, if this is a Rust dylib
+    pub fn rust_metadata(&self) -> Option<RustMetadata<'elf>> {
+        RustMetadata::parse(self.find_section(".rustc")?.file_data()?)
+    }
+
+    /// Returns the embedded LLVM bitcode from `.llvmbc`, if present
+    pub fn embedded_bitcode(&self) -> Option<&'elf [u8]> {
+        let data = self.find_section(".llvmbc")?.file_data()?;
+        is_llvm_bitcode(data).then_some(data)
+    }
+
+    /// Returns the command line which produced the embedded bitcode, from `.llvmcmd`
+    ///
+    /// Arguments are separated by NUL bytes.
+    pub fn embedded_bitcode_cmdline(&self) -> Option<&'elf [u8]> {
+        self.find_section(".llvmcmd")?.file_data()
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod snappy {
+    use crate::leb128::read_uleb128;
+    use alloc::vec::Vec;
+
+    /// Decompress a snappy framed stream
+    pub fn decompress_framed(mut data: &[u8]) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+
+        while !data.is_empty() {
+            let header = data.get(..4)?;
+            let kind = header[0];
+            let len = u32::from_le_bytes([header[1], header[2], header[3], 0]) as usize;
+            let chunk = data.get(4..4 + len)?;
+            data = &data[4 + len..];
+
+            match kind {
+                // stream identifier
+                0xff => {}
+                // compressed data, preceded by a masked CRC-32C
+                0x00 => decompress_block(chunk.get(4..)?, &mut out)?,
+                // uncompressed data, preceded by a masked CRC-32C
+                0x01 => out.extend_from_slice(chunk.get(4..)?),
+                // skippable chunks (e.g. padding)
+                0x80..=0xfe => {}
+                _ => return None,
+            }
+        }
+
+        Some(out)
+    }
+
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+        fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+            let bytes = self.0.get(..n)?;
+            self.0 = &self.0[n..];
+            Some(bytes)
+        }
+
+        /// Read an `n`-byte little-endian integer
+        fn int(&mut self, n: usize) -> Option<usize> {
+            let bytes = self.bytes(n)?;
+            Some(bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as usize))
+        }
+    }
+
+    /// Decompress a raw snappy block, appending to `out`
+    fn decompress_block(data: &[u8], out: &mut Vec<u8>) -> Option<()> {
+        let mut data = Reader(data);
+
+        let expected = usize::try_from(read_uleb128(&mut data.0)?).ok()?;
+
+        let start = out.len();
+        out.reserve(expected);
+
+        while let Some(tag) = data.int(1) {
+            let (len, offset) = match tag & 3 {
+                0 => {
+                    let len = match tag >> 2 {
+                        n @ 0..=59 => n + 1,
+                        n => data.int(n - 59)? + 1,
+                    };
+                    out.extend_from_slice(data.bytes(len)?);
+                    continue;
+                }
+                1 => (4 + ((tag >> 2) & 7), ((tag >> 5) << 8) | data.int(1)?),
+                2 => ((tag >> 2) + 1, data.int(2)?),
+                _ => ((tag >> 2) + 1, data.int(4)?),
+            };
+
+            if offset == 0 || offset > out.len() - start {
+                return None;
+            }
+
+            // Copies may overlap their own output, so go a byte at a time.
+            for _ in 0..len {
+                out.push(out[out.len() - offset]);
+            }
+        }
+
+        (out.len() - start == expected).then_some(())
+    }
+}
@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Section metadata for generating and validating linker scripts
+
+use crate::{Elf, Section, SectionFlags, SectionType};
+
+/// The properties of a section relevant to a linker script
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SectionSummary<'elf> {
+    /// Index of the section in the section header table
+    pub index: u16,
+    /// Section name, or `None` if it cannot be read
+    pub name: Option<&'elf str>,
+    pub kind: SectionType,
+    pub flags: SectionFlags,
+    pub addr: u64,
+    pub size: u64,
+    pub align: u64,
+}
+
+impl<'elf> SectionSummary<'elf> {
+    /// Returns `true` if the section's name matches the linker script wildcard `pattern`
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.name
+            .is_some_and(|name| wildcard_match(pattern.as_bytes(), name.as_bytes()))
+    }
+}
+
+impl<'elf> From<(u16, Section<'_, 'elf>)> for SectionSummary<'elf> {
+    fn from((index, sect): (u16, Section<'_, 'elf>)) -> SectionSummary<'elf> {
+        SectionSummary {
+            index,
+            name: sect.name(),
+            kind: sect.section_type(),
+            flags: sect.flags(),
+            addr: sect.addr(),
+            size: sect.size() as u64,
+            align: sect.addr_align(),
+        }
+    }
+}
+
+/// Returns `true` if `name` matches the linker script wildcard `pattern`
+///
+/// Supports `*`, `?` and bracketed character sets such as `[a-z]` or `[!0-9]`, as in
+/// input section descriptions.
+pub fn section_pattern_matches(pattern: &str, name: &str) -> bool {
+    wildcard_match(pattern.as_bytes(), name.as_bytes())
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position to resume from after the most recent `*`
+    let mut backtrack = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(1),
+            Some(b'[') => match_set(&pattern[p..], name[n]),
+            Some(&c) => (c == name[n]).then_some(1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(len), _) => {
+                p += len;
+                n += 1;
+            }
+            (None, Some((star, at))) => {
+                backtrack = Some((star, at + 1));
+                p = star + 1;
+                n = at + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Match `c` against the set at the start of `pattern`, returning the length of the set
+fn match_set(pattern: &[u8], c: u8) -> Option<usize> {
+    let negate = matches!(pattern.get(1), Some(b'!' | b'^'));
+    let mut i = 1 + negate as usize;
+    let mut matched = false;
+
+    loop {
+        let lo = *pattern.get(i)?;
+        if lo == b']' && i > 1 + negate as usize {
+            break;
+        }
+        if pattern.get(i + 1) == Some(&b'-') && pattern.get(i + 2).is_some_and(|&hi| hi != b']') {
+            matched |= (lo..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+
+    (matched != negate).then_some(i + 1)
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over a summary of each section
+    pub fn section_summaries(&self) -> impl Iterator<Item = SectionSummary<'elf>> + '_ {
+        (0..).zip(self.sections()).map(SectionSummary::from)
+    }
+
+    /// Returns an iterator over the sections whose names match the wildcard `pattern`
+    pub fn sections_matching<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = SectionSummary<'elf>> + 'a {
+        self.section_summaries()
+            .filter(move |sect| sect.matches(pattern))
+    }
+
+    /// Returns an iterator over the patterns in `patterns` which match no section
+    ///
+    /// A pattern which matches nothing usually means a linker script has fallen out of
+    /// date with the code it links.
+    pub fn unmatched_patterns<'a>(
+        &'a self,
+        patterns: &'a [&'a str],
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        patterns
+            .iter()
+            .copied()
+            .filter(move |pattern| self.sections_matching(pattern).next().is_none())
+    }
+}
@@ -28,62 +28,227 @@
  * SPDX-License-Identifier: BSD-3-Clause
  */
 
-use crate::{assert_struct_size, Elf};
+use crate::{
+    assert_struct_size, table::impl_entry, Elf, Endian, LittleEndian, ParseError, StringTable,
+    Structure, U32, U64,
+};
 use core::{
     fmt,
     mem::{align_of, size_of},
 };
 
 pub const SHN_UNDEF: u16 = 0;
+pub const SHN_LORESERVE: u16 = 0xff00;
 pub const SHN_ABS: u16 = 0xfff1;
 pub const SHN_COMMON: u16 = 0xfff2;
 pub const SHN_XINDEX: u16 = 0xffff;
 
 pub struct Section<'a, 'elf> {
     elf: &'a Elf<'elf>,
-    hdr: &'elf SectionHeader,
+    index: u16,
+    hdr: SectionHeader,
 }
 
 impl<'a, 'elf> Section<'a, 'elf> {
     #[inline]
-    pub(crate) fn new(elf: &'a Elf<'elf>, hdr: &'elf SectionHeader) -> Section<'a, 'elf> {
-        Self { elf, hdr }
+    pub(crate) fn new(elf: &'a Elf<'elf>, index: u16, hdr: SectionHeader) -> Section<'a, 'elf> {
+        Self { elf, index, hdr }
+    }
+
+    #[inline]
+    pub(crate) fn elf(&self) -> &'a Elf<'elf> {
+        self.elf
     }
 }
 
 impl<'elf> Section<'_, 'elf> {
+    /// Returns the contents of the section, or `None` if they lie outside of the file
     #[inline]
-    pub fn file_data(&self) -> &'elf [u8] {
-        &self.elf.data[self.file_offset()..][..self.size()]
+    pub fn file_data(&self) -> Option<&'elf [u8]> {
+        self.elf.get_slice(self.file_offset(), self.size())
     }
 
     /// Returns the contents of the section as an array of some type
     ///
+    /// Returns an error if the section's entry size is not the size of `T`, its size is not
+    /// a multiple of it, or its contents are not suitably aligned.
+    ///
+    /// The contents are not converted, so `T` must be laid out for the file's class and
+    /// byte order, such as `Sym32<BigEndian>` for a big-endian ELF32 file.
+    ///
     /// # Safety
     ///
     /// The caller must guarantee that the section's contents represent valid
     /// types of `T`.
-    pub unsafe fn table<T>(&self) -> &'elf [T] {
-        assert_eq!(self.entry_size as usize, size_of::<T>());
-        let data = self.file_data().as_ptr().cast::<T>();
-        assert!(data.align_offset(align_of::<T>()) == 0);
-        assert!(self.size() % size_of::<T>() == 0);
+    pub unsafe fn table<T>(&self) -> Result<&'elf [T], ParseError> {
+        let error = |message| {
+            let offset = self.file_offset() as u64;
+            Err(ParseError::new(
+                Structure::Section(self.index()),
+                offset,
+                message,
+            ))
+        };
+
+        if self.entry_size() as usize != size_of::<T>() {
+            return error("unexpected entry size");
+        }
+        let Some(data) = self.file_data() else {
+            return error("contents out of bounds");
+        };
+        let data = data.as_ptr().cast::<T>();
+        if data.align_offset(align_of::<T>()) != 0 {
+            return error("misaligned contents");
+        }
+        if self.size() % size_of::<T>() != 0 {
+            return error("size is not a multiple of the entry size");
+        }
         let len = self.size() / size_of::<T>();
 
-        core::slice::from_raw_parts(data, len)
+        Ok(core::slice::from_raw_parts(data, len))
     }
 
     pub fn name(&self) -> Option<&'elf str> {
         let elf = self.elf;
         let string_table = elf.section_string_table()?;
 
-        match self.name_index {
+        match self.name_index() {
             0 => None,
-            _ => string_table.get_string(self.name_index as _),
+            index => string_table.get_string(index as _),
+        }
+    }
+
+    /// Returns the index of this section in the section header table
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the section this section is ordered against, if it has `SHF_LINK_ORDER` set
+    ///
+    /// Metadata sections such as `__patchable_function_entries` and `.stack_sizes` use
+    /// this to refer to the code they describe. The linker places them in the same relative
+    /// order as their associated sections.
+    pub fn link_order_section(&self) -> Option<Section<'_, 'elf>> {
+        if !self.flags().link_order() {
+            return None;
+        }
+
+        self.linked_section()
+    }
+
+    /// Returns the section given by `sh_link`, or `None` if it is `SHN_UNDEF`
+    ///
+    /// What the link refers to depends on the section's type: the string table of a
+    /// symbol table or dynamic section, the symbol table of a relocation or hash section,
+    /// and so on.
+    pub fn linked_section(&self) -> Option<Section<'_, 'elf>> {
+        self.elf.section(self.link_as_section_index()?)
+    }
+
+    /// Returns the string table given by `sh_link`
+    ///
+    /// Returns `None` if there is no linked section, it is not an `SHT_STRTAB` section, or
+    /// its contents lie outside of the file.
+    pub fn linked_string_table(&self) -> Option<StringTable<'elf>> {
+        let strtab = self.linked_section()?;
+        match strtab.section_type() {
+            SectionType::Strtab => Some(StringTable::new(strtab.file_data()?)),
+            _ => None,
+        }
+    }
+
+    /// Classifies the section by its type and flags alone
+    ///
+    /// This does not depend on the section's name, so it works on stripped or anonymized
+    /// files.
+    pub fn inferred_role(&self) -> SectionRole {
+        let flags = self.flags();
+
+        match self.section_type() {
+            SectionType::Null => SectionRole::Other,
+            SectionType::Symtab | SectionType::Dynsym | SectionType::SymtabShndx => {
+                SectionRole::SymbolTable
+            }
+            SectionType::Strtab => SectionRole::StringTable,
+            SectionType::Note => SectionRole::Note,
+            SectionType::Rel | SectionType::Rela => SectionRole::Relocations,
+            SectionType::Nobits if flags.alloc() => SectionRole::Bss,
+            _ if flags.alloc() && flags.execinstr() => SectionRole::Code,
+            _ if flags.alloc() && flags.write() => SectionRole::Data,
+            _ if flags.alloc() => SectionRole::ReadOnlyData,
+            SectionType::Progbits => SectionRole::Debug,
+            _ => SectionRole::Other,
+        }
+    }
+}
+
+/// What a section is used for, as guessed by [`Section::inferred_role()`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SectionRole {
+    /// Executable code
+    Code,
+    /// Allocated, read-only data
+    ReadOnlyData,
+    /// Allocated, writable data, including thread-local data
+    Data,
+    /// Zero-initialized data which takes no space in the file
+    Bss,
+    SymbolTable,
+    StringTable,
+    Note,
+    Relocations,
+    /// Non-allocated contents, such as debug information or comments
+    Debug,
+    Other,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over the sections named `name`, ordered by their associated
+    /// `SHF_LINK_ORDER` sections
+    ///
+    /// Sections are ordered by the address and then index of the section they are linked to.
+    /// Sections without `SHF_LINK_ORDER` come last, in their original order.
+    pub fn sections_in_link_order<'a>(&'a self, name: &'a str) -> LinkOrder<'a, 'elf> {
+        LinkOrder {
+            elf: self,
+            name,
+            prev: None,
         }
     }
 }
 
+/// Iterator returned by [`Elf::sections_in_link_order()`]
+pub struct LinkOrder<'a, 'elf> {
+    elf: &'a Elf<'elf>,
+    name: &'a str,
+    prev: Option<(u64, u32, u16)>,
+}
+
+impl<'a, 'elf> Iterator for LinkOrder<'a, 'elf> {
+    type Item = Section<'a, 'elf>;
+
+    fn next(&mut self) -> Option<Section<'a, 'elf>> {
+        // This finds the next section by a linear scan to avoid allocating, which is fine for
+        // the handful of sections that usually share a name.
+        let (key, sect) = self
+            .elf
+            .sections()
+            .filter(|sect| sect.name() == Some(self.name))
+            .map(|sect| {
+                let key = match sect.link_order_section() {
+                    Some(link) => (link.addr(), link.index() as u32, sect.index()),
+                    None => (u64::MAX, u32::MAX, sect.index()),
+                };
+                (key, sect)
+            })
+            .filter(|(key, _)| self.prev.is_none_or(|prev| *key > prev))
+            .min_by_key(|(key, _)| *key)?;
+
+        self.prev = Some(key);
+        Some(sect)
+    }
+}
+
 impl fmt::Debug for Section<'_, '_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Section")
@@ -98,76 +263,284 @@ impl core::ops::Deref for Section<'_, '_> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.hdr
+        &self.hdr
     }
 }
 
 #[repr(C)]
-pub struct SectionHeader {
-    name_index: u32,
-    section_type: u32,
-    flags: u64,
-    addr: u64,
-    offset: u64,
-    size: u64,
-    link: u32,
-    info: u32,
-    addr_align: u64,
-    entry_size: u64,
+#[derive(Clone, Copy)]
+pub struct SectionHeader<E: Endian = LittleEndian> {
+    name_index: U32<E>,
+    section_type: U32<E>,
+    flags: U64<E>,
+    addr: U64<E>,
+    offset: U64<E>,
+    size: U64<E>,
+    link: U32<E>,
+    info: U32<E>,
+    addr_align: U64<E>,
+    entry_size: U64<E>,
 }
 
 assert_struct_size!(SectionHeader, 64);
 
-impl SectionHeader {
+impl<E: Endian> SectionHeader<E> {
+    /// Re-encode the header in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> SectionHeader<F> {
+        SectionHeader {
+            name_index: self.name_index.convert(),
+            section_type: self.section_type.convert(),
+            flags: self.flags.convert(),
+            addr: self.addr.convert(),
+            offset: self.offset.convert(),
+            size: self.size.convert(),
+            link: self.link.convert(),
+            info: self.info.convert(),
+            addr_align: self.addr_align.convert(),
+            entry_size: self.entry_size.convert(),
+        }
+    }
+
+    /// Create a section header with no address, contents, or links
+    pub const fn new(
+        name_index: u32,
+        section_type: SectionType,
+        flags: SectionFlags,
+    ) -> SectionHeader<E> {
+        SectionHeader {
+            name_index: U32::new(name_index),
+            section_type: U32::new(section_type.to_u32()),
+            flags: U64::new(flags.bits()),
+            addr: U64::new(0),
+            offset: U64::new(0),
+            size: U64::new(0),
+            link: U32::new(0),
+            info: U32::new(0),
+            addr_align: U64::new(0),
+            entry_size: U64::new(0),
+        }
+    }
+
+    pub const fn with_addr(mut self, addr: u64) -> SectionHeader<E> {
+        self.addr = U64::new(addr);
+        self
+    }
+
+    /// Set the location of the section's contents in the file
+    ///
+    /// For `SHT_NOBITS` sections, `size` is the size of the section in memory.
+    pub const fn with_file_range(mut self, offset: u64, size: u64) -> SectionHeader<E> {
+        self.offset = U64::new(offset);
+        self.size = U64::new(size);
+        self
+    }
+
+    /// Set the `sh_link` and `sh_info` fields, whose meaning depends on the section type
+    pub const fn with_link(mut self, link: u32, info: u32) -> SectionHeader<E> {
+        self.link = U32::new(link);
+        self.info = U32::new(info);
+        self
+    }
+
+    pub const fn with_addr_align(mut self, align: u64) -> SectionHeader<E> {
+        self.addr_align = U64::new(align);
+        self
+    }
+
+    pub const fn with_entry_size(mut self, entry_size: u64) -> SectionHeader<E> {
+        self.entry_size = U64::new(entry_size);
+        self
+    }
+
+    /// Returns the header as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    pub fn set_file_range(&mut self, offset: u64, size: u64) {
+        self.offset.set(offset);
+        self.size.set(size);
+    }
+
+    pub fn set_name_index(&mut self, name_index: u32) {
+        self.name_index.set(name_index);
+    }
+
+    pub fn set_flags(&mut self, flags: SectionFlags) {
+        self.flags.set(flags.bits());
+    }
+
+    pub fn set_section_type(&mut self, section_type: SectionType) {
+        self.section_type.set(section_type.to_u32());
+    }
+
+    pub fn set_link(&mut self, link: u32) {
+        self.link.set(link);
+    }
+
+    pub fn set_info(&mut self, info: u32) {
+        self.info.set(info);
+    }
+
     #[inline]
     pub const fn name_index(&self) -> u32 {
-        self.name_index
+        self.name_index.get()
     }
 
     #[inline]
     pub const fn section_type(&self) -> SectionType {
-        SectionType::from_u32(self.section_type)
+        SectionType::from_u32(self.section_type.get())
     }
 
     #[inline]
     pub const fn flags(&self) -> SectionFlags {
-        SectionFlags { bits: self.flags }
+        SectionFlags {
+            bits: self.flags.get(),
+        }
     }
 
     #[inline]
     pub const fn addr(&self) -> u64 {
-        self.addr
+        self.addr.get()
     }
 
     #[inline]
     pub const fn file_offset(&self) -> usize {
-        self.offset as _
+        crate::to_usize(self.offset.get())
     }
 
     #[inline]
     pub const fn size(&self) -> usize {
-        self.size as _
+        crate::to_usize(self.size.get())
+    }
+
+    /// Returns the index of the associated section, whose meaning depends on the type
+    #[inline]
+    pub const fn link(&self) -> u32 {
+        self.link.get()
+    }
+
+    /// Returns extra information, whose meaning depends on the type
+    #[inline]
+    pub const fn info(&self) -> u32 {
+        self.info.get()
+    }
+
+    #[inline]
+    pub const fn addr_align(&self) -> u64 {
+        self.addr_align.get()
     }
 
     #[inline]
     pub const fn entry_size(&self) -> u64 {
-        self.entry_size
+        self.entry_size.get()
+    }
+
+    /// Returns the index of the section given by `sh_link`, or `None` if it is `SHN_UNDEF`
+    pub fn link_as_section_index(&self) -> Option<u16> {
+        match u16::try_from(self.link()).ok()? {
+            SHN_UNDEF => None,
+            link => Some(link),
+        }
+    }
+
+    /// Returns the index of the section relocated by an `SHT_REL` or `SHT_RELA` section
+    ///
+    /// Returns `None` for other types, or if `sh_info` is zero, as it is for dynamic
+    /// relocations which apply to no section in particular.
+    pub fn info_as_section_index(&self) -> Option<u16> {
+        match self.section_type() {
+            SectionType::Rel | SectionType::Rela => match u16::try_from(self.info()).ok()? {
+                SHN_UNDEF => None,
+                info => Some(info),
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the last local symbol of an `SHT_SYMTAB` or `SHT_DYNSYM` section
+    ///
+    /// `sh_info` holds one more than this, which is the index of the first non-local
+    /// symbol. Returns `None` for other types, or if `sh_info` is zero.
+    pub fn info_as_last_local(&self) -> Option<u32> {
+        match self.section_type() {
+            SectionType::Symtab | SectionType::Dynsym => self.info().checked_sub(1),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the symbol naming an `SHT_GROUP` section's group
+    ///
+    /// The symbol lies in the symbol table given by `sh_link`.
+    pub fn info_as_signature_symbol(&self) -> Option<u32> {
+        match self.section_type() {
+            SectionType::Group => Some(self.info()),
+            _ => None,
+        }
     }
 }
 
-impl fmt::Debug for SectionHeader {
+/// The section header of an ELF32 file
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SectionHeader32<E: Endian = LittleEndian> {
+    name_index: U32<E>,
+    section_type: U32<E>,
+    flags: U32<E>,
+    addr: U32<E>,
+    offset: U32<E>,
+    size: U32<E>,
+    link: U32<E>,
+    info: U32<E>,
+    addr_align: U32<E>,
+    entry_size: U32<E>,
+}
+
+assert_struct_size!(SectionHeader32, 40);
+
+impl<E: Endian> SectionHeader32<E> {
+    /// Returns the header as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+}
+
+impl<E: Endian> From<SectionHeader32<E>> for SectionHeader<E> {
+    fn from(shdr: SectionHeader32<E>) -> SectionHeader<E> {
+        let widen = |x: U32<E>| U64::new(x.get() as u64);
+        SectionHeader {
+            name_index: shdr.name_index,
+            section_type: shdr.section_type,
+            flags: widen(shdr.flags),
+            addr: widen(shdr.addr),
+            offset: widen(shdr.offset),
+            size: widen(shdr.size),
+            link: shdr.link,
+            info: shdr.info,
+            addr_align: widen(shdr.addr_align),
+            entry_size: widen(shdr.entry_size),
+        }
+    }
+}
+
+impl_entry!(SectionHeader, SectionHeader32);
+
+impl<E: Endian> fmt::Debug for SectionHeader<E> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SectionHeader")
-            .field("name_index", &format_args!("{:#x}", self.name_index))
+            .field("name_index", &format_args!("{:#x}", self.name_index()))
             .field("section_type", &self.section_type())
-            .field("flags", &format_args!("{:#x}", self.flags))
-            .field("address", &format_args!("{:#018x}", self.addr))
-            .field("offset", &format_args!("{:#x}", self.offset))
-            .field("size", &format_args!("{:#x}", self.size))
+            .field("flags", &format_args!("{:#x}", self.flags.get()))
+            .field("address", &format_args!("{:#018x}", self.addr()))
+            .field("offset", &format_args!("{:#x}", self.file_offset()))
+            .field("size", &format_args!("{:#x}", self.size()))
             .field("link", &self.link)
             .field("info", &self.info)
-            .field("address_align", &format_args!("{:#x}", self.addr_align))
-            .field("entry_size", &format_args!("{:x}", self.entry_size))
+            .field(
+                "address_align",
+                &format_args!("{:#x}", self.addr_align.get()),
+            )
+            .field("entry_size", &format_args!("{:x}", self.entry_size()))
             .finish()
     }
 }
@@ -198,6 +571,12 @@ pub enum SectionType {
 }
 
 impl SectionType {
+    pub const SUNW_CAP: Self = Self::EnvSpecific(0x6ffffff5);
+    pub const GNU_HASH: Self = Self::EnvSpecific(0x6ffffff6);
+    pub const GNU_VERDEF: Self = Self::EnvSpecific(0x6ffffffd);
+    pub const GNU_VERNEED: Self = Self::EnvSpecific(0x6ffffffe);
+    pub const GNU_VERSYM: Self = Self::EnvSpecific(0x6fffffff);
+
     pub const X86_64_UNWIND: Self = Self::CpuSpecific(0x70000001);
 }
 
@@ -227,13 +606,71 @@ impl SectionType {
             _ => SectionType::Unknown(x),
         }
     }
+
+    pub const fn to_u32(self) -> u32 {
+        match self {
+            SectionType::Null => 0,
+            SectionType::Progbits => 1,
+            SectionType::Symtab => 2,
+            SectionType::Strtab => 3,
+            SectionType::Rela => 4,
+            SectionType::Hash => 5,
+            SectionType::Dynamic => 6,
+            SectionType::Note => 7,
+            SectionType::Nobits => 8,
+            SectionType::Rel => 9,
+            SectionType::Shlib => 10,
+            SectionType::Dynsym => 11,
+            SectionType::InitArray => 14,
+            SectionType::FiniArray => 15,
+            SectionType::PreinitArray => 16,
+            SectionType::Group => 17,
+            SectionType::SymtabShndx => 18,
+            SectionType::EnvSpecific(x)
+            | SectionType::CpuSpecific(x)
+            | SectionType::UserSpecific(x)
+            | SectionType::Unknown(x) => x,
+        }
+    }
 }
 
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub struct SectionFlags {
     pub(crate) bits: u64,
 }
 
 impl SectionFlags {
+    pub const WRITE: Self = Self::from_bits(0x1);
+    pub const ALLOC: Self = Self::from_bits(0x2);
+    pub const EXECINSTR: Self = Self::from_bits(0x4);
+    pub const MERGE: Self = Self::from_bits(0x10);
+    pub const STRINGS: Self = Self::from_bits(0x20);
+    pub const INFO_LINK: Self = Self::from_bits(0x40);
+    pub const LINK_ORDER: Self = Self::from_bits(0x80);
+    pub const OS_NONCONFORMING: Self = Self::from_bits(0x100);
+    pub const GROUP: Self = Self::from_bits(0x200);
+    pub const TLS: Self = Self::from_bits(0x400);
+
+    #[inline]
+    pub const fn from_bits(bits: u64) -> SectionFlags {
+        Self { bits }
+    }
+
+    #[inline]
+    pub const fn bits(self) -> u64 {
+        self.bits
+    }
+
+    #[inline]
+    pub const fn union(self, other: SectionFlags) -> SectionFlags {
+        Self::from_bits(self.bits | other.bits)
+    }
+
+    #[inline]
+    pub const fn contains(self, other: SectionFlags) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
     #[inline]
     pub const fn write(self) -> bool {
         self.bits & 0x1 != 0
@@ -284,3 +721,11 @@ impl SectionFlags {
         self.bits & 0x400 != 0
     }
 }
+
+impl core::ops::BitOr for SectionFlags {
+    type Output = SectionFlags;
+
+    fn bitor(self, rhs: SectionFlags) -> SectionFlags {
+        self.union(rhs)
+    }
+}
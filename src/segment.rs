@@ -28,25 +28,140 @@
  * SPDX-License-Identifier: BSD-3-Clause
  */
 
-use crate::{assert_struct_size, Elf};
-use core::fmt;
+use crate::{
+    assert_struct_size, table::impl_entry, Elf, Endian, LittleEndian, Section, SectionType, U32,
+    U64,
+};
+use core::{fmt, ops::Range};
 
 pub struct Segment<'a, 'elf> {
     elf: &'a Elf<'elf>,
-    hdr: &'elf ProgramHeader,
+    hdr: ProgramHeader,
 }
 
 impl<'a, 'elf> Segment<'a, 'elf> {
     #[inline]
-    pub(crate) fn new(elf: &'a Elf<'elf>, hdr: &'elf ProgramHeader) -> Segment<'a, 'elf> {
+    pub(crate) fn new(elf: &'a Elf<'elf>, hdr: ProgramHeader) -> Segment<'a, 'elf> {
         Self { elf, hdr }
     }
+
+    #[inline]
+    pub(crate) fn elf(&self) -> &'a Elf<'elf> {
+        self.elf
+    }
 }
 
 impl<'elf> Segment<'_, 'elf> {
+    /// Returns the file image of the segment, or `None` if it lies outside of the file
     #[inline]
-    pub fn file_data(&self) -> &'elf [u8] {
-        &self.elf.data[self.file_offset()..][..self.file_size()]
+    pub fn file_data(&self) -> Option<&'elf [u8]> {
+        self.elf.get_slice(self.file_offset(), self.file_size())
+    }
+
+    /// Returns `true` if `sect` is part of this segment
+    ///
+    /// This follows the rules used by readelf's section to segment mapping: non-`NOBITS`
+    /// sections must lie within the segment's file image, allocated sections must lie within
+    /// its memory image, and TLS sections only belong to `PT_TLS`, `PT_LOAD`, and
+    /// `PT_GNU_RELRO` segments. `.tbss` occupies no memory outside of `PT_TLS`.
+    pub fn contains_section(&self, sect: &Section) -> bool {
+        let kind = self.kind();
+        let flags = sect.flags();
+        let nobits = sect.section_type() == SectionType::Nobits;
+
+        if sect.section_type() == SectionType::Null {
+            return false;
+        }
+
+        let tls_ok = if flags.tls() {
+            matches!(
+                kind,
+                SegmentKind::Tls | SegmentKind::Load | SegmentKind::Relro
+            )
+        } else {
+            !matches!(kind, SegmentKind::Tls | SegmentKind::Phdr)
+        };
+        if !tls_ok {
+            return false;
+        }
+
+        let size = match nobits && flags.tls() && kind != SegmentKind::Tls {
+            true => 0,
+            false => sect.size() as u64,
+        };
+
+        let offset = sect.file_offset() as u64;
+        let seg_offset = self.file_offset() as u64;
+        let file_size = self.file_size() as u64;
+        let in_file = nobits
+            || (offset >= seg_offset
+                && offset - seg_offset <= file_size.saturating_sub(1)
+                && offset - seg_offset + size <= file_size);
+
+        let addr = sect.addr();
+        let vaddr = self.virtual_address();
+        let mem_size = self.mem_size() as u64;
+        let in_memory = !flags.alloc()
+            || (addr >= vaddr
+                && addr - vaddr <= mem_size.saturating_sub(1)
+                && addr - vaddr + size <= mem_size);
+
+        // Empty sections at the very start or end of these segments are not part of them.
+        let strictly_inside = || {
+            (nobits || (offset > seg_offset && offset - seg_offset < file_size))
+                && (!flags.alloc() || (addr > vaddr && addr - vaddr < mem_size))
+        };
+        let edges_ok = !matches!(kind, SegmentKind::Dynamic | SegmentKind::Note)
+            || size != 0
+            || mem_size == 0
+            || strictly_inside();
+
+        in_file && in_memory && edges_ok
+    }
+
+    /// Returns the pieces of the segment's memory image, in order of address
+    ///
+    /// The first `p_filesz` bytes are backed by the file, starting at the given offset, and
+    /// the rest of the image is zero-filled. Empty pieces are skipped, and a file image
+    /// larger than the memory image is truncated to it.
+    pub fn ranges(&self) -> impl Iterator<Item = (Range<u64>, RangeSource)> {
+        let start = self.virtual_address();
+        let mem_size = self.mem_size() as u64;
+        let file_size = (self.file_size() as u64).min(mem_size);
+        let file_end = start.saturating_add(file_size);
+        let end = start.saturating_add(mem_size);
+
+        let file = (file_size != 0).then(|| {
+            let offset = self.file_offset() as u64;
+            (start..file_end, RangeSource::File(offset))
+        });
+        let zero = (file_end < end).then_some((file_end..end, RangeSource::Zero));
+
+        file.into_iter().chain(zero)
+    }
+}
+
+/// Where the contents of a piece of a segment's memory image come from
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RangeSource {
+    /// The file, starting at this offset
+    File(u64),
+    /// Zero-filled memory, such as `.bss`
+    Zero,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the `len` bytes of file data loaded at `vaddr`
+    ///
+    /// Returns `None` unless the whole range lies within the file image of one `PT_LOAD`
+    /// segment.
+    pub fn data_at(&self, vaddr: u64, len: usize) -> Option<&'elf [u8]> {
+        self.segments()
+            .filter(|sgmt| sgmt.kind() == SegmentKind::Load)
+            .find_map(|sgmt| {
+                let offset = usize::try_from(vaddr.checked_sub(sgmt.virtual_address())?).ok()?;
+                sgmt.file_data()?.get(offset..)?.get(..len)
+            })
     }
 }
 
@@ -55,12 +170,18 @@ impl fmt::Debug for Segment<'_, '_> {
         f.debug_struct("Segment")
             .field("kind", &self.kind())
             .field("flags", &self.flags())
-            .field("virt_address", &format_args!("{:#018x}", self.vaddr))
-            .field("phys_address", &format_args!("{:#018x}", self.paddr))
-            .field("file_offset", &format_args!("{:#x}", self.file_offset))
-            .field("file_size", &format_args!("{:#x}", self.file_size))
-            .field("mem_size", &format_args!("{:#x}", self.mem_size))
-            .field("alignment", &format_args!("{:#x}", self.alignment))
+            .field(
+                "virt_address",
+                &format_args!("{:#018x}", self.virtual_address()),
+            )
+            .field(
+                "phys_address",
+                &format_args!("{:#018x}", self.physical_address()),
+            )
+            .field("file_offset", &format_args!("{:#x}", self.file_offset()))
+            .field("file_size", &format_args!("{:#x}", self.file_size()))
+            .field("mem_size", &format_args!("{:#x}", self.mem_size()))
+            .field("alignment", &format_args!("{:#x}", self.alignment()))
             .finish()
     }
 }
@@ -69,7 +190,7 @@ impl core::ops::Deref for Segment<'_, '_> {
     type Target = ProgramHeader;
 
     fn deref(&self) -> &Self::Target {
-        self.hdr
+        &self.hdr
     }
 }
 
@@ -83,56 +204,171 @@ bitflags::bitflags! {
 }
 
 #[repr(C)]
-pub struct ProgramHeader {
-    kind: u32,
-    flags: SegmentFlags,
-    file_offset: u64,
-    vaddr: u64,
-    paddr: u64,
-    file_size: u64,
-    mem_size: u64,
-    pub alignment: u64,
+#[derive(Clone, Copy)]
+pub struct ProgramHeader<E: Endian = LittleEndian> {
+    kind: U32<E>,
+    flags: U32<E>,
+    file_offset: U64<E>,
+    vaddr: U64<E>,
+    paddr: U64<E>,
+    file_size: U64<E>,
+    mem_size: U64<E>,
+    alignment: U64<E>,
 }
 
 assert_struct_size!(ProgramHeader, 56);
 
-impl ProgramHeader {
+impl<E: Endian> ProgramHeader<E> {
+    /// Re-encode the header in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> ProgramHeader<F> {
+        ProgramHeader {
+            kind: self.kind.convert(),
+            flags: self.flags.convert(),
+            file_offset: self.file_offset.convert(),
+            vaddr: self.vaddr.convert(),
+            paddr: self.paddr.convert(),
+            file_size: self.file_size.convert(),
+            mem_size: self.mem_size.convert(),
+            alignment: self.alignment.convert(),
+        }
+    }
+
+    /// Create a program header with no address or contents
+    pub const fn new(kind: SegmentKind, flags: SegmentFlags) -> ProgramHeader<E> {
+        ProgramHeader {
+            kind: U32::new(kind.to_u32()),
+            flags: U32::new(flags.bits()),
+            file_offset: U64::new(0),
+            vaddr: U64::new(0),
+            paddr: U64::new(0),
+            file_size: U64::new(0),
+            mem_size: U64::new(0),
+            alignment: U64::new(0),
+        }
+    }
+
+    /// Set the virtual and physical addresses of the segment
+    pub const fn with_addr(mut self, vaddr: u64, paddr: u64) -> ProgramHeader<E> {
+        self.vaddr = U64::new(vaddr);
+        self.paddr = U64::new(paddr);
+        self
+    }
+
+    /// Set the location of the segment's contents in the file
+    pub const fn with_file_range(mut self, offset: u64, size: u64) -> ProgramHeader<E> {
+        self.file_offset = U64::new(offset);
+        self.file_size = U64::new(size);
+        self
+    }
+
+    pub const fn with_mem_size(mut self, mem_size: u64) -> ProgramHeader<E> {
+        self.mem_size = U64::new(mem_size);
+        self
+    }
+
+    pub const fn with_alignment(mut self, alignment: u64) -> ProgramHeader<E> {
+        self.alignment = U64::new(alignment);
+        self
+    }
+
+    /// Returns the header as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    pub fn set_flags(&mut self, flags: SegmentFlags) {
+        self.flags.set(flags.bits());
+    }
+
+    pub fn set_file_range(&mut self, offset: u64, size: u64) {
+        self.file_offset.set(offset);
+        self.file_size.set(size);
+    }
+
     #[inline]
     pub const fn kind(&self) -> SegmentKind {
-        SegmentKind::from_u32(self.kind)
+        SegmentKind::from_u32(self.kind.get())
     }
 
     #[inline]
     pub const fn flags(&self) -> SegmentFlags {
-        self.flags
+        SegmentFlags::from_bits_truncate(self.flags.get())
     }
 
     #[inline]
     pub const fn file_offset(&self) -> usize {
-        self.file_offset as _
+        crate::to_usize(self.file_offset.get())
     }
 
     #[inline]
     pub const fn file_size(&self) -> usize {
-        self.file_size as _
+        crate::to_usize(self.file_size.get())
     }
 
     #[inline]
     pub const fn mem_size(&self) -> usize {
-        self.mem_size as _
+        crate::to_usize(self.mem_size.get())
     }
 
     #[inline]
     pub const fn virtual_address(&self) -> u64 {
-        self.vaddr
+        self.vaddr.get()
     }
 
     #[inline]
     pub const fn physical_address(&self) -> u64 {
-        self.paddr
+        self.paddr.get()
+    }
+
+    #[inline]
+    pub const fn alignment(&self) -> u64 {
+        self.alignment.get()
     }
 }
 
+/// The program header of an ELF32 file
+///
+/// The flags follow the sizes, rather than the type as in [`ProgramHeader`].
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ProgramHeader32<E: Endian = LittleEndian> {
+    kind: U32<E>,
+    file_offset: U32<E>,
+    vaddr: U32<E>,
+    paddr: U32<E>,
+    file_size: U32<E>,
+    mem_size: U32<E>,
+    flags: U32<E>,
+    alignment: U32<E>,
+}
+
+assert_struct_size!(ProgramHeader32, 32);
+
+impl<E: Endian> ProgramHeader32<E> {
+    /// Returns the header as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+}
+
+impl<E: Endian> From<ProgramHeader32<E>> for ProgramHeader<E> {
+    fn from(phdr: ProgramHeader32<E>) -> ProgramHeader<E> {
+        let widen = |x: U32<E>| U64::new(x.get() as u64);
+        ProgramHeader {
+            kind: phdr.kind,
+            flags: phdr.flags,
+            file_offset: widen(phdr.file_offset),
+            vaddr: widen(phdr.vaddr),
+            paddr: widen(phdr.paddr),
+            file_size: widen(phdr.file_size),
+            mem_size: widen(phdr.mem_size),
+            alignment: widen(phdr.alignment),
+        }
+    }
+}
+
+impl_entry!(ProgramHeader, ProgramHeader32);
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum SegmentKind {
     Null,
@@ -172,4 +408,22 @@ impl SegmentKind {
             _ => Self::Unknown(x),
         }
     }
+
+    pub const fn to_u32(self) -> u32 {
+        match self {
+            Self::Null => 0,
+            Self::Load => 1,
+            Self::Dynamic => 2,
+            Self::Interp => 3,
+            Self::Note => 4,
+            Self::Shlib => 5,
+            Self::Phdr => 6,
+            Self::Tls => 7,
+            Self::Unwind => 0x6464e550,
+            Self::EhFrame => 0x6474e550,
+            Self::Stack => 0x6474e551,
+            Self::Relro => 0x6474e552,
+            Self::EnvSpecific(x) | Self::CpuSpecific(x) | Self::Unknown(x) => x,
+        }
+    }
 }
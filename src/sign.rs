@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Detached signatures
+//!
+//! A signature is stored in a `.signature` section appended to the file after it has been
+//! built. The signature covers a digest of the rest of the file, computed by
+//! [`Elf::signed_digest()`], which is unaffected by appending the signature itself.
+//! Hashing and signature verification are left to the caller.
+
+use crate::{Elf, ElfMut, ParseError, SectionFlags, SectionType, Structure};
+
+/// The name of the section which holds a detached signature
+pub const SIGNATURE_SECTION: &str = ".signature";
+
+/// A hash function which can be fed the signed contents of a file
+pub trait Digest {
+    fn update(&mut self, data: &[u8]);
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignatureError {
+    /// The file has no signature section
+    Missing,
+    /// The signature does not match the file's contents
+    Invalid,
+    /// The contents of a section lie outside of the file
+    Malformed,
+}
+
+impl Elf<'_> {
+    /// Feed the signed contents of the file into `digest`
+    ///
+    /// This covers the file type, machine, entry point and flags, followed by the name,
+    /// type, flags, and contents of every section in order, except the signature section
+    /// and the section name string table. Returns an error if the contents of a section lie
+    /// outside of the file.
+    pub fn signed_digest<D: Digest>(&self, digest: &mut D) -> Result<(), SignatureError> {
+        digest.update(&self.file_type().to_u16().to_le_bytes());
+        digest.update(&self.machine().to_u16().to_le_bytes());
+        digest.update(&self.entry_point().to_le_bytes());
+        digest.update(&self.flags().to_le_bytes());
+
+        let shstrndx = self.shdr_strtab_index() as usize;
+        for (index, sect) in self.sections().enumerate() {
+            let name = sect.name().unwrap_or("");
+            if index == 0 || index == shstrndx || name == SIGNATURE_SECTION {
+                continue;
+            }
+
+            let data = match sect.section_type() {
+                SectionType::Nobits => &[],
+                _ => sect.file_data().ok_or(SignatureError::Malformed)?,
+            };
+
+            digest.update(name.as_bytes());
+            digest.update(&[0]);
+            digest.update(&sect.section_type().to_u32().to_le_bytes());
+            digest.update(&sect.flags().bits().to_le_bytes());
+            digest.update(&(sect.size() as u64).to_le_bytes());
+            digest.update(data);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the contents of the signature section, if present
+    pub fn signature(&self) -> Option<&[u8]> {
+        self.find_section(SIGNATURE_SECTION)?.file_data()
+    }
+
+    /// Verify the file's detached signature
+    ///
+    /// `verify` is passed the digest after it has been fed the signed contents of the file,
+    /// along with the signature, and returns whether the signature is valid.
+    pub fn verify_signature<D, V>(&self, mut digest: D, verify: V) -> Result<(), SignatureError>
+    where
+        D: Digest,
+        V: FnOnce(D, &[u8]) -> bool,
+    {
+        let signature = self.signature().ok_or(SignatureError::Missing)?;
+        self.signed_digest(&mut digest)?;
+
+        match verify(digest, signature) {
+            true => Ok(()),
+            false => Err(SignatureError::Invalid),
+        }
+    }
+}
+
+impl ElfMut<'_> {
+    /// Append a signature section, returning its index
+    ///
+    /// The signature should be computed over [`Elf::signed_digest()`].
+    ///
+    /// Fails at the existing signature section if the file is already signed.
+    pub fn append_signature(&mut self, signature: &[u8]) -> Result<u16, ParseError> {
+        let elf = self.as_elf();
+        if let Some(sect) = elf.find_section(SIGNATURE_SECTION) {
+            if sect.file_data().is_some() {
+                let (index, offset) = (sect.index(), sect.file_offset() as u64);
+                let message = "file is already signed";
+                return Err(ParseError::new(Structure::Section(index), offset, message));
+            }
+        }
+
+        self.append_section(
+            SIGNATURE_SECTION,
+            SectionType::Progbits,
+            SectionFlags::default(),
+            signature,
+        )
+        .map_err(|message| ParseError::new(Structure::FileHeader, 0, message))
+    }
+}
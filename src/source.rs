@@ -0,0 +1,354 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Reading files on demand
+//!
+//! [`Elf`](crate::Elf) requires the whole file in memory. [`ElfReader`] instead fetches the
+//! file header, individual header table entries, and the contents of sections and segments
+//! from an [`ElfSource`] as they are needed, so only the parts in use must be buffered.
+
+use crate::{
+    parse_file_header,
+    table::{Encoding, Entry},
+    FileHeader, ParseError, ProgramHeader, SectionHeader, SectionType, Structure,
+};
+use core::{fmt, mem::size_of};
+
+/// Storage an ELF file can be read from, such as a block device or flash
+pub trait ElfSource {
+    type Error;
+
+    /// Fill `buf` with the bytes of the file starting at `offset`
+    ///
+    /// Returns an error if the range lies outside the file or cannot be read.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+impl ElfSource for [u8] {
+    type Error = ParseError;
+
+    /// Fails at `offset` in the file if the range lies past its end
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), ParseError> {
+        let src = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.get(offset..)?.get(..buf.len()))
+            .ok_or(ParseError::new(
+                Structure::FileHeader,
+                offset,
+                "read out of bounds",
+            ))?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+}
+
+impl<S: ElfSource + ?Sized> ElfSource for &S {
+    type Error = S::Error;
+
+    #[inline]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), S::Error> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+/// An error reading a file from an [`ElfSource`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SourceError<E> {
+    /// The source could not be read
+    Read(E),
+    /// The file is malformed
+    Parse(ParseError),
+}
+
+impl<E: fmt::Display> fmt::Display for SourceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(error) => write!(f, "read failed: {error}"),
+            Self::Parse(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E> From<ParseError> for SourceError<E> {
+    #[inline]
+    fn from(error: ParseError) -> SourceError<E> {
+        Self::Parse(error)
+    }
+}
+
+/// An ELF file whose structures are read from an [`ElfSource`] on demand
+///
+/// Only the file header is kept in memory. Header table entries are decoded into the same
+/// little-endian ELF64 layouts used by [`Elf`](crate::Elf).
+#[derive(Clone, Debug)]
+pub struct ElfReader<S> {
+    source: S,
+    ehdr: FileHeader,
+    encoding: Encoding,
+}
+
+impl<S: ElfSource> ElfReader<S> {
+    /// Read and check the file header
+    pub fn new(source: S) -> Result<ElfReader<S>, SourceError<S::Error>> {
+        // Read the ELF32 header first, which is all there is of a truncated ELF32 file.
+        let mut buf = [0; size_of::<FileHeader>()];
+        let (ident, rest) = buf.split_at_mut(FileHeader::SIZE32);
+        source.read_at(0, ident).map_err(SourceError::Read)?;
+        let len = match ident.get(4) {
+            Some(1) => FileHeader::SIZE32,
+            _ => {
+                source
+                    .read_at(FileHeader::SIZE32 as u64, rest)
+                    .map_err(SourceError::Read)?;
+                buf.len()
+            }
+        };
+        let (ehdr, encoding) = parse_file_header(&buf[..len])?;
+
+        Ok(ElfReader {
+            source,
+            ehdr,
+            encoding,
+        })
+    }
+
+    /// Returns the file header, in the little-endian ELF64 layout
+    #[inline]
+    pub fn header(&self) -> &FileHeader {
+        &self.ehdr
+    }
+
+    /// Returns the source the file is read from
+    #[inline]
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Read the entry at `index` of a header table
+    fn read_entry<T: Entry>(
+        &self,
+        structure: Structure,
+        table: usize,
+        num: u16,
+        index: u16,
+    ) -> Result<T, SourceError<S::Error>> {
+        let error = |message| SourceError::Parse(ParseError::new(structure, 0, message));
+        if index >= num {
+            return Err(error("index out of range"));
+        }
+
+        let size = T::encoded_size(self.encoding);
+        let offset = table as u64 + index as u64 * size as u64;
+        let mut buf = [0; 64];
+        let buf = &mut buf[..size];
+        self.source
+            .read_at(offset, buf)
+            .map_err(SourceError::Read)?;
+
+        T::read(buf, self.encoding).ok_or(SourceError::Parse(ParseError::new(
+            structure,
+            offset,
+            "truncated header",
+        )))
+    }
+
+    /// Read the program header at `index`
+    pub fn program_header(&self, index: u16) -> Result<ProgramHeader, SourceError<S::Error>> {
+        let ehdr = &self.ehdr;
+        self.read_entry(
+            Structure::ProgramHeader(index),
+            ehdr.phdr_offset(),
+            ehdr.phdr_num(),
+            index,
+        )
+    }
+
+    /// Read the section header at `index`
+    pub fn section_header(&self, index: u16) -> Result<SectionHeader, SourceError<S::Error>> {
+        let ehdr = &self.ehdr;
+        self.read_entry(
+            Structure::SectionHeader(index),
+            ehdr.shdr_offset(),
+            ehdr.shdr_num(),
+            index,
+        )
+    }
+
+    /// Returns an iterator which reads each program header in turn
+    pub fn program_headers(
+        &self,
+    ) -> impl Iterator<Item = Result<ProgramHeader, SourceError<S::Error>>> + '_ {
+        (0..self.ehdr.phdr_num()).map(|index| self.program_header(index))
+    }
+
+    /// Returns an iterator which reads each section header in turn
+    pub fn section_headers(
+        &self,
+    ) -> impl Iterator<Item = Result<SectionHeader, SourceError<S::Error>>> + '_ {
+        (0..self.ehdr.shdr_num()).map(|index| self.section_header(index))
+    }
+
+    /// Read `buf.len()` bytes at `offset` within a structure occupying `size` bytes at
+    /// `start` in the file
+    fn read_within(
+        &self,
+        structure: Structure,
+        start: usize,
+        size: usize,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), SourceError<S::Error>> {
+        if offset
+            .checked_add(buf.len() as u64)
+            .is_none_or(|end| end > size as u64)
+        {
+            return Err(SourceError::Parse(ParseError::new(
+                structure,
+                start as u64,
+                "read past the end of the contents",
+            )));
+        }
+
+        self.source
+            .read_at(start as u64 + offset, buf)
+            .map_err(SourceError::Read)
+    }
+
+    /// Read part of the contents of the section at `index`, starting `offset` bytes into it
+    ///
+    /// Returns an error if the section occupies no space in the file, or the range read
+    /// extends past its end.
+    pub fn read_section(
+        &self,
+        index: u16,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), SourceError<S::Error>> {
+        let shdr = self.section_header(index)?;
+        let size = match shdr.section_type() {
+            SectionType::Nobits => 0,
+            _ => shdr.size(),
+        };
+        self.read_within(
+            Structure::Section(index),
+            shdr.file_offset(),
+            size,
+            offset,
+            buf,
+        )
+    }
+
+    /// Read part of the file image of the segment at `index`, starting `offset` bytes into it
+    ///
+    /// Returns an error if the range read extends past the end of the file image.
+    pub fn read_segment(
+        &self,
+        index: u16,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), SourceError<S::Error>> {
+        let phdr = self.program_header(index)?;
+        let structure = Structure::ProgramHeader(index);
+        self.read_within(structure, phdr.file_offset(), phdr.file_size(), offset, buf)
+    }
+
+    /// Read a name from the section name string table
+    fn read_name(
+        &self,
+        strtab: &SectionHeader,
+        name_index: u32,
+        buf: &mut [u8],
+    ) -> Result<usize, SourceError<S::Error>> {
+        let start = name_index as u64;
+        let available = (strtab.size() as u64).saturating_sub(start);
+        let len = buf.len().min(available as usize);
+        if len == 0 {
+            return Ok(0);
+        }
+        let structure = Structure::Section(self.ehdr.shdr_strtab_index());
+        self.read_within(
+            structure,
+            strtab.file_offset(),
+            strtab.size(),
+            start,
+            &mut buf[..len],
+        )?;
+        Ok(len)
+    }
+
+    /// Read the name of a section into `buf`
+    ///
+    /// Returns an error if the name is not valid UTF-8 or does not fit in `buf`.
+    pub fn section_name<'b>(
+        &self,
+        shdr: &SectionHeader,
+        buf: &'b mut [u8],
+    ) -> Result<&'b str, SourceError<S::Error>> {
+        let strtab = self.section_header(self.ehdr.shdr_strtab_index())?;
+        let error = |message| {
+            SourceError::Parse(ParseError::new(
+                Structure::Section(self.ehdr.shdr_strtab_index()),
+                strtab.file_offset() as u64 + shdr.name_index() as u64,
+                message,
+            ))
+        };
+
+        let len = self.read_name(&strtab, shdr.name_index(), buf)?;
+
+        let end = buf[..len]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(error("name is too long"))?;
+        core::str::from_utf8(&buf[..end]).map_err(|_| error("name is not valid UTF-8"))
+    }
+
+    /// Find the section named `name`, returning its index and header
+    pub fn find_section(
+        &self,
+        name: &str,
+    ) -> Result<Option<(u16, SectionHeader)>, SourceError<S::Error>> {
+        // Room for the name and its terminator, which must match too.
+        let mut buf = [0; 256];
+        let Some(buf) = buf.get_mut(..name.len() + 1) else {
+            return Ok(None);
+        };
+
+        let strtab = self.section_header(self.ehdr.shdr_strtab_index())?;
+        for index in 0..self.ehdr.shdr_num() {
+            let shdr = self.section_header(index)?;
+            let len = self.read_name(&strtab, shdr.name_index(), buf)?;
+            if len == buf.len() && buf[..name.len()] == *name.as_bytes() && buf[name.len()] == 0 {
+                return Ok(Some((index, shdr)));
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,190 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Stack usage metadata
+
+use crate::{config_keys, leb128::read_uleb128, table::Encoding, Elf, SegmentFlags, SegmentKind};
+
+/// The stack usage of a single function, from a `.stack_sizes` section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StackSize {
+    /// Address of the function
+    ///
+    /// In relocatable files this is the offset of the function within [`section`].
+    ///
+    /// [`section`]: StackSize::section
+    pub address: u64,
+    /// Size of the function's stack frame, in bytes
+    pub stack_size: u64,
+    /// Index of the section containing the function, if known
+    pub section: Option<u16>,
+}
+
+/// Where a requested stack size was taken from
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StackSizeSource {
+    /// The configuration note's `stack-size` entry
+    ConfigNote,
+    /// The memory size of `PT_GNU_STACK`, as set by `ld -z stack-size`
+    GnuStack,
+}
+
+/// How the initial stack of a program should be sized and protected
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct StackRequirements {
+    /// Requested size of the stack, in bytes
+    pub size: Option<u64>,
+    /// Where [`size`](StackRequirements::size) was taken from
+    pub size_source: Option<StackSizeSource>,
+    /// Protection of the stack
+    ///
+    /// These are the flags of `PT_GNU_STACK`. Without one, the stack is readable, writable,
+    /// and executable.
+    pub flags: SegmentFlags,
+    /// `true` if the file has a `PT_GNU_STACK` segment
+    pub has_gnu_stack: bool,
+    /// The largest frame of any function listed in `.stack_sizes`
+    ///
+    /// The stack must be at least this large for the function to run at all.
+    pub largest_frame: Option<u64>,
+}
+
+impl StackRequirements {
+    /// Returns `true` if the stack must be executable
+    #[inline]
+    pub const fn executable(&self) -> bool {
+        self.flags.contains(SegmentFlags::EXEC)
+    }
+}
+
+/// Iterator over the raw entries of a `.stack_sizes` section
+///
+/// Each entry is a function address followed by the ULEB128-encoded stack size.
+/// Yields the offset of each entry along with its contents.
+#[derive(Clone, Debug)]
+pub struct StackSizes<'elf> {
+    data: &'elf [u8],
+    offset: usize,
+    encoding: Encoding,
+}
+
+impl<'elf> StackSizes<'elf> {
+    /// Read the entries of a little-endian ELF64 file
+    pub const fn new(data: &'elf [u8]) -> StackSizes<'elf> {
+        StackSizes::with_encoding(data, Encoding::NATIVE)
+    }
+
+    /// Read the entries of a file laid out as `encoding`
+    pub(crate) const fn with_encoding(data: &'elf [u8], encoding: Encoding) -> StackSizes<'elf> {
+        StackSizes {
+            data,
+            offset: 0,
+            encoding,
+        }
+    }
+}
+
+impl Iterator for StackSizes<'_> {
+    type Item = (usize, u64, u64);
+
+    fn next(&mut self) -> Option<(usize, u64, u64)> {
+        let mut rest = &self.data[self.offset..];
+        let address = self.encoding.read_word(rest)?;
+        rest = &rest[self.encoding.word_size()..];
+        let stack_size = read_uleb128(&mut rest)?;
+
+        let offset = self.offset;
+        self.offset = self.data.len() - rest.len();
+        Some((offset, address, stack_size))
+    }
+}
+
+impl Elf<'_> {
+    /// Returns an iterator over the entries of all `.stack_sizes` sections
+    ///
+    /// These are emitted by LLVM with `-fstack-size-section`. In relocatable files, function
+    /// addresses are resolved through the relocations against each section, and are
+    /// relative to the text section the entry's section is linked to. Sections whose
+    /// relocations are malformed are skipped.
+    pub fn stack_sizes(&self) -> impl Iterator<Item = StackSize> + '_ {
+        self.sections_in_link_order(".stack_sizes")
+            .filter_map(move |sect| Some((self.relas_for_section(sect.index()).ok()?, sect)))
+            .flat_map(move |(relas, sect)| {
+                let section = sect.link_order_section().map(|text| text.index());
+
+                let data = sect.file_data().unwrap_or_default();
+                StackSizes::with_encoding(data, Encoding::of(self)).map(
+                    move |(offset, raw, stack_size)| {
+                        let (address, reloc_section) =
+                            self.relocated_address(relas, offset as u64, raw);
+
+                        StackSize {
+                            address,
+                            section: reloc_section.or(section),
+                            stack_size,
+                        }
+                    },
+                )
+            })
+    }
+
+    /// Returns how the initial stack of this program should be sized and protected
+    ///
+    /// The requested size is taken from the configuration note's `stack-size` entry, or
+    /// from the size of `PT_GNU_STACK` if it is non-zero.
+    pub fn stack_requirements(&self) -> StackRequirements {
+        let stack = self
+            .segments()
+            .find(|sgmt| sgmt.kind() == SegmentKind::Stack);
+
+        let config_size = self
+            .config_note()
+            .and_then(|config| config.get_u64(config_keys::STACK_SIZE));
+        let gnu_stack_size = stack
+            .as_ref()
+            .map(|sgmt| sgmt.mem_size() as u64)
+            .filter(|&size| size != 0);
+        let (size, size_source) = match (config_size, gnu_stack_size) {
+            (Some(size), _) => (Some(size), Some(StackSizeSource::ConfigNote)),
+            (None, Some(size)) => (Some(size), Some(StackSizeSource::GnuStack)),
+            (None, None) => (None, None),
+        };
+
+        StackRequirements {
+            size,
+            size_source,
+            flags: stack
+                .as_ref()
+                .map_or(SegmentFlags::all(), |sgmt| sgmt.flags()),
+            has_gnu_stack: stack.is_some(),
+            largest_frame: self.stack_sizes().map(|entry| entry.stack_size).max(),
+        }
+    }
+}
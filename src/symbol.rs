@@ -28,14 +28,19 @@
  * SPDX-License-Identifier: BSD-3-Clause
  */
 
-use crate::{assert_struct_size, Elf, Section, StringTable, SHN_ABS, SHN_COMMON, SHN_UNDEF};
+use crate::{
+    assert_struct_size,
+    table::{impl_entry, Encoding, Table},
+    Elf, Endian, LittleEndian, Section, StringTable, SHN_ABS, SHN_COMMON, SHN_UNDEF, U16, U32, U64,
+};
 use core::fmt;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct SymbolTable<'elf> {
     elf: &'elf Elf<'elf>,
-    _strtab: Option<StringTable<'elf>>,
-    data: &'elf [crate::Sym],
+    strtab: Option<StringTable<'elf>>,
+    data: Table<'elf, Sym>,
+    first: usize,
 }
 
 impl<'elf> SymbolTable<'elf> {
@@ -44,16 +49,113 @@ impl<'elf> SymbolTable<'elf> {
         elf: &'elf Elf<'elf>,
         data: &'elf [Sym],
         strtab: Option<StringTable<'elf>>,
+    ) -> SymbolTable<'elf> {
+        Self::from_table(elf, Table::from_entries(data, Encoding::NATIVE), strtab)
+    }
+
+    /// Create a table of ELF32 symbols
+    #[inline]
+    pub fn new32(
+        elf: &'elf Elf<'elf>,
+        data: &'elf [Sym32],
+        strtab: Option<StringTable<'elf>>,
+    ) -> SymbolTable<'elf> {
+        let encoding = Encoding {
+            bits32: true,
+            big_endian: false,
+        };
+        Self::from_table(elf, Table::from_entries(data, encoding), strtab)
+    }
+
+    pub(crate) fn from_table(
+        elf: &'elf Elf<'elf>,
+        data: Table<'elf, Sym>,
+        strtab: Option<StringTable<'elf>>,
     ) -> SymbolTable<'elf> {
         Self {
             elf,
-            _strtab: strtab,
+            strtab,
             data,
+            first: 0,
         }
     }
 
+    /// Returns the number of symbols in the table
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the index of the first symbol of this table within the complete table
+    ///
+    /// This is only non-zero for tables produced by [`chunks()`](Self::chunks)
+    /// or [`split_at()`](Self::split_at).
+    #[inline]
+    pub const fn first_index(&self) -> usize {
+        self.first
+    }
+
+    /// Returns the raw symbol entries, or `None` unless they are little-endian ELF64 symbols
+    #[inline]
+    pub fn raw(&self) -> Option<&'elf [Sym]> {
+        self.data.native()
+    }
+
+    /// Returns the symbol at `index`, relative to the start of this table
+    pub fn get(&self, index: usize) -> Option<Symbol<'_, 'elf>> {
+        self.data.get(index).map(|sym| Symbol {
+            elf: self.elf,
+            strtab: self.strtab,
+            sym,
+        })
+    }
+
+    /// Split the table into two at `mid`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > self.len()`.
+    pub fn split_at(&self, mid: usize) -> (SymbolTable<'elf>, SymbolTable<'elf>) {
+        (
+            SymbolTable {
+                data: self.data.slice(0..mid),
+                ..*self
+            },
+            SymbolTable {
+                data: self.data.slice(mid..self.len()),
+                first: self.first + mid,
+                ..*self
+            },
+        )
+    }
+
+    /// Split the table into sub-tables of at most `size` symbols each
+    ///
+    /// The chunks are independent values which can be sent to other threads and
+    /// processed in parallel. Each chunk remembers its [`first_index()`](Self::first_index).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = SymbolTable<'elf>> + '_ {
+        (0..self.len()).step_by(size).map(move |start| SymbolTable {
+            data: self.data.slice(start..self.len().min(start + size)),
+            first: self.first + start,
+            ..*self
+        })
+    }
+
     pub fn symbols(&self) -> impl Iterator<Item = Symbol<'_, 'elf>> {
-        self.data.iter().map(|sym| Symbol { elf: self.elf, sym })
+        self.data.iter().map(|sym| Symbol {
+            elf: self.elf,
+            strtab: self.strtab,
+            sym,
+        })
     }
 
     #[inline]
@@ -67,18 +169,30 @@ impl<'elf> SymbolTable<'elf> {
 
 pub struct Symbol<'a, 'elf> {
     elf: &'a Elf<'elf>,
-    sym: &'elf Sym,
+    strtab: Option<StringTable<'elf>>,
+    sym: Sym,
+}
+
+impl<'a, 'elf> Symbol<'a, 'elf> {
+    #[inline]
+    pub(crate) fn new(
+        elf: &'a Elf<'elf>,
+        strtab: Option<StringTable<'elf>>,
+        sym: Sym,
+    ) -> Symbol<'a, 'elf> {
+        Self { elf, strtab, sym }
+    }
 }
 
 impl<'elf> Symbol<'_, 'elf> {
     #[inline]
     pub fn name(&self) -> Option<&'elf str> {
-        self.elf.string_table()?.get_string(self.name_index())
+        self.strtab?.get_string(self.name_index())
     }
 
     #[inline]
     pub fn section(&self) -> Option<Section<'_, 'elf>> {
-        self.elf.section(self.section_index)
+        self.elf.section(self.section_index())
     }
 }
 
@@ -101,26 +215,71 @@ impl core::ops::Deref for Symbol<'_, '_> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.sym
+        &self.sym
     }
 }
 
 #[repr(C)]
-#[derive(Clone, Debug)]
-pub struct Sym {
-    name_index: u32,
+#[derive(Clone, Copy, Debug)]
+pub struct Sym<E: Endian = LittleEndian> {
+    name_index: U32<E>,
     info: SymInfo,
-    section_index: u16,
-    value: u64,
-    size: u64,
+    section_index: U16<E>,
+    value: U64<E>,
+    size: U64<E>,
 }
 
 assert_struct_size!(Sym, 24);
 
-impl Sym {
+impl<E: Endian> Sym<E> {
+    /// Re-encode the symbol in byte order `F`
+    pub const fn convert<F: Endian>(&self) -> Sym<F> {
+        Sym {
+            name_index: self.name_index.convert(),
+            info: self.info,
+            section_index: self.section_index.convert(),
+            value: self.value.convert(),
+            size: self.size.convert(),
+        }
+    }
+
+    pub const fn new(
+        name_index: u32,
+        info: SymInfo,
+        section_index: u16,
+        value: u64,
+        size: u64,
+    ) -> Sym<E> {
+        Sym {
+            name_index: U32::new(name_index),
+            info,
+            section_index: U16::new(section_index),
+            value: U64::new(value),
+            size: U64::new(size),
+        }
+    }
+
+    /// Returns the symbol as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+
+    pub fn set_name_index(&mut self, name_index: u32) {
+        self.name_index.set(name_index);
+    }
+
+    pub fn set_section_index(&mut self, section_index: u16) {
+        self.section_index.set(section_index);
+    }
+
+    /// Set the binding, leaving the type and visibility unchanged
+    pub fn set_binding(&mut self, binding: Binding) {
+        self.info.info = binding.to_u8() << 4 | (self.info.info & 0xf);
+    }
+
     #[inline]
     pub const fn name_index(&self) -> usize {
-        self.name_index as usize
+        self.name_index.get() as usize
     }
 
     #[inline]
@@ -140,22 +299,22 @@ impl Sym {
 
     #[inline]
     pub const fn section_index(&self) -> u16 {
-        self.section_index
+        self.section_index.get()
     }
 
     #[inline]
     pub const fn is_resolved(&self) -> bool {
-        self.section_index == SHN_UNDEF
+        self.section_index() == SHN_UNDEF
     }
 
     #[inline]
     pub const fn is_absolute(&self) -> bool {
-        self.section_index == SHN_ABS
+        self.section_index() == SHN_ABS
     }
 
     #[inline]
     pub const fn is_common(&self) -> bool {
-        self.section_index == SHN_COMMON
+        self.section_index() == SHN_COMMON
     }
 
     /// Returns the symbol's value
@@ -173,27 +332,61 @@ impl Sym {
     ///   this case.
     #[inline]
     pub const fn value(&self) -> u64 {
-        self.value
+        self.value.get()
     }
 
     #[inline]
     pub const fn as_ptr<T>(&self) -> *const T {
-        self.value as _
+        self.value() as _
     }
 
     #[inline]
     pub const fn as_mut_ptr<T>(&self) -> *mut T {
-        self.value as _
+        self.value() as _
     }
 
     #[inline]
     pub const fn size(&self) -> u64 {
-        self.size
+        self.size.get()
     }
 
     #[inline]
     pub const fn contains_addr(&self, addr: u64) -> bool {
-        self.value <= addr && addr < (self.value + self.size)
+        self.value() <= addr && addr < (self.value() + self.size())
+    }
+}
+
+/// A symbol table entry of an ELF32 file
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Sym32<E: Endian = LittleEndian> {
+    name_index: U32<E>,
+    value: U32<E>,
+    size: U32<E>,
+    info: SymInfo,
+    section_index: U16<E>,
+}
+
+assert_struct_size!(Sym32, 16);
+
+impl_entry!(Sym, Sym32);
+
+impl<E: Endian> Sym32<E> {
+    /// Returns the symbol as it is laid out on disk
+    pub const fn as_bytes(&self) -> &[u8] {
+        crate::bytes_of(self)
+    }
+}
+
+impl<E: Endian> From<Sym32<E>> for Sym<E> {
+    fn from(sym: Sym32<E>) -> Sym<E> {
+        Sym {
+            name_index: sym.name_index,
+            info: sym.info,
+            section_index: sym.section_index,
+            value: U64::new(sym.value.get() as u64),
+            size: U64::new(sym.size.get() as u64),
+        }
     }
 }
 
@@ -205,6 +398,14 @@ pub struct SymInfo {
 }
 
 impl SymInfo {
+    #[inline]
+    pub const fn new(kind: SymbolKind, binding: Binding, visibility: Visibility) -> SymInfo {
+        SymInfo {
+            info: binding.to_u8() << 4 | kind.to_u8(),
+            other: visibility.to_u8(),
+        }
+    }
+
     #[inline]
     pub const fn kind(self) -> SymbolKind {
         SymbolKind::from_sym_info(self)
@@ -261,6 +462,20 @@ impl SymbolKind {
             x => Self::Unknown(x),
         }
     }
+
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::NoType => 0,
+            Self::Object => 1,
+            Self::Func => 2,
+            Self::Section => 3,
+            Self::File => 4,
+            Self::Common => 5,
+            Self::Tls => 6,
+            Self::Ifunc => 10,
+            Self::EnvSpecific(x) | Self::CpuSpecific(x) | Self::Unknown(x) => x,
+        }
+    }
 }
 
 /// Symbol Binding
@@ -305,6 +520,15 @@ impl Binding {
             x => Binding::Unknown(x),
         }
     }
+
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Binding::Local => 0,
+            Binding::Global => 1,
+            Binding::Weak => 2,
+            Binding::EnvSpecific(x) | Binding::CpuSpecific(x) | Binding::Unknown(x) => x,
+        }
+    }
 }
 
 /// Symbol Visibility
@@ -326,4 +550,13 @@ impl Visibility {
             _ => unreachable!(),
         }
     }
+
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Visibility::Default => 0,
+            Visibility::Internal => 1,
+            Visibility::Hidden => 2,
+            Visibility::Protected => 3,
+        }
+    }
 }
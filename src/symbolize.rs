@@ -0,0 +1,188 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Address symbolization from multiple symbol sources
+
+use crate::{table::Table, Elf, StringTable, Sym, SymbolKind, SHN_UNDEF};
+
+/// A place symbols can be looked up
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SymbolSource {
+    /// The file's `.symtab`
+    Symtab,
+    /// The file's `.dynsym`
+    Dynsym,
+    /// The `.symtab` of the MiniDebugInfo file embedded in `.gnu_debugdata`
+    MiniDebugInfo,
+    /// The `.symtab` of a separate debug file
+    External,
+}
+
+/// The result of symbolizing an address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Symbolized<'a> {
+    pub name: &'a str,
+    /// Offset of the address from the start of the symbol
+    pub offset: u64,
+    /// Size of the symbol, which is zero if unknown
+    pub size: u64,
+    pub source: SymbolSource,
+}
+
+impl Symbolized<'_> {
+    /// Returns `true` if the address lies within the symbol's extent
+    #[inline]
+    pub const fn is_exact(&self) -> bool {
+        self.offset < self.size
+    }
+}
+
+const DEFAULT_ORDER: &[SymbolSource] = &[
+    SymbolSource::Symtab,
+    SymbolSource::Dynsym,
+    SymbolSource::MiniDebugInfo,
+    SymbolSource::External,
+];
+
+/// Looks up addresses in a file's symbol tables and its debug files
+///
+/// Sources are tried in order, and the first symbol which contains the address is returned.
+/// If no symbol contains it, the nearest preceding symbol from any source is returned,
+/// unless disabled with [`with_nearest()`](Symbolizer::with_nearest).
+///
+/// MiniDebugInfo is stored xz-compressed, which this crate cannot decompress; the caller
+/// should decompress [`Elf::gnu_debugdata()`] and pass the result to
+/// [`with_minidebuginfo()`](Symbolizer::with_minidebuginfo).
+#[derive(Clone, Copy)]
+pub struct Symbolizer<'a> {
+    elf: &'a Elf<'a>,
+    minidebuginfo: Option<&'a Elf<'a>>,
+    external: Option<&'a Elf<'a>>,
+    order: &'a [SymbolSource],
+    nearest: bool,
+}
+
+impl<'a> Symbolizer<'a> {
+    pub fn new(elf: &'a Elf<'a>) -> Symbolizer<'a> {
+        Symbolizer {
+            elf,
+            minidebuginfo: None,
+            external: None,
+            order: DEFAULT_ORDER,
+            nearest: true,
+        }
+    }
+
+    pub fn with_minidebuginfo(mut self, elf: &'a Elf<'a>) -> Symbolizer<'a> {
+        self.minidebuginfo = Some(elf);
+        self
+    }
+
+    pub fn with_external(mut self, elf: &'a Elf<'a>) -> Symbolizer<'a> {
+        self.external = Some(elf);
+        self
+    }
+
+    /// Set the sources to consult, in order of preference
+    pub fn with_order(mut self, order: &'a [SymbolSource]) -> Symbolizer<'a> {
+        self.order = order;
+        self
+    }
+
+    /// Set whether to fall back to the nearest preceding symbol
+    pub fn with_nearest(mut self, nearest: bool) -> Symbolizer<'a> {
+        self.nearest = nearest;
+        self
+    }
+
+    fn table(&self, source: SymbolSource) -> Option<(Table<'a, Sym>, Option<StringTable<'a>>)> {
+        let (shdr, strtab) = match source {
+            SymbolSource::Symtab => (self.elf.symtab_section()?, self.elf.string_table()),
+            SymbolSource::Dynsym => (self.elf.dynsym_section()?, self.elf.dynamic_string_table()),
+            SymbolSource::MiniDebugInfo => {
+                let elf = self.minidebuginfo?;
+                (elf.symtab_section()?, elf.string_table())
+            }
+            SymbolSource::External => {
+                let elf = self.external?;
+                (elf.symtab_section()?, elf.string_table())
+            }
+        };
+        Some((Table::from_section(&shdr).ok()?, strtab))
+    }
+
+    /// Find the symbol for `addr`, a link-time virtual address
+    pub fn symbolize(&self, addr: u64) -> Option<Symbolized<'a>> {
+        let mut nearest: Option<Symbolized<'a>> = None;
+
+        for &source in self.order {
+            let Some((syms, Some(strtab))) = self.table(source) else {
+                continue;
+            };
+
+            for sym in syms.iter() {
+                if sym.section_index() == SHN_UNDEF
+                    || !matches!(sym.kind(), SymbolKind::Func | SymbolKind::Object)
+                    || sym.value() > addr
+                {
+                    continue;
+                }
+                let Some(name) = strtab
+                    .get_string(sym.name_index())
+                    .filter(|n| !n.is_empty())
+                else {
+                    continue;
+                };
+
+                let found = Symbolized {
+                    name,
+                    offset: addr - sym.value(),
+                    size: sym.size(),
+                    source,
+                };
+                if found.is_exact() {
+                    return Some(found);
+                }
+                if nearest.is_none_or(|n| found.offset < n.offset) {
+                    nearest = Some(found);
+                }
+            }
+        }
+
+        nearest.filter(|_| self.nearest)
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the xz-compressed MiniDebugInfo file from `.gnu_debugdata`
+    pub fn gnu_debugdata(&self) -> Option<&'elf [u8]> {
+        self.find_section(".gnu_debugdata")?.file_data()
+    }
+}
@@ -0,0 +1,287 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Tables of on-disk structures in either class and byte order
+//!
+//! The structures of this crate are laid out as they are in little-endian ELF64 files.
+//! Entries read from files of other classes or byte orders are widened and re-encoded into
+//! these layouts as they are read.
+
+use crate::{Class, Data, Elf, Layout, ParseError, Pod, Section, Structure};
+use core::{fmt, marker::PhantomData, mem::size_of, ops::Range};
+
+/// The class and byte order of a file, which determine how its structures are laid out
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Encoding {
+    pub(crate) bits32: bool,
+    pub(crate) big_endian: bool,
+}
+
+impl Encoding {
+    /// The layout of the crate's own structures
+    pub(crate) const NATIVE: Encoding = Encoding {
+        bits32: false,
+        big_endian: false,
+    };
+
+    pub(crate) fn of(elf: &Elf) -> Encoding {
+        Encoding {
+            bits32: elf.class() == Class::Bits32,
+            big_endian: elf.data() == Data::TwosCompBig,
+        }
+    }
+
+    /// Decode a `u16` from the start of `bytes`
+    pub(crate) fn read_u16(self, bytes: &[u8]) -> Option<u16> {
+        let bytes = bytes.get(..2)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    /// Decode a `u32` from the start of `bytes`
+    pub(crate) fn read_u32(self, bytes: &[u8]) -> Option<u32> {
+        let bytes = bytes.get(..4)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    /// Decode a `u64` from the start of `bytes`
+    pub(crate) fn read_u64(self, bytes: &[u8]) -> Option<u64> {
+        let bytes = bytes.get(..8)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u64::from_be_bytes(bytes),
+            false => u64::from_le_bytes(bytes),
+        })
+    }
+
+    /// Decode a word of the file's class from the start of `bytes`
+    pub(crate) fn read_word(self, bytes: &[u8]) -> Option<u64> {
+        match self.bits32 {
+            true => self.read_u32(bytes).map(u64::from),
+            false => self.read_u64(bytes),
+        }
+    }
+
+    /// Returns the size of a word of the file's class
+    pub(crate) const fn word_size(self) -> usize {
+        match self.bits32 {
+            true => 4,
+            false => 8,
+        }
+    }
+}
+
+/// A structure which can be decoded from files of either class and byte order
+pub(crate) trait Entry: Pod {
+    /// The size of the entry in ELF32 files
+    const SIZE32: usize;
+
+    /// Decode an entry from the start of `bytes`
+    fn read<L: Layout>(bytes: &[u8], layout: L) -> Option<Self>;
+
+    /// Returns the size of the entry in files with the given layout
+    #[inline]
+    fn encoded_size<L: Layout>(layout: L) -> usize {
+        match layout.bits32() {
+            true => Self::SIZE32,
+            false => size_of::<Self>(),
+        }
+    }
+}
+
+/// Implement [`Entry`] for a structure and its ELF32 counterpart
+///
+/// The structure must have a `convert()` method to change its byte order, and be
+/// convertible from its ELF32 counterpart in the same byte order. Both must consist only
+/// of bytes and byte arrays, as they also implement [`Pod`].
+macro_rules! impl_entry {
+    ($t:ident, $t32:ident) => {
+        $crate::impl_pod!($t, $t32);
+
+        impl $crate::table::Entry for $t {
+            const SIZE32: usize = ::core::mem::size_of::<$t32>();
+
+            #[inline]
+            fn read<L: $crate::Layout>(bytes: &[u8], layout: L) -> Option<$t> {
+                use $crate::{read_struct, BigEndian};
+
+                match (layout.bits32(), layout.big_endian()) {
+                    (false, false) => read_struct::<$t>(bytes),
+                    (false, true) => read_struct::<$t<BigEndian>>(bytes).map(|x| x.convert()),
+                    (true, false) => read_struct::<$t32>(bytes).map($t::from),
+                    (true, true) => read_struct::<$t32<BigEndian>>(bytes)
+                        .map(|x| $t::<BigEndian>::from(x).convert()),
+                }
+            }
+        }
+    };
+}
+pub(crate) use impl_entry;
+
+impl Layout for Encoding {
+    fn bits32(self) -> bool {
+        self.bits32
+    }
+
+    fn big_endian(self) -> bool {
+        self.big_endian
+    }
+}
+
+/// The entries of a table, in the layout of the file they were read from
+pub(crate) struct Table<'elf, T, L = Encoding> {
+    data: &'elf [u8],
+    encoding: L,
+    _entry: PhantomData<T>,
+}
+
+impl<T, L: Layout> Clone for Table<'_, T, L> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, L: Layout> Copy for Table<'_, T, L> {}
+
+impl<T: Entry, L: Layout> fmt::Debug for Table<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("len", &self.len())
+            .field("encoding", &self.encoding)
+            .finish()
+    }
+}
+
+impl<'elf, T: Entry, L: Layout> Table<'elf, T, L> {
+    /// Create a table from `data`, ignoring any partial entry at its end
+    pub(crate) const fn new(data: &'elf [u8], encoding: L) -> Table<'elf, T, L> {
+        Table {
+            data,
+            encoding,
+            _entry: PhantomData,
+        }
+    }
+
+    /// Create a table from a slice of structures laid out as in files with `encoding`
+    pub(crate) fn from_entries<U: Pod>(entries: &'elf [U], encoding: L) -> Table<'elf, T, L> {
+        Table::new(crate::bytes_of_slice(entries), encoding)
+    }
+
+    /// Returns the entries of `sect`, which must lie in a file laid out as `encoding`
+    ///
+    /// See [`Table::from_section()`].
+    pub(crate) fn from_section_in(
+        sect: &Section<'_, 'elf>,
+        encoding: L,
+    ) -> Result<Table<'elf, T, L>, ParseError> {
+        let error = |message| {
+            let offset = sect.file_offset() as u64;
+            Err(ParseError::new(
+                Structure::Section(sect.index()),
+                offset,
+                message,
+            ))
+        };
+
+        let entry_size = T::encoded_size(encoding);
+        if sect.entry_size() as usize != entry_size {
+            return error("unexpected entry size");
+        }
+        let Some(data) = sect.file_data() else {
+            return error("contents out of bounds");
+        };
+        if data.len() % entry_size != 0 {
+            return error("size is not a multiple of the entry size");
+        }
+
+        Ok(Table::new(data, encoding))
+    }
+
+    /// Returns the raw entries, if they are already in the crate's layout
+    pub(crate) fn native(&self) -> Option<&'elf [T]> {
+        if self.encoding.bits32() || self.encoding.big_endian() {
+            return None;
+        }
+        Some(crate::slice_from_bytes(self.data))
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.data.len() / T::encoded_size(self.encoding)
+    }
+
+    /// Returns the entry at `index`, in the crate's layout
+    #[inline]
+    pub(crate) fn get(&self, index: usize) -> Option<T> {
+        let offset = index.checked_mul(T::encoded_size(self.encoding))?;
+        T::read(self.data.get(offset..)?, self.encoding)
+    }
+
+    /// Returns the entries in `range`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub(crate) fn slice(&self, range: Range<usize>) -> Table<'elf, T, L> {
+        let size = T::encoded_size(self.encoding);
+        Table::new(
+            &self.data[range.start * size..range.end * size],
+            self.encoding,
+        )
+    }
+
+    pub(crate) fn iter(self) -> impl Iterator<Item = T> + 'elf {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+}
+
+impl<T> Default for Table<'_, T> {
+    fn default() -> Self {
+        Table {
+            data: &[],
+            encoding: Encoding::NATIVE,
+            _entry: PhantomData,
+        }
+    }
+}
+
+impl<'elf, T: Entry> Table<'elf, T> {
+    /// Returns the entries of `sect`
+    ///
+    /// Returns an error if the section's entry size is not the size of `T` in the file, its
+    /// size is not a multiple of it, or its contents lie outside of the file.
+    pub(crate) fn from_section(sect: &Section<'_, 'elf>) -> Result<Table<'elf, T>, ParseError> {
+        Table::from_section_in(sect, Encoding::of(sect.elf()))
+    }
+}
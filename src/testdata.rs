@@ -0,0 +1,949 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Generated ELF files for tests
+//!
+//! [`Fixture`] builds small but complete shared objects in memory, in either class and
+//! byte order, so tests can cover edge cases without vendoring binary files. The layout
+//! is written field by field, independent of the host's byte order.
+
+use crate::{Class, Data, DynTag, ElfType, Machine, RelocKind, SectionType, SegmentKind};
+use alloc::vec::Vec;
+
+/// Name of the function symbol at the start of `.text`, which is also the entry point
+pub const ENTRY_SYMBOL: &str = "_start";
+
+/// Name of the object symbol covering the pointers in `.data`
+pub const POINTERS_SYMBOL: &str = "pointers";
+
+/// Number of pointers in `.data`, each of which has a relative relocation
+pub const POINTER_COUNT: usize = 4;
+
+const TEXT_SIZE: usize = 16;
+const SHT_RELR: u32 = 19;
+
+/// A description of a generated shared object
+///
+/// The file has a single `PT_LOAD` segment at address zero which covers `.text`, `.data`,
+/// the relocations, `.dynstr`, and `.dynamic`, followed by a `PT_DYNAMIC` segment. `.data` holds
+/// [`POINTER_COUNT`] pointers into `.text`, which are relocated by `R_*_RELATIVE` entries
+/// in `.rela.dyn` or, with [`packed_relocations()`](Fixture::packed_relocations), by a
+/// `.relr.dyn` section. Unless [stripped](Fixture::stripped), `.symtab` defines
+/// [`ENTRY_SYMBOL`] and [`POINTERS_SYMBOL`]. The code is not meant to be run.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Fixture {
+    class: Class,
+    data: Data,
+    machine: Option<Machine>,
+    stripped: bool,
+    relr: bool,
+    relocating_data: bool,
+}
+
+impl Fixture {
+    /// A 64-bit little-endian fixture with a symbol table and `RELA` relocations
+    pub const fn new() -> Fixture {
+        Fixture {
+            class: Class::Bits64,
+            data: Data::TwosCompLittle,
+            machine: None,
+            stripped: false,
+            relr: false,
+            relocating_data: false,
+        }
+    }
+
+    /// Returns every combination of class, byte order, stripping, and relocation format
+    pub fn all() -> impl Iterator<Item = Fixture> {
+        (0..16).map(|bits| {
+            let mut fixture = Fixture::new();
+            if bits & 1 != 0 {
+                fixture = fixture.elf32();
+            }
+            if bits & 2 != 0 {
+                fixture = fixture.big_endian();
+            }
+            if bits & 4 != 0 {
+                fixture = fixture.stripped();
+            }
+            if bits & 8 != 0 {
+                fixture = fixture.packed_relocations();
+            }
+            fixture
+        })
+    }
+
+    pub const fn elf32(mut self) -> Fixture {
+        self.class = Class::Bits32;
+        self
+    }
+
+    pub const fn big_endian(mut self) -> Fixture {
+        self.data = Data::TwosCompBig;
+        self
+    }
+
+    /// Set the machine, which otherwise defaults to x86-64 or 32-bit RISC-V for little
+    /// endian files and AArch64 or PowerPC for big endian files
+    pub const fn with_machine(mut self, machine: Machine) -> Fixture {
+        self.machine = Some(machine);
+        self
+    }
+
+    /// Omit `.symtab` and `.strtab`
+    pub const fn stripped(mut self) -> Fixture {
+        self.stripped = true;
+        self
+    }
+
+    /// Use `SHT_RELR` relocations instead of `SHT_RELA`
+    pub const fn packed_relocations(mut self) -> Fixture {
+        self.relr = true;
+        self
+    }
+
+    /// Point `sh_info` of the `RELA` section at `.data`, as in a relocatable file
+    pub const fn relocating_data(mut self) -> Fixture {
+        self.relocating_data = true;
+        self
+    }
+
+    pub const fn class(&self) -> Class {
+        self.class
+    }
+
+    pub const fn data(&self) -> Data {
+        self.data
+    }
+
+    pub const fn machine(&self) -> Machine {
+        match (self.machine, self.class, self.data) {
+            (Some(machine), _, _) => machine,
+            (None, Class::Bits64, Data::TwosCompBig) => Machine::Aarch64,
+            (None, Class::Bits64, _) => Machine::X86_64,
+            (None, _, Data::TwosCompBig) => Machine::Unknown(20),
+            (None, _, _) => Machine::Riscv,
+        }
+    }
+
+    /// Returns the type of the relative relocations
+    fn relative(&self) -> u32 {
+        match self.machine() {
+            Machine::X86_64 => RelocKind::X86_64_RELATIVE.to_u32(),
+            Machine::Aarch64 => RelocKind::AARCH64_RELATIVE.to_u32(),
+            Machine::Riscv => RelocKind::RISCV_RELATIVE.to_u32(),
+            // R_PPC_RELATIVE and R_PPC64_RELATIVE
+            _ => 22,
+        }
+    }
+
+    /// Generate the file
+    pub fn build(&self) -> Vec<u8> {
+        let w = match self.class {
+            Class::Bits32 => 4,
+            _ => 8,
+        };
+        let is64 = w == 8;
+        let (ehsize, phentsize, shentsize, symentsize) = match is64 {
+            true => (64, 56, 64, 24),
+            false => (52, 32, 40, 16),
+        };
+        let align = |offset: usize, to: usize| offset.next_multiple_of(to);
+
+        let phoff = ehsize;
+        let text = align(phoff + 2 * phentsize, 16);
+        let data = align(text + TEXT_SIZE, w);
+        let relocs = data + POINTER_COUNT * w;
+        let relocs_size = match self.relr {
+            true => 2 * w,
+            false => POINTER_COUNT * 3 * w,
+        };
+        let dynstr = relocs + relocs_size;
+        let dynamic = align(dynstr + 1, w);
+        let dynamic_size = 6 * 2 * w;
+        let alloc_end = dynamic + dynamic_size;
+
+        let strtab_contents = [
+            b"\0",
+            ENTRY_SYMBOL.as_bytes(),
+            b"\0",
+            POINTERS_SYMBOL.as_bytes(),
+            b"\0",
+        ]
+        .concat();
+        let (symtab, symtab_size, strtab, strtab_size) = match self.stripped {
+            true => (alloc_end, 0, alloc_end, 0),
+            false => (
+                alloc_end,
+                3 * symentsize,
+                alloc_end + 3 * symentsize,
+                strtab_contents.len(),
+            ),
+        };
+
+        let relocs_name = match self.relr {
+            true => ".relr.dyn",
+            false => ".rela.dyn",
+        };
+        let mut names: Vec<&str> =
+            alloc::vec![".text", ".data", relocs_name, ".dynstr", ".dynamic"];
+        if !self.stripped {
+            names.extend([".symtab", ".strtab"]);
+        }
+        names.push(".shstrtab");
+        let mut shstrtab_contents = alloc::vec![0];
+        let name_offsets: Vec<u32> = names
+            .iter()
+            .map(|name| {
+                let offset = shstrtab_contents.len() as u32;
+                shstrtab_contents.extend(name.as_bytes());
+                shstrtab_contents.push(0);
+                offset
+            })
+            .collect();
+        let shstrtab = strtab + strtab_size;
+        let shoff = align(shstrtab + shstrtab_contents.len(), w);
+        let shnum = names.len() + 1;
+
+        let mut out = Writer {
+            buf: alloc::vec![0; shoff + shnum * shentsize],
+            big_endian: self.data == Data::TwosCompBig,
+            w,
+        };
+        out.file_header(&Ehdr {
+            file_type: ElfType::Dyn.to_u16(),
+            machine: self.machine().to_u16(),
+            entry: text as u64,
+            phoff: phoff as u64,
+            shoff: shoff as u64,
+            phnum: 2,
+            shnum: shnum as u16,
+            shstrndx: (shnum - 1) as u16,
+        });
+
+        let load = SegmentSpec::identity(SegmentKind::Load, 0x7, 0, alloc_end, 0x1000);
+        let dynamic_segment =
+            SegmentSpec::identity(SegmentKind::Dynamic, 0x6, dynamic, dynamic_size, w);
+        out.program_header(phoff, &load);
+        out.program_header(phoff + phentsize, &dynamic_segment);
+
+        // Contents
+        for i in 0..POINTER_COUNT {
+            let target = (text + i) as u64;
+            let offset = data + i * w;
+            match self.relr {
+                // Packed relocations use the value in place as the addend.
+                true => {
+                    out.word(offset, target);
+                }
+                false => {
+                    let info = match is64 {
+                        true => self.relative() as u64,
+                        false => (self.relative() & 0xff) as u64,
+                    };
+                    let at = out.word(relocs + i * 3 * w, offset as u64);
+                    let at = out.word(at, info);
+                    out.word(at, target);
+                }
+            }
+        }
+        if self.relr {
+            // The address of the first pointer, then a bitmap of the words after it.
+            out.word(relocs, data as u64);
+            out.word(relocs + w, ((1 << (POINTER_COUNT - 1)) - 1) << 1 | 1);
+        }
+        let (tag, size_tag, entry_tag, entry_size) = match self.relr {
+            true => (DynTag::RELR, DynTag::RELRSZ, DynTag::RELRENT, w),
+            false => (DynTag::RELA, DynTag::RELASZ, DynTag::RELAENT, 3 * w),
+        };
+        let entries = [
+            (tag, relocs),
+            (size_tag, relocs_size),
+            (entry_tag, entry_size),
+            (DynTag::STRTAB, dynstr),
+            (DynTag::STRSZ, 1),
+            (DynTag::NULL, 0),
+        ];
+        for (i, (tag, value)) in entries.into_iter().enumerate() {
+            let at = out.word(dynamic + i * 2 * w, tag.to_i64() as u64);
+            out.word(at, value as u64);
+        }
+
+        if !self.stripped {
+            let entry_name = 1;
+            let pointers_name = entry_name + ENTRY_SYMBOL.len() as u32 + 1;
+            // STT_FUNC and STT_OBJECT with STB_GLOBAL
+            out.symbol(symtab + symentsize, entry_name, 0x12, 1, text, TEXT_SIZE);
+            out.symbol(
+                symtab + 2 * symentsize,
+                pointers_name,
+                0x11,
+                2,
+                data,
+                POINTER_COUNT * w,
+            );
+            out.buf[strtab..][..strtab_size].copy_from_slice(&strtab_contents);
+        }
+        out.buf[shstrtab..][..shstrtab_contents.len()].copy_from_slice(&shstrtab_contents);
+
+        // Section headers
+        let (relocs_type, relocs_entsize) = match self.relr {
+            true => (SHT_RELR, w),
+            false => (SectionType::Rela.to_u32(), 3 * w),
+        };
+        let (progbits, strtab_type) =
+            (SectionType::Progbits.to_u32(), SectionType::Strtab.to_u32());
+        let (ax, wa, a) = (0x6, 0x3, 0x2);
+        let relocs_info = match self.relocating_data {
+            true => 2,
+            false => 0,
+        };
+        let mut sections = alloc::vec![
+            Shdr::new(progbits, ax, text, TEXT_SIZE).align(16, 0),
+            Shdr::new(progbits, wa, data, POINTER_COUNT * w).align(w, 0),
+            Shdr::new(relocs_type, a, relocs, relocs_size)
+                .link(0, relocs_info)
+                .align(w, relocs_entsize),
+            Shdr::new(strtab_type, a, dynstr, 1),
+            Shdr::new(SectionType::Dynamic.to_u32(), wa, dynamic, dynamic_size)
+                .link(4, 0)
+                .align(w, 2 * w),
+        ];
+        if !self.stripped {
+            let strtab_index = sections.len() as u32 + 2;
+            sections.push(
+                Shdr::new(SectionType::Symtab.to_u32(), 0, symtab, symtab_size)
+                    .link(strtab_index, 1)
+                    .align(w, symentsize),
+            );
+            sections.push(Shdr::new(strtab_type, 0, strtab, strtab_size));
+        }
+        sections.push(Shdr::new(strtab_type, 0, shstrtab, shstrtab_contents.len()));
+
+        for (i, mut shdr) in sections.into_iter().enumerate() {
+            // Addresses equal file offsets, except for sections which are not loaded.
+            if shdr.flags & a != 0 {
+                shdr.addr = shdr.offset;
+            }
+            out.section_header(shoff + (i + 1) * shentsize, name_offsets[i], &shdr);
+        }
+
+        out.buf
+    }
+}
+
+impl Default for Fixture {
+    fn default() -> Fixture {
+        Fixture::new()
+    }
+}
+
+/// A structured description of an ELF file, for fuzzing
+///
+/// Fuzzers which mutate raw bytes rarely get past the file header checks, so this
+/// describes a file in terms of its headers and section contents instead. The generated
+/// file always has a valid identification and tables at the offsets its file header
+/// gives, but every other field may be inconsistent or out of range. Build one from fuzzer
+/// input through its [`Arbitrary`](arbitrary::Arbitrary) implementation, or by hand to reproduce a failure.
+#[cfg(feature = "arbitrary")]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ElfSpec {
+    pub class: Class,
+    pub data: Data,
+    pub file_type: u16,
+    pub machine: u16,
+    pub entry: u64,
+    pub segments: Vec<SegmentSpec>,
+    pub sections: Vec<SectionSpec>,
+    /// Overrides `e_shstrndx`, which otherwise refers to a generated `.shstrtab` placed
+    /// after `sections`
+    pub shstrndx: Option<u16>,
+}
+
+/// A section of an [`ElfSpec`]
+///
+/// The contents are placed in the file after the program headers, aligned to the size of
+/// an address. `offset`, `size`, and `name` default to the contents' location and a
+/// generated name when `None`.
+#[cfg(feature = "arbitrary")]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SectionSpec {
+    pub name: Option<u32>,
+    pub kind: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: Option<u64>,
+    pub size: Option<u64>,
+    pub link: u32,
+    pub info: u32,
+    pub align: u64,
+    pub entsize: u64,
+    pub contents: Vec<u8>,
+}
+
+/// Names given to generated sections, so lookups by name find them
+#[cfg(feature = "arbitrary")]
+const SECTION_NAMES: &[&str] = &[
+    ".text",
+    ".data",
+    ".bss",
+    ".symtab",
+    ".strtab",
+    ".dynsym",
+    ".dynstr",
+    ".dynamic",
+    ".rela.text",
+    ".note.gnu.build-id",
+    ".eh_frame",
+    ".debug_info",
+    ".init_array",
+    ".gnu.hash",
+    ".interp",
+    ".comment",
+];
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ElfSpec {
+    /// Decode a description from unstructured fuzzer input
+    ///
+    /// Every input produces a description; input which runs out is treated as zeros.
+    /// Most fields are drawn from values the parser treats specially, such as known
+    /// section types, natural entry sizes, and in-bounds offsets, with occasional
+    /// arbitrary values.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<ElfSpec> {
+        let mut input = Input(u);
+        let class = match input.bool() {
+            true => Class::Bits32,
+            false => Class::Bits64,
+        };
+        let data = match input.bool() {
+            true => Data::TwosCompBig,
+            false => Data::TwosCompLittle,
+        };
+        let w = match class {
+            Class::Bits32 => 4,
+            _ => 8,
+        };
+        let file_type = input.pick_or_any(&[1u32, 2, 3, 4]);
+        let machine = input.pick_or_any(&[62u32, 183, 243, 247, 3, 40, 20, 21]);
+        let entry = input.interesting(0x1000);
+
+        let segments = (0..input.below(8))
+            .map(|_| SegmentSpec {
+                kind: input.pick_or_any(&[1, 2, 3, 4, 6, 7, 0x6474e550, 0x6474e551, 0x6474e552]),
+                flags: input.u8() as u32 & 7,
+                offset: input.interesting(0),
+                vaddr: input.interesting(0x1000),
+                paddr: input.interesting(0x1000),
+                file_size: input.interesting(0x100),
+                mem_size: input.interesting(0x100),
+                align: input.pick_or_any(&[0, 1, 8, 0x1000, 0x200000, 3]),
+            })
+            .collect();
+
+        let sections = (0..input.below(16))
+            .map(|_| {
+                let kind = input.pick_or_any(&[
+                    1, 2, 3, 4, 5, 6, 7, 8, 9, 11, 14, 17, 18, 19, 0x6ffffff6, 0x6fffffff,
+                ]);
+                // The natural entry size of tables of each type
+                let natural = match (kind, w) {
+                    (2 | 11, 8) | (4, 8) => 24,
+                    (2 | 11, _) => 16,
+                    (4, _) => 12,
+                    (9, _) | (6, _) => 2 * w,
+                    (19 | 14, _) => w,
+                    (17 | 18, _) => 4,
+                    _ => 0,
+                };
+                let len = input.below(128);
+                SectionSpec {
+                    name: input.bool().then(|| input.u32() % 64),
+                    kind,
+                    flags: input.pick_or_any(&[0, 0x2, 0x3, 0x6, 0x30, 0x40, 0x200, 0x402, 0x800]),
+                    addr: input.interesting(0x1000),
+                    offset: input.bool().then(|| input.interesting(0)),
+                    size: input.bool().then(|| input.interesting(len as u64)),
+                    link: input.below(18) as u32,
+                    info: input.below(18) as u32,
+                    align: input.pick_or_any(&[0, 1, 4, 8, 16, 3]),
+                    entsize: input.pick_or_any(&[natural, natural, 0, 1]),
+                    contents: input.bytes(len),
+                }
+            })
+            .collect();
+
+        let shstrndx = input.bool().then(|| input.u16() % 20);
+        Ok(ElfSpec {
+            class,
+            data,
+            file_type: file_type as u16,
+            machine: machine as u16,
+            entry,
+            segments,
+            sections,
+            shstrndx,
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl ElfSpec {
+    /// Generate the file
+    pub fn build(&self) -> Vec<u8> {
+        let mut out = Writer {
+            buf: Vec::new(),
+            big_endian: self.data == Data::TwosCompBig,
+            w: match self.class {
+                Class::Bits32 => 4,
+                _ => 8,
+            },
+        };
+        let w = out.w;
+        let (ehsize, phentsize, shentsize) = out.header_sizes();
+
+        let phoff = ehsize;
+        let mut end = phoff + self.segments.len() * phentsize;
+        let mut offsets = Vec::with_capacity(self.sections.len());
+        for sect in &self.sections {
+            end = end.next_multiple_of(w);
+            offsets.push(end);
+            end += sect.contents.len();
+        }
+
+        let mut shstrtab = alloc::vec![0];
+        let names: Vec<u32> = (0..self.sections.len())
+            .map(|i| {
+                let offset = shstrtab.len() as u32;
+                shstrtab.extend(SECTION_NAMES[i % SECTION_NAMES.len()].as_bytes());
+                shstrtab.push(0);
+                offset
+            })
+            .collect();
+        let shstrtab_name = shstrtab.len() as u32;
+        shstrtab.extend(b".shstrtab\0");
+        let shstrtab_offset = end;
+        let shoff = (end + shstrtab.len()).next_multiple_of(w);
+        let shnum = self.sections.len() + 2;
+
+        out.buf = alloc::vec![0; shoff + shnum * shentsize];
+        out.file_header(&Ehdr {
+            file_type: self.file_type,
+            machine: self.machine,
+            entry: self.entry,
+            phoff: phoff as u64,
+            shoff: shoff as u64,
+            phnum: self.segments.len() as u16,
+            shnum: shnum as u16,
+            shstrndx: self.shstrndx.unwrap_or(shnum as u16 - 1),
+        });
+        for (i, phdr) in self.segments.iter().enumerate() {
+            out.program_header(phoff + i * phentsize, phdr);
+        }
+
+        for (i, sect) in self.sections.iter().enumerate() {
+            out.buf[offsets[i]..][..sect.contents.len()].copy_from_slice(&sect.contents);
+            let shdr = Shdr {
+                kind: sect.kind,
+                flags: sect.flags,
+                addr: sect.addr,
+                offset: sect.offset.unwrap_or(offsets[i] as u64),
+                size: sect.size.unwrap_or(sect.contents.len() as u64),
+                link: sect.link,
+                info: sect.info,
+                align: sect.align,
+                entsize: sect.entsize,
+            };
+            let name = sect.name.unwrap_or(names[i]);
+            out.section_header(shoff + (i + 1) * shentsize, name, &shdr);
+        }
+        out.buf[shstrtab_offset..][..shstrtab.len()].copy_from_slice(&shstrtab);
+        let shdr = Shdr::new(
+            SectionType::Strtab.to_u32(),
+            0,
+            shstrtab_offset,
+            shstrtab.len(),
+        );
+        out.section_header(shoff + (shnum - 1) * shentsize, shstrtab_name, &shdr);
+
+        out.buf
+    }
+}
+
+/// Unstructured fuzzer input, which reads as zeros once exhausted
+#[cfg(feature = "arbitrary")]
+struct Input<'u, 'a>(&'u mut arbitrary::Unstructured<'a>);
+
+#[cfg(feature = "arbitrary")]
+impl Input<'_, '_> {
+    fn u8(&mut self) -> u8 {
+        // Integers are zero once the input runs out, rather than an error.
+        self.0.arbitrary().unwrap_or(0)
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.u8(), self.u8(), self.u8(), self.u8()])
+    }
+
+    fn u64(&mut self) -> u64 {
+        (self.u32() as u64) << 32 | self.u32() as u64
+    }
+
+    fn bool(&mut self) -> bool {
+        self.u8() & 1 != 0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        match n {
+            0 => 0,
+            n => self.u16() as usize % n,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.u8()).collect()
+    }
+
+    /// Returns one of `choices`, or occasionally an arbitrary value
+    fn pick_or_any<T: Copy + From<u32>>(&mut self, choices: &[T]) -> T {
+        match self.below(choices.len() + 1) {
+            i if i < choices.len() => choices[i],
+            _ => T::from(self.u32()),
+        }
+    }
+
+    /// Returns a value near `typical`, zero, or an arbitrary value
+    fn interesting(&mut self, typical: u64) -> u64 {
+        match self.u8() % 8 {
+            0 => 0,
+            1 => u64::MAX,
+            2 => self.u64(),
+            n => typical.wrapping_add(self.u16() as u64 * (n as u64 - 3)),
+        }
+    }
+}
+
+/// The fields of a program header, which are written in a class-dependent layout
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SegmentSpec {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub file_size: u64,
+    pub mem_size: u64,
+    pub align: u64,
+}
+
+impl SegmentSpec {
+    /// A segment loaded at the address equal to its file offset
+    fn identity(
+        kind: SegmentKind,
+        flags: u32,
+        offset: usize,
+        size: usize,
+        align: usize,
+    ) -> SegmentSpec {
+        SegmentSpec {
+            kind: kind.to_u32(),
+            flags,
+            offset: offset as u64,
+            vaddr: offset as u64,
+            paddr: offset as u64,
+            file_size: size as u64,
+            mem_size: size as u64,
+            align: align as u64,
+        }
+    }
+}
+
+/// The fields of a file header which vary between files
+struct Ehdr {
+    file_type: u16,
+    machine: u16,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    phnum: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+/// The fields of a section header, which are written in a class-dependent layout
+struct Shdr {
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl Shdr {
+    fn new(kind: u32, flags: u64, offset: usize, size: usize) -> Shdr {
+        Shdr {
+            kind,
+            flags,
+            addr: 0,
+            offset: offset as u64,
+            size: size as u64,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        }
+    }
+
+    fn link(mut self, link: u32, info: u32) -> Shdr {
+        self.link = link;
+        self.info = info;
+        self
+    }
+
+    fn align(mut self, align: usize, entsize: usize) -> Shdr {
+        self.align = align as u64;
+        self.entsize = entsize as u64;
+        self
+    }
+}
+
+struct Writer {
+    buf: Vec<u8>,
+    big_endian: bool,
+    /// Size of an address, in bytes
+    w: usize,
+}
+
+impl Writer {
+    /// Write the low `size` bytes of `value` at `offset`
+    fn put(&mut self, offset: usize, size: usize, value: u64) {
+        let dst = &mut self.buf[offset..][..size];
+        match self.big_endian {
+            true => dst.copy_from_slice(&value.to_be_bytes()[8 - size..]),
+            false => dst.copy_from_slice(&value.to_le_bytes()[..size]),
+        }
+    }
+
+    /// Write an address-sized value at `offset`, returning the offset after it
+    fn word(&mut self, offset: usize, value: u64) -> usize {
+        self.put(offset, self.w, value);
+        offset + self.w
+    }
+
+    /// Returns the sizes of the file header, a program header, and a section header
+    fn header_sizes(&self) -> (usize, usize, usize) {
+        match self.w {
+            8 => (64, 56, 64),
+            _ => (52, 32, 40),
+        }
+    }
+
+    fn file_header(&mut self, ehdr: &Ehdr) {
+        let (ehsize, phentsize, shentsize) = self.header_sizes();
+        let class = match self.w {
+            8 => Class::Bits64,
+            _ => Class::Bits32,
+        };
+        let data = match self.big_endian {
+            true => Data::TwosCompBig,
+            false => Data::TwosCompLittle,
+        };
+        self.buf[..4].copy_from_slice(b"\x7fELF");
+        self.buf[4] = class.to_u8();
+        self.buf[5] = data.to_u8();
+        self.buf[6] = 1;
+        self.put(16, 2, ehdr.file_type as u64);
+        self.put(18, 2, ehdr.machine as u64);
+        self.put(20, 4, 1);
+        let at = self.word(24, ehdr.entry);
+        let at = self.word(at, ehdr.phoff);
+        let at = self.word(at, ehdr.shoff);
+        self.put(at + 4, 2, ehsize as u64);
+        self.put(at + 6, 2, phentsize as u64);
+        self.put(at + 8, 2, ehdr.phnum as u64);
+        self.put(at + 10, 2, shentsize as u64);
+        self.put(at + 12, 2, ehdr.shnum as u64);
+        self.put(at + 14, 2, ehdr.shstrndx as u64);
+    }
+
+    fn program_header(&mut self, at: usize, phdr: &SegmentSpec) {
+        self.put(at, 4, phdr.kind as u64);
+        // `p_flags` follows `p_type` in 64-bit files, and `p_memsz` in 32-bit files.
+        let at = match self.w {
+            8 => {
+                self.put(at + 4, 4, phdr.flags as u64);
+                at + 8
+            }
+            _ => {
+                self.put(at + 24, 4, phdr.flags as u64);
+                at + 4
+            }
+        };
+        let at = self.word(at, phdr.offset);
+        let at = self.word(at, phdr.vaddr);
+        let at = self.word(at, phdr.paddr);
+        let at = self.word(at, phdr.file_size);
+        let at = self.word(at, phdr.mem_size);
+        let at = match self.w {
+            8 => at,
+            _ => at + 4,
+        };
+        self.word(at, phdr.align);
+    }
+
+    fn section_header(&mut self, at: usize, name: u32, shdr: &Shdr) {
+        self.put(at, 4, name as u64);
+        self.put(at + 4, 4, shdr.kind as u64);
+        let at = self.word(at + 8, shdr.flags);
+        let at = self.word(at, shdr.addr);
+        let at = self.word(at, shdr.offset);
+        let at = self.word(at, shdr.size);
+        self.put(at, 4, shdr.link as u64);
+        self.put(at + 4, 4, shdr.info as u64);
+        let at = self.word(at + 8, shdr.align);
+        self.word(at, shdr.entsize);
+    }
+
+    fn symbol(&mut self, at: usize, name: u32, info: u8, shndx: u16, value: usize, size: usize) {
+        self.put(at, 4, name as u64);
+        match self.w {
+            8 => {
+                self.buf[at + 4] = info;
+                self.put(at + 6, 2, shndx as u64);
+                self.put(at + 8, 8, value as u64);
+                self.put(at + 16, 8, size as u64);
+            }
+            _ => {
+                self.put(at + 4, 4, value as u64);
+                self.put(at + 8, 4, size as u64);
+                self.buf[at + 12] = info;
+                self.put(at + 14, 2, shndx as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Elf;
+
+    #[test]
+    fn fixtures_round_trip() {
+        for fixture in Fixture::all() {
+            let buf = fixture.build();
+            let elf = Elf::new(&buf).unwrap_or_else(|e| panic!("{fixture:?}: {e:?}"));
+            let w = match fixture.class() {
+                Class::Bits32 => 4,
+                _ => 8,
+            };
+
+            assert_eq!(elf.class(), fixture.class(), "{fixture:?}");
+            assert_eq!(elf.data(), fixture.data(), "{fixture:?}");
+            assert_eq!(elf.machine(), fixture.machine(), "{fixture:?}");
+            assert_eq!(elf.file_type(), ElfType::Dyn, "{fixture:?}");
+
+            let text = elf.section_by_name(".text").unwrap();
+            assert_eq!(elf.entry_point(), text.addr(), "{fixture:?}");
+            assert_eq!(text.size(), TEXT_SIZE, "{fixture:?}");
+            let data = elf.section_by_name(".data").unwrap();
+            assert_eq!(data.size(), POINTER_COUNT * w, "{fixture:?}");
+
+            let kinds: Vec<_> = elf.segments().map(|sgmt| sgmt.kind()).collect();
+            assert_eq!(
+                kinds,
+                [SegmentKind::Load, SegmentKind::Dynamic],
+                "{fixture:?}"
+            );
+
+            let dynamic = elf.dynamic_table().unwrap();
+            let strtab = dynamic.get_unique(DynTag::STRTAB).unwrap().unwrap();
+            let dynstr = elf.section_by_name(".dynstr").unwrap();
+            assert_eq!(strtab.value(), dynstr.addr(), "{fixture:?}");
+
+            let relocs = match fixture.relr {
+                true => ".relr.dyn",
+                false => ".rela.dyn",
+            };
+            let relocs = elf.section_by_name(relocs).unwrap();
+            if !fixture.relr {
+                let entries = relocs.relocation_entries().unwrap();
+                let offsets: Vec<_> = entries.map(|rel| rel.offset()).collect();
+                let expected: Vec<_> = (0..POINTER_COUNT)
+                    .map(|i| data.addr() + (i * w) as u64)
+                    .collect();
+                assert_eq!(offsets, expected, "{fixture:?}");
+            }
+
+            match fixture.stripped {
+                true => assert!(elf.symbol_table().is_none(), "{fixture:?}"),
+                false => {
+                    let symbols = elf.symbol_table().unwrap();
+                    let entry = symbols
+                        .find(|sym| sym.name() == Some(ENTRY_SYMBOL))
+                        .unwrap();
+                    assert_eq!(entry.value(), elf.entry_point(), "{fixture:?}");
+                    let pointers = symbols
+                        .find(|sym| sym.name() == Some(POINTERS_SYMBOL))
+                        .unwrap();
+                    assert_eq!(pointers.value(), data.addr(), "{fixture:?}");
+                    assert_eq!(pointers.size(), (POINTER_COUNT * w) as u64, "{fixture:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixtures_are_relocatable() {
+        for fixture in Fixture::all().filter(|f| f.class() == Class::Bits64) {
+            let buf = fixture.build();
+            let elf = Elf::new(&buf).unwrap();
+            let report = elf.check_pie(|v| panic!("{fixture:?}: {v:?}"));
+
+            let (relative, relr) = match fixture.relr {
+                true => (0, POINTER_COUNT),
+                false => (POINTER_COUNT, 0),
+            };
+            assert_eq!(
+                (report.relative, report.relr),
+                (relative, relr),
+                "{fixture:?}"
+            );
+        }
+    }
+}
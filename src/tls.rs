@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Static thread-local storage
+
+use crate::{
+    table::Table, Elf, Machine, Patch, PatchOp, Rela, RelocKind, SectionType, SegmentKind, Sym,
+    SymbolKind,
+};
+
+/// The position of the static TLS block relative to the thread pointer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TlsVariant {
+    /// Variant I: the block follows a thread control block of `tcb_size` bytes, which the
+    /// thread pointer points to
+    AboveTp { tcb_size: u64 },
+    /// Variant II: the block ends at the thread pointer
+    BelowTp,
+}
+
+impl TlsVariant {
+    pub const fn for_machine(machine: Machine) -> Option<TlsVariant> {
+        match machine {
+            Machine::X86_64 => Some(TlsVariant::BelowTp),
+            Machine::Aarch64 => Some(TlsVariant::AboveTp { tcb_size: 16 }),
+            Machine::Riscv => Some(TlsVariant::AboveTp { tcb_size: 0 }),
+            _ => None,
+        }
+    }
+}
+
+/// The TLS image of a statically-linked executable, which is always module 1
+#[derive(Clone, Copy, Debug)]
+pub struct StaticTls<'elf> {
+    init: &'elf [u8],
+    mem_size: u64,
+    align: u64,
+    variant: TlsVariant,
+}
+
+impl<'elf> StaticTls<'elf> {
+    /// Returns the initialization image, from `.tdata`
+    #[inline]
+    pub const fn init_image(&self) -> &'elf [u8] {
+        self.init
+    }
+
+    /// Returns the size of the TLS block, including zero-initialized `.tbss`
+    #[inline]
+    pub const fn mem_size(&self) -> u64 {
+        self.mem_size
+    }
+
+    #[inline]
+    pub const fn align(&self) -> u64 {
+        self.align
+    }
+
+    #[inline]
+    pub const fn variant(&self) -> TlsVariant {
+        self.variant
+    }
+
+    /// Returns the offset of the start of the TLS block from the thread pointer
+    pub const fn block_offset(&self) -> i64 {
+        match self.variant {
+            TlsVariant::AboveTp { tcb_size } => tcb_size.next_multiple_of(self.align) as i64,
+            TlsVariant::BelowTp => -(self.mem_size.next_multiple_of(self.align) as i64),
+        }
+    }
+
+    /// Returns the offset from the thread pointer of the TLS-relative offset `offset`
+    ///
+    /// This is the value of a `TPOFF`/`TPREL` relocation against a symbol with value
+    /// `offset - addend`.
+    #[inline]
+    pub const fn tp_offset(&self, offset: u64) -> i64 {
+        self.block_offset().wrapping_add(offset as i64)
+    }
+
+    /// Initialize a thread's TLS block
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block` is not [`mem_size()`](StaticTls::mem_size) bytes long.
+    pub fn initialize(&self, block: &mut [u8]) {
+        assert_eq!(block.len() as u64, self.mem_size);
+        let (data, bss) = block.split_at_mut(self.init.len());
+        data.copy_from_slice(self.init);
+        bss.fill(0);
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the static TLS image, if the file has a `PT_TLS` segment
+    pub fn static_tls(&self) -> Option<StaticTls<'elf>> {
+        let variant = TlsVariant::for_machine(self.machine())?;
+        let sgmt = self
+            .segments()
+            .find(|sgmt| sgmt.kind() == SegmentKind::Tls)?;
+
+        Some(StaticTls {
+            init: sgmt.file_data()?,
+            mem_size: sgmt.mem_size() as u64,
+            align: sgmt.alignment().max(1),
+            variant,
+        })
+    }
+
+    /// Returns an iterator over the TLS symbols in the symbol table and their offsets from
+    /// the thread pointer
+    pub fn tls_symbols(&self) -> impl Iterator<Item = (Option<&'elf str>, i64)> + '_ {
+        let tls = self.static_tls();
+        let strtab = self.string_table();
+
+        self.symtab()
+            .into_iter()
+            .flatten()
+            .filter(move |sym| sym.kind() == SymbolKind::Tls && tls.is_some())
+            .map(move |sym| {
+                let name = strtab.and_then(|strtab| strtab.get_string(sym.name_index()));
+                (name, tls.unwrap().tp_offset(sym.value()))
+            })
+    }
+
+    /// Compute the thread pointer offsets for the TLS relocations left in a static
+    /// position-independent executable
+    ///
+    /// Calls `f` with a patch for each `TPOFF`/`TPREL` relocation in allocated `SHT_RELA`
+    /// sections. Patch addresses are relative to the executable's link-time base.
+    pub fn static_tls_patches<F>(&self, mut f: F)
+    where
+        F: FnMut(Patch),
+    {
+        let Some(tls) = self.static_tls() else {
+            return;
+        };
+        let tprel = match self.machine() {
+            Machine::X86_64 => RelocKind::X86_64_TPOFF64,
+            Machine::Aarch64 => RelocKind::AARCH64_TLS_TPREL,
+            Machine::Riscv => RelocKind::RISCV_TLS_TPREL64,
+            _ => return,
+        };
+        let dynsym = self
+            .dynsym_section()
+            .and_then(|shdr| Table::<Sym>::from_section(&shdr).ok())
+            .unwrap_or_default();
+
+        for shdr in self
+            .sections()
+            .filter(|s| s.section_type() == SectionType::Rela && s.flags().alloc())
+        {
+            let Ok(relas) = Table::<Rela>::from_section(&shdr) else {
+                continue;
+            };
+
+            for rela in relas.iter().filter(|rela| rela.kind() == tprel) {
+                let value = match rela.sym() {
+                    0 => 0,
+                    sym => dynsym.get(sym as usize).map_or(0, |sym| sym.value()),
+                };
+                let offset = tls.tp_offset(value.wrapping_add(rela.addend() as u64));
+                f(Patch::new(rela.offset(), 8, offset as u64, PatchOp::Write));
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+//! Parsing specialized to one class and byte order
+//!
+//! [`Elf`] decides how to decode each structure at runtime, from the class and byte order
+//! in its file header. Code which only handles one kind of file can instead read it
+//! through an [`ElfView`] over a static layout such as [`Elf64Le`], whose reads compile
+//! down to plain field loads. [`AnyLayout`] keeps the runtime choice for tools which handle
+//! every kind of file through the same code.
+
+use crate::{
+    table::{Entry, Table},
+    Class, Data, Dyn, Elf, ParseError, ProgramHeader, Rel, Rela, Section, SectionHeader, Structure,
+    Sym,
+};
+use core::fmt;
+
+/// The class and byte order in which a file's structures are laid out
+pub trait Layout: Copy + fmt::Debug + Send + Sync + 'static {
+    /// Returns `true` if structures are laid out as in ELF32 files
+    fn bits32(self) -> bool;
+
+    /// Returns `true` if fields are stored in big-endian byte order
+    fn big_endian(self) -> bool;
+}
+
+macro_rules! static_layout {
+    ($(#[$attr:meta])* $name:ident, $bits32:expr, $big_endian:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+        pub struct $name;
+
+        impl Layout for $name {
+            #[inline(always)]
+            fn bits32(self) -> bool {
+                $bits32
+            }
+
+            #[inline(always)]
+            fn big_endian(self) -> bool {
+                $big_endian
+            }
+        }
+    };
+}
+
+static_layout!(
+    /// The layout of little-endian ELF32 files
+    Elf32Le, true, false
+);
+static_layout!(
+    /// The layout of big-endian ELF32 files
+    Elf32Be, true, true
+);
+static_layout!(
+    /// The layout of little-endian ELF64 files, in which structures need no conversion
+    Elf64Le, false, false
+);
+static_layout!(
+    /// The layout of big-endian ELF64 files
+    Elf64Be, false, true
+);
+
+/// A layout chosen at runtime
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AnyLayout {
+    Elf32Le,
+    Elf32Be,
+    Elf64Le,
+    Elf64Be,
+}
+
+impl AnyLayout {
+    /// Returns the layout of `elf`
+    pub fn of(elf: &Elf) -> AnyLayout {
+        match (
+            elf.class() == Class::Bits32,
+            elf.data() == Data::TwosCompBig,
+        ) {
+            (true, false) => AnyLayout::Elf32Le,
+            (true, true) => AnyLayout::Elf32Be,
+            (false, false) => AnyLayout::Elf64Le,
+            (false, true) => AnyLayout::Elf64Be,
+        }
+    }
+}
+
+impl Layout for AnyLayout {
+    #[inline]
+    fn bits32(self) -> bool {
+        matches!(self, AnyLayout::Elf32Le | AnyLayout::Elf32Be)
+    }
+
+    #[inline]
+    fn big_endian(self) -> bool {
+        matches!(self, AnyLayout::Elf32Be | AnyLayout::Elf64Be)
+    }
+}
+
+/// A file read through a fixed [`Layout`]
+///
+/// Structures are returned in the crate's layout, exactly as through [`Elf`].
+#[derive(Clone, Copy, Debug)]
+pub struct ElfView<'a, 'elf, L> {
+    elf: &'a Elf<'elf>,
+    layout: L,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns a view of the file which reads its structures as laid out in `layout`
+    ///
+    /// Returns `None` if the file has a different class or byte order.
+    pub fn view<L: Layout>(&self, layout: L) -> Option<ElfView<'_, 'elf, L>> {
+        let matches = layout.bits32() == (self.class() == Class::Bits32)
+            && layout.big_endian() == (self.data() == Data::TwosCompBig);
+        matches.then_some(ElfView { elf: self, layout })
+    }
+}
+
+impl<'a, 'elf, L: Layout> ElfView<'a, 'elf, L> {
+    #[inline]
+    pub fn elf(&self) -> &'a Elf<'elf> {
+        self.elf
+    }
+
+    #[inline]
+    pub fn layout(&self) -> L {
+        self.layout
+    }
+
+    #[inline]
+    pub fn program_header(&self, index: u16) -> Option<ProgramHeader> {
+        self.program_table().get(index as usize)
+    }
+
+    #[inline]
+    pub fn section_header(&self, index: u16) -> Option<SectionHeader> {
+        self.section_table().get(index as usize)
+    }
+
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + 'elf {
+        self.program_table().iter()
+    }
+
+    pub fn section_headers(&self) -> impl Iterator<Item = SectionHeader> + 'elf {
+        self.section_table().iter()
+    }
+
+    pub fn section(&self, index: u16) -> Option<Section<'a, 'elf>> {
+        let hdr = self.section_header(index)?;
+        Some(Section::new(self.elf, index, hdr))
+    }
+
+    /// Returns the symbols of the section at `index`
+    ///
+    /// Returns an error if the section does not exist, or its entries are not symbols of
+    /// this layout.
+    pub fn symbols(&self, index: u16) -> Result<impl Iterator<Item = Sym> + 'elf, ParseError> {
+        Ok(self.entries(index)?.iter())
+    }
+
+    /// Returns the relocations of the `SHT_REL` section at `index`
+    pub fn rels(&self, index: u16) -> Result<impl Iterator<Item = Rel> + 'elf, ParseError> {
+        Ok(self.entries(index)?.iter())
+    }
+
+    /// Returns the relocations of the `SHT_RELA` section at `index`
+    pub fn relas(&self, index: u16) -> Result<impl Iterator<Item = Rela> + 'elf, ParseError> {
+        Ok(self.entries(index)?.iter())
+    }
+
+    /// Returns the entries of the `SHT_DYNAMIC` section at `index`
+    pub fn dynamic_entries(
+        &self,
+        index: u16,
+    ) -> Result<impl Iterator<Item = Dyn> + 'elf, ParseError> {
+        Ok(self.entries(index)?.iter())
+    }
+
+    fn program_table(&self) -> Table<'elf, ProgramHeader, L> {
+        let elf = self.elf;
+        elf.header_table(elf.phdr_offset(), elf.phdr_num(), self.layout)
+    }
+
+    fn section_table(&self) -> Table<'elf, SectionHeader, L> {
+        let elf = self.elf;
+        elf.header_table(elf.shdr_offset(), elf.shdr_num(), self.layout)
+    }
+
+    fn entries<T: Entry>(&self, index: u16) -> Result<Table<'elf, T, L>, ParseError> {
+        match self.section(index) {
+            Some(sect) => Table::from_section_in(&sect, self.layout),
+            None => Err(ParseError::new(
+                Structure::Section(index),
+                self.elf.shdr_offset() as u64,
+                "no such section",
+            )),
+        }
+    }
+}
@@ -48,6 +48,14 @@ impl Version {
     pub const fn from_u8(x: u8) -> Version {
         Self::from_u32(x as u32)
     }
+
+    pub const fn to_u32(self) -> u32 {
+        match self {
+            Version::None => 0,
+            Version::Current => 1,
+            Version::Unknown(x) => x,
+        }
+    }
 }
 
 /// ELF File Type
@@ -83,6 +91,17 @@ impl ElfType {
             _ => ElfType::Unknown(x),
         }
     }
+
+    pub const fn to_u16(self) -> u16 {
+        match self {
+            ElfType::None => 0,
+            ElfType::Rel => 1,
+            ElfType::Exec => 2,
+            ElfType::Dyn => 3,
+            ElfType::Core => 4,
+            ElfType::EnvSpecific(x) | ElfType::CpuSpecific(x) | ElfType::Unknown(x) => x,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -91,6 +110,7 @@ pub enum Machine {
     X86_64,
     Aarch64,
     Riscv,
+    Bpf,
     Unknown(u16),
 }
 
@@ -101,9 +121,21 @@ impl Machine {
             62 => Machine::X86_64,
             183 => Machine::Aarch64,
             243 => Machine::Riscv,
+            247 => Machine::Bpf,
             _ => Machine::Unknown(x),
         }
     }
+
+    pub const fn to_u16(self) -> u16 {
+        match self {
+            Machine::None => 0,
+            Machine::X86_64 => 62,
+            Machine::Aarch64 => 183,
+            Machine::Riscv => 243,
+            Machine::Bpf => 247,
+            Machine::Unknown(x) => x,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -151,6 +183,15 @@ impl Data {
             _ => Self::Unknown(x),
         }
     }
+
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::TwosCompLittle => 1,
+            Self::TwosCompBig => 2,
+            Self::Unknown(x) => x,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -158,6 +199,7 @@ pub enum OsAbi {
     SysV,
     NetBSD,
     Linux,
+    Solaris,
     FreeBSD,
     OpenBSD,
     Standalone,
@@ -170,10 +212,24 @@ impl OsAbi {
             0 => Self::SysV,
             2 => Self::NetBSD,
             3 => Self::Linux,
+            6 => Self::Solaris,
             9 => Self::FreeBSD,
             12 => Self::OpenBSD,
             255 => Self::Standalone,
             _ => Self::Unknown(x),
         }
     }
+
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::SysV => 0,
+            Self::NetBSD => 2,
+            Self::Linux => 3,
+            Self::Solaris => 6,
+            Self::FreeBSD => 9,
+            Self::OpenBSD => 12,
+            Self::Standalone => 255,
+            Self::Unknown(x) => x,
+        }
+    }
 }
@@ -0,0 +1,258 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Structural checks of a whole file
+
+use crate::{
+    table::{Encoding, Entry, Table},
+    Dyn, Elf, ElfType, FileHeader, ParseError, Rel, Rela, SectionFlags, SectionType, SegmentFlags,
+    SegmentKind, Structure, Sym, SHN_XINDEX,
+};
+
+impl<'elf> Elf<'elf> {
+    /// Check the structure of the whole file
+    ///
+    /// [`Elf::new()`] checks only what is needed to read the header tables. This also checks
+    /// that segments and sections lie within the file and are aligned, that `PT_LOAD`
+    /// segments do not overlap, that allocated sections are covered by a `PT_LOAD` segment,
+    /// that `sh_link` and `sh_info` refer to existing sections of the right type, that
+    /// string tables are NUL-terminated, and that tables have the right entry size.
+    ///
+    /// Every problem found is passed to `on_problem`, and the number of problems is
+    /// returned.
+    pub fn validate<F>(&self, mut on_problem: F) -> usize
+    where
+        F: FnMut(ParseError),
+    {
+        let mut problems = 0;
+        let mut problem = |structure, offset: usize, message| {
+            problems += 1;
+            on_problem(ParseError::new(structure, offset as u64, message));
+        };
+        let encoding = Encoding::of(self);
+
+        let header_size = FileHeader::encoded_size(encoding);
+        if self.header_size() as usize != header_size {
+            problem(Structure::FileHeader, 0, "unexpected header size");
+        }
+        let shstrndx = self.shdr_strtab_index();
+        if self.shdr_num() != 0 && shstrndx != SHN_XINDEX && shstrndx >= self.shdr_num() {
+            problem(
+                Structure::FileHeader,
+                0,
+                "section name string table out of range",
+            );
+        }
+        let has_entry = matches!(self.file_type(), ElfType::Exec | ElfType::Dyn);
+        if has_entry
+            && self.entry_point() != 0
+            && !self.segments().any(|sgmt| {
+                sgmt.kind() == SegmentKind::Load
+                    && sgmt.flags().contains(SegmentFlags::EXEC)
+                    && (sgmt.virtual_address()
+                        ..sgmt
+                            .virtual_address()
+                            .saturating_add(sgmt.mem_size() as u64))
+                        .contains(&self.entry_point())
+            })
+        {
+            problem(
+                Structure::FileHeader,
+                0,
+                "entry point outside of executable segments",
+            );
+        }
+
+        let data_len = self.data.len();
+        let in_file = |offset: usize, size: usize| {
+            offset.checked_add(size).is_some_and(|end| end <= data_len)
+        };
+        let aligned = |value: u64, align: u64| align <= 1 || value % align == 0;
+
+        for (index, sgmt) in self.segments().enumerate() {
+            let index = index as u16;
+            let structure = Structure::ProgramHeader(index);
+            let offset = self.phdr_offset() + index as usize * self.phdr_size() as usize;
+            let align = sgmt.alignment();
+
+            if !in_file(sgmt.file_offset(), sgmt.file_size()) {
+                problem(structure, offset, "contents out of bounds");
+            }
+            if align > 1 && !align.is_power_of_two() {
+                problem(structure, offset, "alignment is not a power of two");
+            }
+            if sgmt.kind() != SegmentKind::Load {
+                continue;
+            }
+
+            if sgmt.file_size() > sgmt.mem_size() {
+                problem(structure, offset, "file size exceeds memory size");
+            }
+            if align.is_power_of_two()
+                && (sgmt.virtual_address() ^ sgmt.file_offset() as u64) & (align - 1) != 0
+            {
+                problem(
+                    structure,
+                    offset,
+                    "address and offset differ modulo the alignment",
+                );
+            }
+
+            let start = sgmt.virtual_address();
+            let end = start.saturating_add(sgmt.mem_size() as u64);
+            let earlier = || self.segments().take(index as usize);
+            let overlaps = earlier().any(|other| {
+                let other_start = other.virtual_address();
+                let other_end = other_start.saturating_add(other.mem_size() as u64);
+                other.kind() == SegmentKind::Load && start < other_end && other_start < end
+            });
+            if overlaps {
+                problem(structure, offset, "overlaps an earlier PT_LOAD segment");
+            }
+            if earlier()
+                .any(|other| other.kind() == SegmentKind::Load && other.virtual_address() > start)
+            {
+                problem(structure, offset, "PT_LOAD segments not in ascending order");
+            }
+        }
+
+        let shdr_num = self.shdr_num() as u32;
+        let mapped = has_entry && self.segments().any(|sgmt| sgmt.kind() == SegmentKind::Load);
+
+        for sect in self.sections().skip(1) {
+            let index = sect.index();
+            let header = Structure::SectionHeader(index);
+            let header_offset = self.shdr_offset() + index as usize * self.shdr_size() as usize;
+            let contents = Structure::Section(index);
+            let section_type = sect.section_type();
+            let flags = sect.flags();
+            let align = sect.addr_align();
+
+            if section_type != SectionType::Nobits && !in_file(sect.file_offset(), sect.size()) {
+                problem(contents, sect.file_offset(), "contents out of bounds");
+            }
+            if align > 1 && !align.is_power_of_two() {
+                problem(header, header_offset, "alignment is not a power of two");
+            } else if !aligned(sect.addr(), align) {
+                problem(header, header_offset, "address is not aligned");
+            }
+            if mapped
+                && flags.alloc()
+                && sect.size() != 0
+                && !self
+                    .segments()
+                    .any(|sgmt| sgmt.kind() == SegmentKind::Load && sgmt.contains_section(&sect))
+            {
+                problem(header, header_offset, "not covered by a PT_LOAD segment");
+            }
+
+            // Sections whose `sh_link` names another section, and the types it may name.
+            let link_types: Option<&[SectionType]> = match section_type {
+                SectionType::Symtab | SectionType::Dynsym | SectionType::Dynamic => {
+                    Some(&[SectionType::Strtab])
+                }
+                SectionType::Rel | SectionType::Rela | SectionType::Hash | SectionType::Group => {
+                    Some(&[SectionType::Symtab, SectionType::Dynsym])
+                }
+                SectionType::SymtabShndx => Some(&[SectionType::Symtab]),
+                SectionType::GNU_HASH | SectionType::GNU_VERSYM => Some(&[SectionType::Dynsym]),
+                SectionType::GNU_VERDEF | SectionType::GNU_VERNEED => Some(&[SectionType::Strtab]),
+                _ => None,
+            };
+            // A link of zero is allowed, since some relocation sections apply to no symbols.
+            let link = sect.link();
+            match self.section(link as u16).filter(|_| link < shdr_num) {
+                _ if link == 0 => {}
+                None => problem(header, header_offset, "sh_link out of range"),
+                Some(linked)
+                    if link_types.is_some_and(|types| !types.contains(&linked.section_type())) =>
+                {
+                    problem(
+                        header,
+                        header_offset,
+                        "sh_link refers to the wrong type of section",
+                    );
+                }
+                Some(_) => {}
+            }
+
+            let info = sect.info();
+            let info_is_index = flags.contains(SectionFlags::INFO_LINK)
+                || matches!(section_type, SectionType::Rel | SectionType::Rela) && info != 0;
+            if info_is_index && info >= shdr_num {
+                problem(header, header_offset, "sh_info out of range");
+            }
+
+            let table = match section_type {
+                SectionType::Symtab | SectionType::Dynsym => {
+                    let symbols = Table::<Sym>::from_section(&sect);
+                    if symbols.is_ok_and(|symbols| info as usize > symbols.len()) {
+                        problem(
+                            header,
+                            header_offset,
+                            "sh_info past the end of the symbol table",
+                        );
+                    }
+                    symbols.err()
+                }
+                SectionType::Rel => Table::<Rel>::from_section(&sect).err(),
+                SectionType::Rela => Table::<Rela>::from_section(&sect).err(),
+                SectionType::Dynamic => Table::<Dyn>::from_section(&sect).err(),
+                _ => None,
+            };
+            if let Some(error) = table {
+                problem(error.structure, error.offset as usize, error.message);
+            }
+
+            if section_type == SectionType::Strtab {
+                match sect.file_data() {
+                    Some([]) | None => {}
+                    Some(data) if data[0] != 0 => {
+                        problem(
+                            contents,
+                            sect.file_offset(),
+                            "string table does not start with NUL",
+                        );
+                    }
+                    Some(data) if data[data.len() - 1] != 0 => {
+                        problem(
+                            contents,
+                            sect.file_offset(),
+                            "string table is not NUL-terminated",
+                        );
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        problems
+    }
+}
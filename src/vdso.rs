@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! Validation of vDSO images
+//!
+//! A vDSO is a shared object which the kernel maps into every process without running a
+//! dynamic linker over it, so it must work at any address without being relocated.
+
+use crate::{table::Table, Elf, ElfType, ParseError, RelocKind, SectionType, SegmentKind};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VdsoError {
+    /// The file is not a shared object
+    NotShared,
+    /// The file must have exactly one `PT_LOAD` segment
+    LoadSegments(usize),
+    /// The file image of the `PT_LOAD` segment does not match its memory image, or lies
+    /// outside of the file
+    Misaligned,
+    /// The file requests an interpreter
+    Interpreter,
+    /// The file contains a dynamic relocation, at the given address
+    Relocation { address: u64, kind: RelocKind },
+    /// A relocation table is malformed
+    Malformed(ParseError),
+}
+
+/// The layout of a validated vDSO image
+#[derive(Clone, Copy, Debug)]
+pub struct VdsoImage<'elf> {
+    /// Bytes to copy or map into memory, from the start of the file
+    pub data: &'elf [u8],
+    /// Size of the image in memory
+    pub mem_size: u64,
+}
+
+impl VdsoImage<'_> {
+    /// Returns the size of the image rounded up to a whole number of pages
+    #[inline]
+    pub const fn mapped_size(&self, page_size: u64) -> u64 {
+        self.mem_size.next_multiple_of(page_size)
+    }
+}
+
+impl<'elf> Elf<'elf> {
+    /// Check that this file can be used as a vDSO, and return its fixed load layout
+    ///
+    /// The file must be a shared object with a single `PT_LOAD` segment which starts at
+    /// virtual address 0 and file offset 0, and have no dynamic relocations.
+    pub fn vdso_image(&self) -> Result<VdsoImage<'elf>, VdsoError> {
+        if self.file_type() != ElfType::Dyn {
+            return Err(VdsoError::NotShared);
+        }
+        if self
+            .segments()
+            .any(|sgmt| sgmt.kind() == SegmentKind::Interp)
+        {
+            return Err(VdsoError::Interpreter);
+        }
+
+        let count = self
+            .segments()
+            .filter(|s| s.kind() == SegmentKind::Load)
+            .count();
+        let load = match self.segments().find(|s| s.kind() == SegmentKind::Load) {
+            Some(load) if count == 1 => load,
+            _ => return Err(VdsoError::LoadSegments(count)),
+        };
+        let data = match load.file_data() {
+            Some(data) if load.virtual_address() == 0 && load.file_offset() == 0 => data,
+            _ => return Err(VdsoError::Misaligned),
+        };
+
+        for sect in self.sections().filter(|s| s.flags().alloc()) {
+            let kind = sect.section_type();
+            if kind == SectionType::Rela {
+                let relas = Table::<crate::Rela>::from_section(&sect);
+                if let Some(rela) = relas.map_err(VdsoError::Malformed)?.get(0) {
+                    return Err(VdsoError::Relocation {
+                        address: rela.offset(),
+                        kind: rela.kind(),
+                    });
+                }
+            } else if kind == SectionType::Rel {
+                let rels = Table::<crate::Rel>::from_section(&sect);
+                if let Some(rel) = rels.map_err(VdsoError::Malformed)?.get(0) {
+                    return Err(VdsoError::Relocation {
+                        address: rel.offset(),
+                        kind: rel.kind(),
+                    });
+                }
+            }
+        }
+
+        Ok(VdsoImage {
+            data,
+            mem_size: load.mem_size() as u64,
+        })
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! GNU symbol versioning
+
+use crate::{
+    table::{Encoding, Table},
+    Binding, Elf, SectionType, Sym, SymbolKind, SHN_UNDEF,
+};
+
+/// Set in a `SHT_GNU_versym` entry if the symbol is hidden from unversioned references
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+
+/// Set in a version definition's flags if it names the file itself
+pub const VER_FLG_BASE: u16 = 0x1;
+
+/// An entry of the `SHT_GNU_verdef` section
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VersionDef<'elf> {
+    /// Version index, as referenced by `SHT_GNU_versym` entries
+    pub index: u16,
+    pub flags: u16,
+    /// ELF hash of the version name
+    pub hash: u32,
+    pub name: Option<&'elf str>,
+}
+
+/// A symbol exported from the dynamic symbol table, along with its version
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VersionedSymbol<'elf> {
+    pub name: Option<&'elf str>,
+    /// Name of the version the symbol is defined in, if it has one
+    pub version: Option<&'elf str>,
+    /// `true` if the symbol may only be referenced by version
+    pub hidden: bool,
+    pub kind: SymbolKind,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns an iterator over the version definitions
+    pub fn version_definitions(&self) -> impl Iterator<Item = VersionDef<'elf>> + '_ {
+        let data = self
+            .verdef_section()
+            .and_then(|sect| sect.file_data())
+            .unwrap_or_default();
+        let strtab = self.dynamic_string_table();
+        let encoding = Encoding::of(self);
+        let mut offset = Some(0);
+
+        core::iter::from_fn(move || {
+            let entry = data.get(offset?..)?;
+            let u16_at = |i: usize| encoding.read_u16(entry.get(i..)?);
+            let u32_at = |i: usize| encoding.read_u32(entry.get(i..)?);
+
+            let (flags, index, count) = (u16_at(2)?, u16_at(4)?, u16_at(6)?);
+            let (hash, aux, next) = (u32_at(8)?, u32_at(12)?, u32_at(16)?);
+
+            // The first auxiliary entry holds the version's own name, the rest name its parents.
+            let name = match count {
+                0 => None,
+                _ => u32_at(aux as usize).and_then(|name| strtab?.get_string(name as usize)),
+            };
+
+            offset = match next {
+                0 => None,
+                next => offset.map(|offset| offset + next as usize),
+            };
+            Some(VersionDef {
+                index,
+                flags,
+                hash,
+                name,
+            })
+        })
+    }
+
+    /// Returns the `SHT_GNU_versym` entry for the dynamic symbol at `index`
+    ///
+    /// The low 15 bits hold the version index, and [`VERSYM_HIDDEN`] may be set.
+    pub fn symbol_version(&self, index: usize) -> Option<u16> {
+        let versym = self
+            .sections()
+            .find(|s| s.section_type() == SectionType::GNU_VERSYM)?
+            .file_data()?;
+        Encoding::of(self).read_u16(versym.get(index.checked_mul(2)?..)?)
+    }
+
+    /// Returns an iterator over the global and weak symbols defined in the dynamic symbol
+    /// table, with their versions
+    pub fn exported_symbols(&self) -> impl Iterator<Item = VersionedSymbol<'elf>> + '_ {
+        let dynsym = self
+            .dynsym_section()
+            .and_then(|shdr| Table::<Sym>::from_section(&shdr).ok());
+        let strtab = self.dynamic_string_table();
+
+        dynsym
+            .into_iter()
+            .flat_map(Table::iter)
+            .enumerate()
+            .filter(|(_, sym)| {
+                sym.section_index() != SHN_UNDEF
+                    && matches!(sym.binding(), Binding::Global | Binding::Weak)
+            })
+            .map(move |(index, sym)| {
+                let versym = self.symbol_version(index).unwrap_or(1);
+                let version = match versym & !VERSYM_HIDDEN {
+                    // local and global (unversioned)
+                    0 | 1 => None,
+                    ver => self
+                        .version_definitions()
+                        .find(|def| def.index == ver)
+                        .and_then(|def| def.name),
+                };
+
+                VersionedSymbol {
+                    name: strtab.and_then(|strtab| strtab.get_string(sym.name_index())),
+                    version,
+                    hidden: versym & VERSYM_HIDDEN != 0,
+                    kind: sym.kind(),
+                    value: sym.value(),
+                    size: sym.size(),
+                }
+            })
+    }
+}
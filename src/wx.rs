@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) 2022 xvanc and contributors
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * 3. Neither the name of the copyright holder nor the names of its contributors
+ *    may be used to endorse or promote products derived from this software without
+ *    specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES
+ * OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+ * IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT,
+ * INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT
+ * LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ *
+ * SPDX-License-Identifier: BSD-3-Clause
+ */
+
+//! W^X checks for loaded relocatable modules
+
+use crate::{Elf, Section, SectionType};
+use core::ops::Range;
+
+/// A range of memory a loaded module executes from
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ExecRegion {
+    /// Index of the section occupying the range
+    pub section: u16,
+    /// Runtime addresses of the section's contents
+    pub range: Range<u64>,
+}
+
+impl ExecRegion {
+    /// Returns the range of pages of size `page_size` which must be mapped executable
+    ///
+    /// `page_size` must be a power of two.
+    pub fn pages(&self, page_size: u64) -> Range<u64> {
+        let mask = !(page_size - 1);
+        (self.range.start & mask)..(self.range.end.wrapping_add(page_size - 1) & mask)
+    }
+}
+
+/// A reason a module cannot be mapped with no page both writable and executable
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WxViolation {
+    /// An allocated section was not given an address
+    Unplaced(u16),
+    /// An executable section is also writable
+    WritableCode(u16),
+    /// A non-executable section shares the page at this address with an executable one
+    SharedPage { exec: u16, other: u16, page: u64 },
+    /// A relocation against a non-executable section patches executable memory
+    Relocation { section: u16, address: u64 },
+}
+
+impl<'elf> Elf<'elf> {
+    /// Returns the ranges a module will execute from once it has been laid out
+    ///
+    /// `address_of` returns the runtime address assigned to an allocated section. Only
+    /// allocated, executable sections with a non-zero size and an address are included.
+    pub fn executable_regions<'a, F>(
+        &'a self,
+        address_of: F,
+    ) -> impl Iterator<Item = ExecRegion> + 'a
+    where
+        F: Fn(&Section) -> Option<u64> + 'a,
+    {
+        self.sections()
+            .filter(|sect| is_code(sect) && sect.size() != 0)
+            .filter_map(move |sect| {
+                let start = address_of(&sect)?;
+                Some(ExecRegion {
+                    section: sect.index(),
+                    range: start..start.wrapping_add(sect.size() as u64),
+                })
+            })
+    }
+
+    /// Check that the laid out module can be mapped with its executable pages read-only
+    /// and all other pages non-executable
+    ///
+    /// Executable sections must not be writable or share a page with any other allocated
+    /// section, and no relocation against another section may fall in an executable page.
+    /// Relocations against the executable sections themselves are permitted, as they are
+    /// applied before the pages are made read-only.
+    pub fn check_wx<F>(&self, page_size: u64, address_of: F) -> Result<(), WxViolation>
+    where
+        F: Fn(&Section) -> Option<u64>,
+    {
+        let allocated = || {
+            self.sections()
+                .filter(|sect| sect.flags().alloc() && sect.size() != 0)
+        };
+
+        for sect in allocated() {
+            address_of(&sect).ok_or(WxViolation::Unplaced(sect.index()))?;
+            if is_code(&sect) && sect.flags().write() {
+                return Err(WxViolation::WritableCode(sect.index()));
+            }
+        }
+
+        for region in self.executable_regions(&address_of) {
+            let pages = region.pages(page_size);
+
+            for other in allocated().filter(|sect| !is_code(sect)) {
+                let start = address_of(&other).unwrap_or(0);
+                let end = start.wrapping_add(other.size() as u64);
+                if start < pages.end && pages.start < end {
+                    return Err(WxViolation::SharedPage {
+                        exec: region.section,
+                        other: other.index(),
+                        page: start.max(pages.start) & !(page_size - 1),
+                    });
+                }
+
+                for rel in other.relocations() {
+                    let address = start.wrapping_add(rel.offset());
+                    if pages.contains(&address) {
+                        return Err(WxViolation::Relocation {
+                            section: other.index(),
+                            address,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_code(sect: &Section) -> bool {
+    let flags = sect.flags();
+    flags.alloc() && flags.execinstr() && sect.section_type() != SectionType::Nobits
+}